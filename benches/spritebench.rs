@@ -0,0 +1,150 @@
+// The lua/spritebench_* scripts as criterion benchmarks, so drawing speed can be measured without a window.
+// Each one draws the same fixed set of balls into a 640x360 buffer, like a frame of the script.
+
+use aftershock_framework::color::Color;
+use aftershock_framework::rasterizer::{DrawMode, Rasterizer};
+use aftershock_framework::partitioned_rasterizer::PartitionedRasterizer;
+
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 360;
+const BALL_COUNTS: [usize; 3] = [100, 1000, 5000];
+
+struct Ball {
+    x: f64,
+    y: f64,
+    color: Color,
+    opacity: u8,
+}
+
+// Same spread as make_ball in the scripts after they have bounced around for a while, but always the same balls
+fn make_balls(count: usize) -> Vec<Ball> {
+    let mut seed: u64 = 0x2545F4914F6CDD1D;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed >> 11) as f64 / (1u64 << 53) as f64
+    };
+
+    (0..count).map(|_| Ball {
+        x: 8.0 + random() * (WIDTH as f64 - 16.0),
+        y: 8.0 + random() * (HEIGHT as f64 - 16.0),
+        color: Color::hsv(random() * 360.0, 1.0, 0.5 + random() * 0.5),
+        opacity: (random() * 255.0) as u8,
+    }).collect()
+}
+
+fn ball_sprite() -> Rasterizer {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/core/sprites/ball.png");
+    Rasterizer::new_from_image(path).expect("spritebench needs core/sprites/ball.png")
+}
+
+fn spritebench(c: &mut Criterion) {
+    let sprite = ball_sprite();
+    let shared_sprite = Arc::new(sprite.clone());
+    let mut rasterizer = Rasterizer::new(WIDTH, HEIGHT);
+    let mut partitioned = PartitionedRasterizer::new(WIDTH, HEIGHT, num_cpus::get());
+    let background = Color::new(32, 0, 32, 255);
+
+    for count in BALL_COUNTS {
+        let balls = make_balls(count);
+
+        let mut group = c.benchmark_group("spritebench");
+
+        group.bench_with_input(BenchmarkId::new("blit", count), &balls, |b, balls| b.iter(|| {
+            rasterizer.clear_color(background);
+            for ball in balls.iter() {
+                rasterizer.blit(&sprite, ball.x as i64, ball.y as i64);
+            }
+            black_box(&rasterizer.color);
+        }));
+
+        group.bench_with_input(BenchmarkId::new("noop", count), &balls, |b, balls| b.iter(|| {
+            rasterizer.clear_color(background);
+            rasterizer.set_draw_mode(DrawMode::NoOp);
+            for ball in balls.iter() {
+                rasterizer.pimg(&sprite, ball.x as i64, ball.y as i64);
+            }
+            rasterizer.set_draw_mode(DrawMode::Opaque);
+            black_box(&rasterizer.color);
+        }));
+
+        group.bench_with_input(BenchmarkId::new("pimg", count), &balls, |b, balls| b.iter(|| {
+            rasterizer.set_draw_mode(DrawMode::Alpha);
+            rasterizer.clear_color(background);
+            for ball in balls.iter() {
+                rasterizer.tint = ball.color;
+                rasterizer.opacity = ball.opacity;
+                rasterizer.pimg(&sprite, ball.x as i64, ball.y as i64);
+            }
+            rasterizer.set_draw_mode(DrawMode::Opaque);
+            rasterizer.tint = Color::white();
+            rasterizer.opacity = 255;
+            black_box(&rasterizer.color);
+        }));
+
+        // Same as pimg but recorded and drawn in tiles on every core, like the scripts do
+        group.bench_with_input(BenchmarkId::new("deferred_pimg", count), &balls, |b, balls| b.iter(|| {
+            partitioned.set_draw_mode(DrawMode::Alpha);
+            partitioned.clear_color(background);
+            for ball in balls.iter() {
+                partitioned.set_tint(ball.color);
+                partitioned.set_opacity(ball.opacity);
+                partitioned.pimg(&shared_sprite, ball.x as i64, ball.y as i64);
+            }
+            partitioned.set_draw_mode(DrawMode::Opaque);
+            partitioned.set_tint(Color::white());
+            partitioned.set_opacity(255);
+            partitioned.flush();
+            black_box(&partitioned.rasterizer.color);
+        }));
+
+        group.bench_with_input(BenchmarkId::new("pimgmtx", count), &balls, |b, balls| b.iter(|| {
+            rasterizer.camera_scale.x = 2.0;
+            rasterizer.camera_scale.y = 2.0;
+            rasterizer.camera_rotation = 0.1;
+            rasterizer.update_camera();
+
+            rasterizer.set_draw_mode(DrawMode::Alpha);
+            rasterizer.clear_color(background);
+            for ball in balls.iter() {
+                rasterizer.tint = ball.color;
+                rasterizer.opacity = ball.opacity;
+                rasterizer.pimgmtx(&sprite, ball.x, ball.y, 0.0, 1.0, 1.0, 0.5, 0.5);
+            }
+            rasterizer.set_draw_mode(DrawMode::Opaque);
+            rasterizer.tint = Color::white();
+            rasterizer.opacity = 255;
+
+            rasterizer.camera_scale.x = 1.0;
+            rasterizer.camera_scale.y = 1.0;
+            rasterizer.camera_rotation = 0.0;
+            rasterizer.update_camera();
+            black_box(&rasterizer.color);
+        }));
+
+        group.finish();
+    }
+
+    let mut group = c.benchmark_group("fill");
+    group.bench_function("prectangle_alpha", |b| b.iter(|| {
+        rasterizer.set_draw_mode(DrawMode::Alpha);
+        rasterizer.opacity = 128;
+        rasterizer.prectangle(true, 0, 0, WIDTH as i64, HEIGHT as i64, Color::new(255, 128, 0, 255));
+        rasterizer.set_draw_mode(DrawMode::Opaque);
+        rasterizer.opacity = 255;
+        black_box(&rasterizer.color);
+    }));
+    group.bench_function("pcircle_opaque", |b| b.iter(|| {
+        rasterizer.pcircle(true, WIDTH as i64 / 2, HEIGHT as i64 / 2, 160, Color::new(0, 128, 255, 255));
+        black_box(&rasterizer.color);
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, spritebench);
+criterion_main!(benches);
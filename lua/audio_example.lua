@@ -7,13 +7,23 @@ end
 function _init()
 	load_sound("core/sounds/boot.wav", "boot")
 
-	play_sound("boot")
+	-- Everything played through the "world" bus can be muffled or echoed at once
+	create_bus("world")
+	create_filter_lowpass("underwater", 400.0, 2.0)
+	create_filter_echo("cave", 0.25, 0.5)
+	set_bus_filter("world", 0, "underwater")
+	set_bus_filter("world", 1, "cave")
+
+	play_sound_bus("boot", "world")
 end
 
 function _update(delta)
-    
+	-- Drain the water over two seconds
+	if is_mouse_button_pressed(0) then
+		fade_filter_param("world", 0, "underwater", "wet", 0.0, 2.0)
+	end
 end
 
 function _draw()
 
-end
\ No newline at end of file
+end
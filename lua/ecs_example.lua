@@ -0,0 +1,94 @@
+-- Entities: balls rain down and bounce off a paddle that follows the mouse, each fading out as its lifetime runs down
+-- Transform, velocity, sprite and collider are updated and drawn natively, gravity and lifetime are Lua components and systems
+-- Tab shows the colliders
+
+CONTROLS = {}
+CONTROLS.DEBUG = 0
+
+font_tiny = load_font("core/fonts/tiny_font.png", TINY_FONT_GLYPHIDX, 5, 5, -1)
+
+show_colliders = false
+spawn_timer = 0
+
+function _conf()
+	set_window_title("ECS Example")
+	set_resolution(320, 180)
+	set_windowed()
+
+	set_key_bind(CONTROLS.DEBUG, "tab")
+end
+
+function _init()
+	load_image("ball_sprite", "core/sprites/ball.png")
+
+	ecs_component("gravity", {strength = 120})
+	ecs_component("lifetime", {left = 4, total = 4})
+	ecs_component("paddle", {})
+
+	paddle = ecs_spawn({
+		transform = {x = 160, y = 160},
+		collider = {width = 60, height = 6},
+		paddle = true,
+	})
+
+	ecs_system("gravity", {"velocity", "gravity"}, function(id, velocity, gravity, delta)
+		velocity.y = velocity.y + gravity.strength * delta
+	end)
+
+	ecs_system("lifetime", {"sprite", "lifetime"}, function(id, sprite, lifetime, delta)
+		lifetime.left = lifetime.left - delta
+		if lifetime.left <= 0 then
+			ecs_destroy(id)
+		else
+			sprite.opacity = 255 * math.min(1, lifetime.left / lifetime.total * 2)
+		end
+	end)
+
+	-- Collisions are from the last update, so a ball bounces the update after it touches the paddle
+	ecs_system("bounce", {"paddle"}, function(id, _, delta)
+		for _, ball in ipairs(ecs_collisions(id)) do
+			local velocity = ecs_get(ball, "velocity")
+			if velocity.y > 0 then velocity.y = -velocity.y * 0.9 end
+		end
+	end)
+end
+
+function _update(delta)
+	ecs_get(paddle, "transform").x = mouse_x()
+
+	spawn_timer = spawn_timer - delta
+	if spawn_timer <= 0 then
+		spawn_timer = 0.15
+		local size = 0.5 + math.random()
+		ecs_spawn({
+			transform = {x = math.random(10, 310), y = -10, scale = size},
+			velocity = {x = math.random(-30, 30), angular = math.random() * 4 - 2},
+			sprite = {image = "ball_sprite", tint = rgb(math.random(120, 255), math.random(120, 255), 255), layer = math.random(0, 1)},
+			collider = {radius = 8},
+			gravity = true,
+			lifetime = true,
+		})
+	end
+
+	if is_control_pressed(CONTROLS.DEBUG) then
+		show_colliders = not show_colliders
+	end
+end
+
+function _draw()
+	clear_color(rgb(20, 24, 40))
+
+	set_draw_mode_alpha()
+	draw_entities()
+	set_draw_mode_opaque()
+
+	local transform = ecs_get(paddle, "transform")
+	prectangle(true, math.floor(transform.x) - 30, math.floor(transform.y) - 3, 60, 6, rgb(240, 240, 240))
+
+	if show_colliders then
+		draw_colliders(rgb(0, 255, 0))
+	end
+
+	pprint(font_tiny, "ENTITIES " .. ecs_count(), 4, 4)
+	pprint(font_tiny, "TAB TO SHOW COLLIDERS", 4, 12)
+end
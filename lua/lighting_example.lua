@@ -0,0 +1,48 @@
+-- A dark room lit by a torch following the mouse, a turning spot light and a few pillars casting shadows
+
+realtime = 0.0
+
+function _conf()
+	set_window_title("Lighting Example")
+	set_resolution(320, 180)
+	set_windowed()
+end
+
+function _init()
+	set_ambient_light(rgb(24, 20, 40))
+
+	light_point("torch", 160, 90, 110, rgb(255, 190, 120))
+	light_spot("lamp", 40, 30, 160, 0.0, 0.8, rgb(120, 180, 255), 1.5)
+
+	-- Pillars and the walls of the room
+	for i = 0, 3, 1 do
+		occluder_rect("pillars", 60 + i * 64, 70, 12, 12)
+	end
+	occluder_polygon("walls", { 8, 8, 312, 8, 312, 172, 8, 172 })
+end
+
+function _update(delta)
+	realtime = realtime + delta
+	set_light_position("torch", mouse_x(), mouse_y())
+	set_light_intensity("torch", 0.9 + math.sin(realtime * 17.0) * 0.05 + math.sin(realtime * 7.0) * 0.05)
+	set_light_direction("lamp", 0.6 + math.sin(realtime) * 0.5)
+end
+
+function _draw()
+	clear_color(rgb(150, 140, 130))
+
+	-- The floor and pillars, drawn lit
+	for y = 8, 164, 16 do
+		for x = 8, 304, 16 do
+			if (x + y) % 32 == 8 then prectangle(true, x, y, 16, 16, rgb(170, 160, 150)) end
+		end
+	end
+	for i = 0, 3, 1 do
+		prectangle(true, 60 + i * 64, 70, 12, 12, rgb(90, 70, 60))
+	end
+
+	draw_lighting()
+
+	-- Drawn after the lighting so it stays bright
+	pcircle(false, mouse_x(), mouse_y(), 3, rgb(255, 255, 255))
+end
@@ -0,0 +1,58 @@
+-- PICO-8 style palette mode: a waterfall that moves with palette cycling, pal() swaps and a fade to black from screen swaps
+
+-- Each PICO-8 color and the darker one it fades to
+FADE = { [0] = 0, 0, 1, 1, 2, 1, 5, 6, 2, 4, 9, 3, 1, 1, 2, 5 }
+
+fade_time = 0.0
+
+function _conf()
+	set_window_title("Palette Example")
+	set_resolution(128, 128)
+	set_windowed()
+end
+
+function _init()
+	set_palette_mode(true)
+
+	-- Dark blue, light blue, white and dark blue again run down the waterfall
+	palette_cycle(12, 15, 8)
+	set_palette({
+		palette_color(0), palette_color(1), palette_color(2), palette_color(3),
+		palette_color(4), palette_color(5), palette_color(6), palette_color(7),
+		palette_color(8), palette_color(9), palette_color(10), palette_color(11),
+		rgb(29, 43, 83), rgb(41, 173, 255), rgb(255, 241, 232), rgb(41, 173, 255),
+	})
+end
+
+function _update(delta)
+	fade_time = (fade_time + delta) % 6.0
+
+	-- Fade the whole screen out and back in over the last two seconds by showing every color as a darker one
+	pal()
+	local steps = 0
+	if fade_time > 4.0 then steps = math.floor((1.0 - math.abs(fade_time - 5.0)) * 4.0) end
+	for color = 0, 11, 1 do
+		local faded = color
+		for i = 1, steps, 1 do faded = FADE[faded] end
+		pal(color, faded, true)
+	end
+end
+
+function _draw()
+	clear_color(index_color(0))
+
+	-- Cliffs
+	prectangle(true, 0, 20, 44, 108, index_color(5))
+	prectangle(true, 84, 20, 44, 108, index_color(5))
+
+	-- The waterfall only needs drawing once, the cycle moves it
+	for y = 20, 127, 1 do
+		prectangle(true, 44, y, 40, 1, index_color(12 + y % 4))
+	end
+
+	-- The same bush drawn green and then swapped to orange for autumn
+	pcircle(true, 20, 16, 10, index_color(3))
+	pal(3, 9)
+	pcircle(true, 108, 16, 10, index_color(3))
+	pal(3, 3)
+end
@@ -0,0 +1,63 @@
+-- Native particles: a fire following the mouse, sparks bursting on click and a fountain of sprites
+
+function _conf()
+	set_window_title("Particles Example")
+	set_resolution(320, 180)
+	set_windowed()
+end
+
+function _init()
+	load_image("ball_sprite", "core/sprites/ball.png")
+
+	particle_define("fire", {
+		rate = 120,
+		lifetime = {0.4, 0.9},
+		speed = {10, 30},
+		angle = {-math.pi * 0.65, -math.pi * 0.35},
+		radius = 3,
+		gravity = {0, -40},
+		colors = {rgb(255, 240, 160), rgb(255, 140, 20), rgb(160, 20, 10)},
+		sizes = {4, 1},
+		shape = "circle",
+	})
+
+	particle_define("sparks", {
+		rate = 0,
+		lifetime = {0.3, 0.6},
+		speed = {60, 140},
+		drag = 3,
+		colors = rgb(255, 255, 180),
+		max = 400,
+	})
+
+	particle_define("fountain", {
+		rate = 40,
+		lifetime = {1.5, 2.0},
+		speed = {70, 100},
+		angle = {-math.pi * 0.6, -math.pi * 0.4},
+		gravity = {0, 90},
+		spin = {-4, 4},
+		sizes = {0.5, 0.2},
+		opacities = {1, 1, 0},
+		sprite = "ball_sprite",
+	})
+
+	emitter_create("fire", "fire", 160, 90)
+	emitter_create("sparks", "sparks", 160, 90)
+	emitter_create("fountain", "fountain", 60, 170)
+end
+
+function _update(delta)
+	set_emitter_position("fire", mouse_x(), mouse_y())
+	set_emitter_position("sparks", mouse_x(), mouse_y())
+	if is_mouse_button_pressed(0) then
+		emitter_burst("sparks", 60)
+	end
+end
+
+function _draw()
+	clear_color(rgb(20, 16, 28))
+	set_draw_mode_alpha()
+	draw_particles()
+	set_draw_mode_opaque()
+end
@@ -0,0 +1,41 @@
+-- A CRT look from the post-processing chain, with glowing balls and a screen shake every few seconds
+
+realtime = 0.0
+
+function _conf()
+	set_window_title("Post-Processing Example")
+	set_resolution(320, 180)
+	set_windowed()
+end
+
+function _init()
+	-- Effects run in the order they're added
+	post_bloom(180, 4, 0.8)
+	post_chromatic_aberration(2)
+	post_curvature(0.04)
+	post_scanlines(0.35)
+	post_vignette(0.6, 0.4)
+end
+
+function _update(delta)
+	realtime = realtime + delta
+
+	-- A short shake at the start of every third second
+	local shake_time = realtime % 3.0
+	if shake_time < 0.3 then
+		local strength = (0.3 - shake_time) * 20.0
+		set_screen_shake(math.sin(realtime * 90.0) * strength, math.cos(realtime * 70.0) * strength)
+	else
+		set_screen_shake(0, 0)
+	end
+end
+
+function _draw()
+	clear_color(rgb(16, 12, 32))
+
+	for i = 0, 7, 1 do
+		local angle = realtime + i * math.pi / 4
+		pcircle(true, 160 + math.cos(angle) * 60, 90 + math.sin(angle) * 40, 8, hsv(i * 45, 0.6, 1.0))
+	end
+	prectangle(false, 8, 8, 304, 164, rgb(80, 80, 120))
+end
@@ -0,0 +1,47 @@
+-- Textures made with the native generators and map_pixels, with a live expression shader
+
+realtime = 0.0
+
+function _conf()
+	set_window_title("Procedural Example")
+	set_resolution(320, 180)
+	set_windowed()
+end
+
+function _init()
+	image("clouds", 96, 96)
+	generate_noise("clouds", "perlin", 24, 5, 7, rgb(20, 40, 120), rgb(230, 240, 255))
+
+	image("stones", 96, 96)
+	generate_noise("stones", "worley", 16, 1, 3, rgb(40, 40, 40), rgb(200, 190, 170))
+
+	image("sunset", 96, 96)
+	generate_gradient("sunset", 0, 0, 0, 96, { rgb(40, 20, 80), rgb(240, 100, 60), rgb(255, 220, 120) })
+
+	-- A Lua function can do anything, here it darkens every other row
+	image("checker", 96, 96)
+	generate_checker("checker", 12, rgb(200, 200, 200), rgb(120, 120, 120))
+	map_pixels("checker", function(x, y, color)
+		if y % 2 == 0 then
+			return rgb(color.r / 2, color.g / 2, color.b / 2)
+		end
+	end)
+
+	image("plasma", 96, 96)
+end
+
+function _update(delta)
+	realtime = realtime + delta
+
+	-- Expressions run in parallel, fast enough to redo every frame
+	map_pixels("plasma", "128 + 127 * sin(x * 0.1 + t), 128 + 127 * sin(y * 0.1 + t * 1.3), 255 * fbm(u * 4, v * 4 + t * 0.2, 3)", { t = realtime })
+end
+
+function _draw()
+	clear_color(rgb(16, 16, 24))
+	pimg("clouds", 8, 8)
+	pimg("stones", 112, 8)
+	pimg("sunset", 216, 8)
+	pimg("checker", 8, 108)
+	pimg("plasma", 112, 108)
+end
@@ -0,0 +1,84 @@
+-- Scenes: a title screen, a game and a pause menu drawn over the paused game, switched between with transitions
+-- Enter starts the game, escape pauses and unpauses, Q in the pause menu goes back to the title
+
+CONTROLS = {}
+CONTROLS.CONFIRM = 0
+CONTROLS.PAUSE = 1
+CONTROLS.QUIT = 2
+
+font_tiny = load_font("core/fonts/tiny_font.png", TINY_FONT_GLYPHIDX, 5, 5, -1)
+
+title = {
+	enter = function(self)
+		self.time = 0
+	end,
+
+	update = function(self, delta)
+		self.time = self.time + delta
+		if is_control_pressed(CONTROLS.CONFIRM) and not is_scene_transitioning() then
+			scene_switch(game, {type = "wipe", duration = 0.6, direction = "left", easing = "in_out_cubic"})
+		end
+	end,
+
+	draw = function(self)
+		clear_color(rgb(30, 24, 60))
+		pprint(font_tiny, "SCENE EXAMPLE", 128, 70 + math.floor(math.sin(self.time * 3) * 4))
+		pprint(font_tiny, "PRESS ENTER", 134, 110)
+	end,
+}
+
+game = {
+	enter = function(self)
+		self.x, self.y = 160, 90
+		self.angle = 0
+	end,
+
+	update = function(self, delta)
+		self.angle = self.angle + delta * 2
+		self.x = 160 + math.cos(self.angle) * 60
+		self.y = 90 + math.sin(self.angle * 2) * 30
+		if is_control_pressed(CONTROLS.PAUSE) then
+			scene_push(pause, {type = "fade", duration = 0.2})
+		end
+	end,
+
+	draw = function(self)
+		clear_color(rgb(20, 60, 40))
+		pcircle(true, self.x, self.y, 10, rgb(255, 220, 80))
+	end,
+}
+
+-- Drawn over the paused game, which stops moving underneath it
+pause = {
+	overlay = true,
+
+	update = function(self, delta)
+		if is_control_pressed(CONTROLS.PAUSE) then
+			scene_pop({type = "fade", duration = 0.2})
+		elseif is_control_pressed(CONTROLS.QUIT) then
+			scene_switch(title, {type = "fade", duration = 1.0, color = rgb(0, 0, 0)})
+		end
+	end,
+
+	draw = function(self)
+		set_draw_mode_alpha()
+		prectangle(true, 0, 0, 320, 180, rgba(0, 0, 0, 160))
+		set_draw_mode_opaque()
+		pprint(font_tiny, "PAUSED", 146, 80)
+		pprint(font_tiny, "ESC TO RESUME, Q TO QUIT", 100, 96)
+	end,
+}
+
+function _conf()
+	set_window_title("Scene Example")
+	set_resolution(320, 180)
+	set_windowed()
+
+	set_key_bind(CONTROLS.CONFIRM, "return")
+	set_key_bind(CONTROLS.PAUSE, "escape")
+	set_key_bind(CONTROLS.QUIT, "q")
+end
+
+function _init()
+	scene_push(title, "dissolve")
+end
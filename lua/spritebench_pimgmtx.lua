@@ -110,9 +110,12 @@ function _draw()
 
 	
 
+	-- The stats are UI, so they shouldn't move with the camera
+	set_draw_space_screen()
 	pprint(font_tiny, "Update time  : " .. update_time_ms .. "ms", 0, 0)
     pprint(font_tiny, "Draw time    : " .. draw_time_ms .. "ms", 0, 6)
 	pprint(font_tiny, "Total balls  : " .. #balls, 0, 12)
+	set_draw_space_world()
 
 	
 end
\ No newline at end of file
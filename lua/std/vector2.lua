@@ -1,5 +1,6 @@
 -- Vector2 Implementation
 -- Functional, no closures or tables
+-- The engine's vec2(x, y) has all of these as methods, along with + - * / and more
 
 function vec2_distance(x0, y0, x1, y1)
     return math.sqrt((x1 - x0) ^ 2.0 + (y1 - y0) ^ 2.0)
@@ -0,0 +1,64 @@
+-- Tweens and timers: a box bouncing between corners in a sequence, a pulsing ball, camera shake on click
+-- and a flash of tint that fades out
+
+function _conf()
+	set_window_title("Tween Example")
+	set_resolution(320, 180)
+	set_windowed()
+end
+
+function _init()
+	box = {x = 20, y = 20, size = 16}
+	ball = {x = 160, y = 90, radius = 8, color = rgb(80, 160, 255)}
+	dots = {}
+
+	-- Each corner in turn, forever
+	tween_sequence({
+		tween(box, 1.0, {x = 284}, "in_out_cubic"),
+		tween(box, 0.8, {y = 144}, "out_bounce"),
+		tween(box, 1.0, {x = 20}, "in_out_back"),
+		tween(box, 0.8, {y = 20}, "out_elastic"),
+	}, {loops = 0})
+
+	-- Grows and changes color while growing, then shrinks back
+	tween(ball, 0.6, {radius = 20, color = rgb(255, 120, 80)}, "in_out_sine", {loops = 0, yoyo = true})
+
+	-- A new dot every half second, stopping if 10 are ever up at once
+	every(0.5, function()
+		table.insert(dots, {x = math.random(20, 300), y = 170, alpha = 255})
+		local dot = dots[#dots]
+		tween(dot, 1.5, {y = 10, alpha = 0}, "out_quad", {on_complete = function()
+			for i, d in ipairs(dots) do
+				if d == dot then table.remove(dots, i) break end
+			end
+		end})
+		return #dots < 10
+	end)
+end
+
+function _update(delta)
+	if is_mouse_button_pressed(0) then
+		-- Shake the camera with a short back and forth, then settle
+		tween_sequence({
+			tween("camera", 0.05, {x = 6}, "linear", {loops = 6, yoyo = true}),
+			tween("camera", 0.1, {x = 0}),
+		})
+
+		set_tint(rgb(255, 80, 80))
+		after(0.1, function()
+			tween("draw", 0.4, {tint = rgb(255, 255, 255)}, "out_quad")
+		end)
+	end
+end
+
+function _draw()
+	clear_color(rgb(24, 20, 36))
+	prectangle(true, math.floor(box.x), math.floor(box.y), box.size, box.size, rgb(255, 220, 80))
+	pcircle(true, ball.x, ball.y, math.floor(ball.radius), ball.color)
+
+	set_draw_mode_alpha()
+	for _, dot in ipairs(dots) do
+		pcircle(true, dot.x, math.floor(dot.y), 3, rgba(160, 255, 160, math.floor(dot.alpha)))
+	end
+	set_draw_mode_opaque()
+end
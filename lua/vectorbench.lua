@@ -0,0 +1,135 @@
+-- Moves the same balls around with plain numbers, vec2 math and in place vec2 methods, and prints how long each took
+
+BALL_COUNT = 1000
+STEPS = 1000
+
+function _conf()
+	set_window_title("Vectorbench")
+   	set_resolution(640, 360)
+   	set_windowed()
+end
+
+function make_balls(count)
+	local random = math.random
+	math.randomseed(1234)
+
+	local balls = {}
+	for i = 1, count, 1 do
+		balls[i] = { x = random() * 640, y = random() * 360, dx = random() * 256 - 128, dy = random() * 256 - 128 }
+	end
+	return balls
+end
+
+function bench_numbers(delta)
+	local balls = make_balls(BALL_COUNT)
+	local sqrt = math.sqrt
+
+	local time_before = timestamp()
+	for step = 1, STEPS, 1 do
+		for i = 1, #balls, 1 do
+			local ball = balls[i]
+			ball.x = ball.x + ball.dx * delta
+			ball.y = ball.y + ball.dy * delta
+
+			-- Pull towards the center
+			local to_x, to_y = 320 - ball.x, 180 - ball.y
+			local length = sqrt(to_x * to_x + to_y * to_y)
+			if length > 0 then
+				ball.dx = ball.dx + to_x / length * 10 * delta
+				ball.dy = ball.dy + to_y / length * 10 * delta
+			end
+		end
+	end
+	return timestamp() - time_before
+end
+
+function bench_vec2(delta)
+	local balls = make_balls(BALL_COUNT)
+	for i = 1, #balls, 1 do
+		local ball = balls[i]
+		ball.position = vec2(ball.x, ball.y)
+		ball.velocity = vec2(ball.dx, ball.dy)
+	end
+	local center = vec2(320, 180)
+
+	local time_before = timestamp()
+	for step = 1, STEPS, 1 do
+		for i = 1, #balls, 1 do
+			local ball = balls[i]
+			ball.position = ball.position + ball.velocity * delta
+
+			local to_center = center - ball.position
+			if to_center:magnitude_sqr() > 0 then
+				ball.velocity = ball.velocity + to_center:normalized() * (10 * delta)
+			end
+		end
+	end
+	return timestamp() - time_before
+end
+
+function bench_vec2_in_place(delta)
+	local balls = make_balls(BALL_COUNT)
+	for i = 1, #balls, 1 do
+		local ball = balls[i]
+		ball.position = vec2(ball.x, ball.y)
+		ball.velocity = vec2(ball.dx, ball.dy)
+	end
+	local step_velocity = vec2()
+	local to_center = vec2()
+
+	local time_before = timestamp()
+	for step = 1, STEPS, 1 do
+		for i = 1, #balls, 1 do
+			local ball = balls[i]
+			local position, velocity = ball.position, ball.velocity
+
+			step_velocity:set(velocity.x * delta, velocity.y * delta)
+			position:add(step_velocity)
+
+			to_center:set(320 - position.x, 180 - position.y)
+			if to_center:magnitude_sqr() > 0 then
+				to_center:normalize()
+				to_center:mul(10 * delta)
+				velocity:add(to_center)
+			end
+		end
+	end
+	return timestamp() - time_before
+end
+
+-- The same pixels drawn from numbers and from vec2s, to see what taking a vec2 costs the drawing functions
+function bench_pset(use_vec2)
+	local color = rgb(255, 255, 255)
+	local position = vec2()
+
+	local time_before = timestamp()
+	for y = 0, 359, 1 do
+		for x = 0, 639, 1 do
+			if use_vec2 then
+				position:set(x, y)
+				pset(position, color)
+			else
+				pset(x, y, color)
+			end
+		end
+	end
+	return timestamp() - time_before
+end
+
+function _init()
+	local delta = 1.0 / 60.0
+
+	print("Numbers: " .. bench_numbers(delta) .. " seconds!")
+	print("vec2 math: " .. bench_vec2(delta) .. " seconds!")
+	print("vec2 in place: " .. bench_vec2_in_place(delta) .. " seconds!")
+	print("pset numbers: " .. bench_pset(false) .. " seconds!")
+	print("pset vec2: " .. bench_pset(true) .. " seconds!")
+end
+
+function _update(delta)
+
+end
+
+function _draw()
+
+end
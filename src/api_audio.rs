@@ -1,13 +1,22 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
 use mlua::prelude::*;
 use soloud::prelude::*;
+use soloud::{Soloud, Bus, Handle};
 
 use crate::api_shareables::*;
+use crate::audio_filter::AudioFilter;
 
-pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle, assets_sfx: SharedAudioWav, assets_mus: SharedAudioWavStream, lua: &Lua) {
+pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle, assets_sfx: SharedAudioWav, assets_mus: SharedAudioWavStream, audio_buses: SharedAudioBus, audio_filters: SharedAudioFilter, lua: &Lua) {
     println!("Registering API: Audio");
 
+    let filter_state: SharedFilterState = Rc::new(RefCell::new(FilterState::default()));
+
     // SFX //
     let sfxa = assets_sfx.clone();
+    let state = filter_state.clone();
     let fn_load_sound = lua.create_function(move |_, (path_to, name): (String, String)| {
         // Overwrite anything already in the key
         let mut wav = soloud::audio::Wav::default();
@@ -16,14 +25,16 @@ pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle,
         if wav_result.is_err() {
             println!("ERROR - AUDIO: Failed to load Wav at path '{}'! Soloud: {}", path_to, wav_result.err().unwrap());
         }
+        state.borrow_mut().forget(FilterTarget::Sound, &name);
         sfxa.insert(name, wav);
         Ok(())
     }).unwrap();
     let _ = lua.globals().set("load_sound", fn_load_sound);
 
     let sfxa = assets_sfx.clone();
+    let state = filter_state.clone();
     let fn_unload_sound = lua.create_function(move |_, name: String| {
-        
+        state.borrow_mut().forget(FilterTarget::Sound, &name);
         sfxa.remove(&name);
         Ok(())
     }).unwrap();
@@ -31,11 +42,13 @@ pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle,
 
     let soloud = audio.clone();
     let sfxa = assets_sfx.clone();
+    let state = filter_state.clone();
     let fn_sfx = lua.create_function(move |_, name: String| {
         // Play sound, don't save handle
         let find_result = sfxa.get(&name);
         if find_result.is_some() {
-            soloud.play(&*find_result.unwrap());
+            let handle = soloud.play(&*find_result.unwrap());
+            state.borrow_mut().track(&soloud, handle, FilterTarget::Sound, &name);
         }
         Ok(())
     }).unwrap();
@@ -44,11 +57,13 @@ pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle,
     let soloud = audio.clone();
     let sfxa = assets_sfx.clone();
     let handles = audio_handles.clone();
+    let state = filter_state.clone();
     let fn_play_sound_handle = lua.create_function(move |_, (name, handle_name): (String, String)| {
         // Play sound with handle
         let find_result = sfxa.get(&name);
         if find_result.is_some() {
             let handle = soloud.play(&*find_result.unwrap());
+            state.borrow_mut().track(&soloud, handle, FilterTarget::Sound, &name);
             if soloud.is_valid_voice_handle(handle) {
                 handles.insert(handle_name, handle);
             }
@@ -81,6 +96,7 @@ pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle,
 
     // MUSIC //
     let musa = assets_mus.clone();
+    let state = filter_state.clone();
     let fn_load_mus = lua.create_function(move |_, (path_to, name): (String, String)| {
         // Overwrite anything already in the key
         let mut wav = soloud::audio::WavStream::default();
@@ -89,13 +105,16 @@ pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle,
         if wav_result.is_err() {
             println!("ERROR - AUDIO: Failed to load Wav at path '{}'! Soloud: {}", path_to, wav_result.err().unwrap());
         }
+        state.borrow_mut().forget(FilterTarget::Music, &name);
         musa.insert(name, wav);
         Ok(())
     }).unwrap();
     let _ = lua.globals().set("load_music", fn_load_mus);
 
     let musa = assets_mus.clone();
+    let state = filter_state.clone();
     let fn_unload_sound = lua.create_function(move |_, name: String| {
+        state.borrow_mut().forget(FilterTarget::Music, &name);
         musa.remove(&name);
         Ok(())
     }).unwrap();
@@ -103,13 +122,403 @@ pub fn register_audio_api(audio: SharedAudio, audio_handles: SharedAudioHandle,
 
     let soloud = audio.clone();
     let musa = assets_mus.clone();
+    let state = filter_state.clone();
     let fn_mus = lua.create_function(move |_, name: String| {
         // Play sound, don't save handle
         let find_result = musa.get(&name);
         if find_result.is_some() {
-            soloud.play(&*find_result.unwrap());
+            let handle = soloud.play(&*find_result.unwrap());
+            state.borrow_mut().track(&soloud, handle, FilterTarget::Music, &name);
         }
         Ok(())
     }).unwrap();
     let _ = lua.globals().set("play_music", fn_mus);
-}
\ No newline at end of file
+
+    // BUSES //
+    let soloud = audio.clone();
+    let busa = audio_buses.clone();
+    let handles = audio_handles.clone();
+    let state = filter_state.clone();
+    let fn_create_bus = lua.create_function(move |_, name: String| {
+        // Buses are played like any other sound, their handle is saved under the bus name so filters can be changed live.
+        // They only start playing with the first sound played through them, see start_bus
+        let find_result = handles.remove(&name);
+        if let Some((_, handle)) = find_result {
+            if soloud.is_valid_voice_handle(handle) {
+                soloud.stop(handle);
+            }
+        }
+        state.borrow_mut().forget(FilterTarget::Bus, &name);
+        busa.insert(name, soloud::Bus::default());
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_bus", fn_create_bus);
+
+    let soloud = audio.clone();
+    let busa = audio_buses.clone();
+    let handles = audio_handles.clone();
+    let state = filter_state.clone();
+    let fn_destroy_bus = lua.create_function(move |_, name: String| {
+        let find_result = handles.remove(&name);
+        if find_result.is_some() {
+            let (_, handle) = find_result.unwrap();
+            if soloud.is_valid_voice_handle(handle) {
+                soloud.stop(handle);
+            }
+        }
+        state.borrow_mut().forget(FilterTarget::Bus, &name);
+        busa.remove(&name);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("destroy_bus", fn_destroy_bus);
+
+    let soloud = audio.clone();
+    let sfxa = assets_sfx.clone();
+    let busa = audio_buses.clone();
+    let handles = audio_handles.clone();
+    let state = filter_state.clone();
+    let fn_play_sound_bus = lua.create_function(move |_, (name, bus_name, handle_name): (String, String, Option<String>)| {
+        // Play sound through a bus, optionally saving the handle
+        let find_result = sfxa.get(&name);
+        let bus_result = busa.get(&bus_name);
+        if find_result.is_some() && bus_result.is_some() {
+            let bus = bus_result.unwrap();
+            start_bus(&soloud, &handles, &state, &bus_name, &bus);
+            let handle = bus.play(&*find_result.unwrap());
+            state.borrow_mut().track(&soloud, handle, FilterTarget::Sound, &name);
+            if handle_name.is_some() {
+                handles.insert(handle_name.unwrap(), handle);
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("play_sound_bus", fn_play_sound_bus);
+
+    let soloud = audio.clone();
+    let musa = assets_mus.clone();
+    let busa = audio_buses.clone();
+    let handles = audio_handles.clone();
+    let state = filter_state.clone();
+    let fn_play_music_bus = lua.create_function(move |_, (name, bus_name, handle_name): (String, String, Option<String>)| {
+        // Play music through a bus, optionally saving the handle
+        let find_result = musa.get(&name);
+        let bus_result = busa.get(&bus_name);
+        if find_result.is_some() && bus_result.is_some() {
+            let bus = bus_result.unwrap();
+            start_bus(&soloud, &handles, &state, &bus_name, &bus);
+            let handle = bus.play(&*find_result.unwrap());
+            state.borrow_mut().track(&soloud, handle, FilterTarget::Music, &name);
+            if handle_name.is_some() {
+                handles.insert(handle_name.unwrap(), handle);
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("play_music_bus", fn_play_music_bus);
+
+    // FILTERS //
+    // Making a filter with a name that's already used replaces it everywhere it's attached, voices already playing keep the old one
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_lowpass = lua.create_function(move |_, (name, frequency, resonance): (String, f32, f32)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::lowpass(frequency, resonance));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_lowpass", fn_create_filter_lowpass);
+
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_highpass = lua.create_function(move |_, (name, frequency, resonance): (String, f32, f32)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::highpass(frequency, resonance));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_highpass", fn_create_filter_highpass);
+
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_bandpass = lua.create_function(move |_, (name, frequency, resonance): (String, f32, f32)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::bandpass(frequency, resonance));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_bandpass", fn_create_filter_bandpass);
+
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_echo = lua.create_function(move |_, (name, delay, decay, filter): (String, f32, f32, Option<f32>)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::echo(delay, decay, filter.unwrap_or(0.0)));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_echo", fn_create_filter_echo);
+
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_reverb = lua.create_function(move |_, (name, room_size, damp, width): (String, f32, f32, Option<f32>)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::reverb(room_size, damp, width.unwrap_or(1.0)));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_reverb", fn_create_filter_reverb);
+
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_lofi = lua.create_function(move |_, (name, samplerate, bitdepth): (String, f32, f32)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::lofi(samplerate, bitdepth));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_lofi", fn_create_filter_lofi);
+
+    let soloud = audio.clone();
+    let filters = audio_filters.clone();
+    let targets = (assets_sfx.clone(), assets_mus.clone(), audio_buses.clone());
+    let state = filter_state.clone();
+    let fn_create_filter_flanger = lua.create_function(move |_, (name, delay, frequency): (String, f32, f32)| {
+        replace_filter(&soloud, &filters, &targets, &state, name, AudioFilter::flanger(delay, frequency));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("create_filter_flanger", fn_create_filter_flanger);
+
+    // Attach filters to sounds, music and buses. Passing nil as the filter name clears the slot.
+    let sfxa = assets_sfx.clone();
+    let filters = audio_filters.clone();
+    let state = filter_state.clone();
+    let fn_set_sound_filter = lua.create_function(move |_, (name, slot, filter_name): (String, u32, Option<String>)| {
+        if slot >= AudioFilter::MAX_SLOTS {
+            println!("ERROR - AUDIO: Filter slot {} is out of range! Use 0 to {}.", slot, AudioFilter::MAX_SLOTS - 1);
+            return Ok(());
+        }
+
+        let find_result = sfxa.get_mut(&name);
+        if find_result.is_some() {
+            let mut wav = find_result.unwrap();
+            match filter_name {
+                Some(filter_name) => {
+                    let filter_result = filters.get(&filter_name);
+                    if filter_result.is_some() {
+                        filter_result.unwrap().attach(&mut *wav, slot);
+                        state.borrow_mut().set_attachment(FilterTarget::Sound, &name, slot, Some(filter_name));
+                    }
+                },
+                None => {
+                    AudioFilter::detach(&mut *wav, slot);
+                    state.borrow_mut().set_attachment(FilterTarget::Sound, &name, slot, None);
+                }
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_sound_filter", fn_set_sound_filter);
+
+    let musa = assets_mus.clone();
+    let filters = audio_filters.clone();
+    let state = filter_state.clone();
+    let fn_set_music_filter = lua.create_function(move |_, (name, slot, filter_name): (String, u32, Option<String>)| {
+        if slot >= AudioFilter::MAX_SLOTS {
+            println!("ERROR - AUDIO: Filter slot {} is out of range! Use 0 to {}.", slot, AudioFilter::MAX_SLOTS - 1);
+            return Ok(());
+        }
+
+        let find_result = musa.get_mut(&name);
+        if find_result.is_some() {
+            let mut wav = find_result.unwrap();
+            match filter_name {
+                Some(filter_name) => {
+                    let filter_result = filters.get(&filter_name);
+                    if filter_result.is_some() {
+                        filter_result.unwrap().attach(&mut *wav, slot);
+                        state.borrow_mut().set_attachment(FilterTarget::Music, &name, slot, Some(filter_name));
+                    }
+                },
+                None => {
+                    AudioFilter::detach(&mut *wav, slot);
+                    state.borrow_mut().set_attachment(FilterTarget::Music, &name, slot, None);
+                }
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_music_filter", fn_set_music_filter);
+
+    let soloud = audio.clone();
+    let busa = audio_buses.clone();
+    let handles = audio_handles.clone();
+    let filters = audio_filters.clone();
+    let state = filter_state.clone();
+    let fn_set_bus_filter = lua.create_function(move |_, (name, slot, filter_name): (String, u32, Option<String>)| {
+        if slot >= AudioFilter::MAX_SLOTS {
+            println!("ERROR - AUDIO: Filter slot {} is out of range! Use 0 to {}.", slot, AudioFilter::MAX_SLOTS - 1);
+            return Ok(());
+        }
+        // Soloud only sets up a bus's filters when it starts, and restarting it would cut off everything playing through it
+        if bus_playing(&soloud, &handles, &name) {
+            println!("ERROR - AUDIO: Bus '{}' is already playing, its filters have to be set before anything is played through it! Use set_filter_param or fade_filter_param to change them live.", name);
+            return Ok(());
+        }
+
+        let find_result = busa.get_mut(&name);
+        if find_result.is_some() {
+            let mut bus = find_result.unwrap();
+            match filter_name {
+                Some(filter_name) => {
+                    let filter_result = filters.get(&filter_name);
+                    if filter_result.is_some() {
+                        filter_result.unwrap().attach(&mut *bus, slot);
+                        state.borrow_mut().set_attachment(FilterTarget::Bus, &name, slot, Some(filter_name));
+                    }
+                },
+                None => {
+                    AudioFilter::detach(&mut *bus, slot);
+                    state.borrow_mut().set_attachment(FilterTarget::Bus, &name, slot, None);
+                }
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_bus_filter", fn_set_bus_filter);
+
+    // Live filter parameters. Works on any saved handle, including buses since their handle is saved under the bus name.
+    let soloud = audio.clone();
+    let handles = audio_handles.clone();
+    let filters = audio_filters.clone();
+    let fn_set_filter_param = lua.create_function(move |_, (handle_name, slot, filter_name, param, value): (String, u32, String, String, f32)| {
+        let handle_result = handles.get(&handle_name);
+        let filter_result = filters.get(&filter_name);
+        if handle_result.is_some() && filter_result.is_some() {
+            let handle = *handle_result.unwrap().value();
+            if soloud.is_valid_voice_handle(handle) {
+                if !filter_result.unwrap().set_param(&soloud, handle, slot, &param, value, 0.0) {
+                    println!("ERROR - AUDIO: Filter '{}' has no parameter named '{}'!", filter_name, param);
+                }
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_filter_param", fn_set_filter_param);
+
+    let soloud = audio.clone();
+    let handles = audio_handles.clone();
+    let filters = audio_filters.clone();
+    let fn_fade_filter_param = lua.create_function(move |_, (handle_name, slot, filter_name, param, value, time): (String, u32, String, String, f32, f64)| {
+        let handle_result = handles.get(&handle_name);
+        let filter_result = filters.get(&filter_name);
+        if handle_result.is_some() && filter_result.is_some() {
+            let handle = *handle_result.unwrap().value();
+            if soloud.is_valid_voice_handle(handle) {
+                if !filter_result.unwrap().set_param(&soloud, handle, slot, &param, value, time) {
+                    println!("ERROR - AUDIO: Filter '{}' has no parameter named '{}'!", filter_name, param);
+                }
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("fade_filter_param", fn_fade_filter_param);
+}
+
+// Soloud only sets up a bus's filters when it starts playing, so buses start with the first sound played through them
+fn start_bus(soloud: &Soloud, handles: &SharedAudioHandle, state: &SharedFilterState, name: &str, bus: &Bus) {
+    if bus_playing(soloud, handles, name) {
+        return;
+    }
+    let handle = soloud.play(bus);
+    handles.insert(name.to_string(), handle);
+    state.borrow_mut().track(soloud, handle, FilterTarget::Bus, name);
+}
+
+fn bus_playing(soloud: &Soloud, handles: &SharedAudioHandle, name: &str) -> bool {
+    let playing = handles.get(name).map(|handle| *handle.value());
+    matches!(playing, Some(handle) if soloud.is_valid_voice_handle(handle))
+}
+
+// Puts the new filter everywhere the old one was attached, so nothing is left pointing at the old one
+fn replace_filter(soloud: &Soloud, filters: &SharedAudioFilter, targets: &(SharedAudioWav, SharedAudioWavStream, SharedAudioBus), state: &SharedFilterState, name: String, filter: AudioFilter) {
+    let mut state = state.borrow_mut();
+    for (target, target_name, slot) in state.attached_to(&name) {
+        match target {
+            FilterTarget::Sound => { if let Some(mut wav) = targets.0.get_mut(&target_name) { filter.attach(&mut *wav, slot); } },
+            FilterTarget::Music => { if let Some(mut wav) = targets.1.get_mut(&target_name) { filter.attach(&mut *wav, slot); } },
+            FilterTarget::Bus => { if let Some(mut bus) = targets.2.get_mut(&target_name) { filter.attach(&mut *bus, slot); } },
+        }
+    }
+
+    if let Some(old) = filters.insert(name.clone(), filter) {
+        state.retire(soloud, old, &name);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum FilterTarget {
+    Sound,
+    Music,
+    Bus,
+}
+
+// Soloud keeps pointers to the filters attached to sounds and buses, and voices can keep using them after they start.
+// So a replaced filter is kept here until every voice that might be using it has stopped.
+#[derive(Default)]
+struct FilterState {
+    // The filter name in every slot of every sound, music and bus
+    attachments: HashMap<(FilterTarget, String, u32), String>,
+    // Voices started from something with filters attached, and the names of the filters it had
+    voices: Vec<(Handle, Vec<String>)>,
+    // Replaced filters and the voices that were using them
+    retired: Vec<(AudioFilter, Vec<Handle>)>,
+}
+
+type SharedFilterState = Rc<RefCell<FilterState>>;
+
+impl FilterState {
+    fn set_attachment(&mut self, target: FilterTarget, name: &str, slot: u32, filter_name: Option<String>) {
+        match filter_name {
+            Some(filter_name) => { self.attachments.insert((target, name.to_string(), slot), filter_name); },
+            None => { self.attachments.remove(&(target, name.to_string(), slot)); },
+        }
+    }
+
+    // A sound, music or bus was replaced or removed, so its filters went with it
+    fn forget(&mut self, target: FilterTarget, name: &str) {
+        self.attachments.retain(|(t, n, _), _| !(*t == target && n == name));
+    }
+
+    fn attached_to(&self, filter_name: &str) -> Vec<(FilterTarget, String, u32)> {
+        self.attachments.iter().filter(|(_, f)| *f == filter_name).map(|((t, n, slot), _)| (*t, n.clone(), *slot)).collect()
+    }
+
+    // Remembers a voice if what it was started from has filters, and lets go of anything that has stopped
+    fn track(&mut self, soloud: &Soloud, handle: Handle, target: FilterTarget, name: &str) {
+        self.prune(soloud);
+        let filters: Vec<String> = self.attachments.iter()
+            .filter(|((t, n, _), _)| *t == target && n == name)
+            .map(|(_, filter_name)| filter_name.clone())
+            .collect();
+        if !filters.is_empty() {
+            self.voices.push((handle, filters));
+        }
+    }
+
+    // Called once the filter is swapped out everywhere, so only voices that already started can still be using it
+    fn retire(&mut self, soloud: &Soloud, filter: AudioFilter, filter_name: &str) {
+        self.prune(soloud);
+        let users: Vec<Handle> = self.voices.iter()
+            .filter(|(_, filters)| filters.iter().any(|f| f == filter_name))
+            .map(|(voice, _)| *voice)
+            .collect();
+        if !users.is_empty() {
+            self.retired.push((filter, users));
+        }
+    }
+
+    fn prune(&mut self, soloud: &Soloud) {
+        self.voices.retain(|(voice, _)| soloud.is_valid_voice_handle(*voice));
+        self.retired.retain(|(_, users)| users.iter().any(|voice| soloud.is_valid_voice_handle(*voice)));
+    }
+}
@@ -29,4 +29,38 @@ pub fn register_color(lua: &Lua) {
         Ok(Color::hsv(hue, saturation, value))
     }).unwrap();
     let _ = lua.globals().set("hsv", hsv_constructor);
+
+    // HSL //
+    let hsl_constructor = lua.create_function(|_, (hue, saturation, lightness): (f64, f64, f64)| {
+        Ok(Color::hsl(hue, saturation, lightness))
+    }).unwrap();
+    let _ = lua.globals().set("hsl", hsl_constructor);
+
+    // OKLab //
+    let oklab_constructor = lua.create_function(|_, (lightness, a, b): (f64, f64, f64)| {
+        Ok(Color::oklab(lightness, a, b))
+    }).unwrap();
+    let _ = lua.globals().set("oklab", oklab_constructor);
+
+    // Hex //
+    let hex_constructor = lua.create_function(|_, hex: String| {
+        match Color::from_hex(&hex) {
+            Some(color) => Ok(color),
+            None => Err(LuaError::RuntimeError(format!("\"{}\" is not a hex color like #ff8800", hex))),
+        }
+    }).unwrap();
+    let _ = lua.globals().set("hex", hex_constructor);
+
+    // Gradient //
+    // gradient({rgb(0, 0, 0), rgb(255, 0, 0), rgb(255, 255, 255)}, t) with t from 0 to 1
+    let fn_gradient = lua.create_function(|_, (colors, t): (Vec<Color>, f64)| {
+        Ok(Color::gradient(&colors, t))
+    }).unwrap();
+    let _ = lua.globals().set("gradient", fn_gradient);
+
+    // Gradient Steps //
+    let fn_gradient_steps = lua.create_function(|_, (colors, count): (Vec<Color>, f64)| {
+        Ok(Color::gradient_steps(&colors, f64::max(count, 0.0) as usize))
+    }).unwrap();
+    let _ = lua.globals().set("gradient_steps", fn_gradient_steps);
 }
\ No newline at end of file
@@ -0,0 +1,43 @@
+use crate::curves::*;
+use crate::vector2::*;
+use crate::api_drawing::points_from_table;
+use crate::api_math::create_vector_function;
+
+use mlua::prelude::*;
+
+pub fn register_curve(lua: &Lua) {
+    println!("Registering API: Curve");
+
+    // Beizer //
+    let fn_new_beizer = create_vector_function(lua, |_, (x0, y0, x1, y1, mx, my): (f64, f64, f64, f64, f64, f64)| {
+        Ok(Curve::quadratic(Vector2::new(x0, y0), Vector2::new(mx, my), Vector2::new(x1, y1)))
+    }).unwrap();
+    let _ = lua.globals().set("new_beizer", fn_new_beizer);
+
+    // Cubic Beizer //
+    let fn_new_beizer_cubic = create_vector_function(lua, |_, (x0, y0, x1, y1, mx0, my0, mx1, my1): (f64, f64, f64, f64, f64, f64, f64, f64)| {
+        Ok(Curve::cubic(Vector2::new(x0, y0), Vector2::new(mx0, my0), Vector2::new(mx1, my1), Vector2::new(x1, y1)))
+    }).unwrap();
+    let _ = lua.globals().set("new_beizer_cubic", fn_new_beizer_cubic);
+
+    // Spline //
+    // new_spline({0, 0, 32, 16, 64, 0}, false, "bspline"), kind defaults to "catmullrom"
+    let fn_new_spline = lua.create_function(|_, (points, closed, kind): (LuaTable, Option<bool>, Option<String>)| {
+        let points = points_from_table(points)?;
+        let kind = SplineKind::from_str(kind.unwrap_or("catmullrom".to_string()).as_str());
+        Ok(Curve::spline(&points, kind, closed.unwrap_or(false)))
+    }).unwrap();
+    let _ = lua.globals().set("new_spline", fn_new_spline);
+
+    // Arc //
+    let fn_new_arc = create_vector_function(lua, |_, (xc, yc, r, start_angle, end_angle): (f64, f64, f64, f64, f64)| {
+        Ok(Curve::arc(Vector2::new(xc, yc), r, start_angle, end_angle))
+    }).unwrap();
+    let _ = lua.globals().set("new_arc", fn_new_arc);
+
+    // Ellipse //
+    let fn_new_ellipse = create_vector_function(lua, |_, (xc, yc, rx, ry, rotation): (f64, f64, f64, f64, Option<f64>)| {
+        Ok(Curve::ellipse(Vector2::new(xc, yc), rx, ry, rotation.unwrap_or(0.0)))
+    }).unwrap();
+    let _ = lua.globals().set("new_ellipse", fn_new_ellipse);
+}
@@ -1,10 +1,15 @@
 use crate::color::*;
 use crate::rasterizer::*;
 use crate::font::*;
+use crate::shapes::*;
+use crate::curves::*;
+use crate::vector2::*;
+use crate::matrix3::*;
 
 use mlua::prelude::*;
 
 use crate::api_shareables::*;
+use crate::api_math::create_vector_function;
 
 pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasterizer, lua: &Lua) {
     println!("Registering API: Drawing");
@@ -31,6 +36,15 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
     } ).unwrap();
     let _ = lua.globals().set("set_core_limit", fn_set_draw_mode_noop);
 
+    // Deferred Drawing //
+    // Draw calls are recorded and drawn in parallel bands of the screen after _draw, turning it off draws everything right away
+    let rst = rasterizer.clone();
+    let fn_set_deferred_drawing = lua.create_function(move |_, enabled: bool| {
+        rst.borrow_mut().set_deferred(enabled);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_deferred_drawing", fn_set_deferred_drawing);
+
     // Draw Mode: No Operation //
     let rst = rasterizer.clone();
     let fn_set_draw_mode_noop = lua.create_function(move |_, ()| {
@@ -87,6 +101,30 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
     } ).unwrap();
     let _ = lua.globals().set("set_draw_mode_force_tint", fn_set_draw_mode_force_tint);
 
+    // Image Sampling: Nearest //
+    let rst = rasterizer.clone();
+    let fn_set_image_sampling_nearest = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_image_sampling(ImageSampling::Nearest);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_image_sampling_nearest", fn_set_image_sampling_nearest);
+
+    // Image Sampling: Bilinear //
+    let rst = rasterizer.clone();
+    let fn_set_image_sampling_bilinear = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_image_sampling(ImageSampling::Bilinear);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_image_sampling_bilinear", fn_set_image_sampling_bilinear);
+
+    // Image Sampling: RotSprite //
+    let rst = rasterizer.clone();
+    let fn_set_image_sampling_rotsprite = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_image_sampling(ImageSampling::RotSprite);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_image_sampling_rotsprite", fn_set_image_sampling_rotsprite);
+
     // Clear //
     let rst = rasterizer.clone();
     let fn_clear = lua.create_function(move |_, ()| {
@@ -122,7 +160,7 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // Set Camera Position //
     let rst = rasterizer.clone();
-    let fn_set_camera_position = lua.create_function(move |_, (x, y): (f64, f64)| {
+    let fn_set_camera_position = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
         rst.borrow_mut().set_camera_position(x, y);
         Ok(())
     } ).unwrap();
@@ -138,16 +176,199 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // Set Camera Scale //
     let rst = rasterizer.clone();
-    let fn_set_camera_scale = lua.create_function(move |_, (x, y): (f64, f64)| {
+    let fn_set_camera_scale = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
         rst.borrow_mut().set_camera_scale(x, y);
         Ok(())
     } ).unwrap();
     let _ = lua.globals().set("set_camera_scale", fn_set_camera_scale);
 
+    // Draw Space: World //
+    let rst = rasterizer.clone();
+    let fn_set_draw_space_world = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_draw_space(DrawSpace::World);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_draw_space_world", fn_set_draw_space_world);
+
+    // Draw Space: Screen //
+    let rst = rasterizer.clone();
+    let fn_set_draw_space_screen = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_draw_space(DrawSpace::Screen);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_draw_space_screen", fn_set_draw_space_screen);
+
+    // Push Transform //
+    let rst = rasterizer.clone();
+    let fn_push_transform = lua.create_function(move |_, ()| {
+        rst.borrow_mut().push_transform();
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("push_transform", fn_push_transform);
+
+    // Pop Transform //
+    let rst = rasterizer.clone();
+    let fn_pop_transform = lua.create_function(move |_, ()| {
+        rst.borrow_mut().pop_transform();
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pop_transform", fn_pop_transform);
+
+    // Reset Transform //
+    let rst = rasterizer.clone();
+    let fn_reset_transform = lua.create_function(move |_, ()| {
+        rst.borrow_mut().reset_transform();
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("reset_transform", fn_reset_transform);
+
+    // Apply Transform //
+    let rst = rasterizer.clone();
+    let fn_apply_transform = lua.create_function(move |_, mtx: Matrix3| {
+        rst.borrow_mut().apply_transform(mtx);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("apply_transform", fn_apply_transform);
+
+    // Get Transform //
+    let rst = rasterizer.clone();
+    let fn_get_transform = lua.create_function(move |_, ()| {
+        Ok(rst.borrow().rasterizer.transform_stack.last().copied().unwrap_or(Matrix3::identity()))
+    } ).unwrap();
+    let _ = lua.globals().set("get_transform", fn_get_transform);
+
+    // Translate //
+    let rst = rasterizer.clone();
+    let fn_translate = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
+        rst.borrow_mut().translate(x, y);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("translate", fn_translate);
+
+    // Rotate //
+    let rst = rasterizer.clone();
+    let fn_rotate = lua.create_function(move |_, radians: f64| {
+        rst.borrow_mut().rotate(radians);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("rotate", fn_rotate);
+
+    // Scale //
+    let rst = rasterizer.clone();
+    let fn_scale = create_vector_function(lua, move |_, (x, y): (f64, Option<f64>)| {
+        rst.borrow_mut().scale(x, y.unwrap_or(x));
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("scale", fn_scale);
+
+    // Shear //
+    let rst = rasterizer.clone();
+    let fn_shear = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
+        rst.borrow_mut().shear(x, y);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("shear", fn_shear);
+
+    // Set Clip //
+    let rst = rasterizer.clone();
+    let fn_set_clip = create_vector_function(lua, move |_, (x, y, w, h): (f64, f64, f64, f64)| {
+        rst.borrow_mut().set_clip(x as i64, y as i64, w as i64, h as i64);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_clip", fn_set_clip);
+
+    // Push Clip //
+    let rst = rasterizer.clone();
+    let fn_push_clip = create_vector_function(lua, move |_, (x, y, w, h): (f64, f64, f64, f64)| {
+        rst.borrow_mut().push_clip(x as i64, y as i64, w as i64, h as i64);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("push_clip", fn_push_clip);
+
+    // Pop Clip //
+    let rst = rasterizer.clone();
+    let fn_pop_clip = lua.create_function(move |_, ()| {
+        rst.borrow_mut().pop_clip();
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pop_clip", fn_pop_clip);
+
+    // Clear Clip //
+    let rst = rasterizer.clone();
+    let fn_clear_clip = lua.create_function(move |_, ()| {
+        rst.borrow_mut().clear_clip();
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("clear_clip", fn_clear_clip);
+
+    // Stencil Mode: Off //
+    let rst = rasterizer.clone();
+    let fn_set_stencil_mode_off = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_stencil_mode(StencilMode::Off);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_stencil_mode_off", fn_set_stencil_mode_off);
+
+    // Stencil Mode: Write //
+    let rst = rasterizer.clone();
+    let fn_set_stencil_mode_write = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_stencil_mode(StencilMode::Write);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_stencil_mode_write", fn_set_stencil_mode_write);
+
+    // Stencil Mode: Erase //
+    let rst = rasterizer.clone();
+    let fn_set_stencil_mode_erase = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_stencil_mode(StencilMode::Erase);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_stencil_mode_erase", fn_set_stencil_mode_erase);
+
+    // Stencil Mode: Inside //
+    let rst = rasterizer.clone();
+    let fn_set_stencil_mode_inside = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_stencil_mode(StencilMode::Inside);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_stencil_mode_inside", fn_set_stencil_mode_inside);
+
+    // Stencil Mode: Outside //
+    let rst = rasterizer.clone();
+    let fn_set_stencil_mode_outside = lua.create_function(move |_, ()| {
+        rst.borrow_mut().set_stencil_mode(StencilMode::Outside);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("set_stencil_mode_outside", fn_set_stencil_mode_outside);
+
+    // Clear Stencil //
+    let rst = rasterizer.clone();
+    let fn_clear_stencil = lua.create_function(move |_, ()| {
+        rst.borrow_mut().clear_stencil();
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("clear_stencil", fn_clear_stencil);
+
+    // Screen To World //
+    let rst = rasterizer.clone();
+    let fn_screen_to_world = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
+        let world = rst.borrow().screen_to_world(x, y);
+        Ok((world.x, world.y))
+    } ).unwrap();
+    let _ = lua.globals().set("screen_to_world", fn_screen_to_world);
+
+    // World To Screen //
+    let rst = rasterizer.clone();
+    let fn_world_to_screen = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
+        let screen = rst.borrow().world_to_screen(x, y);
+        Ok((screen.x, screen.y))
+    } ).unwrap();
+    let _ = lua.globals().set("world_to_screen", fn_world_to_screen);
+
     // blit sprite //
     let rst = rasterizer.clone();
     let imga = assets_images.clone();
-    let fn_blit = lua.create_function(move |_, (name, x, y): (String, f64, f64)| {
+    let fn_blit = create_vector_function(lua, move |_, (name, x, y): (String, f64, f64)| {
         let img_result = imga.get(&name);
         if img_result.is_some() {
             rst.borrow_mut().blit(&img_result.unwrap(), x as i64, y as i64);
@@ -159,7 +380,7 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // pset //
     let rst = rasterizer.clone();
-    let fn_pset = lua.create_function(move |_, (x, y, color): (i64, i64, Color)| {
+    let fn_pset = create_vector_function(lua, move |_, (x, y, color): (i64, i64, Color)| {
         rst.borrow_mut().pset(x, y, color);
         Ok(())
     } ).unwrap();
@@ -167,7 +388,7 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // pline //
     let rst = rasterizer.clone();
-    let fn_pline = lua.create_function(move |_, (x0, y0, x1, y1, color): (i64, i64, i64, i64, Color)| {
+    let fn_pline = create_vector_function(lua, move |_, (x0, y0, x1, y1, color): (i64, i64, i64, i64, Color)| {
         rst.borrow_mut().pline(x0, y0, x1, y1, color);
         Ok(())
     } ).unwrap();
@@ -175,7 +396,7 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // pcircle //
     let rst = rasterizer.clone();
-    let fn_pcircle = lua.create_function(move |_, (filled, xc, yc, r, color): (bool, i64, i64, i64, Color)| {
+    let fn_pcircle = create_vector_function(lua, move |_, (filled, xc, yc, r, color): (bool, i64, i64, i64, Color)| {
         rst.borrow_mut().pcircle(filled, xc, yc, r, color);
         Ok(())
     } ).unwrap();
@@ -183,7 +404,7 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // prectangle //
     let rst = rasterizer.clone();
-    let fn_prectangle = lua.create_function(move |_, (filled, x, y, w, h, color): (bool, i64, i64, i64, i64, Color)| {
+    let fn_prectangle = create_vector_function(lua, move |_, (filled, x, y, w, h, color): (bool, i64, i64, i64, i64, Color)| {
         rst.borrow_mut().prectangle(filled, x, y, w, h, color);
         Ok(())
     } ).unwrap();
@@ -191,24 +412,148 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     // ptriangle //
     let rst = rasterizer.clone();
-    let fn_ptriangle = lua.create_function(move |_, (filled, x0, y0, x1, y1, x2, y2, color): (bool, i64, i64, i64, i64, i64, i64, Color)| {
-        rst.borrow_mut().rasterizer.ptriangle(filled, x0, y0, x1, y1, x2, y2, color);
+    let fn_ptriangle = create_vector_function(lua, move |_, (filled, x0, y0, x1, y1, x2, y2, color): (bool, i64, i64, i64, i64, i64, i64, Color)| {
+        rst.borrow_mut().ptriangle(filled, x0, y0, x1, y1, x2, y2, color);
         Ok(())
     } ).unwrap();
     let _ = lua.globals().set("ptriangle", fn_ptriangle);
 
+    // draw_mesh //
+    // draw_mesh("sprite", {{x, y, u, v, color, w}, ...}, {1, 2, 3, ...}, "affine"), the image can be nil to only use vertex colors
+    let rst = rasterizer.clone();
+    let imga = assets_images.clone();
+    let fn_draw_mesh = lua.create_function(move |_, (name, vertices, indices, mapping): (Option<String>, LuaTable, Option<LuaTable>, Option<String>)| {
+        let vertices = vertices_from_table(vertices)?;
+        let indices: Vec<usize> = if indices.is_some() {
+            let mut out: Vec<usize> = Vec::new();
+            for index in indices.unwrap().sequence_values::<i64>() {
+                let index = index?;
+                if index < 1 { return Err(LuaError::RuntimeError(format!("mesh index {} is below 1", index))); }
+                out.push(index as usize - 1);
+            }
+            out
+        } else { (0..vertices.len()).collect() };
+        let mapping = TextureMapping::from_str(mapping.unwrap_or("affine".to_string()).as_str());
+
+        if name.is_some() {
+            let img_result = imga.get(&name.unwrap());
+            if img_result.is_some() {
+                rst.borrow_mut().pmesh(Some(&img_result.unwrap()), &vertices, &indices, mapping);
+            }
+        } else {
+            rst.borrow_mut().pmesh(None, &vertices, &indices, mapping);
+        }
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("draw_mesh", fn_draw_mesh);
+
     // pbeizer //
     let rst = rasterizer.clone();
-    let fn_pbeizer = lua.create_function(move |_, (x0, y0, x1, y1, mx, my, color): (i64, i64, i64, i64, i64, i64, Color)| {
-        rst.borrow_mut().rasterizer.pbeizer(x0, y0, x1, y1, mx, my, color);
+    let fn_pbeizer = create_vector_function(lua, move |_, (x0, y0, x1, y1, mx, my, color, width): (f64, f64, f64, f64, f64, f64, Color, Option<f64>)| {
+        let curve = Curve::quadratic(Vector2::new(x0, y0), Vector2::new(mx, my), Vector2::new(x1, y1));
+        rst.borrow_mut().pcurve(&curve, width.unwrap_or(1.0), color);
         Ok(())
     } ).unwrap();
     let _ = lua.globals().set("pbeizer", fn_pbeizer);
 
+    // pbeizer_cubic //
+    let rst = rasterizer.clone();
+    let fn_pbeizer_cubic = create_vector_function(lua, move |_, (x0, y0, x1, y1, mx0, my0, mx1, my1, color, width): (f64, f64, f64, f64, f64, f64, f64, f64, Color, Option<f64>)| {
+        let curve = Curve::cubic(Vector2::new(x0, y0), Vector2::new(mx0, my0), Vector2::new(mx1, my1), Vector2::new(x1, y1));
+        rst.borrow_mut().pcurve(&curve, width.unwrap_or(1.0), color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pbeizer_cubic", fn_pbeizer_cubic);
+
+    // pspline //
+    // pspline({0, 0, 32, 16, 64, 0}, color, 2, false, "catmullrom")
+    let rst = rasterizer.clone();
+    let fn_pspline = lua.create_function(move |_, (points, color, width, closed, kind): (LuaTable, Color, Option<f64>, Option<bool>, Option<String>)| {
+        let points = points_from_table(points)?;
+        let kind = SplineKind::from_str(kind.unwrap_or("catmullrom".to_string()).as_str());
+        let curve = Curve::spline(&points, kind, closed.unwrap_or(false));
+        rst.borrow_mut().pcurve(&curve, width.unwrap_or(1.0), color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pspline", fn_pspline);
+
+    // pcurve //
+    let rst = rasterizer.clone();
+    let fn_pcurve = lua.create_function(move |_, (curve, color, width): (LuaAnyUserData, Color, Option<f64>)| {
+        let curve = curve.borrow::<Curve>()?;
+        rst.borrow_mut().pcurve(&curve, width.unwrap_or(1.0), color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pcurve", fn_pcurve);
+
+    // parc //
+    let rst = rasterizer.clone();
+    let fn_parc = create_vector_function(lua, move |_, (xc, yc, r, start_angle, end_angle, color, width): (f64, f64, f64, f64, f64, Color, Option<f64>)| {
+        rst.borrow_mut().parc(xc, yc, r, start_angle, end_angle, width.unwrap_or(1.0), color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("parc", fn_parc);
+
+    // pellipse //
+    let rst = rasterizer.clone();
+    let fn_pellipse = create_vector_function(lua, move |_, (filled, xc, yc, rx, ry, color, rotation, width): (bool, f64, f64, f64, f64, Color, Option<f64>, Option<f64>)| {
+        rst.borrow_mut().pellipse(filled, xc, yc, rx, ry, rotation.unwrap_or(0.0), width.unwrap_or(1.0), color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pellipse", fn_pellipse);
+
+    // pline_thick //
+    let rst = rasterizer.clone();
+    let fn_pline_thick = create_vector_function(lua, move |_, (x0, y0, x1, y1, width, color, cap): (f64, f64, f64, f64, f64, Color, Option<String>)| {
+        let cap = LineCap::from_str(cap.unwrap_or("butt".to_string()).as_str());
+        rst.borrow_mut().pline_thick(x0, y0, x1, y1, width, cap, color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pline_thick", fn_pline_thick);
+
+    // pline_aa //
+    let rst = rasterizer.clone();
+    let fn_pline_aa = create_vector_function(lua, move |_, (x0, y0, x1, y1, color): (f64, f64, f64, f64, Color)| {
+        rst.borrow_mut().pline_aa(x0, y0, x1, y1, color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pline_aa", fn_pline_aa);
+
+    // ppolyline //
+    // ppolyline({0, 0, 32, 16, 64, 0}, color, 4, false, "round", "round")
+    let rst = rasterizer.clone();
+    let fn_ppolyline = lua.create_function(move |_, (points, color, width, closed, join, cap): (LuaTable, Color, Option<f64>, Option<bool>, Option<String>, Option<String>)| {
+        let points = points_from_table(points)?;
+        let join = LineJoin::from_str(join.unwrap_or("miter".to_string()).as_str());
+        let cap = LineCap::from_str(cap.unwrap_or("butt".to_string()).as_str());
+        rst.borrow_mut().ppolyline(&points, width.unwrap_or(1.0), closed.unwrap_or(false), join, cap, color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("ppolyline", fn_ppolyline);
+
+    // ppolyline_aa //
+    let rst = rasterizer.clone();
+    let fn_ppolyline_aa = lua.create_function(move |_, (points, color, closed): (LuaTable, Color, Option<bool>)| {
+        let points = points_from_table(points)?;
+        rst.borrow_mut().ppolyline_aa(&points, closed.unwrap_or(false), color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("ppolyline_aa", fn_ppolyline_aa);
+
+    // ppolygon //
+    let rst = rasterizer.clone();
+    let fn_ppolygon = lua.create_function(move |_, (filled, points, color, rule): (bool, LuaTable, Color, Option<String>)| {
+        let points = points_from_table(points)?;
+        let rule = FillRule::from_str(rule.unwrap_or("nonzero".to_string()).as_str());
+        rst.borrow_mut().ppolygon(filled, &points, rule, color);
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("ppolygon", fn_ppolygon);
+
     // pimg //
     let rst = rasterizer.clone();
     let imga = assets_images.clone();
-    let fn_pimg = lua.create_function(move |_, (name, x, y): (String, f64, f64)| {
+    let fn_pimg = create_vector_function(lua, move |_, (name, x, y): (String, f64, f64)| {
         //let imga_ref = imga.get();
         let img_result = imga.get(&name);
         if img_result.is_some() {
@@ -221,7 +566,7 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
 
     let rst = rasterizer.clone();
     let imga = assets_images.clone();
-    let fn_pimgrect = lua.create_function(move |_, (name, x, y, image_x, image_y, image_width, image_height ): (String, f64, f64, f64, f64, f64, f64)| {
+    let fn_pimgrect = create_vector_function(lua, move |_, (name, x, y, image_x, image_y, image_width, image_height ): (String, f64, f64, f64, f64, f64, f64)| {
         //let imga_ref = imga.get();
         let img_result = imga.get(&name);
         if img_result.is_some() {
@@ -235,22 +580,92 @@ pub fn register_draw_api(assets_images: SharedImages, rasterizer: SharedRasteriz
     // pimgmtx //
     let imga = assets_images.clone();
     let rst = rasterizer.clone();
-    let fn_pimgmtx = lua.create_function(move |_, (name, x, y, r, sx, sy, ox, oy): (String, f64, f64, f64, f64, f64, f64, f64)| {
+    let fn_pimgmtx = create_vector_function(lua, move |_, (name, x, y, r, sx, sy, ox, oy, flip_x, flip_y): (String, f64, f64, f64, f64, f64, f64, f64, Option<bool>, Option<bool>)| {
         //let imga_ref = imga.get();
         let img_result = imga.get(&name);
         if img_result.is_some() {
-            rst.borrow_mut().pimgmtx(&img_result.unwrap(), x, y, r, sx, sy, ox, oy);
+            let image = img_result.unwrap();
+            rst.borrow_mut().pimgrectmtx(&image, x, y, r, sx, sy, ox, oy, 0, 0, image.width as i64, image.height as i64, flip_x.unwrap_or(false), flip_y.unwrap_or(false));
         }
         
         Ok(())
     } ).unwrap();
     let _ = lua.globals().set("pimgmtx", fn_pimgmtx);
 
+    // pimgrectmtx //
+    let imga = assets_images.clone();
+    let rst = rasterizer.clone();
+    let fn_pimgrectmtx = create_vector_function(lua, move |_, (name, x, y, r, sx, sy, ox, oy, image_x, image_y, image_width, image_height, flip_x, flip_y): (String, f64, f64, f64, f64, f64, f64, f64, f64, f64, f64, f64, Option<bool>, Option<bool>)| {
+        let img_result = imga.get(&name);
+        if img_result.is_some() {
+            rst.borrow_mut().pimgrectmtx(&img_result.unwrap(), x, y, r, sx, sy, ox, oy, image_x as i64, image_y as i64, image_width as i64, image_height as i64, flip_x.unwrap_or(false), flip_y.unwrap_or(false));
+        }
+
+        Ok(())
+    } ).unwrap();
+    let _ = lua.globals().set("pimgrectmtx", fn_pimgrectmtx);
+
     // pprint //
     let rst = rasterizer.clone();
-    let fn_pprint = lua.create_function(move |_, (font, text, x, y): (Font, String, f64, f64)| {
-        rst.borrow_mut().rasterizer.pprint(&font, text, x as i64, y as i64, 2, None);
+    // Fonts are borrowed instead of copied, they can carry large glyph pages and fallback fonts
+    let fn_pprint = create_vector_function(lua, move |_, (font, text, x, y, wrap_width, line_spacing): (LuaAnyUserData, String, f64, f64, Option<f64>, Option<f64>)| {
+        let font = font.borrow::<Font>()?;
+        rst.borrow_mut().pprint(&font, text, x as i64, y as i64, line_spacing.unwrap_or(2.0) as i64, wrap_width.map(|w| w as u32));
         Ok(())
     } ).unwrap();
     let _ = lua.globals().set("pprint", fn_pprint);
-}
\ No newline at end of file
+}
+
+// Points can be a flat table {x0, y0, x1, y1, ...} or a table of points {{x0, y0}, {x = x1, y = y1}, ...}
+pub fn points_from_table(points: LuaTable) -> LuaResult<Vec<Vector2>> {
+    let values: Vec<LuaValue> = points.sequence_values::<LuaValue>().collect::<LuaResult<Vec<LuaValue>>>()?;
+    let mut out: Vec<Vector2> = Vec::with_capacity(values.len());
+
+    if let Some(LuaValue::Table(_)) = values.first() {
+        for value in values {
+            let point = match value {
+                LuaValue::Table(point) => point,
+                _ => return Err(LuaError::RuntimeError("points must all be tables or all be numbers".to_string())),
+            };
+            let x: Option<f64> = point.get("x")?;
+            let y: Option<f64> = point.get("y")?;
+            let x = if x.is_some() { x.unwrap() } else { point.get::<_, f64>(1)? };
+            let y = if y.is_some() { y.unwrap() } else { point.get::<_, f64>(2)? };
+            out.push(Vector2::new(x, y));
+        }
+    } else {
+        let numbers: Vec<f64> = values.iter().map(|value| match value {
+            LuaValue::Integer(n) => Ok(*n as f64),
+            LuaValue::Number(n) => Ok(*n),
+            _ => Err(LuaError::RuntimeError("points must all be tables or all be numbers".to_string())),
+        }).collect::<LuaResult<Vec<f64>>>()?;
+        out.extend(numbers.chunks_exact(2).map(|xy| Vector2::new(xy[0], xy[1])));
+    }
+
+    Ok(out)
+}
+
+// Vertices are tables of {x, y, u, v, color, w} or {x = x, y = y, u = u, v = v, color = color, w = w}
+// Texture coordinates default to 0, colors to white and w to 1
+fn vertices_from_table(vertices: LuaTable) -> LuaResult<Vec<Vertex>> {
+    let mut out: Vec<Vertex> = Vec::new();
+    for vertex in vertices.sequence_values::<LuaTable>() {
+        let vertex = vertex?;
+        let field = |name: &str, idx: i64| -> LuaResult<Option<f64>> {
+            let named: Option<f64> = vertex.get(name)?;
+            if named.is_some() { Ok(named) } else { vertex.get(idx) }
+        };
+
+        let x = field("x", 1)?.unwrap_or(0.0);
+        let y = field("y", 2)?.unwrap_or(0.0);
+        let u = field("u", 3)?.unwrap_or(0.0);
+        let v = field("v", 4)?.unwrap_or(0.0);
+        let color: Option<Color> = vertex.get("color")?;
+        let color = if color.is_some() { color } else { vertex.get(5)? };
+
+        let mut out_vertex = Vertex::new(x, y, u, v, color.unwrap_or(Color::white()));
+        out_vertex.w = field("w", 6)?.unwrap_or(1.0);
+        out.push(out_vertex);
+    }
+    Ok(out)
+}
@@ -0,0 +1,338 @@
+use crate::color::Color;
+use crate::ecs::*;
+use crate::entities::*;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+
+pub fn register_ecs(entities: SharedEntities, assets_images: SharedImages, rasterizer: SharedRasterizer, lua: &Lua) {
+    println!("Registering API: ECS");
+
+    // Define Components //
+    // ecs_component("health", {hp = 100, max_hp = 100})
+    // Every entity gets its own copy of the defaults, and can only have the fields the defaults have
+    // Redefining a component changes its defaults, entities that already have it keep theirs
+    let ents = entities.clone();
+    let fn_ecs_component = lua.create_function(move |lua, (name, defaults): (String, LuaTable)| {
+        if BuiltInComponent::from_str(&name).is_some() {
+            return Err(LuaError::RuntimeError(format!("ecs_component: {} is a built in component", name)));
+        }
+        let defaults = lua.create_registry_value(defaults)?;
+        let mut ents = ents.borrow_mut();
+        match ents.components.get_mut(&name) {
+            Some(component) => component.defaults = defaults,
+            None => { ents.components.insert(name, LuaComponent { defaults, store: ComponentStore::new() }); },
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_component", fn_ecs_component);
+
+    // Spawn Entity //
+    // ecs_spawn({transform = {x = 10, y = 20}, sprite = {image = "ship"}, health = {hp = 50}, player = true})
+    // Returns the new entity's id. Ids of destroyed entities are never valid again, even once their slot is reused
+    let ents = entities.clone();
+    let fn_ecs_spawn = lua.create_function(move |lua, components: Option<LuaTable>| {
+        let entity = ents.borrow_mut().world.spawn();
+        if let Some(components) = components {
+            for pair in components.pairs::<String, LuaValue>() {
+                let added = pair.and_then(|(name, data)| add_component(lua, &ents, entity, &name, data, "ecs_spawn"));
+                // Don't leave half made entities around
+                if let Err(e) = added {
+                    ents.borrow_mut().destroy(entity);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(entity.to_bits())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_spawn", fn_ecs_spawn);
+
+    // Destroy Entity //
+    let ents = entities.clone();
+    let fn_ecs_destroy = lua.create_function(move |_, id: u64| {
+        ents.borrow_mut().destroy(Entity::from_bits(id));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_destroy", fn_ecs_destroy);
+
+    let ents = entities.clone();
+    let fn_ecs_alive = lua.create_function(move |_, id: u64| {
+        Ok(ents.borrow().world.is_alive(Entity::from_bits(id)))
+    }).unwrap();
+    let _ = lua.globals().set("ecs_alive", fn_ecs_alive);
+
+    let ents = entities.clone();
+    let fn_ecs_count = lua.create_function(move |_, ()| {
+        Ok(ents.borrow().world.entity_count())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_count", fn_ecs_count);
+
+    // Destroys every entity, component definitions and systems are kept
+    let ents = entities.clone();
+    let fn_ecs_clear = lua.create_function(move |_, ()| {
+        ents.borrow_mut().clear();
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_clear", fn_ecs_clear);
+
+    // Add and Remove Components //
+    // Adding a component an entity already has sets the given fields and leaves the rest
+    let ents = entities.clone();
+    let fn_ecs_add = lua.create_function(move |lua, (id, name, data): (u64, String, LuaValue)| {
+        add_component(lua, &ents, Entity::from_bits(id), &name, data, "ecs_add")
+    }).unwrap();
+    let _ = lua.globals().set("ecs_add", fn_ecs_add);
+
+    let ents = entities.clone();
+    let fn_ecs_remove = lua.create_function(move |_, (id, name): (u64, String)| {
+        ents.borrow_mut().remove(Entity::from_bits(id), &name).ok_or_else(|| unknown_component("ecs_remove", &name))
+    }).unwrap();
+    let _ = lua.globals().set("ecs_remove", fn_ecs_remove);
+
+    let ents = entities.clone();
+    let fn_ecs_has = lua.create_function(move |_, (id, name): (u64, String)| {
+        let ents = ents.borrow();
+        let entity = Entity::from_bits(id);
+        Ok(ents.world.is_alive(entity) && ents.has(entity, &name).ok_or_else(|| unknown_component("ecs_has", &name))?)
+    }).unwrap();
+    let _ = lua.globals().set("ecs_has", fn_ecs_has);
+
+    // Get Components //
+    // Returns the component itself, so changing its fields changes the entity. Nil if the entity doesn't have it
+    let ents = entities.clone();
+    let fn_ecs_get = lua.create_function(move |lua, (id, name): (u64, String)| {
+        let entity = Entity::from_bits(id);
+        if ents.borrow().has(entity, &name).ok_or_else(|| unknown_component("ecs_get", &name))? {
+            component_value(lua, &ents, entity, &name)
+        } else {
+            Ok(LuaValue::Nil)
+        }
+    }).unwrap();
+    let _ = lua.globals().set("ecs_get", fn_ecs_get);
+
+    // Queries //
+    // ecs_query({"transform", "health"}) returns the ids of every entity with all of them
+    let ents = entities.clone();
+    let fn_ecs_query = lua.create_function(move |_, names: Vec<String>| {
+        let matching = ents.borrow().query(&names).map_err(|e| LuaError::RuntimeError(format!("ecs_query: {}", e)))?;
+        Ok(matching.into_iter().map(|entity| entity.to_bits()).collect::<Vec<u64>>())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_query", fn_ecs_query);
+
+    // ecs_each({"transform", "health"}, function(id, transform, health) ... end)
+    let ents = entities.clone();
+    let fn_ecs_each = lua.create_function(move |lua, (names, function): (Vec<String>, LuaFunction)| {
+        run_query(lua, &ents, &names, &function, None, "ecs_each")
+    }).unwrap();
+    let _ = lua.globals().set("ecs_each", fn_ecs_each);
+
+    // Systems //
+    // ecs_system("regen", {"health"}, function(id, health, delta) ... end)
+    // Systems run every update in the order they were added, before velocities move entities and collisions are found
+    // Adding a system with a name that's taken replaces it, keeping its place in the order
+    let ents = entities.clone();
+    let fn_ecs_system = lua.create_function(move |lua, (name, components, function): (String, Vec<String>, LuaFunction)| {
+        let mut ents = ents.borrow_mut();
+        if let Some(missing) = components.iter().find(|component| !ents.is_component(component)) {
+            return Err(unknown_component("ecs_system", missing));
+        }
+        let system = LuaSystem { name: name.clone(), components, function: lua.create_registry_value(function)?, enabled: true };
+        match ents.systems.iter_mut().find(|other| other.name == name) {
+            Some(existing) => *existing = system,
+            None => ents.systems.push(system),
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_system", fn_ecs_system);
+
+    let ents = entities.clone();
+    let fn_ecs_system_enabled = lua.create_function(move |_, (name, enabled): (String, bool)| {
+        if let Some(system) = ents.borrow_mut().systems.iter_mut().find(|system| system.name == name) {
+            system.enabled = enabled;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_system_enabled", fn_ecs_system_enabled);
+
+    let ents = entities.clone();
+    let fn_ecs_system_remove = lua.create_function(move |_, name: String| {
+        ents.borrow_mut().systems.retain(|system| system.name != name);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("ecs_system_remove", fn_ecs_system_remove);
+
+    // Collisions //
+    // Found every update between entities with a transform and a collider
+    // With an id, the ids of the entities touching it. Without one, every touching pair as {a, b}
+    let ents = entities.clone();
+    let fn_ecs_collisions = lua.create_function(move |lua, id: Option<u64>| {
+        let ents = ents.borrow();
+        let collisions = lua.create_table()?;
+        match id {
+            Some(id) => {
+                for (i, other) in ents.world.collisions_with(Entity::from_bits(id)).into_iter().enumerate() {
+                    collisions.set(i + 1, other.to_bits())?;
+                }
+            },
+            None => {
+                for (i, (a, b)) in ents.world.collisions.iter().enumerate() {
+                    collisions.set(i + 1, vec![a.to_bits(), b.to_bits()])?;
+                }
+            },
+        }
+        Ok(collisions)
+    }).unwrap();
+    let _ = lua.globals().set("ecs_collisions", fn_ecs_collisions);
+
+    // Draw Entities //
+    // Draws every visible sprite at its entity's transform, lowest layer first, using the current draw mode
+    let ents = entities.clone();
+    let imgs = assets_images.clone();
+    let rst = rasterizer.clone();
+    let fn_draw_entities = lua.create_function(move |_, ()| {
+        let image = |name: &str| imgs.get(name).map(|image| image.clone());
+        ents.borrow().world.draw(&mut rst.borrow_mut(), &image);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("draw_entities", fn_draw_entities);
+
+    // Outlines every collider, for debugging
+    let ents = entities.clone();
+    let rst = rasterizer.clone();
+    let fn_draw_colliders = lua.create_function(move |_, color: Color| {
+        ents.borrow().world.draw_colliders(&mut rst.borrow_mut(), color);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("draw_colliders", fn_draw_colliders);
+}
+
+/// Runs every enabled system, then moves entities by their velocities and finds collisions.
+pub fn update_ecs(entities: &SharedEntities, lua: &Lua, dt: f64) -> LuaResult<()> {
+    let systems = {
+        let ents = entities.borrow();
+        let mut systems = Vec::new();
+        for system in ents.systems.iter().filter(|system| system.enabled) {
+            systems.push((system.name.clone(), system.components.clone(), lua.registry_value::<LuaFunction>(&system.function)?));
+        }
+        systems
+    };
+    for (name, components, function) in systems {
+        run_query(lua, entities, &components, &function, Some(dt), &name)?;
+    }
+
+    entities.borrow_mut().world.update(dt);
+    Ok(())
+}
+
+// Calls the function with the id and components of every entity the query matches, plus dt for systems.
+// Nothing is borrowed during the calls, so they can spawn, destroy and change components freely.
+fn run_query(lua: &Lua, entities: &SharedEntities, names: &[String], function: &LuaFunction, dt: Option<f64>, caller: &str) -> LuaResult<()> {
+    let matching = entities.borrow().query(names).map_err(|e| LuaError::RuntimeError(format!("{}: {}", caller, e)))?;
+    for entity in matching {
+        // An earlier call might have destroyed it or taken one of its components
+        if !entities.borrow().matches(entity, names) { continue; }
+
+        let mut args = Vec::with_capacity(names.len() + 2);
+        args.push(entity.to_bits().to_lua(lua)?);
+        for name in names {
+            args.push(component_value(lua, entities, entity, name)?);
+        }
+        if let Some(dt) = dt { args.push(LuaValue::Number(dt)); }
+        function.call::<_, ()>(LuaMultiValue::from_vec(args))?;
+    }
+    Ok(())
+}
+
+// A Lua component's table, or a live view of a built in one
+fn component_value<'lua>(lua: &'lua Lua, entities: &SharedEntities, entity: Entity, name: &str) -> LuaResult<LuaValue<'lua>> {
+    match BuiltInComponent::from_str(name) {
+        Some(component) => lua.create_userdata(ComponentRef { entities: entities.clone(), entity, component }).map(LuaValue::UserData),
+        None => match entities.borrow().components.get(name).and_then(|component| component.store.get(entity)) {
+            Some(key) => lua.registry_value(key),
+            None => Ok(LuaValue::Nil),
+        },
+    }
+}
+
+// Gives an entity a component, with the fields in data set. Data can be a table, or nil or true for all defaults
+fn add_component(lua: &Lua, entities: &SharedEntities, entity: Entity, name: &str, data: LuaValue, function: &str) -> LuaResult<()> {
+    let data = match data {
+        LuaValue::Nil | LuaValue::Boolean(true) => None,
+        LuaValue::Table(data) => Some(data),
+        _ => return Err(LuaError::RuntimeError(format!("{}: {} takes a table of fields", function, name))),
+    };
+    if !entities.borrow().world.is_alive(entity) { return Ok(()); }
+
+    if let Some(component) = BuiltInComponent::from_str(name) {
+        // Fields are checked first so a typo doesn't leave the component half added
+        if let Some(data) = &data {
+            for pair in data.clone().pairs::<String, LuaValue>() {
+                let (field, _) = pair?;
+                if !component.can_set(&field) { return Err(LuaError::RuntimeError(format!("{}: {} has no field \"{}\"", function, name, field))); }
+            }
+        }
+        let mut ents = entities.borrow_mut();
+        component.add_default(&mut ents.world, entity);
+        if let Some(data) = data {
+            for pair in data.pairs::<String, LuaValue>() {
+                let (field, value) = pair?;
+                component.set(lua, &mut ents.world, entity, &field, value).map_err(|e| prefix_error(function, e))?;
+            }
+        }
+        return Ok(());
+    }
+
+    let (defaults, existing) = {
+        let ents = entities.borrow();
+        let component = ents.components.get(name).ok_or_else(|| unknown_component(function, name))?;
+        let existing = match component.store.get(entity) {
+            Some(key) => Some(lua.registry_value::<LuaTable>(key)?),
+            None => None,
+        };
+        (lua.registry_value::<LuaTable>(&component.defaults)?, existing)
+    };
+
+    // A shallow copy of the defaults, so tables in them are shared between entities
+    let is_new = existing.is_none();
+    let table = match existing {
+        Some(table) => table,
+        None => {
+            let table = lua.create_table()?;
+            for pair in defaults.clone().pairs::<LuaValue, LuaValue>() {
+                let (field, value) = pair?;
+                table.set(field, value)?;
+            }
+            table
+        },
+    };
+    if let Some(data) = data {
+        for pair in data.pairs::<String, LuaValue>() {
+            let (field, value) = pair?;
+            if matches!(defaults.raw_get::<_, LuaValue>(field.as_str())?, LuaValue::Nil) {
+                return Err(LuaError::RuntimeError(format!("{}: {} has no field \"{}\"", function, name, field)));
+            }
+            table.set(field, value)?;
+        }
+    }
+
+    if is_new {
+        let key = lua.create_registry_value(table)?;
+        if let Some(component) = entities.borrow_mut().components.get_mut(name) {
+            component.store.insert(entity, key);
+        }
+    }
+    Ok(())
+}
+
+// Built in components' errors don't know which function they came from
+fn prefix_error(function: &str, e: LuaError) -> LuaError {
+    match e {
+        LuaError::RuntimeError(message) => LuaError::RuntimeError(format!("{}: {}", function, message)),
+        e => e,
+    }
+}
+
+fn unknown_component(function: &str, name: &str) -> LuaError {
+    LuaError::RuntimeError(format!("{}: no component called \"{}\"", function, name))
+}
@@ -2,7 +2,9 @@ use crate::font::*;
 
 use mlua::prelude::*;
 
-pub fn register_font(lua: &Lua) {
+use crate::api_shareables::*;
+
+pub fn register_font(font_cache: SharedFontCache, lua: &Lua) {
     println!("Registering API: Font");
 
     let fn_font_load = lua.create_function(move |_, (path_to, glyph_sequence, glyph_width, glyph_height, glyph_spacing): (String, String, f64, f64, f64)| {
@@ -13,4 +15,33 @@ pub fn register_font(lua: &Lua) {
         
     }).unwrap();
     let _ = lua.globals().set("load_font", fn_font_load);
-}
\ No newline at end of file
+
+    let fn_font_load_bmfont = lua.create_function(move |_, path_to: String| {
+        let font_result = Font::new_bmfont(path_to.as_str());
+        if font_result.is_ok() {
+            Ok(font_result.unwrap())
+        } else { /* Handled by Font */ Ok(Font::default()) }
+    }).unwrap();
+    let _ = lua.globals().set("load_bmfont", fn_font_load_bmfont);
+
+    // TTF fonts are cached per file and size, loading the same one twice is free
+    let fonts = font_cache.clone();
+    let fn_font_load_ttf = lua.create_function(move |_, (path_to, point_size, glyph_sequence, glyph_spacing): (String, f32, Option<String>, Option<f64>)| {
+        let glyph_sequence = glyph_sequence.unwrap_or(TTF_DEFAULT_GLYPHIDX.to_string());
+        let font_result = fonts.borrow_mut().load_ttf(Some(path_to.as_str()), glyph_sequence.as_str(), glyph_spacing.unwrap_or(0.0) as i64, point_size);
+        if font_result.is_ok() {
+            Ok(font_result.unwrap())
+        } else { /* Handled by Font */ Ok(Font::default()) }
+    }).unwrap();
+    let _ = lua.globals().set("load_ttf", fn_font_load_ttf);
+
+    // Built-in ABeeZee font
+    let fonts = font_cache.clone();
+    let fn_font_default = lua.create_function(move |_, point_size: Option<f32>| {
+        let font_result = fonts.borrow_mut().load_ttf(None, TTF_DEFAULT_GLYPHIDX, 0, point_size.unwrap_or(16.0));
+        if font_result.is_ok() {
+            Ok(font_result.unwrap())
+        } else { Ok(Font::default()) }
+    }).unwrap();
+    let _ = lua.globals().set("default_font", fn_font_default);
+}
@@ -2,24 +2,31 @@ use crate::{color::Color, rasterizer::{Rasterizer, DrawMode}};
 
 use mlua::prelude::*;
 
+use std::sync::Arc;
+
 use crate::api_shareables::*;
+use crate::api_math::create_vector_function;
 
-pub fn register_image(assets_images: SharedImages, lua: &Lua) {
+pub fn register_image(assets_images: SharedImages, palette: SharedPalette, lua: &Lua) {
     println!("Registering API: Images");
 
     let imgs = assets_images.clone();
     let fn_image_new = lua.create_function(move |_, (name, width, height): (String, f64, f64)| {
-        imgs.insert(name, Rasterizer::new(width as usize, height as usize));
+        imgs.insert(name, Arc::new(Rasterizer::new(width as usize, height as usize)));
         Ok(())
         
     }).unwrap();
     let _ = lua.globals().set("image", fn_image_new);
 
+    // In palette mode images are turned into the nearest palette colors as they load
     let imgs = assets_images.clone();
+    let pal = palette.clone();
     let fn_image_load = lua.create_function(move |_, (name, path_to): (String, String)| {
         let image_result = Rasterizer::new_from_image(&path_to);
         if image_result.is_ok() {
-            imgs.insert(name, image_result.unwrap());
+            let mut image = image_result.unwrap();
+            if pal.borrow().enabled { pal.borrow().palette.quantize(&mut image); }
+            imgs.insert(name, Arc::new(image));
             Ok(())
         } else { /* Handled by Image */ Ok(()) }
         
@@ -31,7 +38,7 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
     let fn_set_image_draw_mode_noop = lua.create_function(move |_, name: String| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().set_draw_mode(DrawMode::NoOp);
+            Arc::make_mut(&mut img_result.unwrap()).set_draw_mode(DrawMode::NoOp);
         }
         Ok(())
     } ).unwrap();
@@ -42,7 +49,7 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
     let fn_set_image_draw_mode_opaque = lua.create_function(move |_, name: String| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().set_draw_mode(DrawMode::Opaque);
+            Arc::make_mut(&mut img_result.unwrap()).set_draw_mode(DrawMode::Opaque);
         }
         Ok(())
     } ).unwrap();
@@ -53,7 +60,7 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
     let fn_set_image_draw_mode_alpha = lua.create_function(move |_, name: String| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().set_draw_mode(DrawMode::Alpha);
+            Arc::make_mut(&mut img_result.unwrap()).set_draw_mode(DrawMode::Alpha);
         }
         Ok(())
     } ).unwrap();
@@ -64,7 +71,7 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
     let fn_set_image_draw_mode_addition = lua.create_function(move |_, name: String| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().set_draw_mode(DrawMode::Addition);
+            Arc::make_mut(&mut img_result.unwrap()).set_draw_mode(DrawMode::Addition);
         }
         Ok(())
     } ).unwrap();
@@ -75,7 +82,7 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
     let fn_set_image_draw_mode_subtraction = lua.create_function(move |_, name: String| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().set_draw_mode(DrawMode::Subtraction);
+            Arc::make_mut(&mut img_result.unwrap()).set_draw_mode(DrawMode::Subtraction);
         }
         Ok(())
     } ).unwrap();
@@ -86,7 +93,7 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
     let fn_set_image_draw_mode_multiply = lua.create_function(move |_, name: String| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().set_draw_mode(DrawMode::Multiply);
+            Arc::make_mut(&mut img_result.unwrap()).set_draw_mode(DrawMode::Multiply);
         }
         Ok(())
     } ).unwrap();
@@ -94,10 +101,10 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
 
     // pset image //
     let imgs = assets_images.clone();
-    let fn_iset = lua.create_function(move |_, (name, x, y, color): (String, i64, i64, Color)| {
+    let fn_iset = create_vector_function(lua, move |_, (name, x, y, color): (String, i64, i64, Color)| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().pset(x, y, color);
+            Arc::make_mut(&mut img_result.unwrap()).pset(x, y, color);
         }
         Ok(())
     } ).unwrap();
@@ -105,10 +112,10 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
 
     // prectangle image //
     let imgs = assets_images.clone();
-    let fn_irectangle = lua.create_function(move |_, (name, filled, x, y, width, height, color): (String, bool, i64, i64, i64, i64, Color)| {
+    let fn_irectangle = create_vector_function(lua, move |_, (name, filled, x, y, width, height, color): (String, bool, i64, i64, i64, i64, Color)| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().prectangle(filled, x, y, width, height, color);
+            Arc::make_mut(&mut img_result.unwrap()).prectangle(filled, x, y, width, height, color);
         }
         Ok(())
     } ).unwrap();
@@ -116,10 +123,10 @@ pub fn register_image(assets_images: SharedImages, lua: &Lua) {
 
     // pcircle image //
     let imgs = assets_images.clone();
-    let fn_icircle = lua.create_function(move |_, (name, filled, xc, yc, radius, color): (String, bool, i64, i64, i64, Color)| {
+    let fn_icircle = create_vector_function(lua, move |_, (name, filled, xc, yc, radius, color): (String, bool, i64, i64, i64, Color)| {
         let img_result = imgs.get_mut(&name);
         if img_result.is_some() {
-            img_result.unwrap().pcircle(filled, xc, yc, radius, color);
+            Arc::make_mut(&mut img_result.unwrap()).pcircle(filled, xc, yc, radius, color);
         }
         Ok(())
     } ).unwrap();
@@ -0,0 +1,170 @@
+use crate::color::Color;
+use crate::lighting::*;
+use crate::vector2::Vector2;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+use crate::api_drawing::points_from_table;
+use crate::api_math::create_vector_function;
+
+pub fn register_lighting(lighting: SharedLighting, rasterizer: SharedRasterizer, lua: &Lua) {
+    println!("Registering API: Lighting");
+
+    // Draw Lighting //
+    // Multiplies everything drawn so far by the light, so call it after the scene and before the UI
+    let lit = lighting.clone();
+    let rst = rasterizer.clone();
+    let fn_draw_lighting = lua.create_function(move |_, ()| {
+        lit.borrow_mut().draw(&mut rst.borrow_mut());
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("draw_lighting", fn_draw_lighting);
+
+    // Ambient Light //
+    let lit = lighting.clone();
+    let fn_set_ambient_light = lua.create_function(move |_, color: Color| {
+        lit.borrow_mut().ambient = color;
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_ambient_light", fn_set_ambient_light);
+
+    // Point Light //
+    // Adding a light with a name that's already used replaces it
+    let lit = lighting.clone();
+    let fn_light_point = create_vector_function(lua, move |_, (name, x, y, radius, color, falloff, intensity): (String, f64, f64, f64, Color, Option<f64>, Option<f64>)| {
+        let mut light = Light::point(Vector2::new(x, y), radius, color);
+        light.falloff = falloff.unwrap_or(light.falloff);
+        light.intensity = intensity.unwrap_or(light.intensity);
+        lit.borrow_mut().lights.insert(name, light);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("light_point", fn_light_point);
+
+    // Spot Light //
+    let lit = lighting.clone();
+    let fn_light_spot = create_vector_function(lua, move |_, (name, x, y, radius, direction, angle, color, falloff, intensity): (String, f64, f64, f64, f64, f64, Color, Option<f64>, Option<f64>)| {
+        let mut light = Light::spot(Vector2::new(x, y), radius, direction, angle, color);
+        light.falloff = falloff.unwrap_or(light.falloff);
+        light.intensity = intensity.unwrap_or(light.intensity);
+        lit.borrow_mut().lights.insert(name, light);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("light_spot", fn_light_spot);
+
+    // Light Properties //
+    let lit = lighting.clone();
+    let fn_set_light_position = create_vector_function(lua, move |_, (name, x, y): (String, f64, f64)| {
+        if let Some(light) = lit.borrow_mut().lights.get_mut(&name) {
+            light.position = Vector2::new(x, y);
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_light_position", fn_set_light_position);
+
+    let lit = lighting.clone();
+    let fn_set_light_direction = lua.create_function(move |_, (name, direction): (String, f64)| {
+        if let Some(light) = lit.borrow_mut().lights.get_mut(&name) {
+            if let LightKind::Spot { angle, .. } = light.kind {
+                light.kind = LightKind::Spot { direction, angle };
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_light_direction", fn_set_light_direction);
+
+    let lit = lighting.clone();
+    let fn_set_light_color = lua.create_function(move |_, (name, color): (String, Color)| {
+        if let Some(light) = lit.borrow_mut().lights.get_mut(&name) {
+            light.color = color;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_light_color", fn_set_light_color);
+
+    let lit = lighting.clone();
+    let fn_set_light_radius = lua.create_function(move |_, (name, radius): (String, f64)| {
+        if let Some(light) = lit.borrow_mut().lights.get_mut(&name) {
+            light.radius = radius;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_light_radius", fn_set_light_radius);
+
+    let lit = lighting.clone();
+    let fn_set_light_intensity = lua.create_function(move |_, (name, intensity): (String, f64)| {
+        if let Some(light) = lit.borrow_mut().lights.get_mut(&name) {
+            light.intensity = intensity;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_light_intensity", fn_set_light_intensity);
+
+    let lit = lighting.clone();
+    let fn_set_light_shadows = lua.create_function(move |_, (name, shadows): (String, bool)| {
+        if let Some(light) = lit.borrow_mut().lights.get_mut(&name) {
+            light.shadows = shadows;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_light_shadows", fn_set_light_shadows);
+
+    // Remove Lights //
+    let lit = lighting.clone();
+    let fn_remove_light = lua.create_function(move |_, name: String| {
+        lit.borrow_mut().lights.remove(&name);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("remove_light", fn_remove_light);
+
+    let lit = lighting.clone();
+    let fn_clear_lights = lua.create_function(move |_, ()| {
+        lit.borrow_mut().lights.clear();
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("clear_lights", fn_clear_lights);
+
+    // Occluders //
+    // Occluders are added to named groups, so a whole group can be cleared when a level changes
+    let lit = lighting.clone();
+    let fn_occluder = create_vector_function(lua, move |_, (group, x0, y0, x1, y1): (String, f64, f64, f64, f64)| {
+        lit.borrow_mut().add_occluder(&group, Vector2::new(x0, y0), Vector2::new(x1, y1));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("occluder", fn_occluder);
+
+    let lit = lighting.clone();
+    let fn_occluder_rect = create_vector_function(lua, move |_, (group, x, y, w, h): (String, f64, f64, f64, f64)| {
+        let corners = [Vector2::new(x, y), Vector2::new(x + w, y), Vector2::new(x + w, y + h), Vector2::new(x, y + h)];
+        lit.borrow_mut().add_occluder_polygon(&group, &corners, true);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("occluder_rect", fn_occluder_rect);
+
+    let lit = lighting.clone();
+    let fn_occluder_polygon = lua.create_function(move |_, (group, points, closed): (String, LuaTable, Option<bool>)| {
+        let points = points_from_table(points)?;
+        lit.borrow_mut().add_occluder_polygon(&group, &points, closed.unwrap_or(true));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("occluder_polygon", fn_occluder_polygon);
+
+    let lit = lighting.clone();
+    let fn_clear_occluders = lua.create_function(move |_, group: Option<String>| {
+        match group {
+            Some(group) => { lit.borrow_mut().occluders.remove(&group); },
+            None => lit.borrow_mut().occluders.clear(),
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("clear_occluders", fn_clear_occluders);
+
+    // Visibility //
+    // What can be seen from a point past the occluders, as a flat table of points ppolygon can fill
+    let lit = lighting.clone();
+    let fn_light_visibility = create_vector_function(lua, move |_, (x, y, radius): (f64, f64, f64)| {
+        let polygon = visibility_polygon(Vector2::new(x, y), radius, &lit.borrow().segments());
+        Ok(polygon.iter().flat_map(|p| [p.x, p.y]).collect::<Vec<f64>>())
+    }).unwrap();
+    let _ = lua.globals().set("light_visibility", fn_light_visibility);
+}
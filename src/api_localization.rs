@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::font::*;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+
+// Registry name of the Lua function called when the language changes
+const LANGUAGE_CALLBACK: &str = "aftershock_language_changed";
+
+pub fn register_localization_api(localization: SharedLocalization, lua: &Lua) {
+    println!("Registering API: Localization");
+
+    // Load a string table, returns the languages it contained //
+    let loc = localization.clone();
+    let fn_load_strings = lua.create_function(move |_, (path_to, language): (String, Option<String>)| {
+        let result = loc.borrow_mut().load_file(path_to.as_str(), language.as_deref());
+        if result.is_ok() {
+            Ok(result.unwrap())
+        } else { /* Handled by Localization */ Ok(Vec::new()) }
+    }).unwrap();
+    let _ = lua.globals().set("load_strings", fn_load_strings);
+
+    // Switch language, calls the on_language_changed callback with the new and previous language //
+    let loc = localization.clone();
+    let fn_set_language = lua.create_function(move |lua, language: String| {
+        let previous = {
+            let mut loc = loc.borrow_mut();
+            if !loc.tables.contains_key(&language) {
+                println!("ERROR - LOCALIZATION: Language '{}' has no strings loaded!", language);
+                return Ok(false);
+            }
+            std::mem::replace(&mut loc.language, language.clone())
+        };
+
+        // The borrow is released first so the callback can use tr
+        if previous != language {
+            let callback: Option<LuaFunction> = lua.named_registry_value(LANGUAGE_CALLBACK)?;
+            if callback.is_some() {
+                callback.unwrap().call::<_, ()>((language, previous))?;
+            }
+        }
+        Ok(true)
+    }).unwrap();
+    let _ = lua.globals().set("set_language", fn_set_language);
+
+    let loc = localization.clone();
+    let fn_get_language = lua.create_function(move |_, ()| {
+        Ok(loc.borrow().language.clone())
+    }).unwrap();
+    let _ = lua.globals().set("get_language", fn_get_language);
+
+    let loc = localization.clone();
+    let fn_get_languages = lua.create_function(move |_, ()| {
+        Ok(loc.borrow().languages())
+    }).unwrap();
+    let _ = lua.globals().set("get_languages", fn_get_languages);
+
+    let loc = localization.clone();
+    let fn_set_fallback_language = lua.create_function(move |_, language: Option<String>| {
+        loc.borrow_mut().fallback_language = language;
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_fallback_language", fn_set_fallback_language);
+
+    let fn_on_language_changed = lua.create_function(move |lua, callback: Option<LuaFunction>| {
+        lua.set_named_registry_value(LANGUAGE_CALLBACK, callback)?;
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("on_language_changed", fn_on_language_changed);
+
+    // Translate //
+    // tr("apples", {count = 3, name = "Sam"}), 'count' also picks the plural form
+    let loc = localization.clone();
+    let fn_tr = lua.create_function(move |_, (key, args): (String, Option<LuaTable>)| {
+        let mut arg_map: HashMap<String, String> = HashMap::new();
+        let mut count: Option<f64> = None;
+
+        if args.is_some() {
+            for pair in args.unwrap().pairs::<LuaValue, LuaValue>() {
+                let (arg_key, arg_value) = pair?;
+                let arg_key = lua_value_to_string(&arg_key);
+                if arg_key.is_none() { continue; }
+                let arg_key = arg_key.unwrap();
+
+                if arg_key == "count" {
+                    count = match arg_value { LuaValue::Integer(n) => Some(n as f64), LuaValue::Number(n) => Some(n), _ => None };
+                }
+
+                let value = lua_value_to_string(&arg_value);
+                if value.is_some() { arg_map.insert(arg_key, value.unwrap()); }
+            }
+        }
+
+        Ok(loc.borrow().tr(key.as_str(), &arg_map, count))
+    }).unwrap();
+    let _ = lua.globals().set("tr", fn_tr);
+
+    // Check every language for missing strings and glyphs the font can't print //
+    let loc = localization.clone();
+    let fn_validate_strings = lua.create_function(move |lua, font: LuaAnyUserData| {
+        let font = font.borrow::<Font>()?;
+        let issues = loc.borrow().validate(&font);
+
+        let issue_table = lua.create_table()?;
+        for (idx, issue) in issues.iter().enumerate() {
+            println!("ERROR - LOCALIZATION: [{}] '{}' {}", issue.language, issue.key, issue.message);
+
+            let entry = lua.create_table()?;
+            entry.set("language", issue.language.clone())?;
+            entry.set("key", issue.key.clone())?;
+            entry.set("message", issue.message.clone())?;
+            issue_table.set(idx + 1, entry)?;
+        }
+        Ok(issue_table)
+    }).unwrap();
+    let _ = lua.globals().set("validate_strings", fn_validate_strings);
+}
+
+// Integers are printed without a decimal point so "{count}" reads "3" and not "3.0"
+fn lua_value_to_string(value: &LuaValue) -> Option<String> {
+    match value {
+        LuaValue::String(s) => s.to_str().ok().map(|s| s.to_string()),
+        LuaValue::Integer(n) => Some(n.to_string()),
+        LuaValue::Number(n) => {
+            if n.fract() == 0.0 && n.abs() < 1e15 { Some((*n as i64).to_string()) } else { Some(n.to_string()) }
+        },
+        LuaValue::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,81 @@
+use crate::vector2::*;
+use crate::matrix3::*;
+
+use mlua::prelude::*;
+
+pub fn register_math(lua: &Lua) {
+    println!("Registering API: Math");
+
+    // Vector2 //
+    // vec2(x, y), vec2(n) sets both and vec2() is zero
+    let fn_vec2 = lua.create_function(|_, (x, y): (Option<f64>, Option<f64>)| {
+        let x = x.unwrap_or(0.0);
+        Ok(Vector2::new(x, y.unwrap_or(x)))
+    }).unwrap();
+    let _ = lua.globals().set("vec2", fn_vec2);
+
+    // Matrix3 //
+    let fn_mat3 = lua.create_function(|_, ()| {
+        Ok(Matrix3::identity())
+    }).unwrap();
+    let _ = lua.globals().set("mat3", fn_mat3);
+
+    // Matrix3 Translated //
+    let fn_mat3_translated = create_vector_function(lua, |_, (x, y): (f64, f64)| {
+        Ok(Matrix3::translated(Vector2::new(x, y)))
+    }).unwrap();
+    let _ = lua.globals().set("mat3_translated", fn_mat3_translated);
+
+    // Matrix3 Rotated //
+    let fn_mat3_rotated = lua.create_function(|_, radians: f64| {
+        Ok(Matrix3::rotated(radians))
+    }).unwrap();
+    let _ = lua.globals().set("mat3_rotated", fn_mat3_rotated);
+
+    // Matrix3 Scaled //
+    let fn_mat3_scaled = create_vector_function(lua, |_, (x, y): (f64, Option<f64>)| {
+        Ok(Matrix3::scaled(Vector2::new(x, y.unwrap_or(x))))
+    }).unwrap();
+    let _ = lua.globals().set("mat3_scaled", fn_mat3_scaled);
+
+    // Matrix3 Sheared //
+    let fn_mat3_sheared = create_vector_function(lua, |_, (x, y): (f64, f64)| {
+        Ok(Matrix3::sheared(Vector2::new(x, y)))
+    }).unwrap();
+    let _ = lua.globals().set("mat3_sheared", fn_mat3_sheared);
+}
+
+/// Creates a Lua function that takes a vec2 anywhere it takes an x and a y, like pset(vec2(4, 8), color) or pset(4, 8, color).
+/// Every vec2 is split into its two numbers before the arguments are read.
+pub fn create_vector_function<'lua, 'callback, A, R, F>(lua: &'lua Lua, func: F) -> LuaResult<LuaFunction<'lua>>
+where
+    'lua: 'callback,
+    A: LuaFromLuaMulti<'callback>,
+    R: LuaToLuaMulti<'callback>,
+    F: 'static + Fn(&'callback Lua, A) -> LuaResult<R>,
+{
+    lua.create_function(move |lua, args: LuaMultiValue| {
+        let args = expand_vectors(args);
+        func(lua, A::from_lua_multi(args, lua)?)
+    })
+}
+
+fn expand_vectors(args: LuaMultiValue) -> LuaMultiValue {
+    // Plain numbers go straight through, so they don't cost anything extra
+    if !args.iter().any(|value| matches!(value, LuaValue::UserData(ud) if ud.is::<Vector2>())) {
+        return args;
+    }
+
+    let mut out: Vec<LuaValue> = Vec::with_capacity(args.len() + 2);
+    for value in args {
+        if let LuaValue::UserData(ud) = &value {
+            if let Ok(v) = ud.borrow::<Vector2>() {
+                out.push(LuaValue::Number(v.x));
+                out.push(LuaValue::Number(v.y));
+                continue;
+            }
+        }
+        out.push(value);
+    }
+    LuaMultiValue::from_vec(out)
+}
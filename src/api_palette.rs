@@ -0,0 +1,143 @@
+use crate::color::Color;
+use crate::palette::*;
+
+use mlua::prelude::*;
+
+use std::sync::Arc;
+
+use crate::api_shareables::*;
+
+pub fn register_palette(assets_images: SharedImages, palette: SharedPalette, rasterizer: SharedRasterizer, lua: &Lua) {
+    println!("Registering API: Palette");
+
+    // Palette Mode //
+    // Colors become indices into the palette, drawn with index_color(i) and turned into real colors when presented
+    let rst = rasterizer.clone();
+    let pal = palette.clone();
+    let fn_set_palette_mode = lua.create_function(move |_, enabled: bool| {
+        pal.borrow_mut().enabled = enabled;
+        let mut rst = rst.borrow_mut();
+        rst.set_palette_mode(enabled);
+        if enabled { rst.clear_color(index_color(0)); }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_palette_mode", fn_set_palette_mode);
+
+    // Load Palette //
+    // .hex, .gpl or .pal
+    let pal = palette.clone();
+    let fn_load_palette = lua.create_function(move |_, path_to: String| {
+        let palette_result = Palette::load(&path_to);
+        if palette_result.is_ok() {
+            pal.borrow_mut().palette = palette_result.unwrap();
+        } /* Handled by Palette */
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("load_palette", fn_load_palette);
+
+    // Set Palette //
+    let pal = palette.clone();
+    let fn_set_palette = lua.create_function(move |_, colors: Vec<Color>| {
+        pal.borrow_mut().palette = Palette::new(&colors);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_palette", fn_set_palette);
+
+    // Palette Color //
+    // The real color of a palette entry
+    let pal = palette.clone();
+    let fn_palette_color = lua.create_function(move |_, index: u8| {
+        Ok(pal.borrow().palette.get(index))
+    }).unwrap();
+    let _ = lua.globals().set("palette_color", fn_palette_color);
+
+    // Palette Size //
+    let pal = palette.clone();
+    let fn_palette_size = lua.create_function(move |_, ()| {
+        Ok(pal.borrow().palette.colors.len())
+    }).unwrap();
+    let _ = lua.globals().set("palette_size", fn_palette_size);
+
+    // Index Color //
+    let fn_index_color = lua.create_function(|_, index: u8| {
+        Ok(index_color(index))
+    }).unwrap();
+    let _ = lua.globals().set("index_color", fn_index_color);
+
+    // Nearest Index //
+    let pal = palette.clone();
+    let fn_nearest_index = lua.create_function(move |_, color: Color| {
+        Ok(pal.borrow().palette.nearest(color))
+    }).unwrap();
+    let _ = lua.globals().set("nearest_index", fn_nearest_index);
+
+    // Pal //
+    // pal(src, dst) draws src as dst from now on, pal(src, dst, true) shows src as dst on screen, everything already drawn too.
+    // pal() undoes both.
+    let rst = rasterizer.clone();
+    let pal = palette.clone();
+    let fn_pal = lua.create_function(move |_, (src, dst, screen): (Option<u8>, Option<u8>, Option<bool>)| {
+        let mut rst = rst.borrow_mut();
+        let mut remap = rst.rasterizer.palette_remap;
+        match (src, dst) {
+            (Some(src), Some(dst)) => {
+                if screen.unwrap_or(false) { pal.borrow_mut().swap(src, dst); } else { remap.set(src, dst); }
+            },
+            (Some(src), None) => {
+                if screen.unwrap_or(false) { pal.borrow_mut().swap(src, src); } else { remap.set(src, src); }
+            },
+            _ => {
+                remap.reset_map();
+                pal.borrow_mut().reset();
+            },
+        }
+        rst.set_palette_remap(remap);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("pal", fn_pal);
+
+    // Palt //
+    // palt(index, true) stops index from being drawn, palt() makes every index drawn again
+    let rst = rasterizer.clone();
+    let fn_palt = lua.create_function(move |_, (index, transparent): (Option<u8>, Option<bool>)| {
+        let mut rst = rst.borrow_mut();
+        let mut remap = rst.rasterizer.palette_remap;
+        match index {
+            Some(index) => remap.set_transparent(index, transparent.unwrap_or(true)),
+            None => remap.reset_transparent(),
+        }
+        rst.set_palette_remap(remap);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("palt", fn_palt);
+
+    // Palette Cycle //
+    // Rotates the entries from start to end by speed steps a second
+    let pal = palette.clone();
+    let fn_palette_cycle = lua.create_function(move |_, (start, end, speed): (u8, u8, f64)| {
+        pal.borrow_mut().add_cycle(start, end, speed);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("palette_cycle", fn_palette_cycle);
+
+    // Clear Palette Cycles //
+    let pal = palette.clone();
+    let fn_clear_palette_cycles = lua.create_function(move |_, ()| {
+        pal.borrow_mut().clear_cycles();
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("clear_palette_cycles", fn_clear_palette_cycles);
+
+    // Quantize Image //
+    // Turns an RGBA image into the nearest palette indices, load_image already does this in palette mode
+    let imgs = assets_images.clone();
+    let pal = palette.clone();
+    let fn_quantize_image = lua.create_function(move |_, name: String| {
+        let img_result = imgs.get_mut(&name);
+        if img_result.is_some() {
+            pal.borrow().palette.quantize(Arc::make_mut(&mut img_result.unwrap()));
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("quantize_image", fn_quantize_image);
+}
@@ -0,0 +1,196 @@
+use crate::color::Color;
+use crate::particles::*;
+use crate::vector2::Vector2;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+use crate::api_math::create_vector_function;
+
+pub fn register_particles(particles: SharedParticles, assets_images: SharedImages, rasterizer: SharedRasterizer, lua: &Lua) {
+    println!("Registering API: Particles");
+
+    // Define Particles //
+    // particle_define(name, {rate = 20, lifetime = {0.5, 1}, speed = {20, 40}, colors = {rgb(255, 200, 0), rgb(255, 0, 0)}, ...})
+    // Anything left out keeps its default, ranges can be one number or {min, max}
+    let parts = particles.clone();
+    let fn_particle_define = lua.create_function(move |lua, (name, table): (String, LuaTable)| {
+        let definition = definition_from_table(lua, table)?;
+        parts.borrow_mut().definitions.insert(name, definition);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("particle_define", fn_particle_define);
+
+    // Create Emitter //
+    // Emitters start running as soon as they're made
+    let parts = particles.clone();
+    let fn_emitter_create = create_vector_function(lua, move |_, (name, definition, x, y): (String, String, f64, f64)| {
+        parts.borrow_mut().create_emitter(&name, &definition, Vector2::new(x, y)).map_err(|e| LuaError::RuntimeError(format!("emitter_create: {}", e)))
+    }).unwrap();
+    let _ = lua.globals().set("emitter_create", fn_emitter_create);
+
+    // Start and Stop Emitters //
+    let parts = particles.clone();
+    let fn_emitter_start = lua.create_function(move |_, name: String| {
+        if let Some(emitter) = parts.borrow_mut().emitters.get_mut(&name) {
+            emitter.running = true;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("emitter_start", fn_emitter_start);
+
+    let parts = particles.clone();
+    let fn_emitter_stop = lua.create_function(move |_, name: String| {
+        if let Some(emitter) = parts.borrow_mut().emitters.get_mut(&name) {
+            emitter.running = false;
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("emitter_stop", fn_emitter_stop);
+
+    // Burst //
+    let parts = particles.clone();
+    let fn_emitter_burst = lua.create_function(move |_, (name, count): (String, usize)| {
+        if let Some(emitter) = parts.borrow_mut().emitters.get_mut(&name) {
+            emitter.burst(count);
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("emitter_burst", fn_emitter_burst);
+
+    // Move Emitter //
+    // Particles already made stay where they are, so moving an emitter every frame leaves a trail
+    let parts = particles.clone();
+    let fn_set_emitter_position = create_vector_function(lua, move |_, (name, x, y): (String, f64, f64)| {
+        if let Some(emitter) = parts.borrow_mut().emitters.get_mut(&name) {
+            emitter.position = Vector2::new(x, y);
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_emitter_position", fn_set_emitter_position);
+
+    // Remove Emitters //
+    let parts = particles.clone();
+    let fn_emitter_remove = lua.create_function(move |_, name: String| {
+        parts.borrow_mut().emitters.remove(&name);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("emitter_remove", fn_emitter_remove);
+
+    let parts = particles.clone();
+    let fn_clear_emitters = lua.create_function(move |_, ()| {
+        parts.borrow_mut().emitters.clear();
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("clear_emitters", fn_clear_emitters);
+
+    // Particle Count //
+    // For one emitter, or every emitter without a name
+    let parts = particles.clone();
+    let fn_particle_count = lua.create_function(move |_, name: Option<String>| {
+        let parts = parts.borrow();
+        Ok(match name {
+            Some(name) => parts.emitters.get(&name).map_or(0, |emitter| emitter.particles.len()),
+            None => parts.particle_count(),
+        })
+    }).unwrap();
+    let _ = lua.globals().set("particle_count", fn_particle_count);
+
+    // Draw Particles //
+    // Draws one emitter, or every emitter in name order without a name, using the current draw mode
+    let parts = particles.clone();
+    let imgs = assets_images.clone();
+    let rst = rasterizer.clone();
+    let fn_draw_particles = lua.create_function(move |_, name: Option<String>| {
+        let parts = parts.borrow();
+        let image = |name: &str| imgs.get(name).map(|image| image.clone());
+        let mut rst = rst.borrow_mut();
+        match name {
+            Some(name) => {
+                if let Some(emitter) = parts.emitters.get(&name) {
+                    emitter.draw(&mut rst, &image);
+                }
+            },
+            None => {
+                let mut names: Vec<&String> = parts.emitters.keys().collect();
+                names.sort();
+                for name in names {
+                    parts.emitters[name].draw(&mut rst, &image);
+                }
+            },
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("draw_particles", fn_draw_particles);
+}
+
+// A number is both ends of the range, a table is {min, max}
+fn range_from_value(value: LuaValue, field: &str) -> LuaResult<(f64, f64)> {
+    match value {
+        LuaValue::Integer(n) => Ok((n as f64, n as f64)),
+        LuaValue::Number(n) => Ok((n, n)),
+        LuaValue::Table(range) => Ok((range.get(1)?, range.get(2)?)),
+        _ => Err(LuaError::RuntimeError(format!("particle_define: {} takes a number or {{min, max}}", field))),
+    }
+}
+
+// A number stays the same the whole lifetime, a table is spread evenly over it
+fn ramp_from_value(value: LuaValue, field: &str) -> LuaResult<Ramp> {
+    match value {
+        LuaValue::Integer(n) => Ok(Ramp::constant(n as f64)),
+        LuaValue::Number(n) => Ok(Ramp::constant(n)),
+        LuaValue::Table(values) => Ok(Ramp::new(values.sequence_values::<f64>().collect::<LuaResult<Vec<f64>>>()?)),
+        _ => Err(LuaError::RuntimeError(format!("particle_define: {} takes a number or a table of numbers", field))),
+    }
+}
+
+fn definition_from_table(lua: &Lua, table: LuaTable) -> LuaResult<ParticleDefinition> {
+    let mut definition = ParticleDefinition::default();
+
+    if let Some(rate) = table.get::<_, Option<f64>>("rate")? { definition.rate = rate; }
+    if let Some(radius) = table.get::<_, Option<f64>>("radius")? { definition.radius = radius; }
+    if let Some(drag) = table.get::<_, Option<f64>>("drag")? { definition.drag = drag; }
+    if let Some(max) = table.get::<_, Option<usize>>("max")? { definition.max_particles = max; }
+
+    for (field, range) in [("lifetime", &mut definition.lifetime), ("speed", &mut definition.speed), ("angle", &mut definition.angle), ("spin", &mut definition.spin)] {
+        let value: LuaValue = table.get(field)?;
+        if !matches!(value, LuaValue::Nil) { *range = range_from_value(value, field)?; }
+    }
+
+    for (field, ramp) in [("sizes", &mut definition.sizes), ("opacities", &mut definition.opacities)] {
+        let value: LuaValue = table.get(field)?;
+        if !matches!(value, LuaValue::Nil) { *ramp = ramp_from_value(value, field)?; }
+    }
+
+    // Gravity can be a vec2 or {x, y}
+    match table.get::<_, LuaValue>("gravity")? {
+        LuaValue::Nil => {},
+        LuaValue::UserData(ud) => definition.gravity = *ud.borrow::<Vector2>()?,
+        LuaValue::Table(gravity) => definition.gravity = Vector2::new(gravity.get(1)?, gravity.get(2)?),
+        _ => return Err(LuaError::RuntimeError("particle_define: gravity takes a vec2 or {x, y}".to_string())),
+    }
+
+    // One color, or colors spread evenly over the lifetime
+    match table.get::<_, LuaValue>("colors")? {
+        LuaValue::Nil => {},
+        LuaValue::Table(colors) => {
+            let colors = colors.sequence_values::<Color>().collect::<LuaResult<Vec<Color>>>()?;
+            if !colors.is_empty() { definition.colors = colors; }
+        },
+        value => definition.colors = vec![lua.unpack::<Color>(value).map_err(|_| LuaError::RuntimeError("particle_define: colors takes a color or a table of colors".to_string()))?],
+    }
+
+    // "pixel", "circle", "rectangle", or sprite = "image name"
+    if let Some(sprite) = table.get::<_, Option<String>>("sprite")? {
+        definition.shape = ParticleShape::Sprite(sprite);
+    } else if let Some(shape) = table.get::<_, Option<String>>("shape")? {
+        definition.shape = match shape.as_str() {
+            "pixel" => ParticleShape::Pixel,
+            "circle" => ParticleShape::Circle,
+            "rectangle" => ParticleShape::Rectangle,
+            _ => return Err(LuaError::RuntimeError(format!("particle_define: unknown shape \"{}\", use pixel, circle or rectangle, or sprite = \"image name\"", shape))),
+        };
+    }
+
+    Ok(definition)
+}
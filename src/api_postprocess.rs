@@ -0,0 +1,123 @@
+use crate::color::Color;
+use crate::palette::Palette;
+use crate::postprocess::*;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+use crate::api_math::create_vector_function;
+
+pub fn register_postprocess(assets_images: SharedImages, palette: SharedPalette, postprocess: SharedPostProcess, lua: &Lua) {
+    println!("Registering API: Post-Processing");
+
+    // Every post_ function adds an effect to the end of the chain, which runs on the finished frame as it's presented
+
+    // Clear Post Effects //
+    let post = postprocess.clone();
+    let fn_post_clear = lua.create_function(move |_, ()| {
+        post.borrow_mut().effects.clear();
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_clear", fn_post_clear);
+
+    // Scanlines //
+    let post = postprocess.clone();
+    let fn_post_scanlines = lua.create_function(move |_, intensity: Option<f64>| {
+        post.borrow_mut().effects.push(PostEffect::Scanlines { intensity: intensity.unwrap_or(0.5) });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_scanlines", fn_post_scanlines);
+
+    // CRT Curvature //
+    let post = postprocess.clone();
+    let fn_post_curvature = lua.create_function(move |_, amount: Option<f64>| {
+        post.borrow_mut().effects.push(PostEffect::Curvature { amount: amount.unwrap_or(0.05) });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_curvature", fn_post_curvature);
+
+    // Chromatic Aberration //
+    let post = postprocess.clone();
+    let fn_post_chromatic_aberration = lua.create_function(move |_, offset: Option<f64>| {
+        post.borrow_mut().effects.push(PostEffect::ChromaticAberration { offset: offset.unwrap_or(2.0) });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_chromatic_aberration", fn_post_chromatic_aberration);
+
+    // Bloom //
+    let post = postprocess.clone();
+    let fn_post_bloom = lua.create_function(move |_, (threshold, radius, intensity): (Option<f64>, Option<f64>, Option<f64>)| {
+        post.borrow_mut().effects.push(PostEffect::Bloom {
+            threshold: f64::clamp(threshold.unwrap_or(200.0), 0.0, 255.0) as u8,
+            radius: f64::max(radius.unwrap_or(4.0), 0.0) as usize,
+            intensity: intensity.unwrap_or(1.0),
+        });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_bloom", fn_post_bloom);
+
+    // Box Blur //
+    let post = postprocess.clone();
+    let fn_post_box_blur = lua.create_function(move |_, radius: f64| {
+        post.borrow_mut().effects.push(PostEffect::BoxBlur { radius: f64::max(radius, 0.0) as usize });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_box_blur", fn_post_box_blur);
+
+    // Gaussian Blur //
+    let post = postprocess.clone();
+    let fn_post_gaussian_blur = lua.create_function(move |_, radius: f64| {
+        post.borrow_mut().effects.push(PostEffect::GaussianBlur { radius: f64::max(radius, 0.0) as usize });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_gaussian_blur", fn_post_gaussian_blur);
+
+    // Vignette //
+    let post = postprocess.clone();
+    let fn_post_vignette = lua.create_function(move |_, (intensity, radius): (Option<f64>, Option<f64>)| {
+        post.borrow_mut().effects.push(PostEffect::Vignette { intensity: intensity.unwrap_or(0.5), radius: radius.unwrap_or(0.5) });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_vignette", fn_post_vignette);
+
+    // Color Grading //
+    // Takes the name of a LUT image, changing the image afterwards doesn't change the effect
+    let imgs = assets_images.clone();
+    let post = postprocess.clone();
+    let fn_post_color_grade = lua.create_function(move |_, name: String| {
+        let img_result = imgs.get(&name);
+        if img_result.is_some() {
+            let lut = img_result.unwrap().clone();
+            if lut.width != lut.height * lut.height {
+                println!("ERROR - POST: {} is {}x{}, a LUT has to be as wide as its height squared, like 256x16", name, lut.width, lut.height);
+            } else {
+                post.borrow_mut().effects.push(PostEffect::ColorGrade { lut });
+            }
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_color_grade", fn_post_color_grade);
+
+    // Dithering //
+    // Dithers to a table of colors, or to the palette if there isn't one
+    let pal = palette.clone();
+    let post = postprocess.clone();
+    let fn_post_dither = lua.create_function(move |_, (colors, strength): (Option<Vec<Color>>, Option<f64>)| {
+        let palette = match colors {
+            Some(colors) => Palette::new(&colors),
+            None => pal.borrow().palette.clone(),
+        };
+        post.borrow_mut().effects.push(PostEffect::dither(palette, strength.unwrap_or(1.0)));
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("post_dither", fn_post_dither);
+
+    // Screen Shake //
+    // Moves the whole frame before the other effects, set_screen_shake(0, 0) puts it back
+    let post = postprocess.clone();
+    let fn_set_screen_shake = create_vector_function(lua, move |_, (x, y): (f64, f64)| {
+        post.borrow_mut().shake = (f64::round(x) as i64, f64::round(y) as i64);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("set_screen_shake", fn_set_screen_shake);
+}
@@ -0,0 +1,154 @@
+use crate::color::Color;
+use crate::expression::Expression;
+use crate::noise::*;
+use crate::procedural::*;
+
+use mlua::prelude::*;
+
+use std::sync::Arc;
+
+use crate::api_shareables::*;
+use crate::api_math::create_vector_function;
+
+pub fn register_procedural(assets_images: SharedImages, lua: &Lua) {
+    println!("Registering API: Procedural");
+
+    // Map Pixels //
+    // map_pixels(name, function(x, y, color) return color end) runs Lua for every pixel, returning nothing leaves the pixel alone.
+    // map_pixels(name, "r, g, b", variables) compiles the expression and runs it over the rows in parallel, much faster.
+    // Both can be limited to a region with x, y, w, h after the variables.
+    let imgs = assets_images.clone();
+    let fn_map_pixels = lua.create_function(move |_, (name, shader, variables, x, y, w, h): (String, LuaValue, Option<LuaTable>, Option<i64>, Option<i64>, Option<i64>, Option<i64>)| {
+        let size = imgs.get(&name).map(|image| (image.width as i64, image.height as i64));
+        if size.is_none() { return Ok(()); }
+        let (width, height) = size.unwrap();
+        let region = (x.unwrap_or(0), y.unwrap_or(0), w.unwrap_or(width), h.unwrap_or(height));
+
+        match shader {
+            LuaValue::String(source) => {
+                // Custom variables in name order, after the ones every pixel gets
+                let mut custom: Vec<(String, f64)> = Vec::new();
+                if let Some(table) = variables {
+                    for pair in table.pairs::<String, f64>() {
+                        custom.push(pair?);
+                    }
+                }
+                custom.sort_by(|a, b| a.0.cmp(&b.0));
+
+                let mut names: Vec<&str> = PIXEL_VARIABLES.to_vec();
+                names.extend(custom.iter().map(|(name, _)| name.as_str()));
+                let values: Vec<f64> = custom.iter().map(|(_, value)| *value).collect();
+
+                let expression = Expression::compile(source.to_str()?, &names).map_err(|e| LuaError::RuntimeError(format!("map_pixels: {}", e)))?;
+                if !matches!(expression.outputs, 1 | 3 | 4) {
+                    return Err(LuaError::RuntimeError(format!("map_pixels: expressions give 1 (gray), 3 (rgb) or 4 (rgba) values, not {}", expression.outputs)));
+                }
+
+                let img_result = imgs.get_mut(&name);
+                if img_result.is_some() {
+                    map_expression(Arc::make_mut(&mut img_result.unwrap()), region, &expression, &values);
+                }
+            },
+            LuaValue::Function(function) => {
+                // Taken out of the map so the Lua function can use other images, even this one, while it runs
+                let mut image = imgs.get(&name).unwrap().clone();
+                let img = Arc::make_mut(&mut image);
+                let (x0, y0) = (i64::max(region.0, 0), i64::max(region.1, 0));
+                let (x1, y1) = (i64::min(region.0 + region.2, width), i64::min(region.1 + region.3, height));
+                for py in y0..y1 {
+                    for px in x0..x1 {
+                        let idx = ((py * width + px) * 4) as usize;
+                        let color = Color::new(img.color[idx], img.color[idx + 1], img.color[idx + 2], img.color[idx + 3]);
+                        if let Some(c) = function.call::<_, Option<Color>>((px, py, color))? {
+                            img.color[idx..idx + 4].copy_from_slice(&[c.r, c.g, c.b, c.a]);
+                        }
+                    }
+                }
+                imgs.insert(name, image);
+            },
+            _ => return Err(LuaError::RuntimeError("map_pixels: takes a function or an expression string".to_string())),
+        }
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("map_pixels", fn_map_pixels);
+
+    // Noise //
+    // Every noise function returns 0.0 to 1.0, and the same numbers for the same position and seed
+    let fn_noise_value = create_vector_function(lua, |_, (x, y, seed): (f64, f64, Option<u32>)| {
+        Ok(value(x, y, seed.unwrap_or(0)))
+    }).unwrap();
+    let _ = lua.globals().set("noise_value", fn_noise_value);
+
+    let fn_noise_perlin = create_vector_function(lua, |_, (x, y, seed): (f64, f64, Option<u32>)| {
+        Ok(perlin(x, y, seed.unwrap_or(0)))
+    }).unwrap();
+    let _ = lua.globals().set("noise_perlin", fn_noise_perlin);
+
+    let fn_noise_simplex = create_vector_function(lua, |_, (x, y, seed): (f64, f64, Option<u32>)| {
+        Ok(simplex(x, y, seed.unwrap_or(0)))
+    }).unwrap();
+    let _ = lua.globals().set("noise_simplex", fn_noise_simplex);
+
+    let fn_noise_worley = create_vector_function(lua, |_, (x, y, seed): (f64, f64, Option<u32>)| {
+        Ok(worley(x, y, seed.unwrap_or(0)))
+    }).unwrap();
+    let _ = lua.globals().set("noise_worley", fn_noise_worley);
+
+    // Fractal Noise //
+    let fn_noise_fbm = create_vector_function(lua, |_, (kind, x, y, octaves, seed): (String, f64, f64, u32, Option<u32>)| {
+        let kind = NoiseKind::from_str(&kind).ok_or(LuaError::RuntimeError(format!("noise_fbm: unknown noise \"{}\", use value, perlin, simplex or worley", kind)))?;
+        Ok(fbm(kind, x, y, u32::clamp(octaves, 1, 16), seed.unwrap_or(0)))
+    }).unwrap();
+    let _ = lua.globals().set("noise_fbm", fn_noise_fbm);
+
+    // Generate Noise //
+    let imgs = assets_images.clone();
+    let fn_generate_noise = lua.create_function(move |_, (name, kind, scale, octaves, seed, low, high): (String, String, f64, Option<u32>, Option<u32>, Option<Color>, Option<Color>)| {
+        let kind = NoiseKind::from_str(&kind).ok_or(LuaError::RuntimeError(format!("generate_noise: unknown noise \"{}\", use value, perlin, simplex or worley", kind)))?;
+        let generator = Generator::Noise {
+            kind, scale,
+            octaves: u32::clamp(octaves.unwrap_or(1), 1, 16),
+            seed: seed.unwrap_or(0),
+            low: low.unwrap_or(Color::black()),
+            high: high.unwrap_or(Color::white()),
+        };
+        generate_image(&imgs, name, &generator);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("generate_noise", fn_generate_noise);
+
+    // Generate Gradient //
+    let imgs = assets_images.clone();
+    let fn_generate_gradient = create_vector_function(lua, move |_, (name, x0, y0, x1, y1, colors): (String, f64, f64, f64, f64, Vec<Color>)| {
+        generate_image(&imgs, name, &Generator::LinearGradient { x0, y0, x1, y1, colors });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("generate_gradient", fn_generate_gradient);
+
+    // Generate Radial Gradient //
+    let imgs = assets_images.clone();
+    let fn_generate_radial_gradient = create_vector_function(lua, move |_, (name, x, y, radius, colors): (String, f64, f64, f64, Vec<Color>)| {
+        generate_image(&imgs, name, &Generator::RadialGradient { x, y, radius, colors });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("generate_radial_gradient", fn_generate_radial_gradient);
+
+    // Generate Checker //
+    let imgs = assets_images.clone();
+    let fn_generate_checker = lua.create_function(move |_, (name, size, even, odd): (String, i64, Color, Color)| {
+        generate_image(&imgs, name, &Generator::Checker { size, even, odd });
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("generate_checker", fn_generate_checker);
+}
+
+// Fills a whole image with a generator, if the image exists
+fn generate_image(imgs: &SharedImages, name: String, generator: &Generator) {
+    let img_result = imgs.get_mut(&name);
+    if img_result.is_some() {
+        let mut img = img_result.unwrap();
+        let image = Arc::make_mut(&mut img);
+        let region = (0, 0, image.width as i64, image.height as i64);
+        generate(image, region, generator);
+    }
+}
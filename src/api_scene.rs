@@ -0,0 +1,248 @@
+use std::rc::Rc;
+
+use crate::color::Color;
+use crate::easing::Easing;
+use crate::scene::*;
+use crate::transition::*;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+
+pub fn register_scene(scenes: SharedScenes, lua: &Lua) {
+    println!("Registering API: Scene");
+
+    // Push Scene //
+    // A scene is a table with any of enter, exit, update, draw, pause and resume, all called with the scene as self
+    // The scene under it is paused, and still drawn under it if the new scene has overlay = true
+    let scn = scenes.clone();
+    let fn_scene_push = lua.create_function(move |lua, (scene, transition): (LuaTable, Option<LuaValue>)| {
+        let transition = transition_from_value(transition, "scene_push")?;
+        let scene: SceneKey = Rc::new(lua.create_registry_value(scene)?);
+        change_scenes(lua, &scn, transition, move |stack| {
+            stack.push(scene);
+            Vec::new()
+        })
+    }).unwrap();
+    let _ = lua.globals().set("scene_push", fn_scene_push);
+
+    // Pop Scene //
+    // The scene under it is resumed
+    let scn = scenes.clone();
+    let fn_scene_pop = lua.create_function(move |lua, transition: Option<LuaValue>| {
+        let transition = transition_from_value(transition, "scene_pop")?;
+        change_scenes(lua, &scn, transition, |stack| stack.pop().into_iter().collect())
+    }).unwrap();
+    let _ = lua.globals().set("scene_pop", fn_scene_pop);
+
+    // Switch Scene //
+    // Every scene on the stack exits and the new one is all that's left, like going from a pause menu back to the title
+    let scn = scenes.clone();
+    let fn_scene_switch = lua.create_function(move |lua, (scene, transition): (LuaTable, Option<LuaValue>)| {
+        let transition = transition_from_value(transition, "scene_switch")?;
+        let scene: SceneKey = Rc::new(lua.create_registry_value(scene)?);
+        change_scenes(lua, &scn, transition, move |stack| {
+            let removed = std::mem::take(stack);
+            stack.push(scene);
+            removed
+        })
+    }).unwrap();
+    let _ = lua.globals().set("scene_switch", fn_scene_switch);
+
+    // Scene Info //
+    let scn = scenes.clone();
+    let fn_scene_current = lua.create_function(move |lua, ()| {
+        match scn.borrow().top() {
+            Some(scene) => lua.registry_value::<LuaTable>(&scene).map(Some),
+            None => Ok(None),
+        }
+    }).unwrap();
+    let _ = lua.globals().set("scene_current", fn_scene_current);
+
+    let scn = scenes.clone();
+    let fn_scene_count = lua.create_function(move |_, ()| {
+        Ok(scn.borrow().stack.len())
+    }).unwrap();
+    let _ = lua.globals().set("scene_count", fn_scene_count);
+
+    let scn = scenes.clone();
+    let fn_is_scene_transitioning = lua.create_function(move |_, ()| {
+        Ok(scn.borrow().transition.is_some())
+    }).unwrap();
+    let _ = lua.globals().set("is_scene_transitioning", fn_is_scene_transitioning);
+}
+
+/// Moves a transition along, then updates the top scene.
+pub fn update_scenes(scenes: &SharedScenes, lua: &Lua, dt: f64) -> LuaResult<()> {
+    let finished = match scenes.borrow_mut().transition.as_mut() {
+        Some(scene_transition) => {
+            scene_transition.transition.update(dt);
+            scene_transition.transition.is_done()
+        },
+        None => false,
+    };
+    if finished { finish_transition(scenes, lua)?; }
+
+    let top = scenes.borrow().top();
+    match top {
+        Some(scene) => call_scene(lua, &scene, "update", Some(dt)),
+        None => Ok(()),
+    }
+}
+
+/// Draws the scenes on screen. During a transition the old scenes are drawn and kept first,
+/// then the new ones are drawn from black and the old frame is blended over them.
+pub fn draw_scenes(scenes: &SharedScenes, lua: &Lua, rasterizer: &SharedRasterizer) -> LuaResult<()> {
+    let from = scenes.borrow().transition.as_ref().map(|scene_transition| scene_transition.from.clone());
+    if let Some(from) = &from {
+        // The old scenes' drawing state is put back afterwards, so none of it leaks into the new ones
+        let ((mode, space, tint, opacity), (camera_position, camera_rotation, camera_scale)) = {
+            let rst = &rasterizer.borrow().rasterizer;
+            ((rst.draw_mode, rst.draw_space, rst.tint, rst.opacity), (rst.camera_position, rst.camera_rotation, rst.camera_scale))
+        };
+        draw_layers(lua, rasterizer, from)?;
+
+        let mut rst = rasterizer.borrow_mut();
+        rst.set_draw_mode(mode);
+        rst.set_draw_space(space);
+        rst.set_tint(tint);
+        rst.set_opacity(opacity);
+        rst.set_camera_position(camera_position.x, camera_position.y);
+        rst.set_camera_rotation(camera_rotation);
+        rst.set_camera_scale(camera_scale.x, camera_scale.y);
+        rst.update_camera();
+        if let Some(scene_transition) = scenes.borrow_mut().transition.as_mut() {
+            let frame = &rst.immediate().color;
+            scene_transition.transition.from.clear();
+            scene_transition.transition.from.extend_from_slice(frame);
+        }
+        rst.clear_color(Color::black());
+    }
+
+    let stack = scenes.borrow().stack.clone();
+    draw_layers(lua, rasterizer, &visible_scenes(lua, &stack)?)?;
+
+    if from.is_some() {
+        let mut rst = rasterizer.borrow_mut();
+        let frame = rst.immediate();
+        let (width, height) = (frame.width, frame.height);
+        if let Some(scene_transition) = scenes.borrow().transition.as_ref() {
+            scene_transition.transition.apply(&mut frame.color, width, height);
+        }
+    }
+    Ok(())
+}
+
+// Changes the stack, then calls the scenes' callbacks once it's let go of, so they can change scenes too.
+// change returns the scenes it took off the stack.
+fn change_scenes(lua: &Lua, scenes: &SharedScenes, transition: Option<Transition>, change: impl FnOnce(&mut Vec<SceneKey>) -> Vec<SceneKey>) -> LuaResult<()> {
+    // Changing scenes mid transition skips to the end of it
+    finish_transition(scenes, lua)?;
+
+    let before = scenes.borrow().stack.clone();
+    let from = visible_scenes(lua, &before)?;
+    let removed = change(&mut scenes.borrow_mut().stack);
+    let (old_top, new_top) = (before.last().cloned(), scenes.borrow().top());
+
+    // Scenes that left exit right away, or when the transition is done so they can keep drawing until then
+    let exiting = match transition {
+        Some(transition) => {
+            scenes.borrow_mut().transition = Some(SceneTransition { transition, from, exiting: removed });
+            Vec::new()
+        },
+        None => removed,
+    };
+    for scene in exiting.iter().rev() {
+        call_scene(lua, scene, "exit", None)?;
+    }
+
+    let still_on_stack = |scene: &SceneKey| scenes.borrow().stack.iter().any(|other| Rc::ptr_eq(other, scene));
+    if let Some(old_top) = &old_top {
+        let is_new_top = matches!(&new_top, Some(new_top) if Rc::ptr_eq(old_top, new_top));
+        if !is_new_top && still_on_stack(old_top) { call_scene(lua, old_top, "pause", None)?; }
+    }
+    if let Some(new_top) = &new_top {
+        let was_on_stack = before.iter().any(|scene| Rc::ptr_eq(scene, new_top));
+        let was_top = matches!(&old_top, Some(old_top) if Rc::ptr_eq(old_top, new_top));
+        if !was_on_stack { call_scene(lua, new_top, "enter", None)?; } else if !was_top { call_scene(lua, new_top, "resume", None)?; }
+    }
+    Ok(())
+}
+
+// Ends the transition, if there is one, and calls exit on the scenes it was keeping around
+fn finish_transition(scenes: &SharedScenes, lua: &Lua) -> LuaResult<()> {
+    let finished = scenes.borrow_mut().transition.take();
+    if let Some(scene_transition) = finished {
+        for scene in scene_transition.exiting.iter().rev() {
+            call_scene(lua, scene, "exit", None)?;
+        }
+    }
+    Ok(())
+}
+
+// The top scene and every scene that shows through the overlays above it, bottom first
+fn visible_scenes(lua: &Lua, stack: &[SceneKey]) -> LuaResult<Vec<SceneKey>> {
+    let mut visible = Vec::new();
+    for scene in stack.iter().rev() {
+        visible.push(scene.clone());
+        let table: LuaTable = lua.registry_value(scene)?;
+        if !table.get::<_, Option<bool>>("overlay")?.unwrap_or(false) { break; }
+    }
+    visible.reverse();
+    Ok(visible)
+}
+
+fn draw_layers(lua: &Lua, rasterizer: &SharedRasterizer, layers: &[SceneKey]) -> LuaResult<()> {
+    for scene in layers {
+        // A scene's transforms don't carry over into the next one
+        rasterizer.borrow_mut().reset_transform();
+        call_scene(lua, scene, "draw", None)?;
+    }
+    Ok(())
+}
+
+// Calls one of a scene's callbacks with the scene as self, if the scene has it
+fn call_scene(lua: &Lua, scene: &SceneKey, callback: &str, dt: Option<f64>) -> LuaResult<()> {
+    let table: LuaTable = lua.registry_value(scene)?;
+    match table.get::<_, Option<LuaFunction>>(callback)? {
+        Some(function) => function.call::<_, ()>((table, dt)),
+        None => Ok(()),
+    }
+}
+
+// "fade", "wipe" or "dissolve", or a table like {type = "wipe", duration = 0.5, direction = "left", easing = "in_out_quad"}
+// Fades take a color to fade through, without one they blend straight from one scene to the other
+fn transition_from_value(value: Option<LuaValue>, function: &str) -> LuaResult<Option<Transition>> {
+    let (kind, options) = match value {
+        None | Some(LuaValue::Nil) => return Ok(None),
+        Some(LuaValue::String(kind)) => (kind.to_str()?.to_string(), None),
+        Some(LuaValue::Table(options)) => (options.get::<_, Option<String>>("type")?.unwrap_or_else(|| "fade".to_string()), Some(options)),
+        Some(_) => return Err(LuaError::RuntimeError(format!("{}: a transition is a name or a table", function))),
+    };
+
+    let mut duration = 0.5;
+    let mut easing = Easing::Linear;
+    let mut color: Option<Color> = None;
+    let mut direction = WipeDirection::Left;
+    if let Some(options) = options {
+        if let Some(seconds) = options.get::<_, Option<f64>>("duration")? { duration = seconds; }
+        if let Some(name) = options.get::<_, Option<String>>("easing")? {
+            easing = Easing::from_str(&name).ok_or_else(|| LuaError::RuntimeError(format!("{}: unknown easing \"{}\"", function, name)))?;
+        }
+        color = options.get("color")?;
+        if let Some(name) = options.get::<_, Option<String>>("direction")? {
+            direction = WipeDirection::from_str(&name).ok_or_else(|| LuaError::RuntimeError(format!("{}: unknown wipe direction \"{}\", use left, right, up or down", function, name)))?;
+        }
+    }
+
+    let kind = match kind.as_str() {
+        "fade" => match color {
+            Some(color) => TransitionKind::FadeColor(color),
+            None => TransitionKind::Fade,
+        },
+        "wipe" => TransitionKind::Wipe(direction),
+        "dissolve" => TransitionKind::Dissolve,
+        _ => return Err(LuaError::RuntimeError(format!("{}: unknown transition \"{}\", use fade, wipe or dissolve", function, kind))),
+    };
+    Ok(Some(Transition::new(kind, duration, easing)))
+}
@@ -1,19 +1,37 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::sync::Arc;
 
 use dashmap::DashMap;
-use soloud::{Soloud, Wav, WavStream};
+use soloud::{Soloud, Wav, WavStream, Bus};
 
 //use rapier2d_f64::prelude::*;
 
 use crate::partitioned_rasterizer::PartitionedRasterizer;
 use crate::VideoData;
 use crate::rasterizer::Rasterizer;
+use crate::palette::ScreenPalette;
+use crate::postprocess::PostProcess;
+use crate::lighting::Lighting;
+use crate::particles::ParticleSystem;
+use crate::tween::TweenManager;
+use crate::scene::SceneManager;
+use crate::entities::EntitySystem;
 use crate::controls::ControlData;
+use crate::audio_filter::AudioFilter;
+use crate::font::FontCache;
+use crate::localization::Localization;
 //use crate::rapier2d_wrap::RapierWorld2D;
 
 pub type SharedVideoData = Rc<RefCell<VideoData>>;
 pub type SharedRasterizer = Rc<RefCell<PartitionedRasterizer>>;
+pub type SharedPalette = Rc<RefCell<ScreenPalette>>;
+pub type SharedPostProcess = Rc<RefCell<PostProcess>>;
+pub type SharedLighting = Rc<RefCell<Lighting>>;
+pub type SharedParticles = Rc<RefCell<ParticleSystem>>;
+pub type SharedTweens = Rc<RefCell<TweenManager>>;
+pub type SharedScenes = Rc<RefCell<SceneManager>>;
+pub type SharedEntities = Rc<RefCell<EntitySystem>>;
 pub type SharedControlData = Rc<RefCell<ControlData>>;
 //pub type SharedPhysics2D = Rc<RefCell<RapierWorld2D>>;
 
@@ -21,6 +39,12 @@ pub type SharedAudio = Rc<Soloud>;
 pub type SharedAudioHandle = Rc<DashMap<String, soloud::Handle>>;
 pub type SharedAudioWav = Rc<DashMap<String, Wav>>;
 pub type SharedAudioWavStream = Rc<DashMap<String, WavStream>>;
+pub type SharedAudioBus = Rc<DashMap<String, Bus>>;
+pub type SharedAudioFilter = Rc<DashMap<String, AudioFilter>>;
 
-pub type SharedImages = Rc<DashMap<String, Rasterizer>>;
+// Images are shared with the command buffer, so changing one that is still waiting to be drawn makes a copy first
+pub type SharedImages = Rc<DashMap<String, Arc<Rasterizer>>>;
+pub type SharedFontCache = Rc<RefCell<FontCache>>;
+
+pub type SharedLocalization = Rc<RefCell<Localization>>;
 
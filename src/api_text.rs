@@ -0,0 +1,34 @@
+use crate::font::*;
+use crate::text::*;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+use crate::api_math::create_vector_function;
+
+use std::time::Instant;
+
+pub fn register_text_api(rasterizer: SharedRasterizer, lua: &Lua) {
+    println!("Registering API: Text");
+
+    // Measure text //
+    let fn_text_measure = lua.create_function(move |_, (font, text, wrap_width, line_spacing): (LuaAnyUserData, String, Option<f64>, Option<f64>)| {
+        let font = font.borrow::<Font>()?;
+        let layout = layout_text(&font, &text, wrap_width.map(|w| w as i64), TextAlign::Left, line_spacing.unwrap_or(2.0) as i64, true);
+        Ok((layout.width, layout.height))
+    } ).unwrap();
+    let _ = lua.globals().set("text_measure", fn_text_measure);
+
+    // Print text in a box with alignment and markup //
+    // Effects are driven by the time since the API was registered so they animate without any extra work from Lua
+    let rst = rasterizer.clone();
+    let start_time = Instant::now();
+    let fn_pprint_box = create_vector_function(lua, move |_, (font, text, x, y, width, align, line_spacing): (LuaAnyUserData, String, f64, f64, f64, Option<String>, Option<f64>)| {
+        let font = font.borrow::<Font>()?;
+        let align = TextAlign::from_str(align.unwrap_or("left".to_string()).as_str());
+        let layout = layout_text(&font, &text, Some(width as i64), align, line_spacing.unwrap_or(2.0) as i64, true);
+        rst.borrow_mut().ptext(&font, &layout, x as i64, y as i64, start_time.elapsed().as_secs_f64());
+        Ok((layout.width, layout.height))
+    } ).unwrap();
+    let _ = lua.globals().set("pprint_box", fn_pprint_box);
+}
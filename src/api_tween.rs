@@ -0,0 +1,145 @@
+use crate::easing::Easing;
+use crate::tween::*;
+
+use mlua::prelude::*;
+
+use crate::api_shareables::*;
+
+pub fn register_tween(tweens: SharedTweens, lua: &Lua) {
+    println!("Registering API: Tween");
+
+    // Tween //
+    // tween(target, duration, {x = 100, y = 50}, "out_quad", {delay = 0.5, loops = 2, yoyo = true, on_complete = function() end})
+    // The target is a table, "camera" for x, y, position, rotation and scale, or "draw" for tint and opacity
+    // Table fields are read and set raw, so __index and __newindex aren't called
+    // Start values are read when the tween starts, after any delay, so tweens can follow one another
+    let twn = tweens.clone();
+    let fn_tween = lua.create_function(move |lua, (target, duration, properties, easing, options): (LuaValue, f64, LuaTable, Option<String>, Option<LuaTable>)| {
+        let target = match target {
+            LuaValue::Table(table) => TweenTarget::Table(lua.create_registry_value(table)?),
+            LuaValue::String(name) => match name.to_str()? {
+                "camera" => TweenTarget::Camera,
+                "draw" => TweenTarget::Draw,
+                name => return Err(LuaError::RuntimeError(format!("tween: unknown target \"{}\", use a table, \"camera\" or \"draw\"", name))),
+            },
+            _ => return Err(LuaError::RuntimeError("tween: the target must be a table, \"camera\" or \"draw\"".to_string())),
+        };
+
+        let mut tween_properties: Vec<TweenProperty> = Vec::new();
+        for pair in properties.pairs::<String, LuaValue>() {
+            let (name, value) = pair?;
+            let to = TweenValue::from_lua_value(&value).ok_or_else(|| LuaError::RuntimeError(format!("tween: \"{}\" must go to a number, vec2 or color", name)))?;
+            target.check(&name, to).map_err(|e| LuaError::RuntimeError(format!("tween: {}", e)))?;
+            tween_properties.push(TweenProperty { name, from: None, to });
+        }
+
+        let easing = easing_from_name(easing, "tween")?;
+        let mut node = TweenNode::new(TweenKind::Tween { target, properties: tween_properties, duration, easing });
+        apply_options(lua, &mut node, options)?;
+        Ok(twn.borrow_mut().add_tween(node))
+    }).unwrap();
+    let _ = lua.globals().set("tween", fn_tween);
+
+    // Sequences and Groups //
+    // tween_sequence({id, id, ...}, options) plays tweens one after another, tween_parallel plays them all at once
+    // The tweens are moved into the new one, their ids stop working and the new id is returned
+    let twn = tweens.clone();
+    let fn_tween_sequence = lua.create_function(move |lua, (ids, options): (Vec<u64>, Option<LuaTable>)| {
+        let children: Vec<TweenNode> = ids.into_iter().filter_map(|id| twn.borrow_mut().take_tween(id)).collect();
+        let mut node = TweenNode::new(TweenKind::Sequence(children));
+        apply_options(lua, &mut node, options)?;
+        Ok(twn.borrow_mut().add_tween(node))
+    }).unwrap();
+    let _ = lua.globals().set("tween_sequence", fn_tween_sequence);
+
+    let twn = tweens.clone();
+    let fn_tween_parallel = lua.create_function(move |lua, (ids, options): (Vec<u64>, Option<LuaTable>)| {
+        let children: Vec<TweenNode> = ids.into_iter().filter_map(|id| twn.borrow_mut().take_tween(id)).collect();
+        let mut node = TweenNode::new(TweenKind::Parallel(children));
+        apply_options(lua, &mut node, options)?;
+        Ok(twn.borrow_mut().add_tween(node))
+    }).unwrap();
+    let _ = lua.globals().set("tween_parallel", fn_tween_parallel);
+
+    // Cancel Tweens //
+    // Stops where it is, on_complete isn't called
+    let twn = tweens.clone();
+    let fn_tween_cancel = lua.create_function(move |_, id: u64| {
+        twn.borrow_mut().cancel(id);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("tween_cancel", fn_tween_cancel);
+
+    // Clears every tween and timer
+    let twn = tweens.clone();
+    let fn_tween_clear = lua.create_function(move |_, ()| {
+        twn.borrow_mut().clear();
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("tween_clear", fn_tween_clear);
+
+    // Ease //
+    // ease("out_bounce", t) for easing things by hand
+    let fn_ease = lua.create_function(move |_, (easing, t): (String, f64)| {
+        Ok(easing_from_name(Some(easing), "ease")?.apply(t))
+    }).unwrap();
+    let _ = lua.globals().set("ease", fn_ease);
+
+    // Timers //
+    // after(seconds, function) calls it once, every(seconds, function) keeps calling it until it returns false
+    let twn = tweens.clone();
+    let fn_after = lua.create_function(move |lua, (seconds, callback): (f64, LuaFunction)| {
+        let callback = lua.create_registry_value(callback)?;
+        Ok(twn.borrow_mut().add_timer(seconds, false, callback))
+    }).unwrap();
+    let _ = lua.globals().set("after", fn_after);
+
+    let twn = tweens.clone();
+    let fn_every = lua.create_function(move |lua, (seconds, callback): (f64, LuaFunction)| {
+        let callback = lua.create_registry_value(callback)?;
+        Ok(twn.borrow_mut().add_timer(seconds, true, callback))
+    }).unwrap();
+    let _ = lua.globals().set("every", fn_every);
+
+    let twn = tweens.clone();
+    let fn_timer_cancel = lua.create_function(move |_, id: u64| {
+        twn.borrow_mut().cancel(id);
+        Ok(())
+    }).unwrap();
+    let _ = lua.globals().set("timer_cancel", fn_timer_cancel);
+}
+
+/// Moves every tween and timer along, then calls the callbacks of the ones that finished.
+/// Callbacks are called after the tweens are let go of, so they can start new tweens and timers.
+pub fn update_tweens(tweens: &SharedTweens, lua: &Lua, rasterizer: &SharedRasterizer, dt: f64) -> LuaResult<()> {
+    let callbacks = tweens.borrow_mut().update(lua, &mut rasterizer.borrow_mut(), dt)?;
+    for (timer, callback) in callbacks {
+        let result: LuaValue = callback.call(())?;
+        if let (Some(id), LuaValue::Boolean(false)) = (timer, result) {
+            tweens.borrow_mut().cancel(id);
+        }
+    }
+    lua.expire_registry_values();
+    Ok(())
+}
+
+fn easing_from_name(name: Option<String>, function: &str) -> LuaResult<Easing> {
+    match name {
+        None => Ok(Easing::Linear),
+        Some(name) => Easing::from_str(&name).ok_or_else(|| LuaError::RuntimeError(format!("{}: unknown easing \"{}\"", function, name))),
+    }
+}
+
+fn apply_options(lua: &Lua, node: &mut TweenNode, options: Option<LuaTable>) -> LuaResult<()> {
+    let options = match options {
+        Some(options) => options,
+        None => return Ok(()),
+    };
+    if let Some(delay) = options.get::<_, Option<f64>>("delay")? { node.delay = f64::max(delay, 0.0); }
+    if let Some(loops) = options.get::<_, Option<i64>>("loops")? { node.loops = loops; }
+    if let Some(yoyo) = options.get::<_, Option<bool>>("yoyo")? { node.yoyo = yoyo; }
+    if let Some(on_complete) = options.get::<_, Option<LuaFunction>>("on_complete")? {
+        node.on_complete = Some(lua.create_registry_value(on_complete)?);
+    }
+    Ok(())
+}
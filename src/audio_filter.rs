@@ -0,0 +1,165 @@
+use soloud::prelude::*;
+use soloud::filter::*;
+use soloud::{Soloud, Handle};
+
+/// Soloud filters that can be attached to sounds, music, or mixer buses.
+/// Soloud allows up to 8 filters per source, so every attached filter needs a slot between 0 and 7.
+/// Filters applied to a sound are only used by voices started *after* the filter was set.
+pub enum AudioFilter {
+	LowPass(BiquadResonantFilter),
+	HighPass(BiquadResonantFilter),
+	BandPass(BiquadResonantFilter),
+	Echo(EchoFilter),
+	Reverb(FreeverbFilter),
+	Lofi(LofiFilter),
+	Flanger(FlangerFilter),
+}
+
+impl AudioFilter {
+	pub const MAX_SLOTS: u32 = 8;
+
+	pub fn lowpass(frequency: f32, resonance: f32) -> AudioFilter {
+		AudioFilter::LowPass(AudioFilter::biquad(BiquadResonantFilterType::LowPass, frequency, resonance))
+	}
+
+	pub fn highpass(frequency: f32, resonance: f32) -> AudioFilter {
+		AudioFilter::HighPass(AudioFilter::biquad(BiquadResonantFilterType::HighPass, frequency, resonance))
+	}
+
+	pub fn bandpass(frequency: f32, resonance: f32) -> AudioFilter {
+		AudioFilter::BandPass(AudioFilter::biquad(BiquadResonantFilterType::BandPass, frequency, resonance))
+	}
+
+	/// Delay is in seconds, decay is how much of the signal is kept per echo (0.0 - 1.0).
+	/// Filter smooths out the echo, 0.0 is no filtering.
+	pub fn echo(delay: f32, decay: f32, filter: f32) -> AudioFilter {
+		let mut echo = EchoFilter::default();
+		let result = echo.set_params_ex(delay, decay, filter);
+		if result.is_err() {
+			println!("ERROR - AUDIO: Echo filter parameters are invalid! Soloud: {}", result.err().unwrap());
+		}
+		AudioFilter::Echo(echo)
+	}
+
+	/// Freeverb reverb. Room size, damp and width all range from 0.0 to 1.0.
+	pub fn reverb(room_size: f32, damp: f32, width: f32) -> AudioFilter {
+		let mut reverb = FreeverbFilter::default();
+		let result = reverb.set_params(0.0, room_size, damp, width);
+		if result.is_err() {
+			println!("ERROR - AUDIO: Reverb filter parameters are invalid! Soloud: {}", result.err().unwrap());
+		}
+		AudioFilter::Reverb(reverb)
+	}
+
+	/// Lowers the samplerate and bitdepth of the signal. Low bitdepths make a good bitcrusher.
+	pub fn lofi(samplerate: f32, bitdepth: f32) -> AudioFilter {
+		let mut lofi = LofiFilter::default();
+		let result = lofi.set_params(samplerate, bitdepth);
+		if result.is_err() {
+			println!("ERROR - AUDIO: Lofi filter parameters are invalid! Soloud: {}", result.err().unwrap());
+		}
+		AudioFilter::Lofi(lofi)
+	}
+
+	/// Delay is in seconds, frequency is how fast the flanger sweeps in hz.
+	pub fn flanger(delay: f32, frequency: f32) -> AudioFilter {
+		let mut flanger = FlangerFilter::default();
+		let result = flanger.set_params(delay, frequency);
+		if result.is_err() {
+			println!("ERROR - AUDIO: Flanger filter parameters are invalid! Soloud: {}", result.err().unwrap());
+		}
+		AudioFilter::Flanger(flanger)
+	}
+
+	fn biquad(filter_type: BiquadResonantFilterType, frequency: f32, resonance: f32) -> BiquadResonantFilter {
+		let mut biquad = BiquadResonantFilter::default();
+		let result = biquad.set_params(filter_type, frequency, resonance);
+		if result.is_err() {
+			println!("ERROR - AUDIO: Biquad filter parameters are invalid! Soloud: {}", result.err().unwrap());
+		}
+		biquad
+	}
+
+	/// Attaches this filter to an audio source or bus in the given slot.
+	pub fn attach<T: AudioExt>(&self, target: &mut T, slot: u32) {
+		match self {
+			AudioFilter::LowPass(f) | AudioFilter::HighPass(f) | AudioFilter::BandPass(f) => { target.set_filter(slot, Some(f)); },
+			AudioFilter::Echo(f) 	=> { target.set_filter(slot, Some(f)); },
+			AudioFilter::Reverb(f) 	=> { target.set_filter(slot, Some(f)); },
+			AudioFilter::Lofi(f) 	=> { target.set_filter(slot, Some(f)); },
+			AudioFilter::Flanger(f) => { target.set_filter(slot, Some(f)); },
+		}
+	}
+
+	/// Removes whatever filter is in the given slot of an audio source or bus.
+	pub fn detach<T: AudioExt>(target: &mut T, slot: u32) {
+		target.set_filter(slot, None::<&BiquadResonantFilter>);
+	}
+
+	/// Changes a parameter of this filter on a playing voice or bus. When 'fade_time' is above zero the parameter is faded
+	/// over that many seconds instead of being set immediately.
+	///
+	/// Every filter has a "wet" parameter (0.0 - 1.0) which mixes between the dry and filtered signal.
+	/// Returns false if the filter does not have a parameter with that name.
+	pub fn set_param(&self, soloud: &Soloud, handle: Handle, slot: u32, param: &str, value: f32, fade_time: f64) -> bool {
+		match self {
+			AudioFilter::LowPass(_) | AudioFilter::HighPass(_) | AudioFilter::BandPass(_) => {
+				let attr = match param {
+					"wet" 		=> BiquadResonantFilterAttr::Wet,
+					"frequency" => BiquadResonantFilterAttr::Frequency,
+					"resonance" => BiquadResonantFilterAttr::Resonance,
+					_ => { return false; }
+				};
+				AudioFilter::apply_param(soloud, handle, slot, attr, value, fade_time);
+			},
+			AudioFilter::Echo(_) => {
+				let attr = match param {
+					"wet" 		=> EchoFilterAttr::Wet,
+					"delay" 	=> EchoFilterAttr::Delay,
+					"decay" 	=> EchoFilterAttr::Decay,
+					"filter" 	=> EchoFilterAttr::Filter,
+					_ => { return false; }
+				};
+				AudioFilter::apply_param(soloud, handle, slot, attr, value, fade_time);
+			},
+			AudioFilter::Reverb(_) => {
+				let attr = match param {
+					"wet" 		=> FreeverbFilterAttr::Wet,
+					"freeze" 	=> FreeverbFilterAttr::Freeze,
+					"room_size" => FreeverbFilterAttr::RoomSize,
+					"damp" 		=> FreeverbFilterAttr::Damp,
+					"width" 	=> FreeverbFilterAttr::Width,
+					_ => { return false; }
+				};
+				AudioFilter::apply_param(soloud, handle, slot, attr, value, fade_time);
+			},
+			AudioFilter::Lofi(_) => {
+				let attr = match param {
+					"wet" 			=> LofiFilterAttr::Wet,
+					"samplerate" 	=> LofiFilterAttr::Samplerate,
+					"bitdepth" 		=> LofiFilterAttr::Bitdepth,
+					_ => { return false; }
+				};
+				AudioFilter::apply_param(soloud, handle, slot, attr, value, fade_time);
+			},
+			AudioFilter::Flanger(_) => {
+				let attr = match param {
+					"wet" 		=> FlangerFilterAttr::Wet,
+					"delay" 	=> FlangerFilterAttr::Delay,
+					"frequency" => FlangerFilterAttr::Freq,
+					_ => { return false; }
+				};
+				AudioFilter::apply_param(soloud, handle, slot, attr, value, fade_time);
+			},
+		}
+		true
+	}
+
+	fn apply_param(soloud: &Soloud, handle: Handle, slot: u32, attr: impl FilterAttr, value: f32, fade_time: f64) {
+		if fade_time > 0.0 {
+			soloud.fade_filter_parameter(handle, slot, attr, value, fade_time);
+		} else {
+			soloud.set_filter_parameter(handle, slot, attr, value);
+		}
+	}
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+/// A single character from a BMFont file.
+#[derive(Copy, Clone, Debug)]
+pub struct BmChar {
+	pub id: u32,
+	pub x: i64,
+	pub y: i64,
+	pub width: i64,
+	pub height: i64,
+	pub offset_x: i64,
+	pub offset_y: i64,
+	pub advance: i64,
+	pub page: usize,
+}
+
+/// Everything the Font needs out of a BMFont descriptor, in any of the three formats AngelCode BMFont can export.
+/// Page paths are exactly as they are written in the file, relative to the descriptor.
+/// <https://www.angelcode.com/products/bmfont/doc/file_format.html>
+#[derive(Clone, Debug, Default)]
+pub struct BmFont {
+	pub line_height: i64,
+	pub base: i64,
+	pub pages: Vec<String>,
+	pub chars: Vec<BmChar>,
+	pub kernings: Vec<(u32, u32, i64)>,
+}
+
+impl BmFont {
+
+	/// Detects the format of the file and parses it. Binary files start with "BMF", XML files with a '<'.
+	pub fn parse(data: &[u8]) -> Result<BmFont, String> {
+		if data.len() >= 4 && &data[0..3] == b"BMF" {
+			return BmFont::parse_binary(data);
+		}
+
+		let text_result = std::str::from_utf8(data);
+		if text_result.is_err() {
+			return Err("ERROR - FONT: BMFont file is not text or binary BMFont!".to_string());
+		}
+
+		// The text and XML formats use the same key=value pairs, XML just wraps them in tags
+		BmFont::parse_text(text_result.unwrap())
+	}
+
+	/// Parses both the text and XML formats.
+	pub fn parse_text(text: &str) -> Result<BmFont, String> {
+		let mut font = BmFont::default();
+
+		for line in text.lines() {
+			let line = line.trim().trim_start_matches('<').trim_end_matches('>').trim_end_matches('/');
+			let (tag, pairs) = BmFont::split_pairs(line);
+			let get = |key: &str| -> i64 {
+				match pairs.get(key) { Some(value) => value.parse::<i64>().unwrap_or(0), None => 0 }
+			};
+
+			match tag.as_str() {
+				"common" => {
+					font.line_height = get("lineHeight");
+					font.base = get("base");
+				},
+				"page" => {
+					let id = get("id") as usize;
+					let file = pairs.get("file").cloned().unwrap_or_default();
+					if font.pages.len() <= id { font.pages.resize(id + 1, String::new()); }
+					font.pages[id] = file;
+				},
+				"char" => {
+					font.chars.push(BmChar {
+						id: get("id") as u32,
+						x: get("x"),
+						y: get("y"),
+						width: get("width"),
+						height: get("height"),
+						offset_x: get("xoffset"),
+						offset_y: get("yoffset"),
+						advance: get("xadvance"),
+						page: get("page") as usize,
+					});
+				},
+				"kerning" => {
+					font.kernings.push((get("first") as u32, get("second") as u32, get("amount")));
+				},
+				_ => {}
+			}
+		}
+
+		if font.pages.is_empty() {
+			return Err("ERROR - FONT: BMFont file has no pages!".to_string());
+		}
+		Ok(font)
+	}
+
+	/// Splits `tag key=value key="quoted value"` into the tag and a map of its values.
+	fn split_pairs(line: &str) -> (String, HashMap<String, String>) {
+		let mut pairs: HashMap<String, String> = HashMap::new();
+		let chars: Vec<char> = line.chars().collect();
+
+		let tag_end = chars.iter().position(|c| c.is_whitespace()).unwrap_or(chars.len());
+		let tag: String = chars[..tag_end].iter().collect();
+
+		let mut i = tag_end;
+		while i < chars.len() {
+			while i < chars.len() && chars[i].is_whitespace() { i += 1; }
+
+			let key_start = i;
+			while i < chars.len() && chars[i] != '=' && !chars[i].is_whitespace() { i += 1; }
+			let key: String = chars[key_start..i].iter().collect();
+
+			if i >= chars.len() || chars[i] != '=' { continue; }
+			i += 1;
+
+			let value: String = if i < chars.len() && chars[i] == '"' {
+				i += 1;
+				let value_start = i;
+				while i < chars.len() && chars[i] != '"' { i += 1; }
+				let value = chars[value_start..i].iter().collect();
+				i += 1;
+				value
+			} else {
+				let value_start = i;
+				while i < chars.len() && !chars[i].is_whitespace() { i += 1; }
+				chars[value_start..i].iter().collect()
+			};
+
+			pairs.insert(key, value);
+		}
+
+		(tag, pairs)
+	}
+
+	/// Parses the version 3 binary format.
+	pub fn parse_binary(data: &[u8]) -> Result<BmFont, String> {
+		if data.len() < 4 {
+			return Err("ERROR - FONT: BMFont binary file is cut short!".to_string());
+		}
+		if data[3] != 3 {
+			return Err(format!("ERROR - FONT: BMFont binary version {} is not supported, only version 3 is!", data[3]));
+		}
+
+		let u16_at = |idx: usize| u16::from_le_bytes([data[idx], data[idx + 1]]) as i64;
+		let i16_at = |idx: usize| i16::from_le_bytes([data[idx], data[idx + 1]]) as i64;
+		let u32_at = |idx: usize| u32::from_le_bytes([data[idx], data[idx + 1], data[idx + 2], data[idx + 3]]);
+
+		let mut font = BmFont::default();
+		let mut idx: usize = 4;
+
+		while idx + 5 <= data.len() {
+			let block_type = data[idx];
+			let block_size = u32_at(idx + 1) as usize;
+			let block_start = idx + 5;
+			let block_end = block_start + block_size;
+
+			if block_end > data.len() {
+				return Err("ERROR - FONT: BMFont binary file is cut short!".to_string());
+			}
+
+			match block_type {
+				// Common
+				2 => {
+					if block_size < 4 {
+						return Err("ERROR - FONT: BMFont binary common block is too small!".to_string());
+					}
+					font.line_height = u16_at(block_start);
+					font.base = u16_at(block_start + 2);
+				},
+				// Pages, null terminated strings back to back
+				3 => {
+					font.pages = data[block_start..block_end]
+						.split(|b| *b == 0)
+						.filter(|name| !name.is_empty())
+						.map(|name| String::from_utf8_lossy(name).to_string())
+						.collect();
+				},
+				// Chars, 20 bytes each
+				4 => {
+					for c in (block_start..block_end).step_by(20) {
+						if c + 20 > block_end { break; }
+						font.chars.push(BmChar {
+							id: u32_at(c),
+							x: u16_at(c + 4),
+							y: u16_at(c + 6),
+							width: u16_at(c + 8),
+							height: u16_at(c + 10),
+							offset_x: i16_at(c + 12),
+							offset_y: i16_at(c + 14),
+							advance: i16_at(c + 16),
+							page: data[c + 18] as usize,
+						});
+					}
+				},
+				// Kerning pairs, 10 bytes each
+				5 => {
+					for k in (block_start..block_end).step_by(10) {
+						if k + 10 > block_end { break; }
+						font.kernings.push((u32_at(k), u32_at(k + 4), i16_at(k + 8)));
+					}
+				},
+				_ => {}
+			}
+
+			idx = block_end;
+		}
+
+		if font.pages.is_empty() {
+			return Err("ERROR - FONT: BMFont file has no pages!".to_string());
+		}
+		Ok(font)
+	}
+}
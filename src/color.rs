@@ -19,52 +19,63 @@ impl Color {
 		}
 	}
 
-	/// Accurate but slow alpha-blending function
+	/// Accurate but slow alpha-blending function, works with clear backgrounds too.
+	/// Both colors are premultiplied, blended and then divided by the new alpha again like blend_premultiplied.
 	pub fn blend_slow(src: Color, dst: Color, opacity: f64) -> Color {
 		if src.a <= 0 { return Color::clear(); }
 
-		let src_rf64 = src.r as f64 / 255.0;
-		let src_gf64 = src.g as f64 / 255.0;
-		let src_bf64 = src.b as f64 / 255.0;
 		let src_af64 = (src.a as f64 / 255.0) * opacity;
-
-		let dst_rf64 = dst.r as f64 / 255.0;
-		let dst_gf64 = dst.g as f64 / 255.0;
-		let dst_bf64 = dst.b as f64 / 255.0;
 		let dst_af64 = dst.a as f64 / 255.0;
 
 		let fa = src_af64 + dst_af64 * (1.0 - src_af64);
-		let fr = (src_rf64 * src_af64 + dst_rf64 * (1.0 - src_af64)) / fa;
-		let fg = (src_gf64 * src_af64 + dst_gf64 * (1.0 - src_af64)) / fa;
-		let fb = (src_bf64 * src_af64 + dst_bf64 * (1.0 - src_af64)) / fa;
-
-		let r = (fr * 255.0).round() as u8;
-		let g = (fg * 255.0).round() as u8;
-		let b = (fb * 255.0).round() as u8;
-		let a = (fa * 255.0).round() as u8;
-		
-		Color { r, g, b, a}
-	}
+		if fa <= 0.0 { return Color::clear(); }
+		let over = |s: u8, d: u8| {
+			let premultiplied = (s as f64 / 255.0) * src_af64 + (d as f64 / 255.0) * dst_af64 * (1.0 - src_af64);
+			((premultiplied / fa) * 255.0).round() as u8
+		};
 
-	/// Faster but less accurate alpha-blending function. Used in rasterizer since it's accurate enough and removes branching in hot code
-	/// <https://www.codeguru.com/cpp/cpp/algorithms/general/article.php/c15989/Tip-An-Optimized-Formula-for-Alpha-Blending-Pixels.htm>
-	pub fn blend_fast(src: Color, dst: Color, opacity: u8) -> Color {
-		let alpha: u32 = (src.a - (255 - opacity)) as u32;
+		Color { r: over(src.r, dst.r), g: over(src.g, dst.g), b: over(src.b, dst.b), a: (fa * 255.0).round() as u8 }
+	}
 
-		let sr: u32 = src.r as u32;
-		let sg: u32 = src.g as u32;
-		let sb: u32 = src.b as u32;
+	/// Alpha-blends colors that are already premultiplied, the result is premultiplied too.
+	/// Opacity fades the whole source color since its channels already include its alpha.
+	pub fn blend_premultiplied(src: Color, dst: Color, opacity: u8) -> Color {
+		let opacity = opacity as u32;
+		let alpha = (src.a as u32 * opacity + 127) / 255;
+		let over = |s: u8, d: u8| ((s as u32 * opacity + d as u32 * (255 - alpha) + 127) / 255).min(255) as u8;
 
-		let dr: u32 = dst.r as u32;
-		let dg: u32 = dst.g as u32;
-		let db: u32 = dst.b as u32;
+		Color { r: over(src.r, dst.r), g: over(src.g, dst.g), b: over(src.b, dst.b), a: over(src.a, dst.a) }
+	}
 
-		let r = ((sr * alpha) + (dr * (255 - alpha))) >> 8;
-		let g = ((sg * alpha) + (dg * (255 - alpha))) >> 8;
-		let b = ((sb * alpha) + (db * (255 - alpha))) >> 8;
+	/// Returns a copy with red, green and blue multiplied by alpha, for blending with blend_premultiplied.
+	pub fn premultiplied(&self) -> Color {
+		let a = self.a as u32;
+		Color {
+			r: ((self.r as u32 * a + 127) / 255) as u8,
+			g: ((self.g as u32 * a + 127) / 255) as u8,
+			b: ((self.b as u32 * a + 127) / 255) as u8,
+			a: self.a,
+		}
+	}
 
-		Color { r: r as u8, g: g as u8, b: b as u8, a: 255}
+	/// Undoes premultiplied, clear colors stay clear.
+	pub fn unpremultiplied(&self) -> Color {
+		if self.a == 0 { return Color::clear(); }
+		let a = self.a as u32;
+		Color {
+			r: ((self.r as u32 * 255 + a / 2) / a).min(255) as u8,
+			g: ((self.g as u32 * 255 + a / 2) / a).min(255) as u8,
+			b: ((self.b as u32 * 255 + a / 2) / a).min(255) as u8,
+			a: self.a,
+		}
+	}
 
+	/// Faster but less accurate alpha-blending function. Used in rasterizer since it's accurate enough and removes branching in hot code
+	/// The background is treated as opaque and the opacity is taken straight off the source alpha instead of multiplied with it,
+	/// so it's blend_premultiplied with an opaque source faded by what's left of its alpha.
+	pub fn blend_fast(src: Color, dst: Color, opacity: u8) -> Color {
+		let alpha = src.a.saturating_sub(255 - opacity);
+		Color::blend_premultiplied(Color { a: 255, ..src }, Color { a: 255, ..dst }, alpha)
 	}
 
 	/// Byte inverted copy of the color
@@ -79,8 +90,10 @@ impl Color {
 
 	/// Hue, Saturation, and Value color definition. Should not be used per pixel due to casting and division use.
 	pub fn hsv(hue: f64, saturation: f64, value: f64) -> Color {
-		let hi: i32 = ((hue / 60.0).floor() as i32) % 6;
-		let f: f64 = (hue / 60.0) - (hue / 60.0).floor();
+		let h = hue.rem_euclid(360.0) / 60.0;
+		// Hues just below 0 round up to 360 itself, which is the start of the first sector again
+		let hi: i32 = h.floor() as i32 % 6;
+		let f: f64 = h - h.floor();
 
 		let p: f64 = value * (1.0 - saturation);
 		let q: f64 = value * (1.0 - (f * saturation));
@@ -88,26 +101,156 @@ impl Color {
 
 		match hi
 		{
-			0 => { return Color::new((value * 255.0) as u8, (t * 255.0) as u8, (p * 255.0) as u8, 255); },
-			1 => { return Color::new((q * 255.0) as u8, (value * 255.0) as u8, (p * 255.0) as u8, 255); },
-			2 => { return Color::new((p * 255.0) as u8, (value * 255.0) as u8, (t * 255.0) as u8, 255); },
-			3 => { return Color::new((p * 255.0) as u8, (q * 255.0) as u8, (value * 255.0) as u8, 255); },
-			4 => { return Color::new((t * 255.0) as u8, (p * 255.0) as u8, (value * 255.0) as u8, 255); },
-			5 => { return Color::new((value * 255.0) as u8, (p * 255.0) as u8, (q * 255.0) as u8, 255); },
-			_ => { return Color::white(); }
+			0 => { Color::new(unit_to_byte(value), unit_to_byte(t), unit_to_byte(p), 255) },
+			1 => { Color::new(unit_to_byte(q), unit_to_byte(value), unit_to_byte(p), 255) },
+			2 => { Color::new(unit_to_byte(p), unit_to_byte(value), unit_to_byte(t), 255) },
+			3 => { Color::new(unit_to_byte(p), unit_to_byte(q), unit_to_byte(value), 255) },
+			4 => { Color::new(unit_to_byte(t), unit_to_byte(p), unit_to_byte(value), 255) },
+			_ => { Color::new(unit_to_byte(value), unit_to_byte(p), unit_to_byte(q), 255) },
 		}
 	}
 
+	/// Returns the hue from 0 to 360, and the saturation and value from 0.0 to 1.0.
+	pub fn to_hsv(&self) -> (f64, f64, f64) {
+		let (r, g, b) = (self.r as f64 / 255.0, self.g as f64 / 255.0, self.b as f64 / 255.0);
+		let max = f64::max(r, f64::max(g, b));
+		let chroma = max - f64::min(r, f64::min(g, b));
+
+		let saturation = if max > 0.0 { chroma / max } else { 0.0 };
+		(hue_of(r, g, b, max, chroma), saturation, max)
+	}
+
+	/// Hue, Saturation, and Lightness color definition. Hue takes a 360 angle, Saturation and Lightness go from 0.0 to 1.0.
+	pub fn hsl(hue: f64, saturation: f64, lightness: f64) -> Color {
+		let saturation = f64::clamp(saturation, 0.0, 1.0);
+		let lightness = f64::clamp(lightness, 0.0, 1.0);
+		let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+		let h = hue.rem_euclid(360.0) / 60.0;
+		let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+
+		let (r, g, b) = match h as i32 {
+			0 => (chroma, x, 0.0),
+			1 => (x, chroma, 0.0),
+			2 => (0.0, chroma, x),
+			3 => (0.0, x, chroma),
+			4 => (x, 0.0, chroma),
+			_ => (chroma, 0.0, x),
+		};
+		let m = lightness - chroma / 2.0;
+		Color::new(unit_to_byte(r + m), unit_to_byte(g + m), unit_to_byte(b + m), 255)
+	}
+
+	/// Returns the hue from 0 to 360, and the saturation and lightness from 0.0 to 1.0.
+	pub fn to_hsl(&self) -> (f64, f64, f64) {
+		let (r, g, b) = (self.r as f64 / 255.0, self.g as f64 / 255.0, self.b as f64 / 255.0);
+		let max = f64::max(r, f64::max(g, b));
+		let min = f64::min(r, f64::min(g, b));
+		let chroma = max - min;
+
+		let lightness = (max + min) / 2.0;
+		let saturation = if chroma > 0.0 { chroma / (1.0 - (2.0 * lightness - 1.0).abs()) } else { 0.0 };
+		(hue_of(r, g, b, max, chroma), saturation, lightness)
+	}
+
+	/// OKLab color definition, where the same step in any direction looks like the same amount of change.
+	/// Lightness goes from 0.0 to 1.0, a and b are roughly -0.4 to 0.4 for green-red and blue-yellow.
+	/// <https://bottosson.github.io/posts/oklab/>
+	pub fn oklab(lightness: f64, a: f64, b: f64) -> Color {
+		let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+		let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+		let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+		Color::new(
+			unit_to_byte(linear_to_srgb( 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s)),
+			unit_to_byte(linear_to_srgb(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s)),
+			unit_to_byte(linear_to_srgb(-0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s)),
+			255,
+		)
+	}
+
+	/// Returns the lightness, a and b of the color in OKLab.
+	pub fn to_oklab(&self) -> (f64, f64, f64) {
+		let r = srgb_to_linear(self.r as f64 / 255.0);
+		let g = srgb_to_linear(self.g as f64 / 255.0);
+		let b = srgb_to_linear(self.b as f64 / 255.0);
+
+		let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+		let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+		let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+		(
+			0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+			1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+			0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+		)
+	}
+
+	/// Reads a hex color like #f80, #f80c, #ff8800 or #ff8800cc, the # is optional.
+	pub fn from_hex(hex: &str) -> Option<Color> {
+		let digits = hex.trim().trim_start_matches('#');
+		if !digits.chars().all(|c| c.is_ascii_hexdigit()) { return None; }
+
+		let channel = |i: usize, width: usize| -> u8 {
+			let value = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).unwrap_or(0);
+			if width == 1 { value * 17 } else { value }
+		};
+
+		match digits.len() {
+			3 => Some(Color::new(channel(0, 1), channel(1, 1), channel(2, 1), 255)),
+			4 => Some(Color::new(channel(0, 1), channel(1, 1), channel(2, 1), channel(3, 1))),
+			6 => Some(Color::new(channel(0, 2), channel(1, 2), channel(2, 2), 255)),
+			8 => Some(Color::new(channel(0, 2), channel(1, 2), channel(2, 2), channel(3, 2))),
+			_ => None,
+		}
+	}
+
+	/// Writes the color as #rrggbb, or #rrggbbaa if it isn't opaque.
+	pub fn to_hex(&self) -> String {
+		if self.a == 255 {
+			format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+		} else {
+			format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+		}
+	}
+
+	/// Blends straight between the channels, t goes from 0.0 to 1.0.
 	pub fn lerp_rgb(c1: Color, c2: Color, t: f64) -> Color {
-		let tb = (f64::clamp(t, 0.0, 1.0) / 255.0) as u8;
-		let mut cf: Color = Color::clear();
+		let t = f64::clamp(t, 0.0, 1.0);
+		let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+		Color::new(channel(c1.r, c2.r), channel(c1.g, c2.g), channel(c1.b, c2.b), channel(c1.a, c2.a))
+	}
+
+	/// Blends between colors through OKLab so the middle doesn't go muddy or dark like lerp_rgb can.
+	pub fn lerp(c1: Color, c2: Color, t: f64) -> Color {
+		let t = f64::clamp(t, 0.0, 1.0);
+		let (l1, a1, b1) = c1.to_oklab();
+		let (l2, a2, b2) = c2.to_oklab();
 
-		cf.r = c1.r + (c2.r - c1.r) * tb;
-		cf.g = c1.g + (c2.g - c1.g) * tb;
-		cf.b = c1.b + (c2.b - c1.b) * tb;
-		cf.a = c1.a;
+		let mut out = Color::oklab(l1 + (l2 - l1) * t, a1 + (a2 - a1) * t, b1 + (b2 - b1) * t);
+		out.a = (c1.a as f64 + (c2.a as f64 - c1.a as f64) * t).round() as u8;
+		out
+	}
+
+	/// Samples a gradient of evenly spaced colors with lerp, t goes from 0.0 at the first color to 1.0 at the last.
+	pub fn gradient(colors: &[Color], t: f64) -> Color {
+		match colors.len() {
+			0 => Color::clear(),
+			1 => colors[0],
+			count => {
+				let position = f64::clamp(t, 0.0, 1.0) * (count - 1) as f64;
+				let idx = usize::min(position as usize, count - 2);
+				Color::lerp(colors[idx], colors[idx + 1], position - idx as f64)
+			}
+		}
+	}
 
-		cf
+	/// Returns count colors spread evenly along a gradient, for things like palettes and fading trails.
+	pub fn gradient_steps(colors: &[Color], count: usize) -> Vec<Color> {
+		match count {
+			0 => Vec::new(),
+			1 => vec![Color::gradient(colors, 0.0)],
+			_ => (0..count).map(|i| Color::gradient(colors, i as f64 / (count - 1) as f64)).collect(),
+		}
 	}
 
 	pub fn clear() -> Color {
@@ -143,23 +286,56 @@ impl Color {
 	}
 }
 
-impl LuaUserData for Color {
-	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
-		methods.add_method("r", |_, c, ()| {
-			Ok(c.r)
-		});
+// Rounds a 0.0 to 1.0 channel to a byte, anything outside is clamped
+fn unit_to_byte(value: f64) -> u8 {
+	(f64::clamp(value, 0.0, 1.0) * 255.0).round() as u8
+}
 
-		methods.add_method("r", |_, c, ()| {
-			Ok(c.g)
-		});
+// The hue shared by HSV and HSL, from 0 to 360
+fn hue_of(r: f64, g: f64, b: f64, max: f64, chroma: f64) -> f64 {
+	if chroma <= 0.0 { return 0.0; }
+	let hue = if max == r {
+		((g - b) / chroma).rem_euclid(6.0)
+	} else if max == g {
+		(b - r) / chroma + 2.0
+	} else {
+		(r - g) / chroma + 4.0
+	};
+	hue * 60.0
+}
 
-		methods.add_method("b", |_, c, ()| {
-			Ok(c.b)
-		});
+fn srgb_to_linear(value: f64) -> f64 {
+	if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
 
-		methods.add_method("a", |_, c, ()| {
-			Ok(c.a)
-		});
+fn linear_to_srgb(value: f64) -> f64 {
+	if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.max(0.0).powf(1.0 / 2.4) - 0.055 }
+}
+
+impl LuaUserData for Color {
+	fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+		fields.add_field_method_get("r", |_, c| Ok(c.r));
+		fields.add_field_method_get("g", |_, c| Ok(c.g));
+		fields.add_field_method_get("b", |_, c| Ok(c.b));
+		fields.add_field_method_get("a", |_, c| Ok(c.a));
+
+		// Same clamping as rgba
+		fields.add_field_method_set("r", |_, c, r: f64| { c.r = f64::clamp(r, 0.0, 255.0) as u8; Ok(()) });
+		fields.add_field_method_set("g", |_, c, g: f64| { c.g = f64::clamp(g, 0.0, 255.0) as u8; Ok(()) });
+		fields.add_field_method_set("b", |_, c, b: f64| { c.b = f64::clamp(b, 0.0, 255.0) as u8; Ok(()) });
+		fields.add_field_method_set("a", |_, c, a: f64| { c.a = f64::clamp(a, 0.0, 255.0) as u8; Ok(()) });
+	}
+
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		methods.add_method("to_hsv", |_, c, ()| Ok(c.to_hsv()));
+		methods.add_method("to_hsl", |_, c, ()| Ok(c.to_hsl()));
+		methods.add_method("to_oklab", |_, c, ()| Ok(c.to_oklab()));
+		methods.add_method("to_hex", |_, c, ()| Ok(c.to_hex()));
+		methods.add_method("inverted", |_, c, ()| Ok(c.inverted()));
+		methods.add_method("premultiplied", |_, c, ()| Ok(c.premultiplied()));
+		methods.add_method("unpremultiplied", |_, c, ()| Ok(c.unpremultiplied()));
+		methods.add_method("lerp", |_, c, (other, t): (Color, f64)| Ok(Color::lerp(*c, other, t)));
+		methods.add_method("lerp_rgb", |_, c, (other, t): (Color, f64)| Ok(Color::lerp_rgb(*c, other, t)));
 
 		methods.add_meta_function(LuaMetaMethod::Add, |_, (c1, c2): (Color, Color)| {
 			Ok(c1 + c2)
@@ -176,18 +352,27 @@ impl LuaUserData for Color {
 		methods.add_meta_function(LuaMetaMethod::Div, |_, (c1, c2): (Color, Color)| {
 			Ok(c1 / c2)
 		});
+
+		methods.add_meta_function(LuaMetaMethod::Eq, |_, (c1, c2): (Color, Color)| {
+			Ok(c1 == c2)
+		});
+
+		methods.add_meta_method(LuaMetaMethod::ToString, |_, c, ()| {
+			Ok(format!("rgba({}, {}, {}, {})", c.r, c.g, c.b, c.a))
+		});
 	}
 }
 
+// Adding and subtracting saturate at 0 and 255 instead of wrapping around
 impl std::ops::Add for Color {
 	type Output = Self;
 
 	fn add(self, rhs: Self) -> Self {
 		Color::new(
-			self.r + rhs.r,
-			self.g + rhs.g,
-			self.b + rhs.b,
-			self.a + rhs.a,
+			self.r.saturating_add(rhs.r),
+			self.g.saturating_add(rhs.g),
+			self.b.saturating_add(rhs.b),
+			self.a.saturating_add(rhs.a),
 		)
 	}
 }
@@ -197,14 +382,15 @@ impl std::ops::Sub for Color {
 
 	fn sub(self, rhs: Self) -> Self {
 		Color::new(
-			self.r - rhs.r,
-			self.g - rhs.g,
-			self.b - rhs.b,
-			self.a - rhs.a,
+			self.r.saturating_sub(rhs.r),
+			self.g.saturating_sub(rhs.g),
+			self.b.saturating_sub(rhs.b),
+			self.a.saturating_sub(rhs.a),
 		)
 	}
 }
 
+// Multiplying treats channels as 0.0 to 1.0, so white changes nothing. Alpha is kept from the left side, like tinting.
 impl std::ops::Mul for Color {
 	type Output = Self;
 
@@ -226,67 +412,45 @@ impl std::ops::Mul for Color {
 	}
 }
 
+// Undoes Mul, dividing by black saturates to white instead of panicking
 impl std::ops::Div for Color {
 	type Output = Self;
 
 	fn div(self, rhs: Self) -> Self {
+		let channel = |a: u8, b: u8| -> u8 {
+			if b == 0 { return if a == 0 { 0 } else { 255 }; }
+			((a as u32 * 255 + b as u32 / 2) / b as u32).min(255) as u8
+		};
+
 		Color::new(
-			self.r / rhs.r,
-			self.g / rhs.g,
-			self.b / rhs.b,
-			self.a / rhs.a,
+			channel(self.r, rhs.r),
+			channel(self.g, rhs.g),
+			channel(self.b, rhs.b),
+			self.a,
 		)
 	}
 }
 
 impl std::ops::AddAssign for Color {
 	fn add_assign(&mut self, rhs: Self) {
-        *self = Color::new(
-			self.r + rhs.r,
-			self.g + rhs.g,
-			self.b + rhs.b,
-			self.a + rhs.a,
-		);
-    }
+		*self = *self + rhs;
+	}
 }
 
 impl std::ops::SubAssign for Color {
 	fn sub_assign(&mut self, rhs: Self) {
-        *self = Color::new(
-			self.r - rhs.r,
-			self.g - rhs.g,
-			self.b - rhs.b,
-			self.a - rhs.a,
-		);
-    }
+		*self = *self - rhs;
+	}
 }
 
 impl std::ops::MulAssign for Color {
 	fn mul_assign(&mut self, rhs: Self) {
-		let sr32 = self.r as u32;
-		let sg32 = self.g as u32;
-		let sb32 = self.b as u32;
-
-		let rhsr32 = rhs.r as u32;
-		let rhsg32 = rhs.g as u32;
-		let rhsb32 = rhs.b as u32;
-		
-		*self = Color::new(
-			(((sr32 * rhsr32 + 255)) >> 8) as u8,
-			(((sg32 * rhsg32 + 255)) >> 8) as u8,
-			(((sb32 * rhsb32 + 255)) >> 8) as u8,
-			self.a,
-		)
+		*self = *self * rhs;
 	}
 }
 
 impl std::ops::DivAssign for Color {
 	fn div_assign(&mut self, rhs: Self) {
-        *self = Color::new(
-			self.r / rhs.r,
-			self.g / rhs.g,
-			self.b / rhs.b,
-			self.a / rhs.a,
-		);
-    }
-}
\ No newline at end of file
+		*self = *self / rhs;
+	}
+}
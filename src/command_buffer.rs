@@ -0,0 +1,341 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::color::*;
+use crate::matrix3::*;
+use crate::palette::*;
+use crate::rasterizer::*;
+use crate::shapes::*;
+use crate::vector2::*;
+
+// Height of a band in rows, every band is the full width of the screen and the bottom one gets whatever is left over.
+// Bigger screens get taller bands, the tallest that still makes at least TARGET_BANDS of them.
+pub const MIN_BAND_HEIGHT: usize = 8;
+pub const MAX_BAND_HEIGHT: usize = 64;
+const TARGET_BANDS: usize = 24;
+
+// Extra pixels around every bounding box, for rounding and bilinear sampling
+const BOUNDS_MARGIN: i64 = 2;
+
+/// Everything a draw call needs to know about the rasterizer when it was made.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawState {
+	pub draw_mode: DrawMode,
+	pub tint: Color,
+	pub opacity: u8,
+	pub image_sampling: ImageSampling,
+	pub palette_remap: PaletteRemap,
+	/// Camera and transform stack combined, in screen pixels.
+	pub transform: Matrix3,
+	/// The top clip rect in screen pixels as (x0, y0, x1, y1).
+	pub clip: Option<(i64, i64, i64, i64)>,
+}
+
+impl DrawState {
+	/// Copies the drawing state out of a full screen rasterizer.
+	pub fn capture(rasterizer: &Rasterizer) -> DrawState {
+		DrawState {
+			draw_mode: rasterizer.draw_mode,
+			tint: rasterizer.tint,
+			opacity: rasterizer.opacity,
+			image_sampling: rasterizer.image_sampling,
+			palette_remap: rasterizer.palette_remap,
+			transform: rasterizer.transform,
+			clip: rasterizer.clip_stack.last().copied(),
+		}
+	}
+
+	/// Sets up a band to draw the same way, its offset is added on top of the transform and clip rect.
+	pub fn apply(&self, band: &mut Rasterizer) {
+		band.set_draw_mode(self.draw_mode);
+		band.tint = self.tint;
+		band.opacity = self.opacity;
+		band.set_image_sampling(self.image_sampling);
+		band.palette_remap = self.palette_remap;
+
+		// Most commands in a row only change the tint, so the transform and clip are only rebuilt when they change
+		if band.draw_space != DrawSpace::Screen || band.transform_stack.len() != 1 || band.transform_stack[0] != self.transform {
+			band.draw_space = DrawSpace::Screen;
+			band.transform_stack.clear();
+			band.transform_stack.push(self.transform);
+			band.update_transform();
+		}
+
+		if band.clip_stack.last().copied() != self.clip || band.clip_stack.len() > 1 {
+			band.clip_stack.clear();
+			band.clip_stack.extend(self.clip);
+			band.update_clip();
+		}
+	}
+}
+
+/// A recorded draw call. Coordinates are the same ones passed to the Rasterizer function it stands for.
+#[derive(Clone)]
+pub enum DrawCommand {
+	Pset { x: i64, y: i64, color: Color },
+	Line { x0: i64, y0: i64, x1: i64, y1: i64, color: Color },
+	Rectangle { filled: bool, x: i64, y: i64, width: i64, height: i64, color: Color },
+	Circle { filled: bool, xc: i64, yc: i64, radius: i64, color: Color },
+	Triangle { filled: bool, x0: i64, y0: i64, x1: i64, y1: i64, x2: i64, y2: i64, color: Color },
+	/// pimg and pimgrect.
+	Image { image: Arc<Rasterizer>, x: i64, y: i64, rx: i64, ry: i64, rw: i64, rh: i64 },
+	/// pimgmtx and pimgrectmtx.
+	ImageMatrix {
+		image: Arc<Rasterizer>, x: f64, y: f64, rotation: f64, scale_x: f64, scale_y: f64, offset_x: f64, offset_y: f64,
+		rx: i64, ry: i64, rw: i64, rh: i64, flip_x: bool, flip_y: bool,
+	},
+	/// Blits ignore the camera, clip rects and draw mode.
+	Blit { image: Arc<Rasterizer>, x: i64, y: i64 },
+	/// pmesh, the indices are checked against the vertices before it's recorded.
+	Mesh { image: Option<Arc<Rasterizer>>, vertices: Vec<Vertex>, indices: Vec<usize>, mapping: TextureMapping },
+	/// ppolyline, curves and thick lines are flattened into one when they're recorded.
+	Polyline { points: Vec<Vector2>, width: f64, closed: bool, join: LineJoin, cap: LineCap, color: Color },
+	/// ppolyline_aa and pline_aa.
+	PolylineAa { points: Vec<Vector2>, closed: bool, color: Color },
+	/// ppolygon, and filled ellipses flattened into one.
+	Polygon { filled: bool, points: Vec<Vector2>, rule: FillRule, color: Color },
+}
+
+impl DrawCommand {
+	/// Returns the screen pixels the command could touch as (x0, y0, x1, y1), or None if it can't be seen.
+	pub fn bounds(&self, state: &DrawState, width: usize, height: usize) -> Option<(i64, i64, i64, i64)> {
+		let place = |x: f64, y: f64| state.transform.forward(Vector2::new(x, y));
+		let around = |points: &[Vector2]| -> (i64, i64, i64, i64) {
+			let (min_x, min_y, max_x, max_y) = points.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |(a, b, c, d), p| {
+				(f64::min(a, p.x), f64::min(b, p.y), f64::max(c, p.x), f64::max(d, p.y))
+			});
+			(
+				f64::floor(min_x) as i64 - BOUNDS_MARGIN, f64::floor(min_y) as i64 - BOUNDS_MARGIN,
+				f64::ceil(max_x) as i64 + BOUNDS_MARGIN, f64::ceil(max_y) as i64 + BOUNDS_MARGIN,
+			)
+		};
+		// Shapes made of many points, pushed out by how far they can reach past them in screen pixels
+		let around_all = |points: &mut dyn Iterator<Item = Vector2>, reach: f64| -> (i64, i64, i64, i64) {
+			let placed: Vec<Vector2> = points.map(|p| state.transform.forward(p)).collect();
+			let (x0, y0, x1, y1) = around(&placed);
+			let reach = f64::ceil(reach * state.transform.max_scale()) as i64;
+			(x0 - reach, y0 - reach, x1 + reach, y1 + reach)
+		};
+
+		let bounds = match self {
+			DrawCommand::Pset { x, y, .. } => around(&[place(*x as f64, *y as f64)]),
+			DrawCommand::Line { x0, y0, x1, y1, .. } => around(&[place(*x0 as f64, *y0 as f64), place(*x1 as f64, *y1 as f64)]),
+			DrawCommand::Rectangle { x, y, width, height, .. } => {
+				let (x0, y0, x1, y1) = (*x as f64, *y as f64, (*x + *width) as f64, (*y + *height) as f64);
+				around(&[place(x0, y0), place(x1, y0), place(x1, y1), place(x0, y1)])
+			},
+			DrawCommand::Circle { xc, yc, radius, .. } => {
+				let center = place(*xc as f64, *yc as f64);
+				let reach = *radius as f64 * state.transform.max_scale() + 1.0;
+				around(&[center - Vector2::new(reach, reach), center + Vector2::new(reach, reach)])
+			},
+			DrawCommand::Triangle { x0, y0, x1, y1, x2, y2, .. } => {
+				around(&[place(*x0 as f64, *y0 as f64), place(*x1 as f64, *y1 as f64), place(*x2 as f64, *y2 as f64)])
+			},
+			DrawCommand::Image { x, y, rw, rh, .. } => {
+				let (x0, y0, x1, y1) = (*x as f64, *y as f64, (*x + *rw) as f64, (*y + *rh) as f64);
+				around(&[place(x0, y0), place(x1, y0), place(x1, y1), place(x0, y1)])
+			},
+			DrawCommand::ImageMatrix { x, y, scale_x, scale_y, offset_x, offset_y, rw, rh, .. } => {
+				// Whatever the rotation, the image stays within this distance of its pivot
+				let far_x = f64::max(offset_x.abs(), (1.0 - offset_x).abs()) * *rw as f64 * scale_x.abs();
+				let far_y = f64::max(offset_y.abs(), (1.0 - offset_y).abs()) * *rh as f64 * scale_y.abs();
+				let reach = f64::sqrt(far_x * far_x + far_y * far_y) * state.transform.max_scale() + 1.0;
+				let center = place(*x, *y);
+				around(&[center - Vector2::new(reach, reach), center + Vector2::new(reach, reach)])
+			},
+			DrawCommand::Blit { image, x, y } => {
+				// Blits that don't fit on the screen are never drawn
+				if *x < 0 || *y < 0 || *x as usize + image.width > width || *y as usize + image.height > height { return None; }
+				return Some((*x, *y, *x + image.width as i64, *y + image.height as i64));
+			},
+			DrawCommand::Mesh { vertices, .. } => around_all(&mut vertices.iter().map(|v| v.position), 0.0),
+			DrawCommand::Polyline { points, width, .. } => around_all(&mut points.iter().copied(), stroke_reach(*width)),
+			DrawCommand::PolylineAa { points, .. } | DrawCommand::Polygon { points, .. } => around_all(&mut points.iter().copied(), 0.0),
+		};
+
+		let (clip_x0, clip_y0, clip_x1, clip_y1) = state.clip.unwrap_or((0, 0, width as i64, height as i64));
+		let x0 = i64::max(bounds.0, i64::max(clip_x0, 0));
+		let y0 = i64::max(bounds.1, i64::max(clip_y0, 0));
+		let x1 = i64::min(bounds.2, i64::min(clip_x1, width as i64));
+		let y1 = i64::min(bounds.3, i64::min(clip_y1, height as i64));
+		if x0 >= x1 || y0 >= y1 { return None; }
+		Some((x0, y0, x1, y1))
+	}
+
+	/// Draws the command into a band, which has already had the commands DrawState applied.
+	pub fn execute(&self, band: &mut Rasterizer) {
+		match self {
+			DrawCommand::Pset { x, y, color } => band.pset(*x, *y, *color),
+			DrawCommand::Line { x0, y0, x1, y1, color } => band.pline(*x0, *y0, *x1, *y1, *color),
+			DrawCommand::Rectangle { filled, x, y, width, height, color } => band.prectangle(*filled, *x, *y, *width, *height, *color),
+			DrawCommand::Circle { filled, xc, yc, radius, color } => band.pcircle(*filled, *xc, *yc, *radius, *color),
+			DrawCommand::Triangle { filled, x0, y0, x1, y1, x2, y2, color } => band.ptriangle(*filled, *x0, *y0, *x1, *y1, *x2, *y2, *color),
+			DrawCommand::Image { image, x, y, rx, ry, rw, rh } => band.pimgrect(image, *x, *y, *rx, *ry, *rw, *rh),
+			DrawCommand::ImageMatrix { image, x, y, rotation, scale_x, scale_y, offset_x, offset_y, rx, ry, rw, rh, flip_x, flip_y } => {
+				band.pimgrectmtx(image, *x, *y, *rotation, *scale_x, *scale_y, *offset_x, *offset_y, *rx, *ry, *rw, *rh, *flip_x, *flip_y);
+			},
+			DrawCommand::Blit { image, x, y } => {
+				// Copy only the rows and columns that land in this band
+				let (local_x, local_y) = (*x - band.offset_x as i64, *y - band.offset_y as i64);
+				let start_x = i64::max(0, -local_x);
+				let end_x = i64::min(image.width as i64, band.width as i64 - local_x);
+				let start_y = i64::max(0, -local_y);
+				let end_y = i64::min(image.height as i64, band.height as i64 - local_y);
+				if start_x >= end_x || start_y >= end_y { return; }
+
+				for row in start_y..end_y {
+					let src = ((row * image.width as i64 + start_x) * 4) as usize;
+					let dst = (((local_y + row) * band.width as i64 + local_x + start_x) * 4) as usize;
+					let length = ((end_x - start_x) * 4) as usize;
+					band.color[dst..dst + length].copy_from_slice(&image.color[src..src + length]);
+				}
+			},
+			DrawCommand::Mesh { image, vertices, indices, mapping } => band.pmesh(image.as_deref(), vertices, indices, *mapping),
+			DrawCommand::Polyline { points, width, closed, join, cap, color } => band.ppolyline(points, *width, *closed, *join, *cap, *color),
+			DrawCommand::PolylineAa { points, closed, color } => band.ppolyline_aa(points, *closed, *color),
+			DrawCommand::Polygon { filled, points, rule, color } => band.ppolygon(*filled, points, *rule, *color),
+		}
+	}
+}
+
+/// Returns the band height for a screen, short screens get short bands so there are still enough to go around every core.
+pub fn band_height_for(height: usize) -> usize {
+	let mut band_height = MAX_BAND_HEIGHT;
+	while band_height > MIN_BAND_HEIGHT && height.div_ceil(band_height) < TARGET_BANDS {
+		band_height /= 2;
+	}
+	band_height
+}
+
+struct Band {
+	/// Draws into its own copy of its rows of the screen, which is copied back once its commands are done.
+	rasterizer: Rasterizer,
+	/// Indices into the command list, in the order they were recorded.
+	commands: Vec<usize>,
+}
+
+/// Records draw calls instead of drawing them, then draws them all at once in parallel.
+/// The screen is split into bands of rows and every command is only given to the bands its bounding box touches,
+/// each band draws its commands in the order they were recorded so overlapping things still stack the same way.
+pub struct CommandBuffer {
+	width: usize,
+	height: usize,
+	band_height: usize,
+	bands: Vec<Band>,
+
+	states: Vec<DrawState>,
+	// The index of the DrawState each command was recorded with
+	commands: Vec<(usize, DrawCommand)>,
+	// Clearing replaces everything recorded before it, so it's only done once when flushing
+	clear: Option<Color>,
+}
+
+impl CommandBuffer {
+	pub fn new(width: usize, height: usize) -> CommandBuffer {
+		let mut buffer = CommandBuffer {
+			width: 0,
+			height: 0,
+			band_height: MAX_BAND_HEIGHT,
+			bands: Vec::new(),
+			states: Vec::new(),
+			commands: Vec::new(),
+			clear: None,
+		};
+		buffer.resize(width, height);
+		buffer
+	}
+
+	/// Rebuilds the bands for a new screen size. Anything recorded is thrown away, so flush first.
+	pub fn resize(&mut self, width: usize, height: usize) {
+		self.discard();
+		self.width = width;
+		self.height = height;
+		self.band_height = band_height_for(height);
+
+		self.bands.clear();
+		for y in (0..height).step_by(self.band_height) {
+			let mut rasterizer = Rasterizer::new(width, usize::min(self.band_height, height - y));
+			rasterizer.offset_y = y;
+			self.bands.push(Band { rasterizer, commands: Vec::new() });
+		}
+	}
+
+	/// Returns true if nothing is waiting to be drawn.
+	pub fn is_empty(&self) -> bool {
+		self.commands.is_empty() && self.clear.is_none()
+	}
+
+	/// Clears the screen before anything else is drawn, and forgets everything recorded so far since it would be covered up.
+	pub fn clear(&mut self, color: Color) {
+		self.discard();
+		self.clear = Some(color);
+	}
+
+	/// Records a command to draw the next time the buffer is flushed. Commands that can't be seen are dropped.
+	pub fn record(&mut self, state: DrawState, command: DrawCommand) {
+		let bounds = command.bounds(&state, self.width, self.height);
+		if bounds.is_none() { return; }
+		let (_, y0, _, y1) = bounds.unwrap();
+
+		if self.states.last() != Some(&state) { self.states.push(state); }
+		let idx = self.commands.len();
+		self.commands.push((self.states.len() - 1, command));
+
+		let band_height = self.band_height as i64;
+		for band in (y0 / band_height)..=((y1 - 1) / band_height) {
+			self.bands[band as usize].commands.push(idx);
+		}
+	}
+
+	/// Draws everything that was recorded into the target, which has to be the size the buffer was made for.
+	pub fn flush(&mut self, target: &mut Rasterizer) {
+		if target.width != self.width || target.height != self.height {
+			println!("ERROR - DRAWING: Command buffer is {}x{} but the screen is {}x{}, dropping this frame's commands!", self.width, self.height, target.width, target.height);
+			self.discard();
+			return;
+		}
+
+		if let Some(color) = self.clear.take() { target.clear_color(color); }
+		if self.commands.is_empty() { return; }
+
+		let (states, commands) = (&self.states, &self.commands);
+
+		// Every band with something to draw copies in its rows of the screen, draws into them on the rayon pool and copies them back
+		target.color.par_chunks_mut(self.band_height * self.width * 4).zip(self.bands.par_iter_mut())
+			.filter(|(_, band)| !band.commands.is_empty())
+			.for_each(|(rows, band)| {
+				let part = &mut band.rasterizer;
+				part.drawn_pixels_since_clear = 0;
+
+				part.color.copy_from_slice(rows);
+				let mut current_state = usize::MAX;
+				for idx in band.commands.iter() {
+					let (state, command) = &commands[*idx];
+					if *state != current_state {
+						states[*state].apply(part);
+						current_state = *state;
+					}
+					command.execute(part);
+				}
+				rows.copy_from_slice(&part.color);
+			});
+
+		target.drawn_pixels_since_clear += self.bands.iter()
+			.filter(|band| !band.commands.is_empty())
+			.map(|band| band.rasterizer.drawn_pixels_since_clear)
+			.sum::<u64>();
+		self.discard();
+	}
+
+	// Forgets every recorded command, but keeps the memory for the next frame
+	fn discard(&mut self) {
+		self.states.clear();
+		self.commands.clear();
+		self.clear = None;
+		for band in self.bands.iter_mut() {
+			band.commands.clear();
+		}
+	}
+}
@@ -0,0 +1,249 @@
+use crate::vector2::*;
+
+use mlua::prelude::*;
+
+/// Which spline goes through a list of points.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SplineKind {
+	/// Passes through every point.
+	CatmullRom,
+	/// Smoother, but only passes near the points in the middle.
+	BSpline,
+}
+
+impl SplineKind {
+	pub fn from_str(name: &str) -> SplineKind {
+		match name {
+			"bspline" 	=> SplineKind::BSpline,
+			_ 			=> SplineKind::CatmullRom,
+		}
+	}
+}
+
+/// A path made of cubic Bezier segments. Every kind of curve is turned into these,
+/// so drawing and sampling work the same for Beziers, splines, arcs and ellipses.
+#[derive(Debug, Clone)]
+pub struct Curve {
+	/// Start, first control, second control and end of each segment.
+	pub segments: Vec<[Vector2; 4]>,
+	pub closed: bool,
+	// Distance along the curve at evenly spaced t, for sampling by distance
+	distances: Vec<f64>,
+}
+
+// Steps per segment in the distance lookup table
+const DISTANCE_STEPS: usize = 32;
+
+// Subdivision stops at this depth even if the curve isn't flat yet
+const MAX_SUBDIVISION: usize = 16;
+
+impl Curve {
+	pub fn new(segments: Vec<[Vector2; 4]>, closed: bool) -> Curve {
+		let mut curve = Curve { segments, closed, distances: Vec::new() };
+
+		let mut distance = 0.0;
+		let mut last = curve.point(0.0);
+		curve.distances.push(0.0);
+		for i in 1..=curve.segments.len() * DISTANCE_STEPS {
+			let point = curve.point(i as f64 / (curve.segments.len() * DISTANCE_STEPS) as f64);
+			distance += Vector2::distance(last, point);
+			curve.distances.push(distance);
+			last = point;
+		}
+		curve
+	}
+
+	/// A quadratic Bezier from start to end, pulled towards one control point.
+	pub fn quadratic(start: Vector2, control: Vector2, end: Vector2) -> Curve {
+		// Every quadratic is also a cubic with its controls two thirds of the way to the shared control point
+		let c0 = start + (control - start) * (2.0 / 3.0);
+		let c1 = end + (control - end) * (2.0 / 3.0);
+		Curve::new(vec![[start, c0, c1, end]], false)
+	}
+
+	/// A cubic Bezier from start to end, pulled towards two control points.
+	pub fn cubic(start: Vector2, control0: Vector2, control1: Vector2, end: Vector2) -> Curve {
+		Curve::new(vec![[start, control0, control1, end]], false)
+	}
+
+	/// A spline through a list of points. Open splines always start and end on the first and last point.
+	pub fn spline(points: &[Vector2], kind: SplineKind, closed: bool) -> Curve {
+		let count = points.len();
+		if count < 2 { return Curve::new(Vec::new(), false); }
+		let closed = closed && count > 2;
+
+		// Open splines repeat their ends so the curve reaches them
+		let padding = match kind { SplineKind::CatmullRom => 1, SplineKind::BSpline => 2 };
+		let point = |i: i64| -> Vector2 {
+			if closed { points[i.rem_euclid(count as i64) as usize] } else { points[i64::clamp(i, 0, count as i64 - 1) as usize] }
+		};
+		let (first, last) = if closed { (0, count as i64) } else { (-padding + 1, count as i64 - 2 + padding) };
+
+		let mut segments: Vec<[Vector2; 4]> = Vec::new();
+		for i in first..last {
+			let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+			segments.push(match kind {
+				SplineKind::CatmullRom => [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2],
+				SplineKind::BSpline => [
+					(p0 + p1 * 4.0 + p2) / 6.0,
+					(p1 * 2.0 + p2) / 3.0,
+					(p1 + p2 * 2.0) / 3.0,
+					(p1 + p2 * 4.0 + p3) / 6.0,
+				],
+			});
+		}
+		Curve::new(segments, closed)
+	}
+
+	/// Part of a circle, going from the start angle to the end angle in radians.
+	pub fn arc(center: Vector2, radius: f64, start_angle: f64, end_angle: f64) -> Curve {
+		Curve::elliptical_arc(center, radius, radius, 0.0, start_angle, end_angle)
+	}
+
+	/// A whole ellipse, rotated in radians.
+	pub fn ellipse(center: Vector2, radius_x: f64, radius_y: f64, rotation: f64) -> Curve {
+		let mut curve = Curve::elliptical_arc(center, radius_x, radius_y, rotation, 0.0, std::f64::consts::TAU);
+		curve.closed = true;
+		curve
+	}
+
+	fn elliptical_arc(center: Vector2, radius_x: f64, radius_y: f64, rotation: f64, start_angle: f64, end_angle: f64) -> Curve {
+		// Cubics can't be perfect circles, but quarter circles or less are off by less than 0.03%
+		let sweep = end_angle - start_angle;
+		let count = usize::max(f64::ceil(sweep.abs() / std::f64::consts::FRAC_PI_2) as usize, 1);
+		let step = sweep / count as f64;
+		let handle = 4.0 / 3.0 * f64::tan(step / 4.0);
+
+		let place = |x: f64, y: f64| center + Vector2::new(x * radius_x, y * radius_y).rotated(rotation);
+		let segments = (0..count).map(|i| {
+			let (a0, a1) = (start_angle + step * i as f64, start_angle + step * (i + 1) as f64);
+			let (cos0, sin0, cos1, sin1) = (a0.cos(), a0.sin(), a1.cos(), a1.sin());
+			[
+				place(cos0, sin0),
+				place(cos0 - sin0 * handle, sin0 + cos0 * handle),
+				place(cos1 + sin1 * handle, sin1 - cos1 * handle),
+				place(cos1, sin1),
+			]
+		}).collect();
+		Curve::new(segments, false)
+	}
+
+	// Picks the segment for t across the whole curve and how far along it t is
+	fn locate(&self, t: f64) -> (usize, f64) {
+		let scaled = f64::clamp(t, 0.0, 1.0) * self.segments.len() as f64;
+		let idx = usize::min(scaled as usize, self.segments.len() - 1);
+		(idx, scaled - idx as f64)
+	}
+
+	/// Returns the point at t, where 0.0 is the start of the curve and 1.0 is the end.
+	/// Each segment gets the same share of t, use point_at_distance to move along at an even speed.
+	pub fn point(&self, t: f64) -> Vector2 {
+		if self.segments.is_empty() { return Vector2::new(0.0, 0.0); }
+		let (idx, t) = self.locate(t);
+		let [p0, p1, p2, p3] = self.segments[idx];
+
+		// De Casteljau
+		let (a, b, c) = (Vector2::lerp(p0, p1, t), Vector2::lerp(p1, p2, t), Vector2::lerp(p2, p3, t));
+		let (d, e) = (Vector2::lerp(a, b, t), Vector2::lerp(b, c, t));
+		Vector2::lerp(d, e, t)
+	}
+
+	/// Returns the direction the curve is going at t, normalized.
+	pub fn tangent(&self, t: f64) -> Vector2 {
+		if self.segments.is_empty() { return Vector2::new(0.0, 0.0); }
+		let (idx, t) = self.locate(t);
+		let [p0, p1, p2, p3] = self.segments[idx];
+
+		let (a, b, c) = (Vector2::lerp(p0, p1, t), Vector2::lerp(p1, p2, t), Vector2::lerp(p2, p3, t));
+		let mut tangent = Vector2::lerp(b, c, t) - Vector2::lerp(a, b, t);
+
+		// Control points on top of the end points have no direction there, so fall back to the chord
+		if tangent.magnitude() < 1e-9 { tangent = p3 - p0; }
+		if tangent.magnitude() < 1e-9 { return Vector2::new(0.0, 0.0); }
+		tangent.normalized()
+	}
+
+	/// Returns the length of the whole curve.
+	pub fn length(&self) -> f64 {
+		self.distances.last().copied().unwrap_or(0.0)
+	}
+
+	/// Returns the t that is a distance along the curve, distances past the end wrap around on closed curves.
+	pub fn t_at_distance(&self, distance: f64) -> f64 {
+		let length = self.length();
+		if length <= 0.0 { return 0.0; }
+		let distance = if self.closed { distance.rem_euclid(length) } else { f64::clamp(distance, 0.0, length) };
+
+		let idx = self.distances.partition_point(|d| *d < distance);
+		if idx == 0 { return 0.0; }
+		let (d0, d1) = (self.distances[idx - 1], self.distances[idx]);
+		let fraction = if d1 > d0 { (distance - d0) / (d1 - d0) } else { 0.0 };
+		((idx - 1) as f64 + fraction) / (self.distances.len() - 1) as f64
+	}
+
+	/// Returns the point a distance along the curve.
+	pub fn point_at_distance(&self, distance: f64) -> Vector2 {
+		self.point(self.t_at_distance(distance))
+	}
+
+	/// Returns points along the curve that are never further than the tolerance from it, using adaptive subdivision.
+	/// Flat parts get few points and tight bends get many.
+	pub fn flatten(&self, tolerance: f64) -> Vec<Vector2> {
+		let mut points: Vec<Vector2> = Vec::new();
+		if self.segments.is_empty() { return points; }
+
+		points.push(self.segments[0][0]);
+		for segment in self.segments.iter() {
+			flatten_cubic(*segment, f64::max(tolerance, 0.01), 0, &mut points);
+		}
+		if self.closed && points.len() > 1 { points.pop(); }
+		points
+	}
+}
+
+// Splits the segment in half until its control points are close enough to the line between its ends
+fn flatten_cubic(segment: [Vector2; 4], tolerance: f64, depth: usize, points: &mut Vec<Vector2>) {
+	let [p0, p1, p2, p3] = segment;
+	let flatness = f64::max(distance_to_line(p1, p0, p3), distance_to_line(p2, p0, p3));
+	if flatness <= tolerance || depth >= MAX_SUBDIVISION {
+		points.push(p3);
+		return;
+	}
+
+	let (a, b, c) = (Vector2::lerp(p0, p1, 0.5), Vector2::lerp(p1, p2, 0.5), Vector2::lerp(p2, p3, 0.5));
+	let (d, e) = (Vector2::lerp(a, b, 0.5), Vector2::lerp(b, c, 0.5));
+	let middle = Vector2::lerp(d, e, 0.5);
+	flatten_cubic([p0, a, d, middle], tolerance, depth + 1, points);
+	flatten_cubic([middle, e, c, p3], tolerance, depth + 1, points);
+}
+
+fn distance_to_line(point: Vector2, start: Vector2, end: Vector2) -> f64 {
+	let line = end - start;
+	let length = line.magnitude();
+	if length < 1e-9 { return Vector2::distance(point, start); }
+	Vector2::cross(line, point - start).abs() / length
+}
+
+impl LuaUserData for Curve {
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		// Returns x, y, tangent_x, tangent_y
+		methods.add_method("sample", |_, curve, t: f64| {
+			let (point, tangent) = (curve.point(t), curve.tangent(t));
+			Ok((point.x, point.y, tangent.x, tangent.y))
+		});
+
+		methods.add_method("sample_distance", |_, curve, distance: f64| {
+			let t = curve.t_at_distance(distance);
+			let (point, tangent) = (curve.point(t), curve.tangent(t));
+			Ok((point.x, point.y, tangent.x, tangent.y))
+		});
+
+		methods.add_method("length", |_, curve, ()| {
+			Ok(curve.length())
+		});
+
+		methods.add_method("is_closed", |_, curve, ()| {
+			Ok(curve.closed)
+		});
+	}
+}
@@ -0,0 +1,141 @@
+// Robert Penner's easing functions. Every one takes and returns 0.0 at the start and 1.0 at the end,
+// back and elastic go past both ends on the way.
+
+use std::f64::consts::PI;
+
+/// The shape of the curve a tween follows from start to end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+	Linear,
+	InQuad, OutQuad, InOutQuad,
+	InCubic, OutCubic, InOutCubic,
+	InQuart, OutQuart, InOutQuart,
+	InQuint, OutQuint, InOutQuint,
+	InSine, OutSine, InOutSine,
+	InExpo, OutExpo, InOutExpo,
+	InCirc, OutCirc, InOutCirc,
+	InBack, OutBack, InOutBack,
+	InElastic, OutElastic, InOutElastic,
+	InBounce, OutBounce, InOutBounce,
+}
+
+impl Easing {
+	/// Names like "in_quad", "out_cubic" and "in_out_bounce".
+	pub fn from_str(name: &str) -> Option<Easing> {
+		let easing = match name {
+			"linear" => Easing::Linear,
+			"in_quad" => Easing::InQuad, "out_quad" => Easing::OutQuad, "in_out_quad" => Easing::InOutQuad,
+			"in_cubic" => Easing::InCubic, "out_cubic" => Easing::OutCubic, "in_out_cubic" => Easing::InOutCubic,
+			"in_quart" => Easing::InQuart, "out_quart" => Easing::OutQuart, "in_out_quart" => Easing::InOutQuart,
+			"in_quint" => Easing::InQuint, "out_quint" => Easing::OutQuint, "in_out_quint" => Easing::InOutQuint,
+			"in_sine" => Easing::InSine, "out_sine" => Easing::OutSine, "in_out_sine" => Easing::InOutSine,
+			"in_expo" => Easing::InExpo, "out_expo" => Easing::OutExpo, "in_out_expo" => Easing::InOutExpo,
+			"in_circ" => Easing::InCirc, "out_circ" => Easing::OutCirc, "in_out_circ" => Easing::InOutCirc,
+			"in_back" => Easing::InBack, "out_back" => Easing::OutBack, "in_out_back" => Easing::InOutBack,
+			"in_elastic" => Easing::InElastic, "out_elastic" => Easing::OutElastic, "in_out_elastic" => Easing::InOutElastic,
+			"in_bounce" => Easing::InBounce, "out_bounce" => Easing::OutBounce, "in_out_bounce" => Easing::InOutBounce,
+			_ => return None,
+		};
+		Some(easing)
+	}
+
+	/// Eases t, which is clamped to 0.0 to 1.0 first.
+	pub fn apply(&self, t: f64) -> f64 {
+		let t = f64::clamp(t, 0.0, 1.0);
+		match self {
+			Easing::Linear => t,
+
+			Easing::InQuad => t * t,
+			Easing::OutQuad => out(t, |t| t * t),
+			Easing::InOutQuad => in_out(t, |t| t * t),
+
+			Easing::InCubic => t * t * t,
+			Easing::OutCubic => out(t, |t| t * t * t),
+			Easing::InOutCubic => in_out(t, |t| t * t * t),
+
+			Easing::InQuart => t * t * t * t,
+			Easing::OutQuart => out(t, |t| t * t * t * t),
+			Easing::InOutQuart => in_out(t, |t| t * t * t * t),
+
+			Easing::InQuint => t * t * t * t * t,
+			Easing::OutQuint => out(t, |t| t * t * t * t * t),
+			Easing::InOutQuint => in_out(t, |t| t * t * t * t * t),
+
+			Easing::InSine => in_sine(t),
+			Easing::OutSine => out(t, in_sine),
+			Easing::InOutSine => in_out(t, in_sine),
+
+			Easing::InExpo => in_expo(t),
+			Easing::OutExpo => out(t, in_expo),
+			Easing::InOutExpo => in_out(t, in_expo),
+
+			Easing::InCirc => in_circ(t),
+			Easing::OutCirc => out(t, in_circ),
+			Easing::InOutCirc => in_out(t, in_circ),
+
+			Easing::InBack => in_back(t),
+			Easing::OutBack => out(t, in_back),
+			Easing::InOutBack => in_out(t, in_back),
+
+			Easing::InElastic => in_elastic(t),
+			Easing::OutElastic => out(t, in_elastic),
+			Easing::InOutElastic => in_out(t, in_elastic),
+
+			Easing::InBounce => out(t, out_bounce),
+			Easing::OutBounce => out_bounce(t),
+			Easing::InOutBounce => in_out(t, |t| out(t, out_bounce)),
+		}
+	}
+}
+
+// Every out easing is its in easing played backwards and upside down
+#[inline]
+fn out(t: f64, ease_in: impl Fn(f64) -> f64) -> f64 {
+	1.0 - ease_in(1.0 - t)
+}
+
+// The in easing squeezed into the first half and the out easing into the second
+#[inline]
+fn in_out(t: f64, ease_in: impl Fn(f64) -> f64) -> f64 {
+	if t < 0.5 { ease_in(t * 2.0) * 0.5 } else { 1.0 - ease_in((1.0 - t) * 2.0) * 0.5 }
+}
+
+fn in_sine(t: f64) -> f64 {
+	1.0 - f64::cos(t * PI * 0.5)
+}
+
+fn in_expo(t: f64) -> f64 {
+	if t <= 0.0 { 0.0 } else { f64::powf(2.0, 10.0 * (t - 1.0)) }
+}
+
+fn in_circ(t: f64) -> f64 {
+	1.0 - f64::sqrt(1.0 - t * t)
+}
+
+fn in_back(t: f64) -> f64 {
+	// Penner's overshoot, pulls back about 10% before going
+	let s = 1.70158;
+	t * t * ((s + 1.0) * t - s)
+}
+
+fn in_elastic(t: f64) -> f64 {
+	if t <= 0.0 || t >= 1.0 { return t; }
+	let period = 0.3;
+	-f64::powf(2.0, 10.0 * (t - 1.0)) * f64::sin((t - 1.0 - period / 4.0) * (2.0 * PI) / period)
+}
+
+fn out_bounce(t: f64) -> f64 {
+	let (n, d) = (7.5625, 2.75);
+	if t < 1.0 / d {
+		n * t * t
+	} else if t < 2.0 / d {
+		let t = t - 1.5 / d;
+		n * t * t + 0.75
+	} else if t < 2.5 / d {
+		let t = t - 2.25 / d;
+		n * t * t + 0.9375
+	} else {
+		let t = t - 2.625 / d;
+		n * t * t + 0.984375
+	}
+}
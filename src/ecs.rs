@@ -0,0 +1,426 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::partitioned_rasterizer::PartitionedRasterizer;
+use crate::rasterizer::Rasterizer;
+use crate::vector2::Vector2;
+
+/// An entity is an index into the world and a generation, so an old entity whose index was reused isn't mistaken for the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity {
+	pub index: u32,
+	pub generation: u32,
+}
+
+impl Entity {
+	/// Both halves packed into one number, for handing to Lua.
+	pub fn to_bits(self) -> u64 {
+		(self.generation as u64) << 32 | self.index as u64
+	}
+
+	pub fn from_bits(bits: u64) -> Entity {
+		Entity { index: bits as u32, generation: (bits >> 32) as u32 }
+	}
+}
+
+// Marks an index in the sparse array with no component
+const NONE: u32 = u32::MAX;
+
+// Generations wrap at 20 bits, so packed entities stay whole numbers in LuaJIT's doubles
+const GENERATION_MASK: u32 = 0xfffff;
+
+/// Components of one kind kept packed together, so going through all of them doesn't skip around memory.
+/// Removing swaps the last component into the gap, so the order changes.
+pub struct ComponentStore<T> {
+	dense: Vec<T>,
+	entities: Vec<Entity>,
+	// For every entity index, where its component is in dense
+	sparse: Vec<u32>,
+}
+
+impl<T> ComponentStore<T> {
+	pub fn new() -> ComponentStore<T> {
+		ComponentStore { dense: Vec::new(), entities: Vec::new(), sparse: Vec::new() }
+	}
+
+	fn position(&self, entity: Entity) -> Option<usize> {
+		let idx = *self.sparse.get(entity.index as usize)?;
+		if idx == NONE || self.entities[idx as usize] != entity { return None; }
+		Some(idx as usize)
+	}
+
+	/// Adds a component, replacing the one the entity already had.
+	pub fn insert(&mut self, entity: Entity, component: T) {
+		if let Some(idx) = self.position(entity) {
+			self.dense[idx] = component;
+			return;
+		}
+		if self.sparse.len() <= entity.index as usize {
+			self.sparse.resize(entity.index as usize + 1, NONE);
+		}
+		self.sparse[entity.index as usize] = self.dense.len() as u32;
+		self.dense.push(component);
+		self.entities.push(entity);
+	}
+
+	pub fn remove(&mut self, entity: Entity) -> Option<T> {
+		let idx = self.position(entity)?;
+		self.sparse[entity.index as usize] = NONE;
+		let moved = *self.entities.last().unwrap();
+		self.entities.swap_remove(idx);
+		let component = self.dense.swap_remove(idx);
+		if moved != entity {
+			self.sparse[moved.index as usize] = idx as u32;
+		}
+		Some(component)
+	}
+
+	pub fn contains(&self, entity: Entity) -> bool {
+		self.position(entity).is_some()
+	}
+
+	pub fn get(&self, entity: Entity) -> Option<&T> {
+		self.position(entity).map(|idx| &self.dense[idx])
+	}
+
+	pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+		self.position(entity).map(|idx| &mut self.dense[idx])
+	}
+
+	pub fn len(&self) -> usize {
+		self.dense.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.dense.is_empty()
+	}
+
+	/// Every entity that has this component, in the same order as iter.
+	pub fn entities(&self) -> &[Entity] {
+		&self.entities
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+		self.entities.iter().copied().zip(self.dense.iter())
+	}
+
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+		self.entities.iter().copied().zip(self.dense.iter_mut())
+	}
+
+	pub fn clear(&mut self) {
+		self.dense.clear();
+		self.entities.clear();
+		self.sparse.clear();
+	}
+}
+
+impl<T> Default for ComponentStore<T> {
+	fn default() -> Self {
+		ComponentStore::new()
+	}
+}
+
+/// Where an entity is in the world. Colliders and sprites are placed by it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+	pub position: Vector2,
+	pub rotation: f64,
+	pub scale: Vector2,
+}
+
+impl Default for Transform {
+	fn default() -> Transform {
+		Transform { position: Vector2::ZERO, rotation: 0.0, scale: Vector2::new(1.0, 1.0) }
+	}
+}
+
+/// Moves the entity's transform every update, in pixels and radians per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Velocity {
+	pub linear: Vector2,
+	pub angular: f64,
+}
+
+impl Default for Velocity {
+	fn default() -> Velocity {
+		Velocity { linear: Vector2::ZERO, angular: 0.0 }
+	}
+}
+
+/// An image drawn at the entity's transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+	pub image: String,
+	/// A section of the image as (x, y, width, height), for sprite sheets. None draws the whole image.
+	pub frame: Option<(i64, i64, i64, i64)>,
+	/// The point in the image placed at the position, from 0.0 to 1.0 of its size.
+	pub origin: Vector2,
+	pub flip_x: bool,
+	pub flip_y: bool,
+	pub tint: Color,
+	pub opacity: u8,
+	/// Lower layers are drawn first, entities on the same layer in the order they were made.
+	pub layer: i64,
+	pub visible: bool,
+}
+
+impl Default for Sprite {
+	fn default() -> Sprite {
+		Sprite {
+			image: String::new(),
+			frame: None,
+			origin: Vector2::new(0.5, 0.5),
+			flip_x: false,
+			flip_y: false,
+			tint: Color::white(),
+			opacity: 255,
+			layer: 0,
+			visible: true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+	/// Centered on the position and never rotated.
+	Rectangle { width: f64, height: f64 },
+	Circle { radius: f64 },
+}
+
+/// A shape centered on the entity's transform, scaled by it. Overlapping colliders are found every update.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collider {
+	pub shape: ColliderShape,
+}
+
+impl Default for Collider {
+	fn default() -> Collider {
+		Collider { shape: ColliderShape::Rectangle { width: 16.0, height: 16.0 } }
+	}
+}
+
+// A collider placed in the world, with its bounding box for the sweep
+#[derive(Clone, Copy)]
+struct PlacedCollider {
+	entity: Entity,
+	center: Vector2,
+	shape: ColliderShape,
+	min: Vector2,
+	max: Vector2,
+}
+
+impl PlacedCollider {
+	fn new(entity: Entity, transform: &Transform, collider: &Collider) -> PlacedCollider {
+		let (sx, sy) = (f64::abs(transform.scale.x), f64::abs(transform.scale.y));
+		let shape = match collider.shape {
+			ColliderShape::Rectangle { width, height } => ColliderShape::Rectangle { width: width * sx, height: height * sy },
+			ColliderShape::Circle { radius } => ColliderShape::Circle { radius: radius * f64::max(sx, sy) },
+		};
+		let half = match shape {
+			ColliderShape::Rectangle { width, height } => Vector2::new(width * 0.5, height * 0.5),
+			ColliderShape::Circle { radius } => Vector2::new(radius, radius),
+		};
+		PlacedCollider { entity, center: transform.position, shape, min: transform.position - half, max: transform.position + half }
+	}
+
+	fn overlaps(&self, other: &PlacedCollider) -> bool {
+		if self.min.y >= other.max.y || other.min.y >= self.max.y { return false; }
+		match (self.shape, other.shape) {
+			(ColliderShape::Rectangle { .. }, ColliderShape::Rectangle { .. }) => true,
+			(ColliderShape::Circle { radius: a }, ColliderShape::Circle { radius: b }) => (self.center - other.center).magnitude_sqr() < (a + b) * (a + b),
+			(ColliderShape::Circle { radius }, ColliderShape::Rectangle { .. }) => circle_overlaps_box(self.center, radius, other.min, other.max),
+			(ColliderShape::Rectangle { .. }, ColliderShape::Circle { radius }) => circle_overlaps_box(other.center, radius, self.min, self.max),
+		}
+	}
+}
+
+fn circle_overlaps_box(center: Vector2, radius: f64, min: Vector2, max: Vector2) -> bool {
+	let closest = Vector2::new(f64::clamp(center.x, min.x, max.x), f64::clamp(center.y, min.y, max.y));
+	(center - closest).magnitude_sqr() < radius * radius
+}
+
+/// Every entity and the built-in components the engine updates and draws itself.
+pub struct World {
+	generations: Vec<u32>,
+	alive: Vec<bool>,
+	free: Vec<u32>,
+	// When each index was last spawned, so sprites on the same layer draw in the order they were made
+	spawned: Vec<u64>,
+	spawn_count: u64,
+
+	pub transforms: ComponentStore<Transform>,
+	pub velocities: ComponentStore<Velocity>,
+	pub sprites: ComponentStore<Sprite>,
+	pub colliders: ComponentStore<Collider>,
+
+	/// Every pair of overlapping colliders found by the last update.
+	pub collisions: Vec<(Entity, Entity)>,
+}
+
+impl World {
+	pub fn new() -> World {
+		World {
+			generations: Vec::new(),
+			alive: Vec::new(),
+			free: Vec::new(),
+			spawned: Vec::new(),
+			spawn_count: 0,
+			transforms: ComponentStore::new(),
+			velocities: ComponentStore::new(),
+			sprites: ComponentStore::new(),
+			colliders: ComponentStore::new(),
+			collisions: Vec::new(),
+		}
+	}
+
+	pub fn spawn(&mut self) -> Entity {
+		self.spawn_count += 1;
+		match self.free.pop() {
+			Some(index) => {
+				self.alive[index as usize] = true;
+				self.spawned[index as usize] = self.spawn_count;
+				Entity { index, generation: self.generations[index as usize] }
+			},
+			None => {
+				self.generations.push(0);
+				self.alive.push(true);
+				self.spawned.push(self.spawn_count);
+				Entity { index: self.generations.len() as u32 - 1, generation: 0 }
+			},
+		}
+	}
+
+	/// Removes the entity and its built-in components. Returns false if it was already gone.
+	pub fn destroy(&mut self, entity: Entity) -> bool {
+		if !self.is_alive(entity) { return false; }
+		self.transforms.remove(entity);
+		self.velocities.remove(entity);
+		self.sprites.remove(entity);
+		self.colliders.remove(entity);
+		self.collisions.retain(|(a, b)| *a != entity && *b != entity);
+
+		let index = entity.index as usize;
+		self.alive[index] = false;
+		self.generations[index] = (self.generations[index] + 1) & GENERATION_MASK;
+		self.free.push(entity.index);
+		true
+	}
+
+	pub fn is_alive(&self, entity: Entity) -> bool {
+		let index = entity.index as usize;
+		index < self.alive.len() && self.alive[index] && self.generations[index] == entity.generation
+	}
+
+	pub fn entity_count(&self) -> usize {
+		self.alive.len() - self.free.len()
+	}
+
+	/// Every entity that's alive, by index.
+	pub fn alive_entities(&self) -> Vec<Entity> {
+		(0..self.alive.len()).filter(|&index| self.alive[index])
+			.map(|index| Entity { index: index as u32, generation: self.generations[index] })
+			.collect()
+	}
+
+	/// Destroys every entity. Indices are kept with their generations moved on, so old entities stay dead.
+	pub fn clear(&mut self) {
+		for index in 0..self.alive.len() {
+			if self.alive[index] {
+				self.alive[index] = false;
+				self.generations[index] = (self.generations[index] + 1) & GENERATION_MASK;
+				self.free.push(index as u32);
+			}
+		}
+		self.transforms.clear();
+		self.velocities.clear();
+		self.sprites.clear();
+		self.colliders.clear();
+		self.collisions.clear();
+	}
+
+	/// Moves every transform by its velocity, then finds the colliders that overlap.
+	pub fn update(&mut self, dt: f64) {
+		for (entity, velocity) in self.velocities.iter() {
+			if let Some(transform) = self.transforms.get_mut(entity) {
+				transform.position += velocity.linear * dt;
+				transform.rotation += velocity.angular * dt;
+			}
+		}
+		self.find_collisions();
+	}
+
+	/// Sweeps the colliders left to right so only the ones that share some x are checked against each other.
+	pub fn find_collisions(&mut self) {
+		self.collisions.clear();
+		let mut placed: Vec<PlacedCollider> = self.colliders.iter()
+			.filter_map(|(entity, collider)| self.transforms.get(entity).map(|transform| PlacedCollider::new(entity, transform, collider)))
+			.collect();
+		placed.sort_by(|a, b| a.min.x.total_cmp(&b.min.x));
+
+		for (i, a) in placed.iter().enumerate() {
+			for b in placed[i + 1..].iter() {
+				if b.min.x >= a.max.x { break; }
+				if a.overlaps(b) {
+					self.collisions.push((a.entity, b.entity));
+				}
+			}
+		}
+	}
+
+	/// Every entity overlapping this one as of the last update.
+	pub fn collisions_with(&self, entity: Entity) -> Vec<Entity> {
+		self.collisions.iter().filter_map(|&(a, b)| {
+			if a == entity { Some(b) } else if b == entity { Some(a) } else { None }
+		}).collect()
+	}
+
+	/// Draws every visible sprite that has a transform, through the camera with the current draw mode. Images are found with image.
+	pub fn draw(&self, rst: &mut PartitionedRasterizer, image: &dyn Fn(&str) -> Option<Arc<Rasterizer>>) {
+		let mut order: Vec<(i64, u64, Entity)> = self.sprites.iter()
+			.filter(|(entity, sprite)| sprite.visible && self.transforms.contains(*entity))
+			.map(|(entity, sprite)| (sprite.layer, self.spawned[entity.index as usize], entity))
+			.collect();
+		order.sort_unstable_by_key(|(layer, spawned, _)| (*layer, *spawned));
+
+		let (tint, opacity) = (rst.rasterizer.tint, rst.rasterizer.opacity);
+		for (_, _, entity) in order {
+			let (sprite, transform) = (self.sprites.get(entity).unwrap(), self.transforms.get(entity).unwrap());
+			let img = match image(&sprite.image) {
+				Some(img) => img,
+				None => continue,
+			};
+			let (ix, iy, iw, ih) = sprite.frame.unwrap_or((0, 0, img.width as i64, img.height as i64));
+			rst.set_tint(sprite.tint);
+			rst.set_opacity(sprite.opacity);
+			rst.pimgrectmtx(&img, transform.position.x, transform.position.y, transform.rotation, transform.scale.x, transform.scale.y, sprite.origin.x, sprite.origin.y, ix, iy, iw, ih, sprite.flip_x, sprite.flip_y);
+		}
+		rst.set_tint(tint);
+		rst.set_opacity(opacity);
+	}
+
+	/// Outlines every collider, for debugging.
+	pub fn draw_colliders(&self, rst: &mut PartitionedRasterizer, color: Color) {
+		for (entity, collider) in self.colliders.iter() {
+			let transform = match self.transforms.get(entity) {
+				Some(transform) => transform,
+				None => continue,
+			};
+			let placed = PlacedCollider::new(entity, transform, collider);
+			match placed.shape {
+				ColliderShape::Rectangle { .. } => {
+					let (x0, y0) = (f64::round(placed.min.x) as i64, f64::round(placed.min.y) as i64);
+					let (x1, y1) = (f64::round(placed.max.x) as i64, f64::round(placed.max.y) as i64);
+					rst.prectangle(false, x0, y0, x1 - x0, y1 - y0, color);
+				},
+				ColliderShape::Circle { radius } => rst.pcircle(false, f64::round(placed.center.x) as i64, f64::round(placed.center.y) as i64, f64::round(radius) as i64, color),
+			}
+		}
+	}
+}
+
+impl Default for World {
+	fn default() -> Self {
+		World::new()
+	}
+}
@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use mlua::prelude::*;
+
+use crate::color::Color;
+use crate::ecs::*;
+use crate::vector2::Vector2;
+
+/// A component defined from Lua. Every entity's component is its own table, made from a copy of the defaults.
+pub struct LuaComponent {
+	pub defaults: LuaRegistryKey,
+	pub store: ComponentStore<LuaRegistryKey>,
+}
+
+/// A Lua function called every update for every entity with all of its components.
+pub struct LuaSystem {
+	pub name: String,
+	pub components: Vec<String>,
+	pub function: LuaRegistryKey,
+	pub enabled: bool,
+}
+
+/// The native world plus the components and systems made in Lua.
+pub struct EntitySystem {
+	pub world: World,
+	pub components: HashMap<String, LuaComponent>,
+	/// Run in the order they were added.
+	pub systems: Vec<LuaSystem>,
+}
+
+impl EntitySystem {
+	pub fn new() -> EntitySystem {
+		EntitySystem { world: World::new(), components: HashMap::new(), systems: Vec::new() }
+	}
+
+	pub fn is_component(&self, name: &str) -> bool {
+		BuiltInComponent::from_str(name).is_some() || self.components.contains_key(name)
+	}
+
+	/// Returns None if there's no component by that name.
+	pub fn has(&self, entity: Entity, name: &str) -> Option<bool> {
+		Some(match name {
+			"transform" => self.world.transforms.contains(entity),
+			"velocity" => self.world.velocities.contains(entity),
+			"sprite" => self.world.sprites.contains(entity),
+			"collider" => self.world.colliders.contains(entity),
+			_ => self.components.get(name)?.store.contains(entity),
+		})
+	}
+
+	/// Takes a component off an entity. Returns None if there's no component by that name.
+	pub fn remove(&mut self, entity: Entity, name: &str) -> Option<()> {
+		match name {
+			"transform" => { self.world.transforms.remove(entity); },
+			"velocity" => { self.world.velocities.remove(entity); },
+			"sprite" => { self.world.sprites.remove(entity); },
+			"collider" => { self.world.colliders.remove(entity); },
+			_ => { self.components.get_mut(name)?.store.remove(entity); },
+		}
+		Some(())
+	}
+
+	pub fn destroy(&mut self, entity: Entity) -> bool {
+		if !self.world.destroy(entity) { return false; }
+		for component in self.components.values_mut() {
+			component.store.remove(entity);
+		}
+		true
+	}
+
+	pub fn clear(&mut self) {
+		self.world.clear();
+		for component in self.components.values_mut() {
+			component.store.clear();
+		}
+	}
+
+	fn entities_with(&self, name: &str) -> Option<&[Entity]> {
+		Some(match name {
+			"transform" => self.world.transforms.entities(),
+			"velocity" => self.world.velocities.entities(),
+			"sprite" => self.world.sprites.entities(),
+			"collider" => self.world.colliders.entities(),
+			_ => self.components.get(name)?.store.entities(),
+		})
+	}
+
+	/// Every entity with all of the components, starting from whichever component the fewest entities have.
+	pub fn query(&self, names: &[String]) -> Result<Vec<Entity>, String> {
+		let mut candidates: Vec<&[Entity]> = Vec::with_capacity(names.len());
+		for name in names {
+			candidates.push(self.entities_with(name).ok_or(format!("no component called \"{}\"", name))?);
+		}
+
+		Ok(match candidates.into_iter().min_by_key(|entities| entities.len()) {
+			Some(entities) => entities.iter().copied().filter(|entity| self.matches(*entity, names)).collect(),
+			// Every entity, for a query without components
+			None => self.world.alive_entities(),
+		})
+	}
+
+	pub fn matches(&self, entity: Entity, names: &[String]) -> bool {
+		self.world.is_alive(entity) && names.iter().all(|name| self.has(entity, name) == Some(true))
+	}
+}
+
+impl Default for EntitySystem {
+	fn default() -> Self {
+		EntitySystem::new()
+	}
+}
+
+/// The components the engine updates and draws itself, everything else is defined from Lua.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltInComponent {
+	Transform,
+	Velocity,
+	Sprite,
+	Collider,
+}
+
+impl BuiltInComponent {
+	pub fn from_str(name: &str) -> Option<BuiltInComponent> {
+		match name {
+			"transform" => Some(BuiltInComponent::Transform),
+			"velocity" => Some(BuiltInComponent::Velocity),
+			"sprite" => Some(BuiltInComponent::Sprite),
+			"collider" => Some(BuiltInComponent::Collider),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			BuiltInComponent::Transform => "transform",
+			BuiltInComponent::Velocity => "velocity",
+			BuiltInComponent::Sprite => "sprite",
+			BuiltInComponent::Collider => "collider",
+		}
+	}
+
+	/// Gives the entity this component with its defaults, if it doesn't have it already.
+	pub fn add_default(&self, world: &mut World, entity: Entity) {
+		match self {
+			BuiltInComponent::Transform => if !world.transforms.contains(entity) { world.transforms.insert(entity, Transform::default()) },
+			BuiltInComponent::Velocity => if !world.velocities.contains(entity) { world.velocities.insert(entity, Velocity::default()) },
+			BuiltInComponent::Sprite => if !world.sprites.contains(entity) { world.sprites.insert(entity, Sprite::default()) },
+			BuiltInComponent::Collider => if !world.colliders.contains(entity) { world.colliders.insert(entity, Collider::default()) },
+		}
+	}
+
+	/// Reads a field of the entity's component, nil if the entity doesn't have it.
+	pub fn get<'lua>(&self, lua: &'lua Lua, world: &World, entity: Entity, field: &str) -> LuaResult<LuaValue<'lua>> {
+		let unknown = || Err(LuaError::RuntimeError(format!("{} has no field \"{}\"", self.name(), field)));
+		match self {
+			BuiltInComponent::Transform => {
+				let transform = match world.transforms.get(entity) { Some(transform) => transform, None => return Ok(LuaValue::Nil) };
+				match field {
+					"x" => transform.position.x.to_lua(lua),
+					"y" => transform.position.y.to_lua(lua),
+					"position" => transform.position.to_lua(lua),
+					"rotation" => transform.rotation.to_lua(lua),
+					"scale_x" => transform.scale.x.to_lua(lua),
+					"scale_y" => transform.scale.y.to_lua(lua),
+					"scale" => transform.scale.to_lua(lua),
+					_ => unknown(),
+				}
+			},
+			BuiltInComponent::Velocity => {
+				let velocity = match world.velocities.get(entity) { Some(velocity) => velocity, None => return Ok(LuaValue::Nil) };
+				match field {
+					"x" => velocity.linear.x.to_lua(lua),
+					"y" => velocity.linear.y.to_lua(lua),
+					"linear" => velocity.linear.to_lua(lua),
+					"angular" => velocity.angular.to_lua(lua),
+					_ => unknown(),
+				}
+			},
+			BuiltInComponent::Sprite => {
+				let sprite = match world.sprites.get(entity) { Some(sprite) => sprite, None => return Ok(LuaValue::Nil) };
+				match field {
+					"image" => sprite.image.as_str().to_lua(lua),
+					"frame" => match sprite.frame {
+						Some((x, y, w, h)) => vec![x, y, w, h].to_lua(lua),
+						None => Ok(LuaValue::Nil),
+					},
+					"origin_x" => sprite.origin.x.to_lua(lua),
+					"origin_y" => sprite.origin.y.to_lua(lua),
+					"flip_x" => sprite.flip_x.to_lua(lua),
+					"flip_y" => sprite.flip_y.to_lua(lua),
+					"tint" => sprite.tint.to_lua(lua),
+					"opacity" => sprite.opacity.to_lua(lua),
+					"layer" => sprite.layer.to_lua(lua),
+					"visible" => sprite.visible.to_lua(lua),
+					_ => unknown(),
+				}
+			},
+			BuiltInComponent::Collider => {
+				let collider = match world.colliders.get(entity) { Some(collider) => collider, None => return Ok(LuaValue::Nil) };
+				match (field, collider.shape) {
+					("shape", ColliderShape::Rectangle { .. }) => "rectangle".to_lua(lua),
+					("shape", ColliderShape::Circle { .. }) => "circle".to_lua(lua),
+					("width", ColliderShape::Rectangle { width, .. }) => width.to_lua(lua),
+					("height", ColliderShape::Rectangle { height, .. }) => height.to_lua(lua),
+					("radius", ColliderShape::Circle { radius }) => radius.to_lua(lua),
+					("width" | "height" | "radius", _) => Ok(LuaValue::Nil),
+					_ => unknown(),
+				}
+			},
+		}
+	}
+
+	/// Whether set takes this field. A collider's shape is only read, it changes with the size fields.
+	pub fn can_set(&self, field: &str) -> bool {
+		match self {
+			BuiltInComponent::Transform => matches!(field, "x" | "y" | "position" | "rotation" | "scale_x" | "scale_y" | "scale"),
+			BuiltInComponent::Velocity => matches!(field, "x" | "y" | "linear" | "angular"),
+			BuiltInComponent::Sprite => matches!(field, "image" | "frame" | "origin_x" | "origin_y" | "flip_x" | "flip_y" | "tint" | "opacity" | "layer" | "visible"),
+			BuiltInComponent::Collider => matches!(field, "width" | "height" | "radius"),
+		}
+	}
+
+	/// Sets a field of the entity's component. Nothing is set if the entity doesn't have it, add_default gives it first.
+	pub fn set(&self, lua: &Lua, world: &mut World, entity: Entity, field: &str, value: LuaValue) -> LuaResult<()> {
+		let unknown = || Err(LuaError::RuntimeError(format!("{} has no field \"{}\"", self.name(), field)));
+		if !self.can_set(field) { return unknown(); }
+		match self {
+			BuiltInComponent::Transform => {
+				let transform = match world.transforms.get_mut(entity) { Some(transform) => transform, None => return Ok(()) };
+				match field {
+					"x" => transform.position.x = lua.unpack(value)?,
+					"y" => transform.position.y = lua.unpack(value)?,
+					"position" => transform.position = lua.unpack(value)?,
+					"rotation" => transform.rotation = lua.unpack(value)?,
+					"scale_x" => transform.scale.x = lua.unpack(value)?,
+					"scale_y" => transform.scale.y = lua.unpack(value)?,
+					// A vec2, or one number for both
+					"scale" => transform.scale = match value {
+						LuaValue::UserData(_) => lua.unpack(value)?,
+						value => { let scale: f64 = lua.unpack(value)?; Vector2::new(scale, scale) },
+					},
+					_ => return unknown(),
+				}
+			},
+			BuiltInComponent::Velocity => {
+				let velocity = match world.velocities.get_mut(entity) { Some(velocity) => velocity, None => return Ok(()) };
+				match field {
+					"x" => velocity.linear.x = lua.unpack(value)?,
+					"y" => velocity.linear.y = lua.unpack(value)?,
+					"linear" => velocity.linear = lua.unpack(value)?,
+					"angular" => velocity.angular = lua.unpack(value)?,
+					_ => return unknown(),
+				}
+			},
+			BuiltInComponent::Sprite => {
+				let sprite = match world.sprites.get_mut(entity) { Some(sprite) => sprite, None => return Ok(()) };
+				match field {
+					"image" => sprite.image = lua.unpack(value)?,
+					// {x, y, width, height} of a sprite sheet, or nil for the whole image
+					"frame" => sprite.frame = match value {
+						LuaValue::Nil => None,
+						value => {
+							let frame: Vec<i64> = lua.unpack(value)?;
+							if frame.len() != 4 { return Err(LuaError::RuntimeError("sprite frame takes {x, y, width, height}".to_string())); }
+							Some((frame[0], frame[1], frame[2], frame[3]))
+						},
+					},
+					"origin_x" => sprite.origin.x = lua.unpack(value)?,
+					"origin_y" => sprite.origin.y = lua.unpack(value)?,
+					"flip_x" => sprite.flip_x = lua.unpack(value)?,
+					"flip_y" => sprite.flip_y = lua.unpack(value)?,
+					"tint" => sprite.tint = lua.unpack::<Color>(value)?,
+					"opacity" => sprite.opacity = f64::clamp(lua.unpack(value)?, 0.0, 255.0) as u8,
+					"layer" => sprite.layer = lua.unpack(value)?,
+					"visible" => sprite.visible = lua.unpack(value)?,
+					_ => return unknown(),
+				}
+			},
+			BuiltInComponent::Collider => {
+				// Setting a radius makes it a circle, setting a width or height makes it a rectangle
+				let collider = match world.colliders.get_mut(entity) { Some(collider) => collider, None => return Ok(()) };
+				let (width, height) = match collider.shape {
+					ColliderShape::Rectangle { width, height } => (width, height),
+					ColliderShape::Circle { radius } => (radius * 2.0, radius * 2.0),
+				};
+				match field {
+					"width" => collider.shape = ColliderShape::Rectangle { width: lua.unpack(value)?, height },
+					"height" => collider.shape = ColliderShape::Rectangle { width, height: lua.unpack(value)? },
+					"radius" => collider.shape = ColliderShape::Circle { radius: lua.unpack(value)? },
+					_ => return unknown(),
+				}
+			},
+		}
+		Ok(())
+	}
+}
+
+/// A live view of an entity's built-in component, reading and writing straight through to the world.
+/// Fields read as nil and writes are ignored once the entity is gone or loses the component, a ref never adds it back.
+#[derive(Clone)]
+pub struct ComponentRef {
+	pub entities: Rc<RefCell<EntitySystem>>,
+	pub entity: Entity,
+	pub component: BuiltInComponent,
+}
+
+impl LuaUserData for ComponentRef {
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		methods.add_meta_method(LuaMetaMethod::Index, |lua, this, field: String| {
+			this.component.get(lua, &this.entities.borrow().world, this.entity, &field)
+		});
+
+		methods.add_meta_method(LuaMetaMethod::NewIndex, |lua, this, (field, value): (String, LuaValue)| {
+			// set checks the field before anything else, and leaves a component the entity lost alone
+			this.component.set(lua, &mut this.entities.borrow_mut().world, this.entity, &field, value)
+		});
+	}
+}
@@ -0,0 +1,510 @@
+use crate::noise::*;
+
+// A tiny compiled math language for per-pixel shading from Lua, like map_pixels("tex", "noise(u * 8, v * 8) * 255").
+// Strings are compiled once into a list of stack operations, which run for every pixel without touching Lua.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+	Sin, Cos, Tan, Asin, Acos, Atan, Atan2,
+	Abs, Floor, Ceil, Round, Fract, Sign, Sqrt, Exp, Log, Pow, Mod,
+	Min, Max, Clamp, Mix, Step, Smoothstep, Length, Distance, If,
+	Noise, Perlin, Simplex, Worley, Fbm,
+}
+
+impl Function {
+	// Name and how many arguments it takes, with the last ones optional for the noise seeds
+	fn from_name(name: &str) -> Option<(Function, usize, usize)> {
+		let found = match name {
+			"sin" => (Function::Sin, 1, 1),
+			"cos" => (Function::Cos, 1, 1),
+			"tan" => (Function::Tan, 1, 1),
+			"asin" => (Function::Asin, 1, 1),
+			"acos" => (Function::Acos, 1, 1),
+			"atan" => (Function::Atan, 1, 1),
+			"atan2" => (Function::Atan2, 2, 2),
+			"abs" => (Function::Abs, 1, 1),
+			"floor" => (Function::Floor, 1, 1),
+			"ceil" => (Function::Ceil, 1, 1),
+			"round" => (Function::Round, 1, 1),
+			"fract" => (Function::Fract, 1, 1),
+			"sign" => (Function::Sign, 1, 1),
+			"sqrt" => (Function::Sqrt, 1, 1),
+			"exp" => (Function::Exp, 1, 1),
+			"log" => (Function::Log, 1, 1),
+			"pow" => (Function::Pow, 2, 2),
+			"mod" => (Function::Mod, 2, 2),
+			"min" => (Function::Min, 2, 2),
+			"max" => (Function::Max, 2, 2),
+			"clamp" => (Function::Clamp, 3, 3),
+			"mix" => (Function::Mix, 3, 3),
+			"step" => (Function::Step, 2, 2),
+			"smoothstep" => (Function::Smoothstep, 3, 3),
+			"length" => (Function::Length, 2, 2),
+			"distance" => (Function::Distance, 4, 4),
+			"if" => (Function::If, 3, 3),
+			"noise" => (Function::Noise, 2, 3),
+			"perlin" => (Function::Perlin, 2, 3),
+			"simplex" => (Function::Simplex, 2, 3),
+			"worley" => (Function::Worley, 2, 3),
+			"fbm" => (Function::Fbm, 3, 4),
+			_ => return None,
+		};
+		Some(found)
+	}
+
+	fn call(&self, args: &[f64]) -> f64 {
+		let seed = |i: usize| args.get(i).map(|s| *s as i64 as u32).unwrap_or(0);
+		match self {
+			Function::Sin => f64::sin(args[0]),
+			Function::Cos => f64::cos(args[0]),
+			Function::Tan => f64::tan(args[0]),
+			Function::Asin => f64::asin(args[0]),
+			Function::Acos => f64::acos(args[0]),
+			Function::Atan => f64::atan(args[0]),
+			Function::Atan2 => f64::atan2(args[0], args[1]),
+			Function::Abs => f64::abs(args[0]),
+			Function::Floor => f64::floor(args[0]),
+			Function::Ceil => f64::ceil(args[0]),
+			Function::Round => f64::round(args[0]),
+			Function::Fract => args[0] - f64::floor(args[0]),
+			Function::Sign => if args[0] > 0.0 { 1.0 } else if args[0] < 0.0 { -1.0 } else { 0.0 },
+			Function::Sqrt => f64::sqrt(args[0]),
+			Function::Exp => f64::exp(args[0]),
+			Function::Log => f64::ln(args[0]),
+			Function::Pow => f64::powf(args[0], args[1]),
+			Function::Mod => f64::rem_euclid(args[0], args[1]),
+			Function::Min => f64::min(args[0], args[1]),
+			Function::Max => f64::max(args[0], args[1]),
+			Function::Clamp => f64::max(args[1], f64::min(args[0], args[2])),
+			Function::Mix => args[0] + (args[1] - args[0]) * args[2],
+			Function::Step => if args[1] < args[0] { 0.0 } else { 1.0 },
+			Function::Smoothstep => {
+				let t = f64::clamp((args[2] - args[0]) / (args[1] - args[0]), 0.0, 1.0);
+				t * t * (3.0 - 2.0 * t)
+			},
+			Function::Length => f64::sqrt(args[0] * args[0] + args[1] * args[1]),
+			Function::Distance => f64::sqrt((args[2] - args[0]) * (args[2] - args[0]) + (args[3] - args[1]) * (args[3] - args[1])),
+			Function::If => if args[0] != 0.0 { args[1] } else { args[2] },
+			Function::Noise => value(args[0], args[1], seed(2)),
+			Function::Perlin => perlin(args[0], args[1], seed(2)),
+			Function::Simplex => simplex(args[0], args[1], seed(2)),
+			Function::Worley => worley(args[0], args[1], seed(2)),
+			Function::Fbm => fbm(NoiseKind::Perlin, args[0], args[1], f64::clamp(args[2], 1.0, 16.0) as u32, seed(3)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+	Number(f64),
+	Variable(usize),
+	Negate,
+	Not,
+	Add, Sub, Mul, Div, Rem, Pow,
+	Less, LessEqual, Greater, GreaterEqual, Equal, NotEqual, And, Or,
+	Call(Function, usize),
+}
+
+/// A compiled expression, or a few of them separated by commas.
+/// Variables are looked up by name when compiling, and their values are passed in by position when running.
+#[derive(Debug, Clone)]
+pub struct Expression {
+	ops: Vec<Op>,
+	/// How many comma separated values it returns.
+	pub outputs: usize,
+	max_stack: usize,
+}
+
+impl Expression {
+	/// Compiles source into an expression, variables are the names it's allowed to use.
+	/// Operators are + - * / % ^, comparisons, && || and !, with true being 1.0 and false 0.0.
+	pub fn compile(source: &str, variables: &[&str]) -> Result<Expression, String> {
+		let tokens = tokenize(source)?;
+		let mut parser = Parser { tokens, position: 0, variables, ops: Vec::new() };
+
+		let mut outputs = 1;
+		parser.expression()?;
+		while parser.accept(&Token::Comma) {
+			parser.expression()?;
+			outputs += 1;
+		}
+		if parser.position < parser.tokens.len() {
+			return Err(format!("unexpected {} in \"{}\"", parser.tokens[parser.position].describe(), source));
+		}
+
+		let ops = parser.ops;
+		let max_stack = stack_depth(&ops);
+		Ok(Expression { ops, outputs, max_stack })
+	}
+
+	/// Runs the expression, writing each comma separated value into out.
+	/// stack is reused between calls so nothing is allocated per pixel.
+	pub fn evaluate(&self, variables: &[f64], stack: &mut Vec<f64>, out: &mut [f64]) {
+		stack.clear();
+		stack.reserve(self.max_stack);
+		for op in self.ops.iter() {
+			match *op {
+				Op::Number(n) => stack.push(n),
+				Op::Variable(i) => stack.push(variables[i]),
+				Op::Negate => { let a = stack.pop().unwrap(); stack.push(-a); },
+				Op::Not => { let a = stack.pop().unwrap(); stack.push(if a == 0.0 { 1.0 } else { 0.0 }); },
+				Op::Call(function, count) => {
+					let start = stack.len() - count;
+					let result = function.call(&stack[start..]);
+					stack.truncate(start);
+					stack.push(result);
+				},
+				_ => {
+					let b = stack.pop().unwrap();
+					let a = stack.pop().unwrap();
+					let truth = |t: bool| if t { 1.0 } else { 0.0 };
+					stack.push(match *op {
+						Op::Add => a + b,
+						Op::Sub => a - b,
+						Op::Mul => a * b,
+						Op::Div => a / b,
+						Op::Rem => f64::rem_euclid(a, b),
+						Op::Pow => f64::powf(a, b),
+						Op::Less => truth(a < b),
+						Op::LessEqual => truth(a <= b),
+						Op::Greater => truth(a > b),
+						Op::GreaterEqual => truth(a >= b),
+						Op::Equal => truth(a == b),
+						Op::NotEqual => truth(a != b),
+						Op::And => truth(a != 0.0 && b != 0.0),
+						Op::Or => truth(a != 0.0 || b != 0.0),
+						_ => unreachable!(),
+					});
+				},
+			}
+		}
+
+		for (o, value) in out.iter_mut().zip(stack.iter()) { *o = *value; }
+	}
+}
+
+// The most values the stack holds at once
+fn stack_depth(ops: &[Op]) -> usize {
+	let (mut depth, mut max) = (0i64, 0i64);
+	for op in ops {
+		depth += match op {
+			Op::Number(_) | Op::Variable(_) => 1,
+			Op::Negate | Op::Not => 0,
+			Op::Call(_, count) => 1 - *count as i64,
+			_ => -1,
+		};
+		max = i64::max(max, depth);
+	}
+	max as usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	Number(f64),
+	Name(String),
+	Symbol(&'static str),
+	Open,
+	Close,
+	Comma,
+}
+
+impl Token {
+	fn describe(&self) -> String {
+		match self {
+			Token::Number(n) => format!("number {}", n),
+			Token::Name(name) => format!("\"{}\"", name),
+			Token::Symbol(symbol) => format!("\"{}\"", symbol),
+			Token::Open => "\"(\"".to_string(),
+			Token::Close => "\")\"".to_string(),
+			Token::Comma => "\",\"".to_string(),
+		}
+	}
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+	const SYMBOLS: [&str; 16] = ["<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "^", "<", ">", "!", "="];
+
+	let chars: Vec<char> = source.chars().collect();
+	let mut tokens = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c.is_whitespace() {
+			i += 1;
+		} else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+			let start = i;
+			while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1; }
+			let text: String = chars[start..i].iter().collect();
+			tokens.push(Token::Number(text.parse().map_err(|_| format!("\"{}\" is not a number", text))?));
+		} else if c.is_alphabetic() || c == '_' {
+			let start = i;
+			while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1; }
+			tokens.push(Token::Name(chars[start..i].iter().collect()));
+		} else if c == '(' {
+			tokens.push(Token::Open);
+			i += 1;
+		} else if c == ')' {
+			tokens.push(Token::Close);
+			i += 1;
+		} else if c == ',' {
+			tokens.push(Token::Comma);
+			i += 1;
+		} else {
+			let rest: String = chars[i..usize::min(i + 2, chars.len())].iter().collect();
+			match SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+				Some(&"=") => return Err("\"=\" isn't allowed, use \"==\" to compare".to_string()),
+				Some(symbol) => {
+					tokens.push(Token::Symbol(symbol));
+					i += symbol.len();
+				},
+				None => return Err(format!("unexpected \"{}\"", c)),
+			}
+		}
+	}
+	Ok(tokens)
+}
+
+// Recursive descent, each level handles one precedence and writes its operators after its operands
+struct Parser<'a> {
+	tokens: Vec<Token>,
+	position: usize,
+	variables: &'a [&'a str],
+	ops: Vec<Op>,
+}
+
+impl<'a> Parser<'a> {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.position)
+	}
+
+	fn accept(&mut self, token: &Token) -> bool {
+		if self.peek() == Some(token) {
+			self.position += 1;
+			return true;
+		}
+		false
+	}
+
+	fn accept_symbol(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+		if let Some(Token::Symbol(symbol)) = self.peek() {
+			if symbols.contains(symbol) {
+				let symbol = *symbol;
+				self.position += 1;
+				return Some(symbol);
+			}
+		}
+		None
+	}
+
+	fn expression(&mut self) -> Result<(), String> {
+		self.or()
+	}
+
+	fn or(&mut self) -> Result<(), String> {
+		self.and()?;
+		while self.accept_symbol(&["||"]).is_some() {
+			self.and()?;
+			self.ops.push(Op::Or);
+		}
+		Ok(())
+	}
+
+	fn and(&mut self) -> Result<(), String> {
+		self.comparison()?;
+		while self.accept_symbol(&["&&"]).is_some() {
+			self.comparison()?;
+			self.ops.push(Op::And);
+		}
+		Ok(())
+	}
+
+	fn comparison(&mut self) -> Result<(), String> {
+		self.sum()?;
+		while let Some(symbol) = self.accept_symbol(&["<", "<=", ">", ">=", "==", "!="]) {
+			self.sum()?;
+			self.ops.push(match symbol {
+				"<" => Op::Less,
+				"<=" => Op::LessEqual,
+				">" => Op::Greater,
+				">=" => Op::GreaterEqual,
+				"==" => Op::Equal,
+				_ => Op::NotEqual,
+			});
+		}
+		Ok(())
+	}
+
+	fn sum(&mut self) -> Result<(), String> {
+		self.product()?;
+		while let Some(symbol) = self.accept_symbol(&["+", "-"]) {
+			self.product()?;
+			self.ops.push(if symbol == "+" { Op::Add } else { Op::Sub });
+		}
+		Ok(())
+	}
+
+	fn product(&mut self) -> Result<(), String> {
+		self.unary()?;
+		while let Some(symbol) = self.accept_symbol(&["*", "/", "%"]) {
+			self.unary()?;
+			self.ops.push(match symbol {
+				"*" => Op::Mul,
+				"/" => Op::Div,
+				_ => Op::Rem,
+			});
+		}
+		Ok(())
+	}
+
+	fn unary(&mut self) -> Result<(), String> {
+		match self.accept_symbol(&["-", "!"]) {
+			Some("-") => { self.unary()?; self.ops.push(Op::Negate); },
+			Some(_) => { self.unary()?; self.ops.push(Op::Not); },
+			None => self.power()?,
+		}
+		Ok(())
+	}
+
+	// ^ goes right to left, and binds tighter than the minus in front of it like in Lua
+	fn power(&mut self) -> Result<(), String> {
+		self.primary()?;
+		if self.accept_symbol(&["^"]).is_some() {
+			self.unary()?;
+			self.ops.push(Op::Pow);
+		}
+		Ok(())
+	}
+
+	fn primary(&mut self) -> Result<(), String> {
+		let token = match self.peek() {
+			Some(token) => token.clone(),
+			None => return Err("the expression ends too early".to_string()),
+		};
+		self.position += 1;
+
+		match token {
+			Token::Number(n) => self.ops.push(Op::Number(n)),
+			Token::Open => {
+				self.expression()?;
+				if !self.accept(&Token::Close) { return Err("missing a \")\"".to_string()); }
+			},
+			Token::Name(name) => {
+				if self.accept(&Token::Open) {
+					let (function, min_args, max_args) = Function::from_name(&name).ok_or(format!("there's no function called \"{}\"", name))?;
+					let mut count = 0;
+					if !self.accept(&Token::Close) {
+						loop {
+							self.expression()?;
+							count += 1;
+							if self.accept(&Token::Close) { break; }
+							if !self.accept(&Token::Comma) { return Err(format!("missing a \")\" after the arguments to {}", name)); }
+						}
+					}
+					if count < min_args || count > max_args {
+						return Err(format!("{} takes {} arguments but was given {}", name, if min_args == max_args { min_args.to_string() } else { format!("{} to {}", min_args, max_args) }, count));
+					}
+					self.ops.push(Op::Call(function, count));
+				} else if let Some(i) = self.variables.iter().position(|v| *v == name) {
+					self.ops.push(Op::Variable(i));
+				} else {
+					match name.as_str() {
+						"pi" => self.ops.push(Op::Number(std::f64::consts::PI)),
+						"tau" => self.ops.push(Op::Number(std::f64::consts::TAU)),
+						_ => return Err(format!("there's no variable called \"{}\"", name)),
+					}
+				}
+			},
+			other => return Err(format!("unexpected {}", other.describe())),
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(source: &str, names: &[&str], values: &[f64]) -> Vec<f64> {
+		let expression = Expression::compile(source, names).unwrap();
+		let mut out = vec![0.0; expression.outputs];
+		expression.evaluate(values, &mut Vec::new(), &mut out);
+		out
+	}
+
+	#[test]
+	fn operator_precedence() {
+		assert_eq!(run("1 + 2 * 3", &[], &[]), vec![7.0]);
+		assert_eq!(run("(1 + 2) * 3", &[], &[]), vec![9.0]);
+		assert_eq!(run("10 - 4 - 3", &[], &[]), vec![3.0]);
+		assert_eq!(run("7 % 4 * 2", &[], &[]), vec![6.0]);
+		assert_eq!(run("-2 ^ 2", &[], &[]), vec![-4.0]);
+		assert_eq!(run("2 ^ 3 ^ 2", &[], &[]), vec![512.0]);
+		assert_eq!(run("2 ^ -1", &[], &[]), vec![0.5]);
+		assert_eq!(run("1 + 1 == 2 && 0 || 1", &[], &[]), vec![1.0]);
+		assert_eq!(run("!0 + 1", &[], &[]), vec![2.0]);
+		assert_eq!(run("1 < 2 == 1", &[], &[]), vec![1.0]);
+	}
+
+	#[test]
+	fn variables_functions_and_constants() {
+		assert_eq!(run("u * 2 + v", &["u", "v"], &[3.0, 1.0]), vec![7.0]);
+		assert_eq!(run("clamp(x, 0, 1)", &["x"], &[4.0]), vec![1.0]);
+		assert_eq!(run("if(x > 0, 10, 20)", &["x"], &[-1.0]), vec![20.0]);
+		assert_eq!(run("mod(-1, 4)", &[], &[]), vec![3.0]);
+		assert_eq!(run("tau / pi", &[], &[]), vec![2.0]);
+		// A variable with the name of a constant wins
+		assert_eq!(run("pi", &["pi"], &[5.0]), vec![5.0]);
+	}
+
+	#[test]
+	fn multiple_outputs() {
+		let expression = Expression::compile("u, v * 2, min(u, v) + 1", &["u", "v"]).unwrap();
+		assert_eq!(expression.outputs, 3);
+		let mut out = [0.0; 3];
+		let mut stack = Vec::new();
+		expression.evaluate(&[4.0, 1.5], &mut stack, &mut out);
+		assert_eq!(out, [4.0, 3.0, 2.5]);
+		// The stack is reused without leaking values from the last run
+		expression.evaluate(&[0.0, 2.0], &mut stack, &mut out);
+		assert_eq!(out, [0.0, 4.0, 1.0]);
+	}
+
+	#[test]
+	fn stack_depth_is_the_most_values_held() {
+		assert_eq!(Expression::compile("1", &[]).unwrap().max_stack, 1);
+		assert_eq!(Expression::compile("1 + 2 * 3", &[]).unwrap().max_stack, 3);
+		assert_eq!(Expression::compile("1 * 2 + 3", &[]).unwrap().max_stack, 2);
+		assert_eq!(Expression::compile("-min(1, 2) + 3", &[]).unwrap().max_stack, 2);
+		assert_eq!(Expression::compile("clamp(1, 2, 3 + 4)", &[]).unwrap().max_stack, 4);
+		assert_eq!(Expression::compile("1, 2, 3", &[]).unwrap().max_stack, 3);
+	}
+
+	#[test]
+	fn unknown_names_are_errors() {
+		assert_eq!(Expression::compile("u + w", &["u"]).unwrap_err(), "there's no variable called \"w\"");
+		assert_eq!(Expression::compile("wobble(u)", &["u"]).unwrap_err(), "there's no function called \"wobble\"");
+	}
+
+	#[test]
+	fn wrong_argument_counts_are_errors() {
+		assert_eq!(Expression::compile("sin(1, 2)", &[]).unwrap_err(), "sin takes 1 arguments but was given 2");
+		assert_eq!(Expression::compile("max()", &[]).unwrap_err(), "max takes 2 arguments but was given 0");
+		assert_eq!(Expression::compile("noise(1)", &[]).unwrap_err(), "noise takes 2 to 3 arguments but was given 1");
+		assert!(Expression::compile("noise(1, 2)", &[]).is_ok());
+		assert!(Expression::compile("noise(1, 2, 3)", &[]).is_ok());
+		assert_eq!(Expression::compile("min(1, 2", &[]).unwrap_err(), "missing a \")\" after the arguments to min");
+	}
+
+	#[test]
+	fn trailing_and_bad_tokens_are_errors() {
+		assert_eq!(Expression::compile("1 2", &[]).unwrap_err(), "unexpected number 2 in \"1 2\"");
+		assert_eq!(Expression::compile("(1))", &[]).unwrap_err(), "unexpected \")\" in \"(1))\"");
+		assert_eq!(Expression::compile("u v", &["u", "v"]).unwrap_err(), "unexpected \"v\" in \"u v\"");
+		assert_eq!(Expression::compile("1 +", &[]).unwrap_err(), "the expression ends too early");
+		assert_eq!(Expression::compile("(1 + 2", &[]).unwrap_err(), "missing a \")\"");
+		assert_eq!(Expression::compile("1,", &[]).unwrap_err(), "the expression ends too early");
+		assert_eq!(Expression::compile("* 2", &[]).unwrap_err(), "unexpected \"*\"");
+		assert_eq!(Expression::compile("u = 1", &["u"]).unwrap_err(), "\"=\" isn't allowed, use \"==\" to compare");
+		assert_eq!(Expression::compile("1 $ 2", &[]).unwrap_err(), "unexpected \"$\"");
+	}
+}
@@ -1,12 +1,12 @@
-//#[cfg(feature = "ttf")]
-
 use mlua::prelude::*;
 
+use crate::bmfont::BmFont;
 use crate::color::*;
 use crate::rasterizer::Rasterizer;
 
-use std::io::prelude::*;
-use std::fs::File;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Bitmap font for drawing simple text. To be used with the Rasterizers pprint function.
 /// All bitmap fonts need a glyph index that's in order of left-to-right, top-to-bottom of the glyphs used
@@ -14,124 +14,450 @@ use std::fs::File;
 ///
 /// For example, a simple five glyph image in the order of 'N', 'O', 'W', 'A', 'Y' must have a glyphidx
 /// of "NOWAY" for it to print your text correctly.
-/// 
+///
 /// The glyph width and height tells the font how big the sections are for the glyphs in the image.
+///
+/// Fonts made from TTF and BMFont files are proportional instead. Each glyph in the index has a matching entry in glyphidx_sizes
+/// with its own page, subimage, offset and advance, and the font keeps the kerning between pairs of glyphs.
+///
+/// Characters missing from a font are looked up in its fallback fonts, in the order they were added.
 
 #[derive(Clone)]
 pub struct Font {
 	pub glyphidx: Vec<char>,
 	pub glyphidx_sizes: Vec<FontGlyph>,
-	pub fontimg: Rasterizer,
+	/// Glyph images. Bitmap and TTF fonts only have one page, BMFont files can have many.
+	pub pages: Vec<Arc<Rasterizer>>,
 	pub glyph_width: usize,
 	pub glyph_height: usize,
 	pub glyph_spacing: i64,
+
+	/// Distance from the top of the line to the baseline. Used to line up glyphs from fallback fonts.
+	pub baseline: i64,
+
+	/// Hashed copy of the glyph index so looking up a character doesn't scan the whole index.
+	pub glyph_lookup: HashMap<char, usize>,
+
+	/// Fonts used for characters missing from this one. Kept flat, fallbacks of fallbacks are added to the end.
+	pub fallbacks: Vec<Font>,
+
+	/// Extra horizontal space between pairs of glyphs, from BMFont files. TTF fonts look theirs up in the file instead.
+	pub kerning: HashMap<(char, char), i64>,
+
+	/// Parsed TTF file shared between every size made from it, so new sizes don't need to read the file again.
+	pub ttf: Option<Arc<rusttype::Font<'static>>>,
+	/// Pixel size the TTF glyphs were rendered at, zero for other fonts.
+	pub point_size: f32,
+	/// TTF kerning for every pair looked up so far. Fonts can have thousands of glyphs, so pairs are only looked up once text uses them.
+	kerning_cache: RefCell<HashMap<(char, char), i64>>,
 }
 
+/// Glyphs used by TTF fonts when no glyph index is given. Covers printable ASCII.
+pub const TTF_DEFAULT_GLYPHIDX: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+const DEFAULT_TTF: &[u8] = include_bytes!("abeezee.ttf");
 
 impl Font {
 
-	/// Load a font image from disk. The order
+	/// Load a font image from disk. The order of the glyphs in the image must match the glyph index.
 	pub fn new(path_image: &str, glyphidxstr: &str, glyph_width: usize, glyph_height: usize, glyph_spacing: i64) -> Result<Font, String> {
-
-		let glyphidx = glyphidxstr.to_string().chars().collect();
-		let glyphidx_sizes: Vec<FontGlyph> = Vec::new();
-
-		
 		let fontimg_result = Rasterizer::new_from_image(path_image);
 		if fontimg_result.is_ok() {
-
-	
-			Ok(Font {
-				glyphidx,
-				glyphidx_sizes,
-				fontimg: fontimg_result.unwrap(),
-				glyph_width,
-				glyph_height,
-				glyph_spacing,
-			})
+			Ok(Font::new_from_rasterizer(fontimg_result.unwrap(), glyphidxstr, glyph_width, glyph_height, glyph_spacing))
 		} else {
 			println!("ERROR - FONT: Font image could not be loaded from path {} !", path_image);
 			Err(format!("ERROR - FONT: Font image could not be loaded from path {} !", path_image))
 		}
+	}
 
-		
+	/// Makes a bitmap font out of an image that is already in memory.
+	pub fn new_from_rasterizer(fontimg: Rasterizer, glyphidxstr: &str, glyph_width: usize, glyph_height: usize, glyph_spacing: i64) -> Font {
+		let glyphidx: Vec<char> = glyphidxstr.chars().collect();
+		Font {
+			glyph_lookup: Font::build_lookup(&glyphidx),
+			glyphidx,
+			glyphidx_sizes: Vec::new(),
+			pages: vec![Arc::new(fontimg)],
+			glyph_width,
+			glyph_height,
+			glyph_spacing,
+			baseline: glyph_height as i64,
+			fallbacks: Vec::new(),
+			kerning: HashMap::new(),
+			ttf: None,
+			point_size: 0.0,
+			kerning_cache: RefCell::new(HashMap::new()),
+		}
 	}
 
 	pub fn default() -> Font {
-		Font { glyphidx: Vec::new(), glyphidx_sizes: Vec::new(), fontimg: Rasterizer::new(0, 0), glyph_width: 0, glyph_height: 0, glyph_spacing: 0 }
+		Font::new_from_rasterizer(Rasterizer::new(0, 0), "", 0, 0, 0)
+	}
+
+	/// Returns true if the glyphs have their own sizes instead of a fixed grid.
+	pub fn is_proportional(&self) -> bool {
+		!self.glyphidx_sizes.is_empty()
+	}
+
+	/// Load a TTF file from disk and render the glyphs in the glyph index into an anti-aliased atlas at the given pixel size.
+	pub fn new_ttf(path_ttf: &str, glyphidxstr: &str, glyph_spacing: i64, point_size: f32) -> Result<Font, String> {
+		let ttf_result = Font::parse_ttf(path_ttf);
+		if ttf_result.is_ok() {
+			Ok(Font::new_from_ttf(ttf_result.unwrap(), glyphidxstr, glyph_spacing, point_size))
+		} else {
+			Err(ttf_result.err().unwrap())
+		}
+	}
+
+	/// Reads and parses a TTF file without rendering any glyphs.
+	pub fn parse_ttf(path_ttf: &str) -> Result<Arc<rusttype::Font<'static>>, String> {
+		let ttf_buffer_result = std::fs::read(path_ttf);
+		if ttf_buffer_result.is_err() {
+			println!("ERROR - FONT: TTF file {} could not be read!", path_ttf);
+			return Err(format!("ERROR - FONT: TTF file {} could not be read!", path_ttf));
+		}
+
+		let ttf_result = rusttype::Font::try_from_vec(ttf_buffer_result.unwrap());
+		if ttf_result.is_none() {
+			println!("ERROR - FONT: TTF Font {} cannot be constructed. Make sure there is only one font inside the TTF file.", path_ttf);
+			return Err(format!("ERROR - FONT: TTF Font {} cannot be constructed. Make sure there is only one font inside the TTF file.", path_ttf));
+		}
+
+		Ok(Arc::new(ttf_result.unwrap()))
+	}
+
+	/// Parses the built-in ABeeZee font.
+	pub fn parse_default_ttf() -> Arc<rusttype::Font<'static>> {
+		Arc::new(rusttype::Font::try_from_bytes(DEFAULT_TTF).expect("ERROR - FONT: Built-in font is broken; abeezee.ttf cannot be constructed!"))
 	}
 
-	pub fn new_ttf(path_ttf: &str, glyphidxstr: &str, glyph_spacing: i64, point_size: f32, alpha_threshold: f32) -> Font {
-		
-		let mut ttf_file = File::open(path_ttf).expect(format!("ERROR - FONT: TTF file {} does not exist!", path_ttf).as_str());
-		let mut ttf_buffer: Vec<u8> = Vec::new();
+	/// Load a BMFont descriptor (.fnt in text, XML or binary format) and all of its page images.
+	/// Page images are loaded relative to the descriptor.
+	pub fn new_bmfont(path_fnt: &str) -> Result<Font, String> {
+		let data_result = std::fs::read(path_fnt);
+		if data_result.is_err() {
+			println!("ERROR - FONT: BMFont file {} could not be read!", path_fnt);
+			return Err(format!("ERROR - FONT: BMFont file {} could not be read!", path_fnt));
+		}
+
+		let bmfont_result = BmFont::parse(&data_result.unwrap());
+		if bmfont_result.is_err() {
+			println!("{} Path: {}", bmfont_result.as_ref().err().unwrap(), path_fnt);
+			return Err(bmfont_result.err().unwrap());
+		}
+		let bmfont = bmfont_result.unwrap();
+
+		let folder = std::path::Path::new(path_fnt).parent().unwrap_or(std::path::Path::new(""));
+		let mut pages: Vec<Arc<Rasterizer>> = Vec::with_capacity(bmfont.pages.len());
+		for page in &bmfont.pages {
+			let page_path = folder.join(page);
+			let page_result = Rasterizer::new_from_image(page_path.to_str().unwrap_or(page));
+			if page_result.is_err() {
+				return Err(page_result.err().unwrap());
+			}
+			pages.push(Arc::new(page_result.unwrap()));
+		}
+
+		// Characters outside of unicode are skipped
+		let mut glyphidx: Vec<char> = Vec::with_capacity(bmfont.chars.len());
+		let mut glyphidx_sizes: Vec<FontGlyph> = Vec::with_capacity(bmfont.chars.len());
+		for bmchar in &bmfont.chars {
+			if let Some(c) = char::from_u32(bmchar.id) {
+				glyphidx.push(c);
+				glyphidx_sizes.push(FontGlyph {
+					x: bmchar.x,
+					y: bmchar.y,
+					w: bmchar.width,
+					h: bmchar.height,
+					offset_x: bmchar.offset_x,
+					offset_y: bmchar.offset_y,
+					advance: bmchar.advance,
+					page: bmchar.page,
+				});
+			}
+		}
 
-		let _bytecount = ttf_file.read_to_end(&mut ttf_buffer).expect("ERROR - FONT: TTF File could not be read.");
+		let mut kerning: HashMap<(char, char), i64> = HashMap::new();
+		for (first, second, amount) in &bmfont.kernings {
+			if let (Some(a), Some(b)) = (char::from_u32(*first), char::from_u32(*second)) {
+				kerning.insert((a, b), *amount);
+			}
+		}
+
+		let space_width = match glyphidx.iter().position(|c| *c == ' ') {
+			Some(idx) => glyphidx_sizes[idx].advance as usize,
+			None => (bmfont.line_height / 4).max(1) as usize,
+		};
+
+		Ok(Font {
+			glyph_lookup: Font::build_lookup(&glyphidx),
+			glyphidx,
+			glyphidx_sizes,
+			pages,
+			glyph_width: space_width,
+			glyph_height: bmfont.line_height as usize,
+			glyph_spacing: 0,
+			baseline: bmfont.base,
+			fallbacks: Vec::new(),
+			kerning,
+			ttf: None,
+			point_size: 0.0,
+			kerning_cache: RefCell::new(HashMap::new()),
+		})
+	}
 
-		let ttf = rusttype::Font::try_from_vec(ttf_buffer).expect(format!("ERROR - FONT: TTF Font {} cannot be constructed. Make sure there is only one font inside the TTF file.", path_ttf).as_str());
+	/// The built-in ABeeZee font at the given pixel size.
+	pub fn default_ttf(point_size: f32) -> Font {
+		Font::new_from_ttf(Font::parse_default_ttf(), TTF_DEFAULT_GLYPHIDX, 0, point_size)
+	}
 
+	/// Makes another copy of a TTF font at a different size without reading the file again.
+	/// Bitmap fonts can't be resized and are returned as-is.
+	pub fn resized(&self, point_size: f32) -> Font {
+		match &self.ttf {
+			Some(ttf) => {
+				let glyphidxstr: String = self.glyphidx.iter().collect();
+				Font::new_from_ttf(ttf.clone(), glyphidxstr.as_str(), self.glyph_spacing, point_size)
+			},
+			None => { self.clone() }
+		}
+	}
 
-		let glyphidx: Vec<char> = glyphidxstr.to_string().chars().collect();
+	/// Renders glyphs from a parsed TTF font into an atlas, recording their metrics and kerning.
+	pub fn new_from_ttf(ttf: Arc<rusttype::Font<'static>>, glyphidxstr: &str, glyph_spacing: i64, point_size: f32) -> Font {
+		let glyphidx: Vec<char> = glyphidxstr.chars().collect();
 
 		let scale = rusttype::Scale::uniform(point_size);
-        let mut v_metrics = ttf.v_metrics(scale);
-        v_metrics.line_gap = point_size;
-
-        let glyphs: Vec<_> = ttf.layout(
-            glyphidxstr, 
-            rusttype::Scale::uniform(point_size), 
-            rusttype::point(0.0, v_metrics.ascent)).collect();
-
-        // work out the layout size
-        let glyphs_height = (v_metrics.ascent - v_metrics.descent).ceil() as u32;
-        let glyphs_width = {
-            let min_x = glyphs
-                .first()
-                .map(|g| g.pixel_bounding_box().unwrap().min.x)
-                .unwrap();
-            let max_x = glyphs
-                .last()
-                .map(|g| g.pixel_bounding_box().unwrap().max.x)
-                .unwrap();
-            (max_x - min_x) as u32
-        };
-
-		let mut fontimg: Rasterizer = Rasterizer::new(glyphs_width as usize, glyphs_height as usize);
+		let v_metrics = ttf.v_metrics(scale);
+
+		// The baseline sits at the ascent so glyph offsets are relative to the top of the line
+		let line_height = (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap).ceil() as usize;
+		let baseline = rusttype::point(0.0, v_metrics.ascent);
+
+		let glyphs: Vec<rusttype::PositionedGlyph> = glyphidx.iter().map(|c| {
+			ttf.glyph(*c).scaled(scale).positioned(baseline)
+		}).collect();
+
+		// First pass: Pack every glyph bounding box into rows so the atlas stays roughly square
+		let total_area: i64 = glyphs.iter().map(|g| {
+			match g.pixel_bounding_box() {
+				Some(bb) => (bb.width() as i64 + 1) * (bb.height() as i64 + 1),
+				None => 0,
+			}
+		}).sum();
+
+		let widest: i64 = glyphs.iter().map(|g| {
+			match g.pixel_bounding_box() { Some(bb) => bb.width() as i64 + 1, None => 0 }
+		}).max().unwrap_or(0);
+
+		let atlas_width: i64 = i64::max((total_area as f64).sqrt().ceil() as i64, widest).max(1);
 
 		let mut glyphidx_sizes: Vec<FontGlyph> = Vec::with_capacity(glyphidx.len());
+		let (mut pen_x, mut pen_y, mut row_height) = (0i64, 0i64, 0i64);
+
+		for glyph in &glyphs {
+			let advance = glyph.unpositioned().h_metrics().advance_width.round() as i64;
+
+			match glyph.pixel_bounding_box() {
+				Some(bb) => {
+					let (w, h) = (bb.width() as i64, bb.height() as i64);
+					if pen_x + w > atlas_width {
+						pen_x = 0;
+						pen_y += row_height;
+						row_height = 0;
+					}
+
+					glyphidx_sizes.push(FontGlyph::new_metrics(pen_x, pen_y, w, h, bb.min.x as i64, bb.min.y as i64, advance));
+
+					// One pixel of padding so neighbouring glyphs don't bleed into each other
+					pen_x += w + 1;
+					row_height = i64::max(row_height, h + 1);
+				},
+				// Whitespace has no pixels but still moves the cursor
+				None => { glyphidx_sizes.push(FontGlyph::new_metrics(0, 0, 0, 0, 0, 0, advance)); }
+			}
+		}
+
+		let atlas_height = (pen_y + row_height).max(1);
+		let mut fontimg: Rasterizer = Rasterizer::new(atlas_width as usize, atlas_height as usize);
+		fontimg.set_draw_mode(crate::rasterizer::DrawMode::NoOp);
+
+		// Second pass: Draw the glyph coverage into the atlas as alpha
+		for i in 0..glyphs.len() {
+			let size = glyphidx_sizes[i];
+			glyphs[i].draw(|x, y, v| {
+				let alpha = f32::clamp(v * 255.0, 0.0, 255.0).round() as u8;
+				fontimg.pset(size.x + x as i64, size.y + y as i64, Color::new(255, 255, 255, alpha));
+			});
+		}
 
-        for i in 0..glyphs.len() {
-            if let Some(bounding_box) = glyphs[i].pixel_bounding_box() {
-				glyphidx_sizes[i].x = bounding_box.min.x as i64;
-				glyphidx_sizes[i].y = bounding_box.min.y as i64;
-				glyphidx_sizes[i].w = bounding_box.max.x as i64;
-				glyphidx_sizes[i].h = bounding_box.max.y as i64;
-
-                // Draw the glyph into the image per-pixel by using the draw closure
-                glyphs[i].draw(|x, y, v| {
-                    fontimg.pset(
-                        // Offset the position by the glyph bounding box
-                        x as i64 + bounding_box.min.x as i64,
-                        y as i64 + bounding_box.min.y as i64,
-                        // Turn the coverage into an alpha value
-                        Color::new(255, 255, 255, if v > alpha_threshold { 255 } else { 0 })
-                    )
-                });
-            }
-        }
+		let space_width = match glyphidx.iter().position(|c| *c == ' ') {
+			Some(idx) => glyphidx_sizes[idx].advance as usize,
+			None => (point_size / 4.0).ceil() as usize,
+		};
 
 		Font {
+			glyph_lookup: Font::build_lookup(&glyphidx),
 			glyphidx,
 			glyphidx_sizes,
-			fontimg,
-			glyph_width: point_size as usize,
-			glyph_height: point_size as usize,
-			glyph_spacing
+			pages: vec![Arc::new(fontimg)],
+			glyph_width: space_width,
+			glyph_height: line_height,
+			glyph_spacing,
+			baseline: v_metrics.ascent.round() as i64,
+			fallbacks: Vec::new(),
+			kerning: HashMap::new(),
+			ttf: Some(ttf),
+			point_size,
+			kerning_cache: RefCell::new(HashMap::new()),
+		}
+	}
+
+	pub fn build_lookup(glyphidx: &Vec<char>) -> HashMap<char, usize> {
+		let mut lookup: HashMap<char, usize> = HashMap::with_capacity(glyphidx.len());
+		for (idx, c) in glyphidx.iter().enumerate() {
+			// Keep the first glyph if the index has duplicates, same as scanning it would
+			lookup.entry(*c).or_insert(idx);
+		}
+		lookup
+	}
+
+	/// Finds where a character is in this fonts glyph index, ignoring fallbacks.
+	pub fn glyph_index(&self, c: char) -> Option<usize> {
+		self.glyph_lookup.get(&c).copied()
+	}
+
+	/// Finds a character in this font or its fallbacks.
+	/// Returns which font in the chain has it (0 is this font, 1 is the first fallback, ...) and where it is in that fonts glyph index.
+	pub fn resolve(&self, c: char) -> Option<(usize, usize)> {
+		if let Some(idx) = self.glyph_index(c) {
+			return Some((0, idx));
+		}
+
+		for (chain_idx, fallback) in self.fallbacks.iter().enumerate() {
+			if let Some(idx) = fallback.glyph_index(c) {
+				return Some((chain_idx + 1, idx));
+			}
+		}
+		None
+	}
+
+	/// Gets a font in the fallback chain, where 0 is this font.
+	pub fn chain(&self, chain_idx: usize) -> &Font {
+		if chain_idx == 0 { self } else { &self.fallbacks[chain_idx - 1] }
+	}
+
+	/// Where a glyph is on its page and where it's drawn from the cursor, or None if it has nothing to draw.
+	pub fn glyph_rect(&self, glyph: usize) -> Option<FontGlyph> {
+		if self.is_proportional() {
+			let size = self.glyphidx_sizes[glyph];
+			if size.w > 0 && size.h > 0 && size.page < self.pages.len() { Some(size) } else { None }
+		} else {
+			let fontimg = &self.pages[0];
+			let rectx: i64 = (glyph as i64 * self.glyph_width as i64) % (fontimg.width as i64);
+			let recty: i64 = ((glyph as i64 * self.glyph_width as i64) / fontimg.width as i64) * self.glyph_height as i64;
+			Some(FontGlyph::new(rectx, recty, self.glyph_width as i64, self.glyph_height as i64))
+		}
+	}
+
+	/// Adds a font to use for characters missing from this one.
+	pub fn add_fallback(&mut self, font: Font) {
+		let mut font = font;
+		let nested = std::mem::take(&mut font.fallbacks);
+		self.fallbacks.push(font);
+		self.fallbacks.extend(nested);
+	}
+
+	/// How far the cursor moves after drawing a character, including glyph spacing.
+	/// Characters missing from the font and its fallbacks don't move the cursor, except for spaces which use the glyph width.
+	pub fn advance(&self, c: char) -> i64 {
+		match self.resolve(c) {
+			Some((chain_idx, idx)) => {
+				let font = self.chain(chain_idx);
+				if font.is_proportional() {
+					font.glyphidx_sizes[idx].advance + font.glyph_spacing
+				} else if c == ' ' {
+					font.glyph_width as i64
+				} else {
+					font.glyph_width as i64 + font.glyph_spacing
+				}
+			},
+			None => { if c == ' ' { self.glyph_width as i64 } else { 0 } }
+		}
+	}
+
+	/// Horizontal adjustment between two glyphs, or zero if the pair has no kerning.
+	pub fn kern(&self, left: char, right: char) -> i64 {
+		if let Some(kern) = self.kerning.get(&(left, right)) { return *kern; }
+		let ttf = match &self.ttf { Some(ttf) => ttf, None => return 0 };
+
+		if let Some(kern) = self.kerning_cache.borrow().get(&(left, right)) { return *kern; }
+		let kern = ttf.pair_kerning(rusttype::Scale::uniform(self.point_size), left, right).round() as i64;
+		self.kerning_cache.borrow_mut().insert((left, right), kern);
+		kern
+	}
+}
+
+/// Keeps parsed TTF files and the atlases made from them, so loading the same font at the same size twice is free
+/// and loading a new size doesn't need to read the file again.
+#[derive(Default)]
+pub struct FontCache {
+	ttf_files: HashMap<String, Arc<rusttype::Font<'static>>>,
+	atlases: HashMap<(String, String, u32), Font>,
+}
+
+impl FontCache {
+	pub fn new() -> FontCache {
+		FontCache::default()
+	}
+
+	/// Gets a TTF font at the given size, rendering a new atlas only if this size hasn't been used before.
+	/// A path of None uses the built-in font.
+	pub fn load_ttf(&mut self, path_ttf: Option<&str>, glyphidxstr: &str, glyph_spacing: i64, point_size: f32) -> Result<Font, String> {
+		let path_key = path_ttf.unwrap_or("").to_string();
+		// Sizes are stored in 1/64ths of a pixel like most font engines
+		let atlas_key = (path_key.clone(), glyphidxstr.to_string(), (point_size * 64.0).round() as u32);
+
+		if let Some(font) = self.atlases.get(&atlas_key) {
+			let mut font = font.clone();
+			font.glyph_spacing = glyph_spacing;
+			return Ok(font);
+		}
+
+		if !self.ttf_files.contains_key(&path_key) {
+			let ttf = match path_ttf {
+				Some(path) => {
+					let ttf_result = Font::parse_ttf(path);
+					if ttf_result.is_err() { return Err(ttf_result.err().unwrap()); }
+					ttf_result.unwrap()
+				},
+				None => { Font::parse_default_ttf() }
+			};
+			self.ttf_files.insert(path_key.clone(), ttf);
 		}
+
+		let font = Font::new_from_ttf(self.ttf_files[&path_key].clone(), glyphidxstr, glyph_spacing, point_size);
+		self.atlases.insert(atlas_key, font.clone());
+		Ok(font)
 	}
 }
 
-impl LuaUserData for Font {}
+impl LuaUserData for Font {
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		methods.add_method("resized", |_, font, point_size: f32| {
+			Ok(font.resized(point_size))
+		});
+
+		methods.add_method_mut("add_fallback", |_, font, fallback: Font| {
+			font.add_fallback(fallback);
+			Ok(())
+		});
+
+		methods.add_method("has_glyph", |_, font, c: String| {
+			Ok(c.chars().all(|c| c == ' ' || c == '\n' || font.resolve(c).is_some()))
+		});
+	}
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct FontGlyph {
@@ -139,6 +465,16 @@ pub struct FontGlyph {
 	pub y: i64,
 	pub w: i64,
 	pub h: i64,
+
+	/// Where the glyph image is drawn relative to the cursor, with y starting at the top of the line.
+	pub offset_x: i64,
+	pub offset_y: i64,
+
+	/// How far the cursor moves after drawing this glyph.
+	pub advance: i64,
+
+	/// Which of the fonts pages the glyph is on.
+	pub page: usize,
 }
 
 impl FontGlyph {
@@ -148,6 +484,23 @@ impl FontGlyph {
 			y,
 			w,
 			h,
+			offset_x: 0,
+			offset_y: 0,
+			advance: w,
+			page: 0,
+		}
+	}
+
+	pub fn new_metrics(x: i64, y: i64, w: i64, h: i64, offset_x: i64, offset_y: i64, advance: i64) -> FontGlyph {
+		FontGlyph {
+			x,
+			y,
+			w,
+			h,
+			offset_x,
+			offset_y,
+			advance,
+			page: 0,
 		}
 	}
-}
\ No newline at end of file
+}
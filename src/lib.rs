@@ -0,0 +1,41 @@
+// The engine's math and software rendering, split out of the binary so benchmarks can use it too.
+// main.rs pulls these back in with use, so the rest of the engine still finds them at crate::
+
+// Math
+pub mod math;
+pub mod vector2;
+pub mod matrix3;
+
+// Software Rendering
+pub mod color;
+pub mod bmfont;
+pub mod font;
+pub mod text;
+pub mod shapes;
+pub mod curves;
+pub mod span;
+pub mod palette;
+pub mod postprocess;
+pub mod rasterizer;
+pub mod partitioned_rasterizer;
+pub mod command_buffer;
+
+// Lighting
+pub mod lighting;
+
+// Particles
+pub mod particles;
+
+// Animation
+pub mod easing;
+
+// Scenes
+pub mod transition;
+
+// Entities
+pub mod ecs;
+
+// Procedural Textures
+pub mod noise;
+pub mod expression;
+pub mod procedural;
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::color::Color;
+use crate::matrix3::Matrix3;
+use crate::partitioned_rasterizer::PartitionedRasterizer;
+use crate::rasterizer::{Rasterizer, DrawMode, DrawSpace};
+use crate::vector2::Vector2;
+
+// How much of a spot light's cone fades out at its edge, as a part of half the cone
+const SPOT_EDGE: f64 = 0.2;
+
+// Rays are cast this far to either side of every occluder corner so they can see past it
+const CORNER_EPSILON: f64 = 0.00001;
+
+/// The shape of a light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+	/// Shines the same amount in every direction.
+	Point,
+	/// Shines in a cone. Direction is in radians and angle is the whole width of the cone.
+	Spot { direction: f64, angle: f64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+	pub kind: LightKind,
+	pub position: Vector2,
+	pub radius: f64,
+	pub color: Color,
+	/// How the light fades out to its radius. 1.0 fades evenly, higher numbers fade faster near the center.
+	pub falloff: f64,
+	pub intensity: f64,
+	/// Whether occluders block this light.
+	pub shadows: bool,
+}
+
+impl Light {
+	pub fn point(position: Vector2, radius: f64, color: Color) -> Light {
+		Light { kind: LightKind::Point, position, radius, color, falloff: 2.0, intensity: 1.0, shadows: true }
+	}
+
+	pub fn spot(position: Vector2, radius: f64, direction: f64, angle: f64, color: Color) -> Light {
+		Light { kind: LightKind::Spot { direction, angle }, position, radius, color, falloff: 2.0, intensity: 1.0, shadows: true }
+	}
+}
+
+// A light moved onto the screen, with everything each pixel needs worked out ahead of time
+struct ScreenLight {
+	position: Vector2,
+	radius: f64,
+	color: [f64; 3],
+	falloff: f64,
+	// Direction, and the cosines of the angles where the edge starts fading and where it's gone
+	spot: Option<(Vector2, f64, f64)>,
+	// None when nothing blocks the light
+	polygon: Option<Vec<Vector2>>,
+}
+
+/// 2D lights and shadows, drawn on top of the scene by multiplying it with the light.
+/// Lights and occluders are in world coordinates, so they move with the camera like everything else.
+pub struct Lighting {
+	/// The light everything gets even without any lights, black is pitch dark and white turns lighting off.
+	pub ambient: Color,
+	pub lights: HashMap<String, Light>,
+	/// Line segments that cast shadows, in named groups so a level's walls can be replaced all at once.
+	pub occluders: HashMap<String, Vec<(Vector2, Vector2)>>,
+
+	// The light inverted, since Multiply draws the inverse of its color
+	shade: Arc<Rasterizer>,
+}
+
+impl Lighting {
+	pub fn new() -> Lighting {
+		Lighting {
+			ambient: Color::new(32, 32, 48, 255),
+			lights: HashMap::new(),
+			occluders: HashMap::new(),
+			shade: Arc::new(Rasterizer::new(1, 1)),
+		}
+	}
+
+	pub fn add_occluder(&mut self, group: &str, start: Vector2, end: Vector2) {
+		self.occluders.entry(group.to_string()).or_default().push((start, end));
+	}
+
+	/// Adds a segment between every pair of points, and from the last one back to the first if it's closed.
+	pub fn add_occluder_polygon(&mut self, group: &str, points: &[Vector2], closed: bool) {
+		if points.len() < 2 { return; }
+		let segments = self.occluders.entry(group.to_string()).or_default();
+		for pair in points.windows(2) {
+			segments.push((pair[0], pair[1]));
+		}
+		if closed && points.len() > 2 {
+			segments.push((points[points.len() - 1], points[0]));
+		}
+	}
+
+	/// Every occluder segment from every group.
+	pub fn segments(&self) -> Vec<(Vector2, Vector2)> {
+		self.occluders.values().flatten().copied().collect()
+	}
+
+	/// Fills the shade buffer with the ambient light and every light, seen through the camera.
+	/// Rows are lit in parallel, and each light's visibility polygon is found in parallel first.
+	pub fn render(&mut self, width: usize, height: usize, camera: Matrix3) {
+		let segments = self.segments();
+		let scale = camera.max_scale();
+
+		let lights: Vec<ScreenLight> = self.lights.values().collect::<Vec<&Light>>().par_iter().filter(|light| light.radius > 0.0 && light.intensity > 0.0).map(|light| {
+			let position = camera.forward(light.position);
+			let spot = match light.kind {
+				LightKind::Point => None,
+				LightKind::Spot { direction, angle } => {
+					let half = f64::clamp(angle * 0.5, 0.0, std::f64::consts::PI);
+					let toward = camera.forward(light.position + Vector2::new(f64::cos(direction), f64::sin(direction))) - position;
+					Some((toward.normalized(), f64::cos(half * (1.0 - SPOT_EDGE)), f64::cos(half)))
+				},
+			};
+
+			// Found in world space, the camera only moves, turns and scales it
+			let polygon = if light.shadows && !segments.is_empty() {
+				Some(visibility_polygon(light.position, light.radius, &segments).iter().map(|p| camera.forward(*p)).collect())
+			} else {
+				None
+			};
+
+			ScreenLight {
+				position,
+				radius: light.radius * scale,
+				color: [light.color.r as f64 * light.intensity, light.color.g as f64 * light.intensity, light.color.b as f64 * light.intensity],
+				falloff: f64::max(light.falloff, 0.0),
+				spot,
+				polygon,
+			}
+		}).collect();
+
+		let shade = Arc::make_mut(&mut self.shade);
+		if shade.width != width || shade.height != height {
+			*shade = Rasterizer::new(width, height);
+		}
+
+		let ambient = [self.ambient.r as f64, self.ambient.g as f64, self.ambient.b as f64];
+		shade.color.par_chunks_mut(width * 4).enumerate().for_each(|(y, row)| {
+			let mut accum: Vec<[f64; 3]> = vec![ambient; width];
+			let mut spans: Vec<(f64, f64)> = Vec::new();
+			let sample_y = y as f64 + 0.5;
+
+			for light in &lights {
+				let dy = sample_y - light.position.y;
+				if dy.abs() >= light.radius { continue; }
+
+				// The part of the row inside the light's circle, cut down to what it can see
+				let half = f64::sqrt(light.radius * light.radius - dy * dy);
+				let (circle_x0, circle_x1) = (light.position.x - half, light.position.x + half);
+				spans.clear();
+				match &light.polygon {
+					Some(polygon) => polygon_spans(polygon, sample_y, &mut spans),
+					None => spans.push((circle_x0, circle_x1)),
+				}
+
+				for (span_x0, span_x1) in spans.iter() {
+					// Pixels whose centers are inside the span
+					let x0 = f64::clamp(f64::ceil(f64::max(*span_x0, circle_x0) - 0.5), 0.0, width as f64) as usize;
+					let x1 = f64::clamp(f64::ceil(f64::min(*span_x1, circle_x1) - 0.5), 0.0, width as f64) as usize;
+
+					for (x, pixel) in accum.iter_mut().enumerate().take(x1).skip(x0) {
+						let dx = x as f64 + 0.5 - light.position.x;
+						let distance = f64::sqrt(dx * dx + dy * dy);
+						let mut strength = f64::powf(f64::max(1.0 - distance / light.radius, 0.0), light.falloff);
+
+						if let Some((direction, inner, outer)) = light.spot {
+							let cos = if distance > 0.0 { (dx * direction.x + dy * direction.y) / distance } else { 1.0 };
+							if cos <= outer { continue; }
+							if cos < inner {
+								let t = (cos - outer) / (inner - outer);
+								strength *= t * t * (3.0 - 2.0 * t);
+							}
+						}
+
+						for (channel, color) in pixel.iter_mut().zip(light.color.iter()) { *channel += color * strength; }
+					}
+				}
+			}
+
+			for (pixel, light) in row.chunks_exact_mut(4).zip(accum.iter()) {
+				let channel = |v: f64| 255 - f64::clamp(v + 0.5, 0.0, 255.0) as u8;
+				pixel.copy_from_slice(&[channel(light[0]), channel(light[1]), channel(light[2]), 255]);
+			}
+		});
+	}
+
+	/// Renders the light and multiplies it onto everything drawn so far, through the Multiply draw mode.
+	/// Anything drawn after this, like the UI, isn't lit.
+	pub fn draw(&mut self, rst: &mut PartitionedRasterizer) {
+		self.render(rst.rasterizer.width, rst.rasterizer.height, rst.rasterizer.camera_matrix);
+
+		// Drawn over the whole screen as is, then the drawing state is put back
+		let (mode, space, tint, opacity) = (rst.rasterizer.draw_mode, rst.rasterizer.draw_space, rst.rasterizer.tint, rst.rasterizer.opacity);
+		let transform_stack = rst.rasterizer.transform_stack.clone();
+		rst.set_draw_mode(DrawMode::Multiply);
+		rst.set_draw_space(DrawSpace::Screen);
+		rst.set_tint(Color::white());
+		rst.set_opacity(255);
+		rst.reset_transform();
+
+		rst.pimg(&self.shade, 0, 0);
+
+		rst.set_draw_mode(mode);
+		rst.set_draw_space(space);
+		rst.set_tint(tint);
+		rst.set_opacity(opacity);
+		rst.set_transform_stack(transform_stack);
+	}
+}
+
+impl Default for Lighting {
+	fn default() -> Self {
+		Lighting::new()
+	}
+}
+
+// The spans of a row at y inside a polygon, as (x0, x1) pairs
+fn polygon_spans(polygon: &[Vector2], y: f64, spans: &mut Vec<(f64, f64)>) {
+	let mut crossings: Vec<f64> = Vec::new();
+	for i in 0..polygon.len() {
+		let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+		if (a.y <= y) != (b.y <= y) {
+			crossings.push(a.x + (y - a.y) / (b.y - a.y) * (b.x - a.x));
+		}
+	}
+	crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+	for pair in crossings.chunks_exact(2) {
+		spans.push((pair[0], pair[1]));
+	}
+}
+
+fn distance_to_segment(point: Vector2, start: Vector2, end: Vector2) -> f64 {
+	let along = end - start;
+	let length_sqr = along.magnitude_sqr();
+	let t = if length_sqr > 0.0 { f64::clamp(Vector2::dot(point - start, along) / length_sqr, 0.0, 1.0) } else { 0.0 };
+	Vector2::distance(point, start + along * t)
+}
+
+/// Everything that can be seen from origin out to radius past the segments, as a polygon sorted by angle around it.
+/// Rays are cast at every segment's corners and just to either side of them, the closest hit of each ray is a point.
+pub fn visibility_polygon(origin: Vector2, radius: f64, segments: &[(Vector2, Vector2)]) -> Vec<Vector2> {
+	// A box just outside the radius so every ray hits something
+	let reach = radius + 1.0;
+	let corners = [
+		origin + Vector2::new(-reach, -reach),
+		origin + Vector2::new(reach, -reach),
+		origin + Vector2::new(reach, reach),
+		origin + Vector2::new(-reach, reach),
+	];
+	let mut walls: Vec<(Vector2, Vector2)> = (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect();
+	walls.extend(segments.iter().filter(|(start, end)| distance_to_segment(origin, *start, *end) < reach));
+
+	let mut angles: Vec<f64> = Vec::with_capacity(walls.len() * 6);
+	for (start, end) in walls.iter() {
+		for point in [start, end] {
+			let angle = f64::atan2(point.y - origin.y, point.x - origin.x);
+			angles.extend_from_slice(&[angle - CORNER_EPSILON, angle, angle + CORNER_EPSILON]);
+		}
+	}
+	angles.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+	angles.dedup();
+
+	// Long enough to reach the corners of the box
+	let ray_length = reach * 2.0;
+	angles.iter().filter_map(|angle| {
+		let direction = Vector2::new(f64::cos(*angle), f64::sin(*angle));
+		let ray_end = origin + direction * ray_length;
+		let mut closest: Option<(f64, Vector2)> = None;
+		for (start, end) in walls.iter() {
+			// Segments with both ends on the same side of the ray can't be hit, which skips most of them cheaply
+			let (side_start, side_end) = (Vector2::cross(direction, *start - origin), Vector2::cross(direction, *end - origin));
+			if (side_start > 0.0 && side_end > 0.0) || (side_start < 0.0 && side_end < 0.0) { continue; }
+
+			let (hit, point) = Vector2::intersection_segment(origin, ray_end, *start, *end);
+			if hit {
+				let distance = (point - origin).magnitude_sqr();
+				let nearer = match closest {
+					Some((closest_distance, _)) => distance < closest_distance,
+					None => true,
+				};
+				if nearer {
+					closest = Some((distance, point));
+				}
+			}
+		}
+		closest.map(|(_, point)| point)
+	}).collect()
+}
@@ -0,0 +1,652 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::font::Font;
+use crate::text::parse_markup;
+
+/// A translated string. Plural strings hold one form per plural category of their language, see plural_index.
+#[derive(Clone, Debug)]
+pub enum LocalizedString {
+	Single(String),
+	Plural(Vec<String>),
+}
+
+/// Every translated string of one language, keyed by string id.
+pub type StringTable = HashMap<String, LocalizedString>;
+
+/// Something the validation pass found wrong with a translation.
+#[derive(Clone, Debug)]
+pub struct LocalizationIssue {
+	pub language: String,
+	pub key: String,
+	pub message: String,
+}
+
+/// String tables for every loaded language and the language currently in use.
+/// Strings missing from the current language are looked up in the fallback language, then the key itself is returned.
+pub struct Localization {
+	pub tables: HashMap<String, StringTable>,
+	pub language: String,
+	pub fallback_language: Option<String>,
+}
+
+impl Localization {
+	pub fn new() -> Localization {
+		Localization { tables: HashMap::new(), language: String::new(), fallback_language: None }
+	}
+
+	/// Loads a string table, the format comes from the file extension (.csv, .po or .json).
+	/// CSV files hold every language in one file, so 'language' is only needed for PO and JSON files.
+	/// Returns the languages that were loaded.
+	pub fn load_file(&mut self, path: &str, language: Option<&str>) -> Result<Vec<String>, String> {
+		let text_result = std::fs::read_to_string(path);
+		if text_result.is_err() {
+			let err = format!("ERROR - LOCALIZATION: Could not read {}! {}", path, text_result.err().unwrap());
+			println!("{}", err);
+			return Err(err);
+		}
+		let text = text_result.unwrap();
+
+		let extension = Path::new(path).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+		let result = match (extension.as_str(), language) {
+			("csv", _) 				=> self.load_csv(&text),
+			("po", Some(lang)) 		=> self.load_po(&text, lang).map(|_| vec![lang.to_string()]),
+			("json", Some(lang)) 	=> self.load_json(&text, lang).map(|_| vec![lang.to_string()]),
+			("po", None) | ("json", None) => Err(format!("ERROR - LOCALIZATION: {} needs a language to load into!", path)),
+			_ => Err(format!("ERROR - LOCALIZATION: {} is not a .csv, .po or .json file!", path)),
+		};
+
+		if result.is_err() {
+			println!("{}", result.as_ref().err().unwrap());
+		}
+		result
+	}
+
+	/// CSV tables have a header row of 'key,<language>,<language>...' followed by one row per string.
+	/// Plural forms are written as separate rows with the form index after the key, like 'apples[0]' and 'apples[1]'.
+	/// Empty cells are left out so the fallback language is used for them.
+	pub fn load_csv(&mut self, text: &str) -> Result<Vec<String>, String> {
+		let rows = parse_csv(text);
+		if rows.is_empty() || rows[0].len() < 2 {
+			return Err("ERROR - LOCALIZATION: CSV string table needs a header row with at least one language!".to_string());
+		}
+
+		let languages: Vec<String> = rows[0][1..].iter().map(|l| l.trim().to_string()).collect();
+		for row in rows.iter().skip(1) {
+			if row.is_empty() || row[0].trim().is_empty() { continue; }
+
+			let (key, form) = split_plural_key(row[0].trim());
+			for (column, value) in row.iter().enumerate().skip(1) {
+				if value.is_empty() || column > languages.len() { continue; }
+				self.insert(&languages[column - 1], key, form, value.clone());
+			}
+		}
+
+		for language in languages.iter() { self.added_language(language); }
+		Ok(languages)
+	}
+
+	/// Gettext PO files. The msgid is the key, msgid_plural entries fill in every msgstr[n] as plural forms.
+	/// Entries with a msgctxt are keyed as "context.msgid", like nested JSON keys.
+	/// Fuzzy and untranslated entries are skipped like gettext does.
+	pub fn load_po(&mut self, text: &str, language: &str) -> Result<(), String> {
+		let mut entry = PoEntry::default();
+		let mut entries: Vec<PoEntry> = Vec::new();
+
+		// 0 = msgctxt, 1 = msgid, 2 = msgid_plural, 3 + n = msgstr[n]
+		let mut field: usize = 0;
+
+		let mut flush = |entry: &mut PoEntry| {
+			let finished = std::mem::take(entry);
+			if finished.id.is_some() { entries.push(finished); }
+		};
+
+		for (line_idx, line) in text.lines().enumerate() {
+			let line = line.trim();
+
+			if line.is_empty() {
+				flush(&mut entry);
+				continue;
+			}
+
+			// Entries don't have to be separated by blank lines, so comments, msgctxt or msgid after a msgstr start a new one
+			let starts_entry = line.starts_with('#') || line.starts_with("msgctxt") || line.starts_with("msgid ");
+			if starts_entry && !entry.forms.is_empty() {
+				flush(&mut entry);
+			}
+
+			if line.starts_with("#,") && line.contains("fuzzy") {
+				entry.fuzzy = true;
+				continue;
+			}
+			if line.starts_with('#') { continue; }
+
+			let (keyword, rest) = match line.find(char::is_whitespace) {
+				Some(split) if !line.starts_with('"') => (&line[..split], line[split..].trim()),
+				_ => ("", line),
+			};
+
+			let value = unquote_po(rest);
+			if value.is_none() {
+				return Err(format!("ERROR - LOCALIZATION: PO file line {} is not a quoted string!", line_idx + 1));
+			}
+			let value = value.unwrap();
+
+			match keyword {
+				"msgctxt" => {
+					entry.context = Some(value);
+					field = 0;
+				},
+				"msgid" => {
+					entry.id = Some(value);
+					field = 1;
+				},
+				"msgid_plural" => {
+					entry.plural = true;
+					field = 2;
+				},
+				"" => {
+					match field {
+						0 => { if let Some(context) = entry.context.as_mut() { context.push_str(&value); } },
+						1 => { if let Some(id) = entry.id.as_mut() { id.push_str(&value); } },
+						// The msgstr it continues is gone if a blank line ended the entry in between
+						n if n >= 3 => match entry.forms.get_mut(n - 3) {
+							Some(form) => form.push_str(&value),
+							None => return Err(format!("ERROR - LOCALIZATION: PO file line {} is a string outside of any msgstr!", line_idx + 1)),
+						},
+						_ => {}
+					}
+				},
+				_ => {
+					let form = if keyword == "msgstr" {
+						Some(0)
+					} else {
+						keyword.strip_prefix("msgstr[").and_then(|f| f.strip_suffix(']')).and_then(|f| f.parse::<usize>().ok())
+					};
+
+					if form.is_none() {
+						return Err(format!("ERROR - LOCALIZATION: PO file line {} has an unknown keyword '{}'!", line_idx + 1, keyword));
+					}
+
+					let form = form.unwrap();
+					if entry.forms.len() <= form { entry.forms.resize(form + 1, String::new()); }
+					entry.forms[form] = value;
+					field = 3 + form;
+				}
+			}
+		}
+		flush(&mut entry);
+
+		for entry in entries {
+			let id = entry.id.unwrap_or_default();
+			// The entry with an empty msgid is the PO header
+			if id.is_empty() || entry.fuzzy || entry.forms.iter().all(|f| f.is_empty()) { continue; }
+
+			let key = match entry.context {
+				Some(context) => format!("{}.{}", context, id),
+				None => id,
+			};
+			if entry.plural {
+				for (form, value) in entry.forms.into_iter().enumerate() {
+					self.insert(language, &key, Some(form), value);
+				}
+			} else {
+				self.insert(language, &key, None, entry.forms.into_iter().next().unwrap_or_default());
+			}
+		}
+
+		self.added_language(language);
+		Ok(())
+	}
+
+	/// JSON objects of key to string, or key to an array of plural forms.
+	/// Nested objects are flattened with dots, so {"menu": {"start": "Start"}} becomes "menu.start".
+	pub fn load_json(&mut self, text: &str, language: &str) -> Result<(), String> {
+		let json_result = serde_json::from_str::<serde_json::Value>(text);
+		if json_result.is_err() {
+			return Err(format!("ERROR - LOCALIZATION: JSON string table is invalid! {}", json_result.err().unwrap()));
+		}
+
+		let json = json_result.unwrap();
+		if !json.is_object() {
+			return Err("ERROR - LOCALIZATION: JSON string table must be an object!".to_string());
+		}
+
+		let mut pending: Vec<(String, &serde_json::Value)> = vec![(String::new(), &json)];
+		while let Some((prefix, value)) = pending.pop() {
+			match value {
+				serde_json::Value::Object(map) => {
+					for (key, child) in map.iter() {
+						let full_key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+						pending.push((full_key, child));
+					}
+				},
+				serde_json::Value::Array(forms) => {
+					for (form, value) in forms.iter().enumerate() {
+						self.insert(language, &prefix, Some(form), json_to_string(value));
+					}
+				},
+				_ => { self.insert(language, &prefix, None, json_to_string(value)); }
+			}
+		}
+
+		self.added_language(language);
+		Ok(())
+	}
+
+	fn insert(&mut self, language: &str, key: &str, form: Option<usize>, value: String) {
+		let table = self.tables.entry(language.to_string()).or_insert_with(HashMap::new);
+
+		match form {
+			None => { table.insert(key.to_string(), LocalizedString::Single(value)); },
+			Some(form) => {
+				let entry = table.entry(key.to_string()).or_insert(LocalizedString::Plural(Vec::new()));
+				if let LocalizedString::Single(_) = entry {
+					*entry = LocalizedString::Plural(Vec::new());
+				}
+				if let LocalizedString::Plural(forms) = entry {
+					if forms.len() <= form { forms.resize(form + 1, String::new()); }
+					forms[form] = value;
+				}
+			}
+		}
+	}
+
+	// The first language loaded becomes the current and fallback language until they are changed
+	fn added_language(&mut self, language: &str) {
+		if self.language.is_empty() { self.language = language.to_string(); }
+		if self.fallback_language.is_none() { self.fallback_language = Some(language.to_string()); }
+	}
+
+	/// Sorted list of every loaded language.
+	pub fn languages(&self) -> Vec<String> {
+		let mut languages: Vec<String> = self.tables.keys().cloned().collect();
+		languages.sort();
+		languages
+	}
+
+	/// Looks up a string and fills in its placeholders.
+	/// '{name}' is replaced by args["name"], use '{{' for a literal '{'. Unknown placeholders are left in the text.
+	/// When 'count' is set the plural form for it is picked using the rules of the language the string came from.
+	pub fn tr(&self, key: &str, args: &HashMap<String, String>, count: Option<f64>) -> String {
+		let mut found: Option<(&str, &LocalizedString)> = None;
+		for language in [Some(&self.language), self.fallback_language.as_ref()].into_iter().flatten() {
+			let entry = self.tables.get(language).and_then(|t| t.get(key));
+			if entry.is_some() {
+				found = Some((language.as_str(), entry.unwrap()));
+				break;
+			}
+		}
+
+		let text = match found {
+			None => key,
+			Some((_, LocalizedString::Single(text))) => text.as_str(),
+			Some((language, LocalizedString::Plural(forms))) => {
+				let form = plural_index(language, count.unwrap_or(1.0));
+				// Fall back to the last form if the table doesn't have enough, it's usually the most general one
+				forms.get(form).or(forms.last()).map(|f| f.as_str()).unwrap_or(key)
+			}
+		};
+
+		fill_placeholders(text, args)
+	}
+
+	/// Checks every language against the fallback language and the font it will be printed with.
+	/// Reports strings missing from a language, plural strings with too few forms, and characters that neither
+	/// the font or any of its fallbacks have a glyph for. Markup tags and placeholders are not checked.
+	pub fn validate(&self, font: &Font) -> Vec<LocalizationIssue> {
+		let mut issues: Vec<LocalizationIssue> = Vec::new();
+		let fallback = self.fallback_language.as_ref().and_then(|l| self.tables.get(l).map(|t| (l, t)));
+
+		for language in self.languages() {
+			let table = &self.tables[&language];
+
+			if fallback.is_some() {
+				let (fallback_language, fallback_table) = fallback.unwrap();
+				let mut missing: Vec<&String> = fallback_table.keys().filter(|k| !table.contains_key(*k)).collect();
+				missing.sort();
+				for key in missing {
+					issues.push(LocalizationIssue { language: language.clone(), key: key.clone(), message: format!("missing, '{}' will be used", fallback_language) });
+				}
+			}
+
+			let mut keys: Vec<&String> = table.keys().collect();
+			keys.sort();
+
+			for key in keys {
+				let texts: Vec<&String> = match &table[key] {
+					LocalizedString::Single(text) => vec![text],
+					LocalizedString::Plural(forms) => {
+						let needed = (0..200).map(|n| plural_index(&language, n as f64)).max().unwrap_or(0) + 1;
+						if forms.len() < needed {
+							issues.push(LocalizationIssue { language: language.clone(), key: key.clone(), message: format!("has {} plural forms, {} needs {}", forms.len(), language, needed) });
+						}
+						forms.iter().collect()
+					}
+				};
+
+				let mut missing_glyphs: Vec<char> = Vec::new();
+				for text in texts {
+					for (c, _) in parse_markup(&strip_placeholders(text)) {
+						if c.is_whitespace() || c.is_control() || missing_glyphs.contains(&c) { continue; }
+						if font.resolve(c).is_none() { missing_glyphs.push(c); }
+					}
+				}
+
+				if !missing_glyphs.is_empty() {
+					let glyphs: String = missing_glyphs.iter().collect();
+					issues.push(LocalizationIssue { language: language.clone(), key: key.clone(), message: format!("font has no glyphs for '{}'", glyphs) });
+				}
+			}
+		}
+
+		issues
+	}
+}
+
+/// Which plural form to use for 'count' in a language, following the CLDR/gettext rules for the most common languages.
+/// Languages are matched on their base code, so "pt_BR" and "pt-BR" use the "pt" rules.
+/// Unknown languages use the English rule of one singular and one plural form.
+pub fn plural_index(language: &str, count: f64) -> usize {
+	let base = language.split(|c| c == '_' || c == '-').next().unwrap_or("").to_lowercase();
+	let n = count.abs();
+	let i = n.floor() as u64;
+	let whole = n.fract() == 0.0;
+
+	match base.as_str() {
+		// One form for everything
+		"ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" | "tr" => 0,
+		// 0 and 1 are singular
+		"fr" | "pt" | "hi" | "fa" => if i <= 1 { 0 } else { 1 },
+		// one, few, many
+		"ru" | "uk" | "be" | "sr" | "hr" | "bs" => {
+			if !whole { 1 }
+			else if i % 10 == 1 && i % 100 != 11 { 0 }
+			else if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) { 1 }
+			else { 2 }
+		},
+		"pl" => {
+			if !whole { 1 }
+			else if i == 1 { 0 }
+			else if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) { 1 }
+			else { 2 }
+		},
+		"cs" | "sk" => {
+			if !whole { 1 }
+			else if i == 1 { 0 }
+			else if (2..=4).contains(&i) { 1 }
+			else { 2 }
+		},
+		// zero, one, two, few, many, other
+		"ar" => {
+			if !whole { 5 }
+			else if i == 0 { 0 }
+			else if i == 1 { 1 }
+			else if i == 2 { 2 }
+			else if (3..=10).contains(&(i % 100)) { 3 }
+			else if (11..=99).contains(&(i % 100)) { 4 }
+			else { 5 }
+		},
+		_ => if whole && i == 1 { 0 } else { 1 },
+	}
+}
+
+/// Replaces '{name}' with args["name"]. '{{' is a literal '{'.
+pub fn fill_placeholders(text: &str, args: &HashMap<String, String>) -> String {
+	if !text.contains('{') { return text.to_string(); }
+
+	let mut filled = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(open) = rest.find('{') {
+		filled.push_str(&rest[..open]);
+		rest = &rest[open..];
+
+		if rest.starts_with("{{") {
+			filled.push('{');
+			rest = &rest[2..];
+			continue;
+		}
+
+		match rest.find('}').and_then(|close| args.get(&rest[1..close]).map(|value| (close, value))) {
+			Some((close, value)) => {
+				filled.push_str(value);
+				rest = &rest[close + 1..];
+			},
+			None => {
+				filled.push('{');
+				rest = &rest[1..];
+			}
+		}
+	}
+	filled.push_str(rest);
+	filled
+}
+
+// Placeholders are filled in at runtime so their names don't need glyphs
+fn strip_placeholders(text: &str) -> String {
+	let mut stripped = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(open) = rest.find('{') {
+		stripped.push_str(&rest[..open]);
+		if rest[open..].starts_with("{{") {
+			stripped.push('{');
+			rest = &rest[open + 2..];
+			continue;
+		}
+		match rest[open..].find('}') {
+			Some(close) => { rest = &rest[open + close + 1..]; },
+			None => { rest = &rest[open..]; break; }
+		}
+	}
+	stripped.push_str(rest);
+	stripped
+}
+
+// 'apples[1]' -> ("apples", Some(1))
+fn split_plural_key(key: &str) -> (&str, Option<usize>) {
+	if key.ends_with(']') {
+		let open = key.rfind('[');
+		if open.is_some() {
+			let form = key[open.unwrap() + 1..key.len() - 1].parse::<usize>();
+			if form.is_ok() {
+				return (&key[..open.unwrap()], Some(form.unwrap()));
+			}
+		}
+	}
+	(key, None)
+}
+
+fn json_to_string(value: &serde_json::Value) -> String {
+	match value {
+		serde_json::Value::String(text) => text.clone(),
+		serde_json::Value::Null => String::new(),
+		other => other.to_string(),
+	}
+}
+
+// One gettext entry while a PO file is being read
+#[derive(Default)]
+struct PoEntry {
+	context: Option<String>,
+	id: Option<String>,
+	plural: bool,
+	forms: Vec<String>,
+	fuzzy: bool,
+}
+
+// Strips the quotes off a PO string and handles its C style escapes
+fn unquote_po(text: &str) -> Option<String> {
+	let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+
+	let mut unquoted = String::with_capacity(inner.len());
+	let mut chars = inner.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			unquoted.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => unquoted.push('\n'),
+			Some('t') => unquoted.push('\t'),
+			Some('r') => unquoted.push('\r'),
+			Some(other) => unquoted.push(other),
+			None => {}
+		}
+	}
+	Some(unquoted)
+}
+
+/// RFC 4180 style CSV. Quoted cells can contain commas, newlines and "" for a quote.
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+	let mut rows: Vec<Vec<String>> = Vec::new();
+	let mut row: Vec<String> = Vec::new();
+	let mut cell = String::new();
+	let mut quoted = false;
+
+	let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+	while let Some(c) = chars.next() {
+		if quoted {
+			if c == '"' {
+				if chars.peek() == Some(&'"') {
+					cell.push('"');
+					chars.next();
+				} else {
+					quoted = false;
+				}
+			} else {
+				cell.push(c);
+			}
+			continue;
+		}
+
+		match c {
+			'"' => { quoted = true; },
+			',' => { row.push(std::mem::take(&mut cell)); },
+			'\r' => {},
+			'\n' => {
+				row.push(std::mem::take(&mut cell));
+				rows.push(std::mem::take(&mut row));
+			},
+			_ => { cell.push(c); }
+		}
+	}
+
+	if !cell.is_empty() || !row.is_empty() {
+		row.push(cell);
+		rows.push(row);
+	}
+
+	// Blank lines come through as a single empty cell
+	rows.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+	rows
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+		pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+	}
+
+	#[test]
+	fn csv_quoted_cells() {
+		let rows = parse_csv("\u{feff}key,en\r\n\"a, b\",\"say \"\"hi\"\"\"\n\nline,\"two\nlines\"\nlast,no newline");
+		assert_eq!(rows, vec![
+			vec!["key", "en"],
+			vec!["a, b", "say \"hi\""],
+			vec!["line", "two\nlines"],
+			vec!["last", "no newline"],
+		]);
+	}
+
+	#[test]
+	fn csv_plurals_and_fallback() {
+		let mut loc = Localization::new();
+		let languages = loc.load_csv("key,en,de\nhello,Hello,Hallo\nonly_en,English,\napples[0],apple,Apfel\napples[1],apples,Äpfel\n").unwrap();
+		assert_eq!(languages, vec!["en", "de"]);
+		assert_eq!(loc.language, "en");
+
+		loc.language = "de".to_string();
+		assert_eq!(loc.tr("hello", &HashMap::new(), None), "Hallo");
+		assert_eq!(loc.tr("only_en", &HashMap::new(), None), "English");
+		assert_eq!(loc.tr("missing", &HashMap::new(), None), "missing");
+		assert_eq!(loc.tr("apples", &HashMap::new(), Some(1.0)), "Apfel");
+		assert_eq!(loc.tr("apples", &HashMap::new(), Some(3.0)), "Äpfel");
+	}
+
+	#[test]
+	fn po_entries() {
+		let po = r#"
+msgid ""
+msgstr ""
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+#, fuzzy
+msgid "old"
+msgstr "Alt"
+
+msgctxt "menu"
+msgid "start"
+msgstr "Start"
+msgctxt "game"
+msgid "start"
+msgstr "Los"
+
+msgid ""
+"long "
+"line"
+msgstr "lange "
+"Zeile \"zwei\"\n"
+
+msgid "untranslated"
+msgstr ""
+
+msgid "apple"
+msgid_plural "apples"
+msgstr[0] "Apfel"
+msgstr[1] "Äpfel"
+"#;
+		let mut loc = Localization::new();
+		loc.load_po(po, "de").unwrap();
+		let table = &loc.tables["de"];
+		assert!(!table.contains_key("") && !table.contains_key("old") && !table.contains_key("untranslated"));
+
+		assert_eq!(loc.tr("menu.start", &HashMap::new(), None), "Start");
+		assert_eq!(loc.tr("game.start", &HashMap::new(), None), "Los");
+		assert_eq!(loc.tr("long line", &HashMap::new(), None), "lange Zeile \"zwei\"\n");
+		assert_eq!(loc.tr("apple", &HashMap::new(), Some(1.0)), "Apfel");
+		assert_eq!(loc.tr("apple", &HashMap::new(), Some(2.0)), "Äpfel");
+	}
+
+	#[test]
+	fn po_bad_lines_are_errors() {
+		let mut loc = Localization::new();
+		assert!(loc.load_po("msgid \"a\"\nmsgstr \"b\"\n\n\"c\"\n", "de").is_err());
+		assert!(loc.load_po("msgid \"a\"\nmsgstr unquoted\n", "de").is_err());
+		assert!(loc.load_po("msgid \"a\"\nmsgtext \"b\"\n", "de").is_err());
+	}
+
+	#[test]
+	fn plural_rules() {
+		let forms = |language: &str, counts: &[f64]| -> Vec<usize> { counts.iter().map(|n| plural_index(language, *n)).collect() };
+		assert_eq!(forms("en", &[0.0, 1.0, 2.0, 1.5, -1.0]), vec![1, 0, 1, 1, 0]);
+		assert_eq!(forms("pt_BR", &[0.0, 1.0, 2.0]), vec![0, 0, 1]);
+		assert_eq!(forms("fr-CA", &[0.0, 1.0, 2.0]), vec![0, 0, 1]);
+		assert_eq!(forms("ja", &[0.0, 1.0, 5.0]), vec![0, 0, 0]);
+		assert_eq!(forms("ru", &[1.0, 21.0, 11.0, 3.0, 13.0, 5.0, 1.5]), vec![0, 0, 2, 1, 2, 2, 1]);
+		assert_eq!(forms("pl", &[1.0, 21.0, 22.0, 12.0, 5.0]), vec![0, 2, 1, 2, 2]);
+		assert_eq!(forms("cs", &[1.0, 3.0, 5.0, 0.5]), vec![0, 1, 2, 1]);
+		assert_eq!(forms("ar", &[0.0, 1.0, 2.0, 5.0, 11.0, 100.0, 0.5]), vec![0, 1, 2, 3, 4, 5, 5]);
+	}
+
+	#[test]
+	fn placeholders() {
+		let values = args(&[("name", "Ada"), ("n", "3")]);
+		assert_eq!(fill_placeholders("Hi {name}, {n} new", &values), "Hi Ada, 3 new");
+		assert_eq!(fill_placeholders("{{name} is {name}", &values), "{name} is Ada");
+		assert_eq!(fill_placeholders("{missing} and {name", &values), "{missing} and {name");
+		assert_eq!(fill_placeholders("no braces", &values), "no braces");
+		assert_eq!(strip_placeholders("{{a} {name}b"), "{a} b");
+	}
+}
@@ -3,17 +3,38 @@ use mlua::prelude::*;
 
 use crate::api_audio::*;
 use crate::api_color::*;
+use crate::api_curve::*;
 use crate::api_display::*;
 use crate::api_drawing::*;
+use crate::api_ecs::*;
 use crate::api_font::*;
 use crate::api_image::*;
 use crate::api_input::*;
+use crate::api_localization::*;
+use crate::api_lighting::*;
+use crate::api_math::*;
+use crate::api_particles::*;
+use crate::api_palette::*;
+use crate::api_postprocess::*;
+use crate::api_procedural::*;
 //use crate::api_physics::register_physics2d_api;
 use crate::api_profiling::*;
+use crate::api_scene::*;
+use crate::api_text::*;
+use crate::api_tween::*;
 
 use crate::api_shareables::*;
 
 use crate::controls::ControlData;
+use crate::font::FontCache;
+use crate::localization::Localization;
+use crate::palette::ScreenPalette;
+use crate::postprocess::PostProcess;
+use crate::lighting::Lighting;
+use crate::particles::ParticleSystem;
+use crate::tween::TweenManager;
+use crate::scene::SceneManager;
+use crate::entities::EntitySystem;
 use crate::VideoData;
 use crate::EngineVideoMode;
 
@@ -30,6 +51,13 @@ pub struct LuaScript {
 
     pub controls:       SharedControlData,
     pub rasterizer:     SharedRasterizer,
+    pub palette:        SharedPalette,
+    pub postprocess:    SharedPostProcess,
+    pub lighting:       SharedLighting,
+    pub particles:      SharedParticles,
+    pub tweens:         SharedTweens,
+    pub scenes:         SharedScenes,
+    pub entities:       SharedEntities,
     //pub physics:        SharedPhysics2D,
 
     // Handled in main but this makes it easier to get info
@@ -41,7 +69,11 @@ pub struct LuaScript {
 
     pub assets_sfx:     SharedAudioWav,
     pub assets_mus:     SharedAudioWavStream,
+    pub audio_buses:    SharedAudioBus,
+    pub audio_filters:  SharedAudioFilter,
     pub assets_img:     SharedImages,
+    pub font_cache:     SharedFontCache,
+    pub localization:   SharedLocalization,
 }
 
 impl LuaScript {
@@ -62,6 +94,13 @@ impl LuaScript {
         );
 
         let rasterizer: SharedRasterizer        = Rc::new(RefCell::new(PartitionedRasterizer::new(384, 216, 0)));
+        let palette:    SharedPalette           = Rc::new(RefCell::new(ScreenPalette::new()));
+        let postprocess: SharedPostProcess      = Rc::new(RefCell::new(PostProcess::new()));
+        let lighting:   SharedLighting          = Rc::new(RefCell::new(Lighting::new()));
+        let particles:  SharedParticles         = Rc::new(RefCell::new(ParticleSystem::new()));
+        let tweens:     SharedTweens            = Rc::new(RefCell::new(TweenManager::new()));
+        let scenes:     SharedScenes            = Rc::new(RefCell::new(SceneManager::new()));
+        let entities:   SharedEntities          = Rc::new(RefCell::new(EntitySystem::new()));
         let controls:   SharedControlData       = Rc::new(RefCell::new(ControlData::new()));
 
         //let physics:    SharedPhysics2D         = Rc::new(RefCell::new(RapierWorld2D::new(hz_update)));
@@ -69,20 +108,36 @@ impl LuaScript {
         let audio_handles: SharedAudioHandle    = Rc::new(DashMap::new());
         let assets_sfx: SharedAudioWav          = Rc::new(DashMap::new());
         let assets_mus: SharedAudioWavStream    = Rc::new(DashMap::new());
+        let audio_buses: SharedAudioBus         = Rc::new(DashMap::new());
+        let audio_filters: SharedAudioFilter    = Rc::new(DashMap::new());
 
         let assets_img: SharedImages            = Rc::new(DashMap::new());
+        let font_cache: SharedFontCache         = Rc::new(RefCell::new(FontCache::new()));
+        let localization: SharedLocalization    = Rc::new(RefCell::new(Localization::new()));
 
         let soloud: SharedAudio = Rc::new(soloud_result.unwrap());
 
-        register_audio_api(soloud.clone(), audio_handles.clone(), assets_sfx.clone(), assets_mus.clone(), &lua);
+        register_audio_api(soloud.clone(), audio_handles.clone(), assets_sfx.clone(), assets_mus.clone(), audio_buses.clone(), audio_filters.clone(), &lua);
         register_color(&lua);
+        register_math(&lua);
+        register_curve(&lua);
         register_display_api(rasterizer.clone(), video_data.clone(), &lua);
         register_draw_api(assets_img.clone(), rasterizer.clone(), &lua);
         register_input_api(controls.clone(), &lua);
-        register_image(assets_img.clone(),&lua);
+        register_image(assets_img.clone(), palette.clone(), &lua);
+        register_palette(assets_img.clone(), palette.clone(), rasterizer.clone(), &lua);
+        register_postprocess(assets_img.clone(), palette.clone(), postprocess.clone(), &lua);
+        register_procedural(assets_img.clone(), &lua);
+        register_lighting(lighting.clone(), rasterizer.clone(), &lua);
+        register_particles(particles.clone(), assets_img.clone(), rasterizer.clone(), &lua);
+        register_tween(tweens.clone(), &lua);
+        register_scene(scenes.clone(), &lua);
+        register_ecs(entities.clone(), assets_img.clone(), rasterizer.clone(), &lua);
         //register_physics2d_api(physics.clone(), &lua);
         register_profiling_api(&lua);
-        register_font(&lua);
+        register_font(font_cache.clone(), &lua);
+        register_text_api(rasterizer.clone(), &lua);
+        register_localization_api(localization.clone(), &lua);
 
         
         // Exec is recommended so variables written outside callback functions are readied
@@ -91,7 +146,7 @@ impl LuaScript {
             let e = test_file.err().unwrap();
             Err(format!("Lua: file failed to load! Error: {}", e))
         } else {
-            Ok(LuaScript {hz_update, hz_draw, video_data, lua, controls, rasterizer, audio: soloud, audio_handles, assets_sfx, assets_mus, assets_img})
+            Ok(LuaScript {hz_update, hz_draw, video_data, lua, controls, rasterizer, palette, postprocess, lighting, particles, tweens, scenes, entities, audio: soloud, audio_handles, assets_sfx, assets_mus, audio_buses, audio_filters, assets_img, font_cache, localization})
         }
     }
 
@@ -113,10 +168,34 @@ impl LuaScript {
 
     pub fn update(&mut self, dt: f64) -> Result<(), LuaError> {
         //self.physics.borrow_mut().step();
-        self.lua.globals().call_function("_update".to_lua(&self.lua).unwrap(), dt.to_lua_multi(&self.lua).unwrap())
+        self.palette.borrow_mut().update(dt);
+        self.particles.borrow_mut().update(dt);
+        update_tweens(&self.tweens, &self.lua, &self.rasterizer, dt)?;
+        update_ecs(&self.entities, &self.lua, dt)?;
+        if self.needs_callback("_update") {
+            self.lua.globals().call_function::<_, _, ()>("_update".to_lua(&self.lua).unwrap(), dt.to_lua_multi(&self.lua).unwrap())?;
+        }
+        update_scenes(&self.scenes, &self.lua, dt)
     }
 
     pub fn draw(&mut self) -> Result<(), LuaError>{
-        self.lua.globals().call_function("_draw".to_lua(&self.lua).unwrap(), ())
+        // Every frame starts without a transform, so a missing pop_transform can't build up over frames
+        self.rasterizer.borrow_mut().reset_transform();
+
+        // Scenes are drawn first, so _draw can draw over every scene
+        let mut result = draw_scenes(&self.scenes, &self.lua, &self.rasterizer);
+        if result.is_ok() && self.needs_callback("_draw") {
+            self.rasterizer.borrow_mut().reset_transform();
+            result = self.lua.globals().call_function("_draw".to_lua(&self.lua).unwrap(), ());
+        }
+
+        // Whatever _draw recorded has to be on the screen before it's presented
+        self.rasterizer.borrow_mut().flush();
+        result
+    }
+
+    // _update and _draw can be left out once there are scenes to update and draw instead
+    fn needs_callback(&self, name: &str) -> bool {
+        self.scenes.borrow().is_empty() || !matches!(self.lua.globals().get::<_, LuaValue>(name), Ok(LuaValue::Nil))
     }
 }
\ No newline at end of file
@@ -3,16 +3,23 @@ mod controls;
 //mod rhai;
 mod lua;
 
-// Math
-mod math;
-mod vector2;
-mod matrix3;
+// Math and Software Rendering, see lib.rs
+use aftershock_framework::{vector2, matrix3, color, font, text, shapes, curves, palette, postprocess, noise, expression, procedural, lighting, particles, easing, transition, ecs, rasterizer, partitioned_rasterizer};
 
-// Software Rendering
-mod color;
-mod font;
-mod rasterizer;
-mod partitioned_rasterizer;
+// Audio
+mod audio_filter;
+
+// Localization
+mod localization;
+
+// Animation
+mod tween;
+
+// Scenes
+mod scene;
+
+// Entities
+mod entities;
 
 // Physics
 //mod rapier2d_wrap;
@@ -21,13 +28,25 @@ mod partitioned_rasterizer;
 mod api_shareables;
 mod api_audio;
 mod api_color;
+mod api_curve;
 mod api_display;
 mod api_drawing;
+mod api_ecs;
 mod api_font;
 mod api_image;
 mod api_input;
+mod api_localization;
+mod api_lighting;
+mod api_math;
+mod api_particles;
+mod api_palette;
+mod api_postprocess;
+mod api_procedural;
 mod api_physics;
 mod api_profiling;
+mod api_scene;
+mod api_text;
+mod api_tween;
 
 mod error_data;
 
@@ -357,8 +376,17 @@ pub fn main() {
                     lua_error = Some(format!("Runtime Error: Lua: {}", draw_error.err().unwrap()));
                 }
 
-                // Present to screen
-                let _ = screentex.update(None, &engine.lua_global.rasterizer.borrow().rasterizer.color, (engine.lua_global.rasterizer.borrow().rasterizer.width * 4) as usize);
+                // Present to screen, in palette mode the indices are turned into colors first and then post-processing runs on them
+                {
+                    let rst = engine.lua_global.rasterizer.borrow();
+                    let mut palette = engine.lua_global.palette.borrow_mut();
+                    let mut postprocess = engine.lua_global.postprocess.borrow_mut();
+                    let mut pixels: &[u8] = if palette.enabled { palette.resolve(&rst.rasterizer.color) } else { &rst.rasterizer.color };
+                    if postprocess.is_active() {
+                        pixels = postprocess.apply(pixels, rst.rasterizer.width, rst.rasterizer.height);
+                    }
+                    let _ = screentex.update(None, pixels, (rst.rasterizer.width * 4) as usize);
+                }
                 let _ = canvas.copy(&screentex, None, None);
                 canvas.present();
 
@@ -381,14 +409,7 @@ pub fn main() {
         //let error_text_img: Rasterizer = error_data::raster_text_to_image(512, 512, error_text);
         let tiny_font_img = error_data::get_tiny_font();
 
-        let tiny_font: Font = Font {
-            fontimg: tiny_font_img,
-            glyph_height: 10,
-            glyph_width: 10,
-            glyph_spacing: 0,
-            glyphidx_sizes: Vec::new(),
-            glyphidx: "ABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890!?/\\@#$%^&*()[]_-+=\"';:.".to_string().chars().collect()
-        };
+        let tiny_font: Font = Font::new_from_rasterizer(tiny_font_img, "ABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890!?/\\@#$%^&*()[]_-+=\"';:.", 10, 10, 0);
 
         let mut error_rast: Rasterizer = Rasterizer::new(512, 512);
         
@@ -38,6 +38,11 @@ pub fn cross2(x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
 	(x0 * y1) - (y0 * x1)
 }
 
+/// Returns twice the signed area of the triangle a, b, p. It's positive on one side of the edge a to b and negative on the other.
+pub fn edge2(ax: f64, ay: f64, bx: f64, by: f64, px: f64, py: f64) -> f64 {
+	cross2(bx - ax, by - ay, px - ax, py - ay)
+}
+
 pub fn barycentric2(v1x: f64, v1y: f64, v2x: f64, v2y: f64, v3x: f64, v3y: f64) -> (f64, f64, f64) {
 	let b0 = (v2x - v1x, v2y - v1y);
 	let b1 = (v3x - v1x, v3y - v1y);
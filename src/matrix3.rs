@@ -1,7 +1,9 @@
 use crate::vector2::*;
 
+use mlua::prelude::*;
+
 /// 3x3 Matrix mainly for transforming 2D images, but can be used for anything.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Matrix3 {
 	m: [[f64; 3]; 3],
 }
@@ -60,6 +62,34 @@ impl Matrix3 {
 		return nmtx;
 	}
 
+	/// Returns true if the Matrix only moves things, without rotating, scaling or shearing them.
+	pub fn is_translation(&self) -> bool {
+		self.m[0][0] == 1.0 && self.m[1][1] == 1.0 && self.m[0][1] == 0.0 && self.m[1][0] == 0.0
+	}
+
+	/// Returns true if the Matrix keeps horizontal and vertical lines horizontal and vertical, so it can only move, scale or flip.
+	pub fn is_axis_aligned(&self) -> bool {
+		self.m[0][1] == 0.0 && self.m[1][0] == 0.0
+	}
+
+	/// Returns the scale if the Matrix scales evenly in every direction, no matter how it is rotated.
+	/// Returns None if it stretches or shears, because circles would stop being circles.
+	pub fn uniform_scale(&self) -> Option<f64> {
+		let scale_x = (self.m[0][0] * self.m[0][0] + self.m[0][1] * self.m[0][1]).sqrt();
+		let scale_y = (self.m[1][0] * self.m[1][0] + self.m[1][1] * self.m[1][1]).sqrt();
+		let skew = self.m[0][0] * self.m[1][0] + self.m[0][1] * self.m[1][1];
+
+		let epsilon = 1e-9 * f64::max(scale_x, 1.0);
+		if (scale_x - scale_y).abs() <= epsilon && skew.abs() <= epsilon * scale_x { Some(scale_x) } else { None }
+	}
+
+	/// Returns the largest amount the Matrix scales along either of its axes.
+	pub fn max_scale(&self) -> f64 {
+		let scale_x = (self.m[0][0] * self.m[0][0] + self.m[0][1] * self.m[0][1]).sqrt();
+		let scale_y = (self.m[1][0] * self.m[1][0] + self.m[1][1] * self.m[1][1]).sqrt();
+		f64::max(scale_x, scale_y)
+	}
+
 	/// Creates an inverse of this Matrix, usually for getting correct pixel information when drawing 2D Images.
 	pub fn inv(&self) -> Matrix3 {
 		let mut out = Matrix3::identity();
@@ -84,6 +114,36 @@ impl Matrix3 {
 }
 
 
+// mat3 in Lua, multiplying by another mat3 combines them and multiplying by a vec2 transforms it
+impl LuaUserData for Matrix3 {
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		methods.add_method("forward", |_, m, v: Vector2| Ok(m.forward(v)));
+		methods.add_method("inverse", |_, m, ()| Ok(m.inv()));
+		methods.add_method("is_translation", |_, m, ()| Ok(m.is_translation()));
+		methods.add_method("is_axis_aligned", |_, m, ()| Ok(m.is_axis_aligned()));
+		methods.add_method("uniform_scale", |_, m, ()| Ok(m.uniform_scale()));
+		methods.add_method("max_scale", |_, m, ()| Ok(m.max_scale()));
+
+		// Same order as translate, rotate, scale and shear on the transform stack, the new one happens first
+		methods.add_method("translated", |_, m, (x, y): (f64, f64)| Ok(*m * Matrix3::translated(Vector2::new(x, y))));
+		methods.add_method("rotated", |_, m, radians: f64| Ok(*m * Matrix3::rotated(radians)));
+		methods.add_method("scaled", |_, m, (x, y): (f64, Option<f64>)| Ok(*m * Matrix3::scaled(Vector2::new(x, y.unwrap_or(x)))));
+		methods.add_method("sheared", |_, m, (x, y): (f64, f64)| Ok(*m * Matrix3::sheared(Vector2::new(x, y))));
+
+		methods.add_meta_function(LuaMetaMethod::Mul, |lua, (m, other): (Matrix3, LuaAnyUserData)| {
+			if let Ok(v) = other.borrow::<Vector2>() {
+				m.forward(*v).to_lua(lua)
+			} else {
+				(m * *other.borrow::<Matrix3>()?).to_lua(lua)
+			}
+		});
+		methods.add_meta_function(LuaMetaMethod::Eq, |_, (a, b): (Matrix3, Matrix3)| Ok(a == b));
+		methods.add_meta_method(LuaMetaMethod::ToString, |_, m, ()| {
+			Ok(format!("mat3({}, {}, {}, {}, {}, {})", m.m[0][0], m.m[1][0], m.m[2][0], m.m[0][1], m.m[1][1], m.m[2][1]))
+		});
+	}
+}
+
 // Matrix3 Operator Assignments
 impl std::ops::Mul for Matrix3 {
 	type Output = Self;
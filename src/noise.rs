@@ -0,0 +1,157 @@
+// Coherent noise for procedural textures. Every function is seeded, repeats exactly for the same inputs and returns 0.0 to 1.0.
+
+/// Which noise fbm and the noise generator use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseKind {
+	/// Random values at every whole coordinate, smoothly blended. Blocky, but the cheapest.
+	Value,
+	/// Random slopes at every whole coordinate, smoother than value noise.
+	Perlin,
+	/// Like Perlin on a triangle grid, without Perlin's square looking artifacts.
+	Simplex,
+	/// Distance to the closest of a scattered set of points, cells like stones or scales.
+	Worley,
+}
+
+impl NoiseKind {
+	pub fn from_str(name: &str) -> Option<NoiseKind> {
+		match name {
+			"value" => Some(NoiseKind::Value),
+			"perlin" => Some(NoiseKind::Perlin),
+			"simplex" => Some(NoiseKind::Simplex),
+			"worley" => Some(NoiseKind::Worley),
+			_ => None,
+		}
+	}
+
+	pub fn sample(&self, x: f64, y: f64, seed: u32) -> f64 {
+		match self {
+			NoiseKind::Value => value(x, y, seed),
+			NoiseKind::Perlin => perlin(x, y, seed),
+			NoiseKind::Simplex => simplex(x, y, seed),
+			NoiseKind::Worley => worley(x, y, seed),
+		}
+	}
+}
+
+// Mixes a grid position and a seed into a random looking number
+#[inline]
+fn hash(x: i64, y: i64, seed: u32) -> u32 {
+	let mut h = (x as u32).wrapping_mul(0x27d4eb2d) ^ (y as u32).wrapping_mul(0x165667b1) ^ seed.wrapping_mul(0x9e3779b9);
+	h ^= h >> 15;
+	h = h.wrapping_mul(0x85ebca6b);
+	h ^= h >> 13;
+	h = h.wrapping_mul(0xc2b2ae35);
+	h ^ (h >> 16)
+}
+
+#[inline]
+fn hash_unit(x: i64, y: i64, seed: u32) -> f64 {
+	hash(x, y, seed) as f64 / u32::MAX as f64
+}
+
+// Eases 0.0 to 1.0 with no jump in slope or curve at either end
+#[inline]
+fn fade(t: f64) -> f64 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+	a + (b - a) * t
+}
+
+// One of eight directions picked by the hash, dotted with the offset from its grid point
+#[inline]
+fn gradient_dot(h: u32, x: f64, y: f64) -> f64 {
+	match h & 7 {
+		0 => x + y,
+		1 => -x + y,
+		2 => x - y,
+		3 => -x - y,
+		4 => x * std::f64::consts::SQRT_2,
+		5 => -x * std::f64::consts::SQRT_2,
+		6 => y * std::f64::consts::SQRT_2,
+		_ => -y * std::f64::consts::SQRT_2,
+	}
+}
+
+pub fn value(x: f64, y: f64, seed: u32) -> f64 {
+	let (x0, y0) = (f64::floor(x), f64::floor(y));
+	let (tx, ty) = (fade(x - x0), fade(y - y0));
+	let (ix, iy) = (x0 as i64, y0 as i64);
+
+	let top = lerp(hash_unit(ix, iy, seed), hash_unit(ix + 1, iy, seed), tx);
+	let bottom = lerp(hash_unit(ix, iy + 1, seed), hash_unit(ix + 1, iy + 1, seed), tx);
+	lerp(top, bottom, ty)
+}
+
+pub fn perlin(x: f64, y: f64, seed: u32) -> f64 {
+	let (x0, y0) = (f64::floor(x), f64::floor(y));
+	let (fx, fy) = (x - x0, y - y0);
+	let (ix, iy) = (x0 as i64, y0 as i64);
+
+	let top = lerp(gradient_dot(hash(ix, iy, seed), fx, fy), gradient_dot(hash(ix + 1, iy, seed), fx - 1.0, fy), fade(fx));
+	let bottom = lerp(gradient_dot(hash(ix, iy + 1, seed), fx, fy - 1.0), gradient_dot(hash(ix + 1, iy + 1, seed), fx - 1.0, fy - 1.0), fade(fx));
+
+	// Gradients are at most sqrt(2) long, so the result stays inside -1.0 to 1.0
+	f64::clamp(lerp(top, bottom, fade(fy)) * 0.5 + 0.5, 0.0, 1.0)
+}
+
+pub fn simplex(x: f64, y: f64, seed: u32) -> f64 {
+	// Skews the square grid into triangles and back
+	let skew = 0.5 * (f64::sqrt(3.0) - 1.0);
+	let unskew = (3.0 - f64::sqrt(3.0)) / 6.0;
+
+	let s = (x + y) * skew;
+	let (i, j) = (f64::floor(x + s), f64::floor(y + s));
+	let t = (i + j) * unskew;
+	let (x0, y0) = (x - (i - t), y - (j - t));
+
+	// Which of the two triangles in the square the point is in
+	let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+	let (x1, y1) = (x0 - i1 as f64 + unskew, y0 - j1 as f64 + unskew);
+	let (x2, y2) = (x0 - 1.0 + 2.0 * unskew, y0 - 1.0 + 2.0 * unskew);
+
+	let (i, j) = (i as i64, j as i64);
+	let corner = |h: u32, x: f64, y: f64| {
+		let t = 0.5 - x * x - y * y;
+		if t < 0.0 { 0.0 } else { t * t * t * t * gradient_dot(h, x, y) }
+	};
+
+	let n = corner(hash(i, j, seed), x0, y0)
+		+ corner(hash(i + i1, j + j1, seed), x1, y1)
+		+ corner(hash(i + 1, j + 1, seed), x2, y2);
+
+	// Scaled so the result fills 0.0 to 1.0
+	f64::clamp(n * 35.0 + 0.5, 0.0, 1.0)
+}
+
+pub fn worley(x: f64, y: f64, seed: u32) -> f64 {
+	let (ix, iy) = (f64::floor(x) as i64, f64::floor(y) as i64);
+
+	// Every cell has one point in it, the closest one is always in this cell or one next to it
+	let mut closest = f64::MAX;
+	for cy in (iy - 1)..=(iy + 1) {
+		for cx in (ix - 1)..=(ix + 1) {
+			let h = hash(cx, cy, seed);
+			let px = cx as f64 + (h & 0xffff) as f64 / 65535.0;
+			let py = cy as f64 + (h >> 16) as f64 / 65535.0;
+			let (dx, dy) = (px - x, py - y);
+			closest = f64::min(closest, dx * dx + dy * dy);
+		}
+	}
+	f64::min(f64::sqrt(closest), 1.0)
+}
+
+/// Fractal noise, octaves of noise each twice as detailed and half as strong as the last.
+pub fn fbm(kind: NoiseKind, x: f64, y: f64, octaves: u32, seed: u32) -> f64 {
+	let (mut total, mut amplitude, mut frequency, mut weight) = (0.0, 1.0, 1.0, 0.0);
+	for octave in 0..u32::max(octaves, 1) {
+		total += kind.sample(x * frequency, y * frequency, seed.wrapping_add(octave)) * amplitude;
+		weight += amplitude;
+		amplitude *= 0.5;
+		frequency *= 2.0;
+	}
+	total / weight
+}
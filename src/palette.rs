@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::color::*;
+use crate::rasterizer::Rasterizer;
+
+// Palettes never have more colors than an index can point to
+pub const PALETTE_SIZE: usize = 256;
+
+// Palette mode keeps its indices in the same RGBA buffers as everything else, with the index in every channel.
+// That's four times the memory an 8-bit buffer would need, but every drawing function, the command buffer, images
+// and pget work on indices unchanged, where a real 8-bit framebuffer would need its own copy of all of them.
+// Only DrawMode::Palette writes whole indices, blending would mix two indices into some other index,
+// so PartitionedRasterizer holds the draw mode on it while palette mode is on.
+
+/// Up to 256 colors that palette mode draws with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+	pub colors: Vec<Color>,
+}
+
+impl Palette {
+	/// Makes a palette from a list of colors, anything past 256 colors is dropped.
+	pub fn new(colors: &[Color]) -> Palette {
+		Palette { colors: colors.iter().take(PALETTE_SIZE).map(|c| Color::new(c.r, c.g, c.b, 255)).collect() }
+	}
+
+	/// The 16 colors of the PICO-8, which palette mode starts with.
+	pub fn pico8() -> Palette {
+		let hex = [
+			0x000000, 0x1d2b53, 0x7e2553, 0x008751, 0xab5236, 0x5f574f, 0xc2c3c7, 0xfff1e8,
+			0xff004d, 0xffa300, 0xffec27, 0x00e436, 0x29adff, 0x83769c, 0xff77a8, 0xffccaa,
+		];
+		Palette { colors: hex.iter().map(|h: &u32| Color::new((h >> 16) as u8, (h >> 8) as u8, *h as u8, 255)).collect() }
+	}
+
+	/// Loads a palette from a .hex (one rrggbb per line, like Lospec), .gpl (GIMP) or .pal (JASC, or raw RGB bytes) file.
+	pub fn load(path_to: &str) -> Result<Palette, String> {
+		let bytes = match std::fs::read(path_to) {
+			Ok(bytes) => bytes,
+			Err(reason) => {
+				println!("ERROR - PALETTE: Could not load {} | {}", path_to, reason);
+				return Err(format!("ERROR - PALETTE: Could not load {} | {}", path_to, reason));
+			}
+		};
+
+		let extension = path_to.rsplit('.').next().unwrap_or("").to_lowercase();
+		let text = String::from_utf8_lossy(&bytes);
+		let palette = match extension.as_str() {
+			"hex" => Palette::parse_hex(&text),
+			"gpl" => Palette::parse_gpl(&text),
+			"pal" => {
+				if text.starts_with("JASC-PAL") { Palette::parse_jasc(&text) } else { Palette::parse_raw(&bytes) }
+			},
+			_ => Err(format!("unknown palette format .{}, use .hex, .gpl or .pal", extension)),
+		};
+
+		match palette {
+			Ok(palette) if palette.colors.is_empty() => {
+				println!("ERROR - PALETTE: {} has no colors", path_to);
+				Err(format!("ERROR - PALETTE: {} has no colors", path_to))
+			},
+			Ok(palette) => Ok(palette),
+			Err(reason) => {
+				println!("ERROR - PALETTE: Could not load {} | {}", path_to, reason);
+				Err(format!("ERROR - PALETTE: Could not load {} | {}", path_to, reason))
+			}
+		}
+	}
+
+	/// One hex color per line, with or without a #. Blank lines and lines starting with ; are skipped.
+	pub fn parse_hex(text: &str) -> Result<Palette, String> {
+		let mut colors = Vec::new();
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with(';') { continue; }
+			match Color::from_hex(line) {
+				Some(color) => colors.push(color),
+				None => return Err(format!("line {} \"{}\" is not a hex color", number + 1, line)),
+			}
+		}
+		Ok(Palette::new(&colors))
+	}
+
+	/// GIMP palettes, a "GIMP Palette" header and then "r g b name" lines.
+	pub fn parse_gpl(text: &str) -> Result<Palette, String> {
+		let mut lines = text.lines();
+		if lines.next().map(|l| l.trim()) != Some("GIMP Palette") {
+			return Err("missing the \"GIMP Palette\" header".to_string());
+		}
+
+		let mut colors = Vec::new();
+		for line in lines {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') || line.contains(':') { continue; }
+			colors.push(parse_rgb_line(line)?);
+		}
+		Ok(Palette::new(&colors))
+	}
+
+	/// JASC palettes (Paint Shop Pro, Aseprite), a header, a version, a color count and then "r g b" lines.
+	pub fn parse_jasc(text: &str) -> Result<Palette, String> {
+		let lines: Vec<&str> = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+		if lines.len() < 3 { return Err("JASC palette is missing its header".to_string()); }
+
+		let count: usize = lines[2].parse().map_err(|_| format!("\"{}\" is not a color count", lines[2]))?;
+		let colors = lines[3..].iter().take(count).map(|line| parse_rgb_line(line)).collect::<Result<Vec<Color>, String>>()?;
+		Ok(Palette::new(&colors))
+	}
+
+	/// Plain RGB bytes, three for each color.
+	pub fn parse_raw(bytes: &[u8]) -> Result<Palette, String> {
+		if !bytes.len().is_multiple_of(3) { return Err(format!("{} bytes is not a list of RGB colors", bytes.len())); }
+		let colors: Vec<Color> = bytes.chunks_exact(3).map(|c| Color::new(c[0], c[1], c[2], 255)).collect();
+		Ok(Palette::new(&colors))
+	}
+
+	/// The color at an index, black if the palette doesn't go that far.
+	pub fn get(&self, index: u8) -> Color {
+		self.colors.get(index as usize).copied().unwrap_or(Color::black())
+	}
+
+	/// The index of the closest color in the palette.
+	/// Green counts most and blue least, roughly how strongly we see them.
+	pub fn nearest(&self, color: Color) -> u8 {
+		let distance = |c: &Color| {
+			let (dr, dg, db) = (c.r as i32 - color.r as i32, c.g as i32 - color.g as i32, c.b as i32 - color.b as i32);
+			2 * dr * dr + 4 * dg * dg + 3 * db * db
+		};
+		self.colors.iter().enumerate().min_by_key(|(_, c)| distance(c)).map(|(i, _)| i as u8).unwrap_or(0)
+	}
+
+	/// Turns an RGBA image into palette indices, each pixel becoming the nearest palette color.
+	/// Pixels less than half opaque become clear so they are skipped when drawn.
+	pub fn quantize(&self, image: &mut Rasterizer) {
+		let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+		for c in image.color.chunks_exact_mut(4) {
+			if c[3] < 128 {
+				c.copy_from_slice(&[0, 0, 0, 0]);
+				continue;
+			}
+			let index = *cache.entry([c[0], c[1], c[2]]).or_insert_with(|| self.nearest(Color::new(c[0], c[1], c[2], 255)));
+			c.copy_from_slice(&[index, index, index, 255]);
+		}
+	}
+}
+
+// "r g b" with anything after the third number ignored, like the names in GIMP palettes
+fn parse_rgb_line(line: &str) -> Result<Color, String> {
+	let channels: Vec<u8> = line.split_whitespace().take(3).map(|n| n.parse::<u8>()).collect::<Result<Vec<u8>, _>>()
+		.map_err(|_| format!("\"{}\" is not an r g b color", line))?;
+	if channels.len() < 3 { return Err(format!("\"{}\" is not an r g b color", line)); }
+	Ok(Color::new(channels[0], channels[1], channels[2], 255))
+}
+
+/// Returns the color palette mode draws for an index. The index is kept in every channel of the RGBA buffer so pget still reads it back.
+pub fn index_color(index: u8) -> Color {
+	Color::new(index, index, index, 255)
+}
+
+/// Draw time palette changes, pal(src, dst) and palt(index, true).
+/// Every index drawn is swapped through the map, unless it's transparent, and then it isn't drawn at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteRemap {
+	pub map: [u8; PALETTE_SIZE],
+	// One bit per index
+	transparent: [u64; 4],
+}
+
+impl PaletteRemap {
+	/// Every index draws as itself and none of them are transparent.
+	pub fn identity() -> PaletteRemap {
+		let mut map = [0; PALETTE_SIZE];
+		for (i, index) in map.iter_mut().enumerate() { *index = i as u8; }
+		PaletteRemap { map, transparent: [0; 4] }
+	}
+
+	/// Draws src as dst from now on.
+	pub fn set(&mut self, src: u8, dst: u8) {
+		self.map[src as usize] = dst;
+	}
+
+	/// Puts every index back to drawing as itself, transparency is left alone.
+	pub fn reset_map(&mut self) {
+		self.map = PaletteRemap::identity().map;
+	}
+
+	pub fn set_transparent(&mut self, index: u8, transparent: bool) {
+		let (word, bit) = (index as usize / 64, index as usize % 64);
+		if transparent { self.transparent[word] |= 1 << bit; } else { self.transparent[word] &= !(1 << bit); }
+	}
+
+	pub fn reset_transparent(&mut self) {
+		self.transparent = [0; 4];
+	}
+
+	pub fn is_transparent(&self, index: u8) -> bool {
+		self.transparent[index as usize / 64] & (1 << (index as usize % 64)) != 0
+	}
+
+	/// The index to draw for a source index, None if it's transparent.
+	#[inline]
+	pub fn apply(&self, index: u8) -> Option<u8> {
+		if self.is_transparent(index) { return None; }
+		Some(self.map[index as usize])
+	}
+}
+
+/// Rotates a range of palette entries over time, for waterfalls and glowing lava without redrawing anything.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteCycle {
+	pub start: u8,
+	pub end: u8,
+	/// Steps per second, negative cycles backwards.
+	pub speed: f64,
+	position: f64,
+}
+
+impl PaletteCycle {
+	pub fn new(start: u8, end: u8, speed: f64) -> PaletteCycle {
+		PaletteCycle { start: u8::min(start, end), end: u8::max(start, end), speed, position: 0.0 }
+	}
+
+	// Where an index inside the range has moved to
+	fn shift(&self, index: u8) -> u8 {
+		if index < self.start || index > self.end { return index; }
+		let length = (self.end - self.start) as i64 + 1;
+		let step = f64::floor(self.position) as i64;
+		(self.start as i64 + (index as i64 - self.start as i64 + step).rem_euclid(length)) as u8
+	}
+}
+
+/// How the indices on screen turn into colors when presented.
+/// Swapping entries here changes everything already drawn, which is how fades and flashes are done.
+pub struct ScreenPalette {
+	pub enabled: bool,
+	pub palette: Palette,
+	/// The palette entry shown for each index on screen.
+	pub map: [u8; PALETTE_SIZE],
+	pub cycles: Vec<PaletteCycle>,
+
+	// RGBA for the window, reused every frame
+	output: Vec<u8>,
+}
+
+impl ScreenPalette {
+	pub fn new() -> ScreenPalette {
+		ScreenPalette {
+			enabled: false,
+			palette: Palette::pico8(),
+			map: PaletteRemap::identity().map,
+			cycles: Vec::new(),
+			output: Vec::new(),
+		}
+	}
+
+	/// Shows index as the palette entry at dst, for everything on screen.
+	pub fn swap(&mut self, index: u8, dst: u8) {
+		self.map[index as usize] = dst;
+	}
+
+	/// Undoes every swap, cycles keep running.
+	pub fn reset(&mut self) {
+		self.map = PaletteRemap::identity().map;
+	}
+
+	pub fn add_cycle(&mut self, start: u8, end: u8, speed: f64) {
+		self.cycles.push(PaletteCycle::new(start, end, speed));
+	}
+
+	pub fn clear_cycles(&mut self) {
+		self.cycles.clear();
+	}
+
+	/// Moves the cycles along.
+	pub fn update(&mut self, dt: f64) {
+		for cycle in self.cycles.iter_mut() {
+			let length = (cycle.end - cycle.start) as f64 + 1.0;
+			cycle.position = (cycle.position + cycle.speed * dt).rem_euclid(length);
+		}
+	}
+
+	/// The color shown for every index right now, after the swaps and cycles.
+	pub fn lookup(&self) -> [Color; PALETTE_SIZE] {
+		let mut lookup = [Color::black(); PALETTE_SIZE];
+		for (i, color) in lookup.iter_mut().enumerate() {
+			let index = self.cycles.iter().fold(i as u8, |index, cycle| cycle.shift(index));
+			*color = self.palette.get(self.map[index as usize]);
+		}
+		lookup
+	}
+
+	/// Turns a framebuffer of indices into the RGBA the window shows.
+	pub fn resolve(&mut self, indices: &[u8]) -> &[u8] {
+		let lookup = self.lookup();
+		self.output.resize(indices.len(), 0);
+		self.output.par_chunks_mut(4096).zip(indices.par_chunks(4096)).for_each(|(out, src)| {
+			for (o, s) in out.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+				let c = lookup[s[0] as usize];
+				o.copy_from_slice(&[c.r, c.g, c.b, 255]);
+			}
+		});
+		&self.output
+	}
+}
+
+impl Default for ScreenPalette {
+	fn default() -> Self {
+		ScreenPalette::new()
+	}
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::color::Color;
+use crate::partitioned_rasterizer::PartitionedRasterizer;
+use crate::rasterizer::Rasterizer;
+use crate::vector2::Vector2;
+
+// Emitters with more particles than this update them in parallel
+const PARALLEL_PARTICLES: usize = 2048;
+
+// Colors over the lifetime are blended ahead of time into this many steps, instead of for every particle
+const COLOR_STEPS: usize = 256;
+
+/// What every particle of an emitter is drawn as.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParticleShape {
+	/// A single pixel, size is ignored.
+	Pixel,
+	/// A filled circle, size is its diameter.
+	Circle,
+	/// A filled square, size is its width.
+	Rectangle,
+	/// An image by name, centered, turned by the particle's rotation and scaled by its size.
+	Sprite(String),
+}
+
+/// Values spread evenly over a particle's life, blended between. One value stays the same the whole time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ramp {
+	pub values: Vec<f64>,
+}
+
+impl Ramp {
+	pub fn new(values: Vec<f64>) -> Ramp {
+		Ramp { values }
+	}
+
+	pub fn constant(value: f64) -> Ramp {
+		Ramp { values: vec![value] }
+	}
+
+	pub fn sample(&self, t: f64) -> f64 {
+		match self.values.len() {
+			0 => 0.0,
+			1 => self.values[0],
+			count => {
+				let position = f64::clamp(t, 0.0, 1.0) * (count - 1) as f64;
+				let idx = usize::min(position as usize, count - 2);
+				let fraction = position - idx as f64;
+				self.values[idx] + (self.values[idx + 1] - self.values[idx]) * fraction
+			},
+		}
+	}
+}
+
+/// How an emitter's particles are made, move and look. Ranges are (min, max) and picked from at random for every particle.
+#[derive(Debug, Clone)]
+pub struct ParticleDefinition {
+	/// Particles made every second while the emitter is running.
+	pub rate: f64,
+	/// Seconds each particle lives.
+	pub lifetime: (f64, f64),
+	/// Pixels per second each particle starts with.
+	pub speed: (f64, f64),
+	/// The direction each particle starts moving in, in radians.
+	pub angle: (f64, f64),
+	/// Particles start anywhere inside this far from the emitter.
+	pub radius: f64,
+	/// Pixels per second added to every particle's velocity each second.
+	pub gravity: Vector2,
+	/// How fast particles slow down, the part of their speed they lose every second.
+	pub drag: f64,
+	/// Radians per second each particle turns, only sprites show it.
+	pub spin: (f64, f64),
+	pub colors: Vec<Color>,
+	pub sizes: Ramp,
+	/// 0.0 to 1.0, multiplied with the color's alpha.
+	pub opacities: Ramp,
+	pub shape: ParticleShape,
+	/// Particles past this are not made until others die.
+	pub max_particles: usize,
+}
+
+impl Default for ParticleDefinition {
+	fn default() -> ParticleDefinition {
+		ParticleDefinition {
+			rate: 20.0,
+			lifetime: (1.0, 1.0),
+			speed: (20.0, 40.0),
+			angle: (0.0, std::f64::consts::TAU),
+			radius: 0.0,
+			gravity: Vector2::ZERO,
+			drag: 0.0,
+			spin: (0.0, 0.0),
+			colors: vec![Color::white()],
+			sizes: Ramp::constant(1.0),
+			opacities: Ramp::new(vec![1.0, 0.0]),
+			shape: ParticleShape::Pixel,
+			max_particles: 1000,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+	pub position: Vector2,
+	pub velocity: Vector2,
+	pub rotation: f64,
+	pub spin: f64,
+	pub age: f64,
+	pub lifetime: f64,
+}
+
+/// Makes particles at a position in the world. Particles stay where they were made when the emitter moves.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+	pub definition: ParticleDefinition,
+	pub position: Vector2,
+	/// Stopped emitters don't make new particles, but the ones they made live out their lifetime.
+	pub running: bool,
+	pub particles: Vec<Particle>,
+
+	// The definition's colors spread over COLOR_STEPS steps, made once since drawing needs them every frame
+	colors: Vec<Color>,
+	spawn_timer: f64,
+	random_state: u32,
+}
+
+impl Emitter {
+	pub fn new(definition: ParticleDefinition, position: Vector2, seed: u32) -> Emitter {
+		let colors = if definition.colors.len() > 1 { Color::gradient_steps(&definition.colors, COLOR_STEPS) } else { definition.colors.clone() };
+		Emitter {
+			colors,
+			definition,
+			position,
+			running: true,
+			particles: Vec::new(),
+			spawn_timer: 0.0,
+			// Xorshift gets stuck on zero
+			random_state: seed | 1,
+		}
+	}
+
+	// Xorshift, fast and good enough for particles
+	fn random(&mut self) -> f64 {
+		let mut x = self.random_state;
+		x ^= x << 13;
+		x ^= x >> 17;
+		x ^= x << 5;
+		self.random_state = x;
+		x as f64 / u32::MAX as f64
+	}
+
+	fn random_range(&mut self, (min, max): (f64, f64)) -> f64 {
+		min + (max - min) * self.random()
+	}
+
+	/// Makes count particles at once, up to the definition's max_particles.
+	pub fn burst(&mut self, count: usize) {
+		let count = usize::min(count, self.definition.max_particles.saturating_sub(self.particles.len()));
+		for _ in 0..count {
+			// Square root keeps them evenly spread over the circle instead of bunched in the middle
+			let offset_angle = self.random() * std::f64::consts::TAU;
+			let offset_distance = f64::sqrt(self.random()) * self.definition.radius;
+			let angle = self.random_range(self.definition.angle);
+			let speed = self.random_range(self.definition.speed);
+			let particle = Particle {
+				position: self.position + Vector2::new(f64::cos(offset_angle), f64::sin(offset_angle)) * offset_distance,
+				velocity: Vector2::new(f64::cos(angle), f64::sin(angle)) * speed,
+				rotation: angle,
+				spin: self.random_range(self.definition.spin),
+				age: 0.0,
+				lifetime: f64::max(self.random_range(self.definition.lifetime), 0.0001),
+			};
+			self.particles.push(particle);
+		}
+	}
+
+	pub fn update(&mut self, dt: f64) {
+		let gravity = self.definition.gravity * dt;
+		let drag = f64::exp(-f64::max(self.definition.drag, 0.0) * dt);
+		let step = |particle: &mut Particle| {
+			particle.age += dt;
+			particle.velocity = (particle.velocity + gravity) * drag;
+			particle.position += particle.velocity * dt;
+			particle.rotation += particle.spin * dt;
+		};
+		if self.particles.len() >= PARALLEL_PARTICLES {
+			self.particles.par_iter_mut().for_each(step);
+		} else {
+			self.particles.iter_mut().for_each(step);
+		}
+		self.particles.retain(|particle| particle.age < particle.lifetime);
+
+		if self.running && self.definition.rate > 0.0 {
+			self.spawn_timer += dt * self.definition.rate;
+			let count = self.spawn_timer as usize;
+			self.spawn_timer -= count as f64;
+			self.burst(count);
+		}
+	}
+
+	/// Draws every particle through the camera with the current draw mode. Sprites are found with image.
+	pub fn draw(&self, rst: &mut PartitionedRasterizer, image: &dyn Fn(&str) -> Option<Arc<Rasterizer>>) {
+		let definition = &self.definition;
+		let sprite = match &definition.shape {
+			ParticleShape::Sprite(name) => image(name),
+			_ => None,
+		};
+		if matches!(definition.shape, ParticleShape::Sprite(_)) && sprite.is_none() { return; }
+
+		let colors = &self.colors;
+		if colors.is_empty() { return; }
+
+		let (tint, opacity) = (rst.rasterizer.tint, rst.rasterizer.opacity);
+		for particle in self.particles.iter() {
+			let t = particle.age / particle.lifetime;
+			let mut color = colors[usize::min((t * (colors.len() - 1) as f64 + 0.5) as usize, colors.len() - 1)];
+			color.a = f64::clamp(color.a as f64 * definition.opacities.sample(t) + 0.5, 0.0, 255.0) as u8;
+			if color.a == 0 { continue; }
+
+			let size = definition.sizes.sample(t);
+			let (x, y) = (particle.position.x, particle.position.y);
+			match &definition.shape {
+				ParticleShape::Pixel => rst.pset(f64::floor(x) as i64, f64::floor(y) as i64, color),
+				ParticleShape::Circle => rst.pcircle(true, f64::floor(x) as i64, f64::floor(y) as i64, f64::round(size * 0.5) as i64, color),
+				ParticleShape::Rectangle => {
+					let side = f64::max(f64::round(size), 1.0);
+					rst.prectangle(true, f64::round(x - side * 0.5) as i64, f64::round(y - side * 0.5) as i64, side as i64, side as i64, color);
+				},
+				ParticleShape::Sprite(_) => {
+					// Images take their color from the tint and opacity
+					rst.set_tint(Color::new(color.r, color.g, color.b, 255));
+					rst.set_opacity(color.a);
+					rst.pimgmtx(sprite.as_ref().unwrap(), x, y, particle.rotation, size, size, 0.5, 0.5);
+				},
+			}
+		}
+
+		if sprite.is_some() {
+			rst.set_tint(tint);
+			rst.set_opacity(opacity);
+		}
+	}
+}
+
+/// Every particle definition and emitter, by name.
+pub struct ParticleSystem {
+	pub definitions: HashMap<String, ParticleDefinition>,
+	pub emitters: HashMap<String, Emitter>,
+	// Every new emitter gets a different seed so they don't all look the same
+	seed: u32,
+}
+
+impl ParticleSystem {
+	pub fn new() -> ParticleSystem {
+		ParticleSystem { definitions: HashMap::new(), emitters: HashMap::new(), seed: 0x2545f491 }
+	}
+
+	/// Makes an emitter from a definition, replacing any emitter with the same name.
+	/// The definition is copied, so changing it later doesn't change emitters already made from it.
+	pub fn create_emitter(&mut self, name: &str, definition: &str, position: Vector2) -> Result<(), String> {
+		let definition = self.definitions.get(definition).ok_or(format!("no particle definition called \"{}\"", definition))?.clone();
+		self.seed = self.seed.wrapping_mul(0x9e3779b9).wrapping_add(0x7f4a7c15);
+		self.emitters.insert(name.to_string(), Emitter::new(definition, position, self.seed));
+		Ok(())
+	}
+
+	pub fn update(&mut self, dt: f64) {
+		for emitter in self.emitters.values_mut() {
+			emitter.update(dt);
+		}
+	}
+
+	pub fn particle_count(&self) -> usize {
+		self.emitters.values().map(|emitter| emitter.particles.len()).sum()
+	}
+}
+
+impl Default for ParticleSystem {
+	fn default() -> Self {
+		ParticleSystem::new()
+	}
+}
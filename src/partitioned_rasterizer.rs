@@ -1,9 +1,17 @@
 use crate::rasterizer::*;
 use crate::color::*;
-
+use crate::command_buffer::*;
+use crate::palette::*;
+use crate::curves::*;
+use crate::font::*;
+use crate::shapes::*;
+use crate::text::*;
+
+use std::sync::Arc;
 use std::thread::*;
 
 use crate::vector2::*;
+use crate::matrix3::*;
 
 // If a bounding area in pixels is greater than this number, run in parallel instead
 // Otherwise the extra setup is not worth the effort
@@ -16,17 +24,70 @@ pub enum BoundingParallelThreshold {
 	VeryLow = 65536,
 }
 
+// Partitions smaller than this on either side aren't worth a thread of their own
+pub const MIN_PARTITION_SIZE: usize = 32;
+
+/// How the screen is split into partitions.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PartitionScheme {
 	Full,
-	Split2x1,
-	Split1x2,
-	Split2x2,
-	Split3x1,
-	Split3x2,
-	Split3x3,
-	Split4x4,
-	Split5x5,
-	Split8x8,
+	/// Columns by rows of partitions, the right column and bottom row also get the pixels that don't divide evenly.
+	Grid { columns: usize, rows: usize },
+}
+
+impl PartitionScheme {
+	/// Picks a grid with a partition for every core, shaped so the partitions are as close to square as possible.
+	/// Small screens get fewer partitions so none of them end up thinner than MIN_PARTITION_SIZE.
+	pub fn for_cores(cores: usize, width: usize, height: usize) -> PartitionScheme {
+		let max_columns = usize::max(1, width / MIN_PARTITION_SIZE);
+		let max_rows = usize::max(1, height / MIN_PARTITION_SIZE);
+
+		for count in (1..=usize::min(cores, max_columns * max_rows)).rev() {
+			let mut best: Option<(usize, usize)> = None;
+			let mut best_score = f64::MAX;
+
+			for columns in (1..=count).filter(|columns| count % columns == 0) {
+				let rows = count / columns;
+				if columns > max_columns || rows > max_rows { continue; }
+
+				// How far from square a partition is, the same whether it's too wide or too tall
+				let aspect = (width as f64 / columns as f64) / (height as f64 / rows as f64);
+				let score = f64::abs(f64::ln(aspect));
+				if score < best_score {
+					best_score = score;
+					best = Some((columns, rows));
+				}
+			}
+
+			match best {
+				Some((1, 1)) => return PartitionScheme::Full,
+				Some((columns, rows)) => return PartitionScheme::Grid { columns, rows },
+				None => continue,
+			}
+		}
+
+		PartitionScheme::Full
+	}
+
+	/// Returns where every partition goes as (x, y, width, height), left to right and then top to bottom.
+	/// Together they cover every pixel of the screen exactly once.
+	pub fn cells(&self, width: usize, height: usize) -> Vec<(usize, usize, usize, usize)> {
+		let (columns, rows) = match *self {
+			PartitionScheme::Full => (1, 1),
+			PartitionScheme::Grid { columns, rows } => (usize::clamp(columns, 1, usize::max(width, 1)), usize::clamp(rows, 1, usize::max(height, 1))),
+		};
+		let (cell_x, cell_y) = (width / columns, height / rows);
+
+		let mut cells = Vec::with_capacity(columns * rows);
+		for y in 0..rows {
+			for x in 0..columns {
+				let cell_width = if x == columns - 1 { width - cell_x * x } else { cell_x };
+				let cell_height = if y == rows - 1 { height - cell_y * y } else { cell_y };
+				cells.push((cell_x * x, cell_y * y, cell_width, cell_height));
+			}
+		}
+		cells
+	}
 }
 
 pub struct PartitionedRasterizer {
@@ -34,9 +95,21 @@ pub struct PartitionedRasterizer {
 	pub partitions: Vec<Rasterizer>,
 	pub scheme: PartitionScheme,
 	pub threshold: BoundingParallelThreshold,
+	/// How many cores set_core_limit was asked for, 0 is every core. The scheme is picked again from it when resizing.
+	pub core_limit: usize,
+
+	/// While deferred, draw calls are recorded here and drawn in parallel bands by flush instead of right away.
+	pub commands: CommandBuffer,
+	pub deferred: bool,
+
+	/// While on, every draw mode is drawn as DrawMode::Palette so the screen only ever holds palette indices.
+	pub palette_mode: bool,
 }
 
 /// A rasterizer that allows for parallel rendering by partioning the image into smaller pieces, usually by how many cores the current CPU has.
+/// Partitions are given the same world coordinates as the screen, their transform includes their offset so everything lines up.
+/// By default drawing is deferred: draw calls are recorded into a command buffer and all drawn at once by flush,
+/// anything that touches the main rasterizer directly has to go through immediate so it lands in the right order.
 impl PartitionedRasterizer {
 	pub fn new(width: usize, height: usize, cores: usize) -> PartitionedRasterizer {
 	
@@ -45,6 +118,10 @@ impl PartitionedRasterizer {
 			partitions:  Vec::new(),
 			scheme: PartitionScheme::Full,
 			threshold: BoundingParallelThreshold::High,
+			core_limit: cores,
+			commands: CommandBuffer::new(width, height),
+			deferred: true,
+			palette_mode: false,
 		};
 
 		pr.set_core_limit(cores);
@@ -73,6 +150,11 @@ impl PartitionedRasterizer {
 	}
 
 	pub fn clear(&mut self) {
+		if self.deferred {
+			self.commands.clear(Color::clear());
+			return;
+		}
+
 		self.rasterizer.clear();
 		for part in &mut self.partitions {
 			part.clear();
@@ -80,49 +162,79 @@ impl PartitionedRasterizer {
 	}
 
 	pub fn clear_color(&mut self, color: Color) {
+		if self.deferred {
+			self.commands.clear(color);
+			return;
+		}
+
 		self.rasterizer.clear_color(color);
 		for part in &mut self.partitions {
 			part.clear_color(color);
 		}
 	}
 
+	/// Draws everything in the command buffer into the main rasterizer.
+	pub fn flush(&mut self) {
+		if !self.commands.is_empty() {
+			self.commands.flush(&mut self.rasterizer);
+		}
+	}
+
+	/// Flushes and returns the main rasterizer, for drawing straight into it without the command buffer.
+	pub fn immediate(&mut self) -> &mut Rasterizer {
+		self.flush();
+		&mut self.rasterizer
+	}
+
+	/// Turns the command buffer on or off, anything already recorded is drawn first.
+	pub fn set_deferred(&mut self, deferred: bool) {
+		self.flush();
+		self.deferred = deferred;
+	}
+
+	// Draws are recorded while deferred, unless they need the stencil which only the main rasterizer has.
+	// Otherwise the command buffer is flushed so the draw lands on top of everything before it.
+	fn should_record(&mut self) -> bool {
+		if self.deferred && self.can_run_parallel() { return true; }
+		self.flush();
+		false
+	}
+
+	fn record(&mut self, command: DrawCommand) {
+		let state = DrawState::capture(&self.rasterizer);
+		self.commands.record(state, command);
+	}
+
 	pub fn set_core_limit(&mut self, cores: usize) {
+		self.core_limit = cores;
 		let cpu_count = if cores == 0 { num_cpus::get() } else { cores };
 
-		let mut is_unknown: bool = false;
-
-		let mut scheme: PartitionScheme = match cpu_count {
-			1 => { PartitionScheme::Full },
-			2 => { if self.rasterizer.height > self.rasterizer.width { PartitionScheme::Split2x1 } else { PartitionScheme::Split1x2 }},
-			3 => { PartitionScheme::Split3x1 }
-			4 => { PartitionScheme::Split2x2 },
-			5 => { PartitionScheme::Split2x2 },
-			6 => { PartitionScheme::Split3x2 },
-			8 => { PartitionScheme::Split3x3 },
-			10 => { PartitionScheme::Split3x3 },
-			12 => { PartitionScheme::Split4x4 },
-			16 => { PartitionScheme::Split4x4 },
-			20 => { PartitionScheme::Split5x5 },
-			24 => { PartitionScheme::Split5x5 },
-			_ => { is_unknown = true; PartitionScheme::Split4x4 }
-		};
-
-		if is_unknown {
-			if cpu_count > 24 {
-				scheme = PartitionScheme::Split8x8;
-			}
-		}
-		self.scheme = scheme;
+		self.scheme = PartitionScheme::for_cores(cpu_count, self.rasterizer.width, self.rasterizer.height);
 		self.generate_partitions();
 	}
 
+	/// Turns palette mode on or off, turning it off goes back to DrawMode::Opaque.
+	pub fn set_palette_mode(&mut self, enabled: bool) {
+		self.palette_mode = enabled;
+		self.set_draw_mode(if enabled { DrawMode::Palette } else { DrawMode::Opaque });
+	}
+
+	/// In palette mode this always sets DrawMode::Palette, blending palette indices would make other indices.
 	pub fn set_draw_mode(&mut self, mode: DrawMode) {
+		let mode = if self.palette_mode { DrawMode::Palette } else { mode };
 		self.rasterizer.set_draw_mode(mode);
 		for part in &mut self.partitions {
 			part.set_draw_mode(mode);
 		}
 	}
 
+	pub fn set_image_sampling(&mut self, sampling: ImageSampling) {
+		self.rasterizer.set_image_sampling(sampling);
+		for part in &mut self.partitions {
+			part.set_image_sampling(sampling);
+		}
+	}
+
 	pub fn set_tint(&mut self, color: Color) {
 		self.rasterizer.tint = color;
 		for part in &mut self.partitions {
@@ -130,6 +242,14 @@ impl PartitionedRasterizer {
 		}
 	}
 
+	/// pal() and palt() for DrawMode::Palette.
+	pub fn set_palette_remap(&mut self, remap: PaletteRemap) {
+		self.rasterizer.palette_remap = remap;
+		for part in &mut self.partitions {
+			part.palette_remap = remap;
+		}
+	}
+
 	pub fn set_opacity(&mut self, opacity: u8) {
 		self.rasterizer.opacity = opacity;
 		for part in &mut self.partitions {
@@ -158,23 +278,162 @@ impl PartitionedRasterizer {
 		}
 	}
 
+	/// Partitions are smaller than the screen, so they copy the screens camera instead of centering their own.
 	pub fn update_camera(&mut self) {
 		self.rasterizer.update_camera();
 		for part in &mut self.partitions {
-			part.update_camera();
+			part.camera_matrix = self.rasterizer.camera_matrix;
+			part.update_transform();
 		}
 	}
 
+	pub fn set_draw_space(&mut self, space: DrawSpace) {
+		self.rasterizer.set_draw_space(space);
+		for part in &mut self.partitions {
+			part.set_draw_space(space);
+		}
+	}
+
+	pub fn push_transform(&mut self) {
+		self.rasterizer.push_transform();
+		for part in &mut self.partitions {
+			part.push_transform();
+		}
+	}
+
+	pub fn pop_transform(&mut self) {
+		// Copied instead of popped so a bad pop only reports its error once
+		self.rasterizer.pop_transform();
+		for part in &mut self.partitions {
+			part.transform_stack = self.rasterizer.transform_stack.clone();
+			part.update_transform();
+		}
+	}
+
+	pub fn reset_transform(&mut self) {
+		self.rasterizer.reset_transform();
+		for part in &mut self.partitions {
+			part.reset_transform();
+		}
+	}
+
+	/// Replaces the whole transform stack, for putting back one saved from rasterizer.transform_stack.
+	pub fn set_transform_stack(&mut self, stack: Vec<Matrix3>) {
+		self.rasterizer.transform_stack = stack;
+		self.rasterizer.update_transform();
+		for part in &mut self.partitions {
+			part.transform_stack = self.rasterizer.transform_stack.clone();
+			part.update_transform();
+		}
+	}
+
+	pub fn apply_transform(&mut self, mtx: Matrix3) {
+		self.rasterizer.apply_transform(mtx);
+		for part in &mut self.partitions {
+			part.apply_transform(mtx);
+		}
+	}
+
+	pub fn translate(&mut self, x: f64, y: f64) {
+		self.rasterizer.translate(x, y);
+		for part in &mut self.partitions {
+			part.translate(x, y);
+		}
+	}
+
+	pub fn rotate(&mut self, radians: f64) {
+		self.rasterizer.rotate(radians);
+		for part in &mut self.partitions {
+			part.rotate(radians);
+		}
+	}
+
+	pub fn scale(&mut self, x: f64, y: f64) {
+		self.rasterizer.scale(x, y);
+		for part in &mut self.partitions {
+			part.scale(x, y);
+		}
+	}
+
+	pub fn shear(&mut self, x: f64, y: f64) {
+		self.rasterizer.shear(x, y);
+		for part in &mut self.partitions {
+			part.shear(x, y);
+		}
+	}
+
+	pub fn set_clip(&mut self, x: i64, y: i64, w: i64, h: i64) {
+		self.rasterizer.set_clip(x, y, w, h);
+		for part in &mut self.partitions {
+			part.set_clip(x, y, w, h);
+		}
+	}
+
+	pub fn push_clip(&mut self, x: i64, y: i64, w: i64, h: i64) {
+		self.rasterizer.push_clip(x, y, w, h);
+		for part in &mut self.partitions {
+			part.push_clip(x, y, w, h);
+		}
+	}
+
+	pub fn pop_clip(&mut self) {
+		// Copied instead of popped so a bad pop only reports its error once
+		self.rasterizer.pop_clip();
+		for part in &mut self.partitions {
+			part.clip_stack = self.rasterizer.clip_stack.clone();
+			part.update_clip();
+		}
+	}
+
+	pub fn clear_clip(&mut self) {
+		self.rasterizer.clear_clip();
+		for part in &mut self.partitions {
+			part.clear_clip();
+		}
+	}
+
+	/// The stencil only lives on the main rasterizer, partitions are skipped while it's in use.
+	pub fn set_stencil_mode(&mut self, mode: StencilMode) {
+		self.rasterizer.set_stencil_mode(mode);
+	}
+
+	pub fn clear_stencil(&mut self) {
+		self.rasterizer.clear_stencil();
+	}
+
+	// Partitions have their own buffers, so anything that reads or writes the stencil has to be drawn by the main rasterizer
+	fn can_run_parallel(&self) -> bool {
+		self.rasterizer.stencil_mode == StencilMode::Off
+	}
+
+	pub fn screen_to_world(&self, x: f64, y: f64) -> Vector2 {
+		self.rasterizer.screen_to_world(x, y)
+	}
+
+	pub fn world_to_screen(&self, x: f64, y: f64) -> Vector2 {
+		self.rasterizer.world_to_screen(x, y)
+	}
+
 	pub fn resize(&mut self, width: usize, height: usize) {
+		self.flush();
 		self.rasterizer.resize(width, height);
-		self.generate_partitions();
+		self.set_core_limit(self.core_limit);
+		self.commands.resize(width, height);
 	}
 
-	pub fn blit(&mut self, image: &Rasterizer, x: i64, y: i64) {
+	pub fn blit(&mut self, image: &Arc<Rasterizer>, x: i64, y: i64) {
+		if self.should_record() {
+			self.record(DrawCommand::Blit { image: image.clone(), x, y });
+			return;
+		}
 		self.rasterizer.blit(image, x, y);
 	}
 
 	pub fn pset(&mut self, x: i64, y: i64, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Pset { x, y, color });
+			return;
+		}
 		self.rasterizer.pset(x, y, color);
 	}
 
@@ -182,27 +441,40 @@ impl PartitionedRasterizer {
 		self.rasterizer.pget(x, y);
 	}
 
-	// Too simple to parallelize
+	// Too simple to parallelize on its own, but still recorded so it stays in order with everything else
 	pub fn pline(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Line { x0, y0, x1, y1, color });
+			return;
+		}
 		self.rasterizer.pline(x0, y0, x1, y1, color);
 	}
 
+	pub fn ptriangle(&mut self, filled: bool, x0: i64, y0: i64, x1: i64, y1: i64, x2: i64, y2: i64, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Triangle { filled, x0, y0, x1, y1, x2, y2, color });
+			return;
+		}
+		self.rasterizer.ptriangle(filled, x0, y0, x1, y1, x2, y2, color);
+	}
+
 	pub fn prectangle(&mut self, filled: bool, x: i64, y: i64, width: i64, height: i64, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Rectangle { filled, x, y, width, height, color });
+			return;
+		}
+
 		let total_area = width * height;
 
 		// Run in parallel
-		if filled && total_area >= self.threshold as i64 {
+		if filled && total_area >= self.threshold as i64 && self.can_run_parallel() {
 			scope(|s| {
 				let mut join_handles: Vec<ScopedJoinHandle<&mut Rasterizer>> = Vec::new();
 			
 				for part in & mut self.partitions {
-	
-					let rx = x - part.offset_x as i64;
-					let ry = y - part.offset_y as i64;
-	
 					let handle = s.spawn(move || {
 	
-						part.prectangle(filled, rx, ry, width, height, color);
+						part.prectangle(filled, x, y, width, height, color);
 	
 						part
 					});
@@ -225,22 +497,23 @@ impl PartitionedRasterizer {
 	}
 
 	pub fn pcircle(&mut self, filled: bool, xc: i64, yc: i64, radius: i64, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Circle { filled, xc, yc, radius, color });
+			return;
+		}
+
 		let total_area = std::f64::consts::PI * (radius * radius) as f64 ;
 
 		// Run in parallel
-		if total_area >= self.threshold as i64 as f64 {
+		if total_area >= self.threshold as i64 as f64 && self.can_run_parallel() {
 			scope(|s| {
 				let mut join_handles: Vec<ScopedJoinHandle<&mut Rasterizer>> = Vec::new();
 			
 				for part in & mut self.partitions {
 					//let mut part_clone = part.clone();
-	
-					let rx = xc - part.offset_x as i64;
-					let ry = yc - part.offset_y as i64;
-	
 					let handle = s.spawn(move || {
 	
-						part.pcircle(filled, rx, ry, radius, color);
+						part.pcircle(filled, xc, yc, radius, color);
 	
 						part
 					});
@@ -263,7 +536,11 @@ impl PartitionedRasterizer {
 		}
 	}
 
-	pub fn pimg(&mut self, image: &Rasterizer, x: i64, y: i64) {
+	pub fn pimg(&mut self, image: &Arc<Rasterizer>, x: i64, y: i64) {
+		if self.should_record() {
+			self.record(DrawCommand::Image { image: image.clone(), x, y, rx: 0, ry: 0, rw: image.width as i64, rh: image.height as i64 });
+			return;
+		}
 
 		let width = image.width;
 		let height = image.height;
@@ -271,19 +548,14 @@ impl PartitionedRasterizer {
 		let total_area = (width as i64) * (height as i64);
 
 		// Run in parallel
-		if total_area >= self.threshold as i64 {
+		if total_area >= self.threshold as i64 && self.can_run_parallel() {
 			scope(|s| {
 				let mut join_handles: Vec<ScopedJoinHandle<&mut Rasterizer>> = Vec::new();
 			
 				for part in &mut self.partitions {
-
-					let rx = x - part.offset_x as i64;
-					let ry = y - part.offset_y as i64;
-
-
 					let handle = s.spawn(move || {
 	
-						part.pimg(image, rx, ry);
+						part.pimg(image, x, y);
 	
 						part
 					});
@@ -302,12 +574,16 @@ impl PartitionedRasterizer {
 			})
 			
 		} else {
-			self.rasterizer.pimg(&image, x, y);
+			self.rasterizer.pimg(image, x, y);
 		}
 		
 	}
 
-	pub fn pimgrect(&mut self, image: &Rasterizer, x: i64, y: i64, ix: i64, iy: i64, iw: i64, ih: i64) {
+	pub fn pimgrect(&mut self, image: &Arc<Rasterizer>, x: i64, y: i64, ix: i64, iy: i64, iw: i64, ih: i64) {
+		if self.should_record() {
+			self.record(DrawCommand::Image { image: image.clone(), x, y, rx: ix, ry: iy, rw: iw, rh: ih });
+			return;
+		}
 
 		let width = image.width;
 		let height = image.height;
@@ -315,19 +591,14 @@ impl PartitionedRasterizer {
 		let total_area = (width as i64) * (height as i64);
 
 		// Run in parallel
-		if total_area >= self.threshold as i64 {
+		if total_area >= self.threshold as i64 && self.can_run_parallel() {
 			scope(|s| {
 				let mut join_handles: Vec<ScopedJoinHandle<&mut Rasterizer>> = Vec::new();
 			
 				for part in &mut self.partitions {
-
-					let rx = x - part.offset_x as i64;
-					let ry = y - part.offset_y as i64;
-
-
 					let handle = s.spawn(move || {
 	
-						part.pimgrect(image, rx, ry, ix, iy, iw, ih);
+						part.pimgrect(image, x, y, ix, iy, iw, ih);
 	
 						part
 					});
@@ -346,32 +617,38 @@ impl PartitionedRasterizer {
 			})
 			
 		} else {
-			self.rasterizer.pimg(&image, x, y);
+			self.rasterizer.pimgrect(image, x, y, ix, iy, iw, ih);
 		}
 		
 	}
 
-	pub fn pimgmtx(&mut self, image: &Rasterizer, x: f64, y: f64, rotation: f64, scale_x: f64, scale_y: f64, offset_x: f64, offset_y: f64) {
+	pub fn pimgmtx(&mut self, image: &Arc<Rasterizer>, x: f64, y: f64, rotation: f64, scale_x: f64, scale_y: f64, offset_x: f64, offset_y: f64) {
+		self.pimgrectmtx(image, x, y, rotation, scale_x, scale_y, offset_x, offset_y, 0, 0, image.width as i64, image.height as i64, false, false);
+	}
+
+	pub fn pimgrectmtx(&mut self, image: &Arc<Rasterizer>, x: f64, y: f64, rotation: f64, scale_x: f64, scale_y: f64, offset_x: f64, offset_y: f64, ix: i64, iy: i64, iw: i64, ih: i64, flip_x: bool, flip_y: bool) {
+		if self.should_record() {
+			self.record(DrawCommand::ImageMatrix {
+				image: image.clone(), x, y, rotation, scale_x, scale_y, offset_x, offset_y,
+				rx: ix, ry: iy, rw: iw, rh: ih, flip_x, flip_y,
+			});
+			return;
+		}
 
-		let width = image.width;
-		let height = image.height;
 		// Approximate area, can be bigger depending on rotation
-		let total_area = (width as f64 * scale_x) * (height as f64 * scale_y);
+		let total_area = ((iw as f64 * scale_x) * (ih as f64 * scale_y)).abs();
 
 		// Run in parallel
-		if total_area >= self.threshold as i64 as f64 {
+		if total_area >= self.threshold as i64 as f64 && self.can_run_parallel() {
 			scope(|s| {
 
 				let mut join_handles: Vec<ScopedJoinHandle<&mut Rasterizer>> = Vec::new();
 
 				// First pass: Find all regions that contain the image
 				for part in &mut self.partitions {
-					let rx = x - part.offset_x as f64;
-					let ry = y - part.offset_y as f64;
-					
 					let handle = s.spawn( move || {
 						
-						part.pimgmtx(image, rx, ry, rotation, scale_x, scale_y, offset_x, offset_y);
+						part.pimgrectmtx(image, x, y, rotation, scale_x, scale_y, offset_x, offset_y, ix, iy, iw, ih, flip_x, flip_y);
 	
 						part
 					});
@@ -387,213 +664,183 @@ impl PartitionedRasterizer {
 						println!("ERROR - THREAD PANIC: Partition failed in pimgmtx function!")
 					}
 				}
-				
-				//self.rasterizer.prectangle(false, rsx as i64, rsy as i64, (rex - rsx) as i64, (rey - rsy) as i64, Color::blue());
 			})
 			
 		} else {
-			self.rasterizer.pimgmtx(&image, x, y, rotation, scale_x, scale_y, offset_x, offset_y);
+			self.rasterizer.pimgrectmtx(image, x, y, rotation, scale_x, scale_y, offset_x, offset_y, ix, iy, iw, ih, flip_x, flip_y);
 		}
 		
 	}
 
 
-	fn generate_partitions(&mut self) {
-		self.partitions.clear();
-		/*let mut divx = min_pixel_size;
-
-		for i in min_pixel_size..self.rasterizer.width {
-			if (self.rasterizer.width % i) == 0 {
-				divx = i;
-				break;
-			} 
-		}
-
-		let mut divy = min_pixel_size;
-
-		for i in min_pixel_size..self.rasterizer.height {
-			if (self.rasterizer.height % i) == 0 {
-				divy = i;
-				break;
-			} 
-		}
-
-		let (cx, cy) = (self.rasterizer.width / divx, self.rasterizer.height / divy);
-
-		for y in 0..cy {
-			for x in 0..cx {
-				let mut r = Rasterizer::new(divx, divy);
-				r.offset_x = x * divx;
-				r.offset_y = y * divy;
-				self.partitions.push(r);
+	/// Meshes are recorded whole, their indices are checked here so a bad one only reports once.
+	pub fn pmesh(&mut self, texture: Option<&Arc<Rasterizer>>, vertices: &[Vertex], indices: &[usize], mapping: TextureMapping) {
+		if self.should_record() {
+			if indices.iter().any(|i| *i >= vertices.len()) {
+				println!("ERROR - DRAWING: Mesh has indices past its {} vertices!", vertices.len());
+				return;
 			}
-		}*/
-
-		match self.scheme {
-			PartitionScheme::Full => { self.partition_full(); },
-			PartitionScheme::Split1x2 => { self.partition_split_vertical(); },
-			PartitionScheme::Split2x1 => { self.partition_split_horizontal(); },
-			PartitionScheme::Split2x2 => { self.partition_split_2x2(); },
-			PartitionScheme::Split3x3 => { self.partition_split_3x3(); },
-			PartitionScheme::Split3x2 => { self.partition_split_3x2(); },
-			PartitionScheme::Split3x1 => { self.partition_split_3x1(); },
-			PartitionScheme::Split4x4 => { self.partition_split_4x4(); },
-			PartitionScheme::Split5x5 => { self.partition_split_5x5(); },
-			PartitionScheme::Split8x8 => { self.partition_split_8x8(); },
+			self.record(DrawCommand::Mesh { image: texture.cloned(), vertices: vertices.to_vec(), indices: indices.to_vec(), mapping });
+			return;
 		}
+		self.rasterizer.pmesh(texture.map(|t| t.as_ref()), vertices, indices, mapping);
 	}
 
-	pub fn draw_debug_view(&mut self) {
-		for part in &self.partitions {
-			self.rasterizer.pline(
-				part.offset_x as i64, 
-				part.offset_y as i64, 
-				(part.offset_x + part.width)  as i64, 
-				part.offset_y  as i64, 
-				Color::new(255, 0, 255, 255)
-			);
-
-			self.rasterizer.pline(
-				part.offset_x as i64, 
-				part.offset_y as i64, 
-				part.offset_x  as i64, 
-				(part.offset_y + part.height) as i64, 
-				Color::new(255, 0, 255, 255)
-			);
-		}
+	// Curves are flattened before they're recorded, so the bands don't all flatten them again
+	pub fn pcurve(&mut self, curve: &Curve, width: f64, color: Color) {
+		let points = curve.flatten(self.rasterizer.curve_tolerance());
+		self.ppolyline(&points, width, curve.closed, LineJoin::Miter, LineCap::Butt, color);
 	}
 
-	fn partition_full(&mut self) {
-		self.partitions.push(Rasterizer::new(self.rasterizer.width, self.rasterizer.height));
+	pub fn parc(&mut self, xc: f64, yc: f64, r: f64, start_angle: f64, end_angle: f64, width: f64, color: Color) {
+		self.pcurve(&Curve::arc(Vector2::new(xc, yc), r, start_angle, end_angle), width, color);
 	}
 
-	fn partition_split_vertical(&mut self) {
-		let mut left_half: Rasterizer = Rasterizer::new(self.rasterizer.width / 2, self.rasterizer.height);
-		left_half.offset_x = 0;
-		left_half.offset_y = 0;
-
-		let mut right_half: Rasterizer = Rasterizer::new(self.rasterizer.width / 2, self.rasterizer.height);
-		right_half.offset_x = self.rasterizer.width / 2;
-		right_half.offset_y = 0;
-
-		self.partitions.push(left_half);
-		self.partitions.push(right_half);
+	pub fn pellipse(&mut self, filled: bool, xc: f64, yc: f64, rx: f64, ry: f64, rotation: f64, width: f64, color: Color) {
+		let curve = Curve::ellipse(Vector2::new(xc, yc), rx, ry, rotation);
+		if filled {
+			let points = curve.flatten(self.rasterizer.curve_tolerance());
+			self.ppolygon(true, &points, FillRule::NonZero, color);
+		} else {
+			self.pcurve(&curve, width, color);
+		}
 	}
 
-	fn partition_split_horizontal(&mut self) {
-		let mut top_half: Rasterizer = Rasterizer::new(self.rasterizer.width, self.rasterizer.height / 2);
-		top_half.offset_x = 0;
-		top_half.offset_y = 0;
-
-		let mut bottom_half: Rasterizer = Rasterizer::new(self.rasterizer.width, self.rasterizer.height / 2);
-		bottom_half.offset_x = 0;
-		bottom_half.offset_y = self.rasterizer.height / 2;
-
-		self.partitions.push(top_half);
-		self.partitions.push(bottom_half);
+	pub fn pline_thick(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, width: f64, cap: LineCap, color: Color) {
+		self.ppolyline(&[Vector2::new(x0, y0), Vector2::new(x1, y1)], width, false, LineJoin::Miter, cap, color);
 	}
 
-	fn partition_split_2x2(&mut self) {
-		let cell_x = self.rasterizer.width / 2;
-		let cell_y = self.rasterizer.height / 2;
+	pub fn pline_aa(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: Color) {
+		self.ppolyline_aa(&[Vector2::new(x0, y0), Vector2::new(x1, y1)], false, color);
+	}
 
-		for y in 0..2 {
-			for x in 0..2 {
-				let mut r = Rasterizer::new(cell_x, cell_y);
-				r.offset_x = cell_x * x;
-				r.offset_y = cell_y * y;
-				self.partitions.push(r);
-			}
+	pub fn ppolyline(&mut self, points: &[Vector2], width: f64, closed: bool, join: LineJoin, cap: LineCap, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Polyline { points: points.to_vec(), width, closed, join, cap, color });
+			return;
 		}
+		self.rasterizer.ppolyline(points, width, closed, join, cap, color);
 	}
 
-	fn partition_split_3x1(&mut self) {
-		let cell_x = self.rasterizer.width / 3;
-		let cell_y = self.rasterizer.height;
-
-		let mut r = Rasterizer::new(cell_x, cell_y);
-		r.offset_x = 0;
-		r.offset_y = 0;
-		self.partitions.push(r);
-
-		let mut r = Rasterizer::new(cell_x, cell_y);
-		r.offset_x = cell_x;
-		r.offset_y = 0;
-		self.partitions.push(r);
-
-		let mut r = Rasterizer::new(cell_x, cell_y);
-		r.offset_x = cell_x * 2;
-		r.offset_y = 0;
-		self.partitions.push(r);
+	pub fn ppolyline_aa(&mut self, points: &[Vector2], closed: bool, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::PolylineAa { points: points.to_vec(), closed, color });
+			return;
+		}
+		self.rasterizer.ppolyline_aa(points, closed, color);
 	}
 
-	fn partition_split_3x2(&mut self) {
-		let cell_x = self.rasterizer.width / 3;
-		let cell_y = self.rasterizer.height / 2;
-
-		for y in 0..2 {
-			for x in 0..3 {
-				let mut r = Rasterizer::new(cell_x, cell_y);
-				r.offset_x = cell_x * x;
-				r.offset_y = cell_y * y;
-				self.partitions.push(r);
-			}
+	pub fn ppolygon(&mut self, filled: bool, points: &[Vector2], rule: FillRule, color: Color) {
+		if self.should_record() {
+			self.record(DrawCommand::Polygon { filled, points: points.to_vec(), rule, color });
+			return;
 		}
+		self.rasterizer.ppolygon(filled, points, rule, color);
 	}
 
-	fn partition_split_3x3(&mut self) {
-		let cell_x = self.rasterizer.width / 3;
-		let cell_y = self.rasterizer.height / 3;
+	pub fn pprint(&mut self, font: &Font, text: String, x: i64, y: i64, newline_space: i64, wrap_width: Option<u32>) {
+		let layout = layout_text(font, &text, wrap_width.map(|w| w as i64), TextAlign::Left, newline_space, false);
+		self.ptext(font, &layout, x, y, 0.0);
+	}
 
-		for y in 0..3 {
-			for x in 0..3 {
-				let mut r = Rasterizer::new(cell_x, cell_y);
-				r.offset_x = cell_x * x;
-				r.offset_y = cell_y * y;
-				self.partitions.push(r);
-			}
+	/// Text is recorded as an image for every glyph, each with the tint its markup gives it.
+	pub fn ptext(&mut self, font: &Font, layout: &TextLayout, x: i64, y: i64, time: f64) {
+		if !self.should_record() {
+			self.rasterizer.ptext(font, layout, x, y, time);
+			return;
+		}
+
+		let tint = self.rasterizer.tint;
+		for glyph in &layout.glyphs {
+			let glyph_font = font.chain(glyph.font);
+			let rect = glyph_font.glyph_rect(glyph.glyph);
+			if rect.is_none() { continue; }
+			let rect = rect.unwrap();
+
+			let (gx, gy) = glyph.position(font, x, y, time);
+			self.rasterizer.tint = match glyph.style.color {
+				Some(color) => color * tint,
+				None => tint,
+			};
+			self.record(DrawCommand::Image {
+				image: glyph_font.pages[rect.page].clone(), x: gx + rect.offset_x, y: gy + rect.offset_y,
+				rx: rect.x, ry: rect.y, rw: rect.w, rh: rect.h,
+			});
 		}
+		self.rasterizer.tint = tint;
 	}
 
-	fn partition_split_4x4(&mut self) {
-		let cell_x = self.rasterizer.width / 4;
-		let cell_y = self.rasterizer.height / 4;
+	fn generate_partitions(&mut self) {
+		self.partitions.clear();
+		for (x, y, width, height) in self.scheme.cells(self.rasterizer.width, self.rasterizer.height) {
+			let mut r = Rasterizer::new(width, height);
+			r.offset_x = x;
+			r.offset_y = y;
+			self.partitions.push(r);
+		}
 
-		for y in 0..4 {
-			for x in 0..4 {
-				let mut r = Rasterizer::new(cell_x, cell_y);
-				r.offset_x = cell_x * x;
-				r.offset_y = cell_y * y;
-				self.partitions.push(r);
-			}
+		// New partitions need the screens camera and clip rects, and their offset has to be part of both
+		for part in &mut self.partitions {
+			part.camera_matrix = self.rasterizer.camera_matrix;
+			part.draw_space = self.rasterizer.draw_space;
+			part.image_sampling = self.rasterizer.image_sampling;
+			part.set_draw_mode(self.rasterizer.draw_mode);
+			part.palette_remap = self.rasterizer.palette_remap;
+			part.transform_stack = self.rasterizer.transform_stack.clone();
+			part.update_transform();
+			part.clip_stack = self.rasterizer.clip_stack.clone();
+			part.update_clip();
 		}
 	}
 
-	fn partition_split_5x5(&mut self) {
-		let cell_x = self.rasterizer.width / 5;
-		let cell_y = self.rasterizer.height / 5;
+	pub fn draw_debug_view(&mut self) {
+		self.flush();
+		for part in &self.partitions {
+			self.rasterizer.pline_raw(
+				part.offset_x as i64, 
+				part.offset_y as i64, 
+				(part.offset_x + part.width)  as i64, 
+				part.offset_y  as i64, 
+				Color::new(255, 0, 255, 255)
+			);
 
-		for y in 0..5 {
-			for x in 0..5 {
-				let mut r = Rasterizer::new(cell_x, cell_y);
-				r.offset_x = cell_x * x;
-				r.offset_y = cell_y * y;
-				self.partitions.push(r);
-			}
+			self.rasterizer.pline_raw(
+				part.offset_x as i64, 
+				part.offset_y as i64, 
+				part.offset_x  as i64, 
+				(part.offset_y + part.height) as i64, 
+				Color::new(255, 0, 255, 255)
+			);
 		}
 	}
+}
 
-	fn partition_split_8x8(&mut self) {
-		let cell_x = self.rasterizer.width / 8;
-		let cell_y = self.rasterizer.height / 8;
-
-		for y in 0..8 {
-			for x in 0..8 {
-				let mut r = Rasterizer::new(cell_x, cell_y);
-				r.offset_x = cell_x * x;
-				r.offset_y = cell_y * y;
-				self.partitions.push(r);
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn partitions_exactly_tile_the_framebuffer() {
+		let sizes = [(1, 1), (31, 33), (333, 211), (641, 359), (1000, 37)];
+
+		for (width, height) in sizes {
+			for cores in [1, 2, 3, 6, 8, 13, 64] {
+				let pr = PartitionedRasterizer::new(width, height, cores);
+				assert!(pr.partitions.len() <= cores, "{}x{} with {} cores made {} partitions", width, height, cores, pr.partitions.len());
+
+				// Every pixel has to belong to exactly one partition
+				let mut coverage = vec![0u32; width * height];
+				for part in &pr.partitions {
+					assert!(part.width > 0 && part.height > 0, "{}x{} with {} cores made an empty partition", width, height, cores);
+					assert!(part.offset_x + part.width <= width && part.offset_y + part.height <= height);
+					for y in part.offset_y..part.offset_y + part.height {
+						for x in part.offset_x..part.offset_x + part.width {
+							coverage[y * width + x] += 1;
+						}
+					}
+				}
+				assert!(coverage.iter().all(|count| *count == 1), "{}x{} with {} cores doesn't cover every pixel once", width, height, cores);
 			}
 		}
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,366 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::color::*;
+use crate::palette::Palette;
+use crate::rasterizer::Rasterizer;
+
+// 4x4 Bayer matrix for ordered dithering, 0 to 15
+const BAYER_4X4: [[u8; 4]; 4] = [
+	[0, 8, 2, 10],
+	[12, 4, 14, 6],
+	[3, 11, 1, 9],
+	[15, 7, 13, 5],
+];
+
+/// One step of the post-process chain, run on the whole framebuffer after _draw.
+#[derive(Clone)]
+pub enum PostEffect {
+	/// Darkens every other row, intensity from 0.0 to 1.0.
+	Scanlines { intensity: f64 },
+	/// Bends the picture like a CRT, 0.0 is flat. Whatever bends off the screen turns black.
+	Curvature { amount: f64 },
+	/// Pulls red and blue apart towards the edges of the screen, offset in pixels at the corners.
+	ChromaticAberration { offset: f64 },
+	/// Makes everything brighter than threshold glow, blurred by radius pixels.
+	Bloom { threshold: u8, radius: usize, intensity: f64 },
+	BoxBlur { radius: usize },
+	GaussianBlur { radius: usize },
+	/// Darkens the corners. Radius is where the darkening starts, 1.0 being the corners.
+	Vignette { intensity: f64, radius: f64 },
+	/// Swaps every color for one from a LUT image, a strip of square slices going from no blue on the left to full blue on the right.
+	/// The usual 256x16 and 1024x32 LUTs both work.
+	ColorGrade { lut: Arc<Rasterizer> },
+	/// Rounds every color to the closest palette color, with a Bayer pattern to make up the colors in between.
+	/// The lookup is the nearest palette index for every 15-bit color, made for the colors in lookup_palette
+	/// and only made again when the palette has changed since. Use PostEffect::dither to start it empty.
+	Dither { palette: Palette, strength: f64, lookup: Vec<u8>, lookup_palette: Vec<Color> },
+}
+
+impl PostEffect {
+	pub fn dither(palette: Palette, strength: f64) -> PostEffect {
+		PostEffect::Dither { palette, strength, lookup: Vec::new(), lookup_palette: Vec::new() }
+	}
+}
+
+/// The post-process chain, applied in order when the frame is presented.
+pub struct PostProcess {
+	pub effects: Vec<PostEffect>,
+	/// Moves the whole picture by this many pixels before any effects, for screen shake.
+	pub shake: (i64, i64),
+
+	// Every effect reads one buffer and writes into the other
+	front: Vec<u8>,
+	back: Vec<u8>,
+	blur: Vec<u8>,
+}
+
+impl PostProcess {
+	pub fn new() -> PostProcess {
+		PostProcess { effects: Vec::new(), shake: (0, 0), front: Vec::new(), back: Vec::new(), blur: Vec::new() }
+	}
+
+	/// Returns true if presenting the frame needs apply.
+	pub fn is_active(&self) -> bool {
+		!self.effects.is_empty() || self.shake != (0, 0)
+	}
+
+	/// Runs the chain on an RGBA framebuffer and returns the finished frame.
+	pub fn apply(&mut self, pixels: &[u8], width: usize, height: usize) -> &[u8] {
+		self.front.clear();
+		self.front.extend_from_slice(pixels);
+		self.back.resize(pixels.len(), 0);
+		if width == 0 || height == 0 { return &self.front; }
+
+		if self.shake != (0, 0) {
+			shake(&self.front, &mut self.back, width, self.shake);
+			std::mem::swap(&mut self.front, &mut self.back);
+		}
+
+		for effect in self.effects.iter_mut() {
+			let (src, dst) = (&self.front, &mut self.back);
+			match effect {
+				PostEffect::Scanlines { intensity } => scanlines(src, dst, width, *intensity),
+				PostEffect::Curvature { amount } => curvature(src, dst, width, height, *amount),
+				PostEffect::ChromaticAberration { offset } => chromatic_aberration(src, dst, width, height, *offset),
+				PostEffect::Bloom { threshold, radius, intensity } => {
+					bright_pass(src, dst, width, *threshold);
+					self.blur.resize(src.len(), 0);
+					gaussian_blur(dst, &mut self.blur, width, height, *radius);
+					add_scaled(src, dst, width, *intensity);
+				},
+				PostEffect::BoxBlur { radius } => {
+					self.blur.resize(src.len(), 0);
+					dst.copy_from_slice(src);
+					box_blur(dst, &mut self.blur, width, height, *radius);
+				},
+				PostEffect::GaussianBlur { radius } => {
+					self.blur.resize(src.len(), 0);
+					dst.copy_from_slice(src);
+					gaussian_blur(dst, &mut self.blur, width, height, *radius);
+				},
+				PostEffect::Vignette { intensity, radius } => vignette(src, dst, width, height, *intensity, *radius),
+				PostEffect::ColorGrade { lut } => color_grade(src, dst, width, lut),
+				PostEffect::Dither { palette, strength, lookup, lookup_palette } => {
+					if *lookup_palette != palette.colors || lookup.is_empty() {
+						*lookup = dither_lookup(palette);
+						*lookup_palette = palette.colors.clone();
+					}
+					dither(src, dst, width, palette, lookup, *strength);
+				},
+			}
+			std::mem::swap(&mut self.front, &mut self.back);
+		}
+
+		&self.front
+	}
+}
+
+impl Default for PostProcess {
+	fn default() -> Self {
+		PostProcess::new()
+	}
+}
+
+// Reads a pixel, anything off the screen is black
+#[inline]
+fn fetch(src: &[u8], width: usize, height: usize, x: i64, y: i64) -> [u8; 4] {
+	if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 { return [0, 0, 0, 255]; }
+	let idx = (y as usize * width + x as usize) * 4;
+	[src[idx], src[idx + 1], src[idx + 2], src[idx + 3]]
+}
+
+fn shake(src: &[u8], dst: &mut [u8], width: usize, (offset_x, offset_y): (i64, i64)) {
+	let height = src.len() / (width * 4);
+	dst.par_chunks_mut(width * 4).enumerate().for_each(|(y, row)| {
+		for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+			pixel.copy_from_slice(&fetch(src, width, height, x as i64 - offset_x, y as i64 - offset_y));
+		}
+	});
+}
+
+fn scanlines(src: &[u8], dst: &mut [u8], width: usize, intensity: f64) {
+	let keep = ((1.0 - f64::clamp(intensity, 0.0, 1.0)) * 256.0) as u32;
+	dst.par_chunks_mut(width * 4).zip(src.par_chunks(width * 4)).enumerate().for_each(|(y, (row, src_row))| {
+		if y % 2 == 0 {
+			row.copy_from_slice(src_row);
+			return;
+		}
+		for (d, s) in row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)) {
+			d[0] = ((s[0] as u32 * keep) >> 8) as u8;
+			d[1] = ((s[1] as u32 * keep) >> 8) as u8;
+			d[2] = ((s[2] as u32 * keep) >> 8) as u8;
+			d[3] = s[3];
+		}
+	});
+}
+
+fn curvature(src: &[u8], dst: &mut [u8], width: usize, height: usize, amount: f64) {
+	let (half_w, half_h) = (width as f64 * 0.5, height as f64 * 0.5);
+	dst.par_chunks_mut(width * 4).enumerate().for_each(|(y, row)| {
+		let v = (y as f64 + 0.5) / half_h - 1.0;
+		for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+			let u = (x as f64 + 0.5) / half_w - 1.0;
+
+			// Points further from the center are pushed further out, so the middle of the screen bulges
+			let bend = 1.0 + amount * (u * u + v * v);
+			let (sx, sy) = ((u * bend + 1.0) * half_w, (v * bend + 1.0) * half_h);
+			pixel.copy_from_slice(&fetch(src, width, height, f64::floor(sx) as i64, f64::floor(sy) as i64));
+		}
+	});
+}
+
+fn chromatic_aberration(src: &[u8], dst: &mut [u8], width: usize, height: usize, offset: f64) {
+	let (half_w, half_h) = (width as f64 * 0.5, height as f64 * 0.5);
+	dst.par_chunks_mut(width * 4).enumerate().for_each(|(y, row)| {
+		let v = (y as f64 + 0.5 - half_h) / half_h;
+		for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+			let u = (x as f64 + 0.5 - half_w) / half_w;
+
+			// Red moves out and blue moves in, further the closer to the edge
+			let (dx, dy) = (f64::round(u * offset * 0.5) as i64, f64::round(v * offset * 0.5) as i64);
+			let (x, y) = (x as i64, y as i64);
+			let center = fetch(src, width, height, x, y);
+			pixel[0] = fetch(src, width, height, x - dx, y - dy)[0];
+			pixel[1] = center[1];
+			pixel[2] = fetch(src, width, height, x + dx, y + dy)[2];
+			pixel[3] = center[3];
+		}
+	});
+}
+
+fn bright_pass(src: &[u8], dst: &mut [u8], width: usize, threshold: u8) {
+	dst.par_chunks_mut(width * 4).zip(src.par_chunks(width * 4)).for_each(|(row, src_row)| {
+		for (d, s) in row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)) {
+			let brightness = (s[0] as u32 * 54 + s[1] as u32 * 183 + s[2] as u32 * 19) >> 8;
+			if brightness > threshold as u32 {
+				d.copy_from_slice(&[s[0], s[1], s[2], 255]);
+			} else {
+				d.copy_from_slice(&[0, 0, 0, 255]);
+			}
+		}
+	});
+}
+
+// dst = src + dst * intensity, saturating
+fn add_scaled(src: &[u8], dst: &mut [u8], width: usize, intensity: f64) {
+	let scale = (f64::max(intensity, 0.0) * 256.0) as u32;
+	dst.par_chunks_mut(width * 4).zip(src.par_chunks(width * 4)).for_each(|(row, src_row)| {
+		for (d, s) in row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)) {
+			for c in 0..3 {
+				d[c] = u32::min(s[c] as u32 + ((d[c] as u32 * scale) >> 8), 255) as u8;
+			}
+			d[3] = s[3];
+		}
+	});
+}
+
+// Blurs pixels in place with the same weights horizontally and then vertically, scratch holds the pass in between.
+// Weights are fixed point and add up to 1 << 16.
+fn separable_blur(pixels: &mut [u8], scratch: &mut [u8], width: usize, height: usize, weights: &[u32]) {
+	let radius = (weights.len() / 2) as i64;
+
+	// Rows, padded with copies of the edge pixels so every tap is just an offset into the row
+	scratch.par_chunks_mut(width * 4).zip(pixels.par_chunks(width * 4)).for_each(|(row, src_row)| {
+		let mut padded: Vec<u8> = Vec::with_capacity((width + radius as usize * 2) * 4);
+		for _ in 0..radius { padded.extend_from_slice(&src_row[0..4]); }
+		padded.extend_from_slice(src_row);
+		for _ in 0..radius { padded.extend_from_slice(&src_row[(width - 1) * 4..width * 4]); }
+
+		let mut sum = vec![0u32; width * 4];
+		for (i, weight) in weights.iter().enumerate() {
+			for (total, value) in sum.iter_mut().zip(padded[i * 4..(i + width) * 4].iter()) { *total += *value as u32 * weight; }
+		}
+		for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+			for c in 0..3 { pixel[c] = ((sum[x * 4 + c] + 32768) >> 16) as u8; }
+			pixel[3] = src_row[x * 4 + 3];
+		}
+	});
+
+	// Columns, adding whole rows at a time so the reads stay in order
+	let scratch: &[u8] = scratch;
+	pixels.par_chunks_mut(width * 4).enumerate().for_each(|(y, row)| {
+		let mut sum = vec![0u32; width * 4];
+		for (i, weight) in weights.iter().enumerate() {
+			let sy = i64::clamp(y as i64 + i as i64 - radius, 0, height as i64 - 1) as usize;
+			let src_row = &scratch[sy * width * 4..(sy + 1) * width * 4];
+			for (total, value) in sum.iter_mut().zip(src_row.iter()) { *total += *value as u32 * weight; }
+		}
+		for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+			for c in 0..3 { pixel[c] = ((sum[x * 4 + c] + 32768) >> 16) as u8; }
+		}
+	});
+}
+
+// Turns weights into fixed point, giving whatever rounding left over to the middle so they still add up
+fn fixed_weights(weights: &[f32]) -> Vec<u32> {
+	let total: f32 = weights.iter().sum();
+	let mut fixed: Vec<u32> = weights.iter().map(|w| (w / total * 65536.0) as u32).collect();
+	let middle = fixed.len() / 2;
+	fixed[middle] += 65536 - fixed.iter().sum::<u32>();
+	fixed
+}
+
+fn box_blur(pixels: &mut [u8], scratch: &mut [u8], width: usize, height: usize, radius: usize) {
+	if radius == 0 { return; }
+	separable_blur(pixels, scratch, width, height, &fixed_weights(&vec![1.0; radius * 2 + 1]));
+}
+
+fn gaussian_blur(pixels: &mut [u8], scratch: &mut [u8], width: usize, height: usize, radius: usize) {
+	if radius == 0 { return; }
+
+	// The curve is mostly gone three sigmas out, so the radius covers that much
+	let sigma = f32::max(radius as f32 / 3.0, 0.5);
+	let weights: Vec<f32> = (0..radius * 2 + 1).map(|i| {
+		let d = i as f32 - radius as f32;
+		f32::exp(-(d * d) / (2.0 * sigma * sigma))
+	}).collect();
+
+	separable_blur(pixels, scratch, width, height, &fixed_weights(&weights));
+}
+
+fn vignette(src: &[u8], dst: &mut [u8], width: usize, height: usize, intensity: f64, radius: f64) {
+	let (half_w, half_h) = (width as f64 * 0.5, height as f64 * 0.5);
+	let intensity = f64::clamp(intensity, 0.0, 1.0);
+	let radius = f64::clamp(radius, 0.0, 0.999);
+
+	dst.par_chunks_mut(width * 4).zip(src.par_chunks(width * 4)).enumerate().for_each(|(y, (row, src_row))| {
+		let v = (y as f64 + 0.5 - half_h) / half_h;
+		for (x, (d, s)) in row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)).enumerate() {
+			let u = (x as f64 + 0.5 - half_w) / half_w;
+
+			// 0.0 in the center and 1.0 in the corners, smoothed from radius outwards
+			let distance = f64::sqrt((u * u + v * v) * 0.5);
+			let t = f64::clamp((distance - radius) / (1.0 - radius), 0.0, 1.0);
+			let keep = ((1.0 - intensity * t * t * (3.0 - 2.0 * t)) * 256.0) as u32;
+
+			for c in 0..3 { d[c] = ((s[c] as u32 * keep) >> 8) as u8; }
+			d[3] = s[3];
+		}
+	});
+}
+
+fn color_grade(src: &[u8], dst: &mut [u8], width: usize, lut: &Rasterizer) {
+	// A strip of size slices, each size x size
+	let size = lut.height;
+	if size < 2 || lut.width != size * size {
+		dst.copy_from_slice(src);
+		return;
+	}
+
+	let scale = (size - 1) as f32 / 255.0;
+	let lut_pixel = |r: usize, g: usize, b: usize| -> [f32; 3] {
+		let idx = (g * lut.width + b * size + r) * 4;
+		[lut.color[idx] as f32, lut.color[idx + 1] as f32, lut.color[idx + 2] as f32]
+	};
+
+	dst.par_chunks_mut(width * 4).zip(src.par_chunks(width * 4)).for_each(|(row, src_row)| {
+		for (d, s) in row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)) {
+			// Trilinear, blending the eight LUT entries around the color
+			let position = [s[0] as f32 * scale, s[1] as f32 * scale, s[2] as f32 * scale];
+			let low = position.map(|p| usize::min(p as usize, size - 2));
+			let t = [position[0] - low[0] as f32, position[1] - low[1] as f32, position[2] - low[2] as f32];
+
+			let mut out = [0.0f32; 3];
+			for corner in 0..8 {
+				let (cr, cg, cb) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+				let weight = (if cr == 1 { t[0] } else { 1.0 - t[0] })
+					* (if cg == 1 { t[1] } else { 1.0 - t[1] })
+					* (if cb == 1 { t[2] } else { 1.0 - t[2] });
+				if weight == 0.0 { continue; }
+				let c = lut_pixel(low[0] + cr, low[1] + cg, low[2] + cb);
+				for i in 0..3 { out[i] += c[i] * weight; }
+			}
+
+			for i in 0..3 { d[i] = f32::clamp(out[i] + 0.5, 0.0, 255.0) as u8; }
+			d[3] = s[3];
+		}
+	});
+}
+
+// Nearest palette index for every 15-bit color, far quicker than searching the palette for every pixel
+fn dither_lookup(palette: &Palette) -> Vec<u8> {
+	(0..32768u32).into_par_iter().map(|c| {
+		let channel = |shift: u32| (((c >> shift) & 31) * 255 / 31) as u8;
+		palette.nearest(Color::new(channel(10), channel(5), channel(0), 255))
+	}).collect()
+}
+
+fn dither(src: &[u8], dst: &mut [u8], width: usize, palette: &Palette, lookup: &[u8], strength: f64) {
+	if palette.colors.is_empty() {
+		dst.copy_from_slice(src);
+		return;
+	}
+
+	// How far the pattern pushes a color, a full step of a 16 color palette at strength 1.0
+	let spread = f64::max(strength, 0.0) * 32.0;
+	dst.par_chunks_mut(width * 4).zip(src.par_chunks(width * 4)).enumerate().for_each(|(y, (row, src_row))| {
+		for (x, (d, s)) in row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)).enumerate() {
+			let threshold = (BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.0 - 0.5;
+			let nudge = |c: u8| (f64::clamp(c as f64 + threshold * spread, 0.0, 255.0) as u32 * 31 + 127) / 255;
+			let color = palette.get(lookup[(nudge(s[0]) << 10 | nudge(s[1]) << 5 | nudge(s[2])) as usize]);
+			d.copy_from_slice(&[color.r, color.g, color.b, s[3]]);
+		}
+	});
+}
@@ -0,0 +1,115 @@
+use rayon::prelude::*;
+
+use crate::color::*;
+use crate::expression::*;
+use crate::noise::*;
+use crate::rasterizer::Rasterizer;
+
+/// Variables every pixel expression can use, followed by any custom ones.
+/// x and y are the pixel, u and v are 0.0 to 1.0 across the region, w and h are the region's size,
+/// and r, g, b, a are the pixel's color before it was changed.
+pub const PIXEL_VARIABLES: [&str; 10] = ["x", "y", "u", "v", "w", "h", "r", "g", "b", "a"];
+
+/// Built in images drawn straight into an image's pixels, without going through the draw mode.
+#[derive(Debug, Clone)]
+pub enum Generator {
+	/// Noise at scale pixels per cell, going from low to high.
+	Noise { kind: NoiseKind, scale: f64, octaves: u32, seed: u32, low: Color, high: Color },
+	/// A gradient through the colors from one point to another, blended in OKLab.
+	LinearGradient { x0: f64, y0: f64, x1: f64, y1: f64, colors: Vec<Color> },
+	/// A gradient through the colors from the center out to the radius.
+	RadialGradient { x: f64, y: f64, radius: f64, colors: Vec<Color> },
+	/// Squares of size pixels, alternating between two colors.
+	Checker { size: i64, even: Color, odd: Color },
+}
+
+impl Generator {
+	pub fn color_at(&self, x: f64, y: f64) -> Color {
+		match self {
+			Generator::Noise { kind, scale, octaves, seed, low, high } => {
+				let scale = f64::max(*scale, 0.0001);
+				let t = fbm(*kind, x / scale, y / scale, *octaves, *seed);
+				Color::lerp_rgb(*low, *high, t)
+			},
+			Generator::LinearGradient { x0, y0, x1, y1, colors } => {
+				let (dx, dy) = (x1 - x0, y1 - y0);
+				let length_sqr = dx * dx + dy * dy;
+				let t = if length_sqr > 0.0 { ((x - x0) * dx + (y - y0) * dy) / length_sqr } else { 0.0 };
+				Color::gradient(colors, f64::clamp(t, 0.0, 1.0))
+			},
+			Generator::RadialGradient { x: cx, y: cy, radius, colors } => {
+				let distance = f64::sqrt((x - cx) * (x - cx) + (y - cy) * (y - cy));
+				let t = if *radius > 0.0 { distance / radius } else { 1.0 };
+				Color::gradient(colors, f64::clamp(t, 0.0, 1.0))
+			},
+			Generator::Checker { size, even, odd } => {
+				let size = i64::max(*size, 1);
+				let (cx, cy) = ((f64::floor(x) as i64).div_euclid(size), (f64::floor(y) as i64).div_euclid(size));
+				if (cx + cy) % 2 == 0 { *even } else { *odd }
+			},
+		}
+	}
+}
+
+// The part of (x, y, w, h) inside the image as (x0, y0, x1, y1), None if none of it is
+fn clip_region(image: &Rasterizer, (x, y, w, h): (i64, i64, i64, i64)) -> Option<(usize, usize, usize, usize)> {
+	let (x0, y0) = (i64::max(x, 0), i64::max(y, 0));
+	let (x1, y1) = (i64::min(x + w, image.width as i64), i64::min(y + h, image.height as i64));
+	if x0 >= x1 || y0 >= y1 { return None; }
+	Some((x0 as usize, y0 as usize, x1 as usize, y1 as usize))
+}
+
+/// Fills a region of an image with a generator, in parallel over the rows.
+/// Generators are sampled at the center of each pixel.
+pub fn generate(image: &mut Rasterizer, region: (i64, i64, i64, i64), generator: &Generator) {
+	let (x0, y0, x1, y1) = match clip_region(image, region) {
+		Some(clipped) => clipped,
+		None => return,
+	};
+
+	let width = image.width;
+	image.color.par_chunks_mut(width * 4).enumerate().skip(y0).take(y1 - y0).for_each(|(y, row)| {
+		for x in x0..x1 {
+			let c = generator.color_at(x as f64 + 0.5, y as f64 + 0.5);
+			row[x * 4..x * 4 + 4].copy_from_slice(&[c.r, c.g, c.b, c.a]);
+		}
+	});
+}
+
+/// Runs an expression compiled with PIXEL_VARIABLES (and then any custom ones) over a region, in parallel over the rows.
+/// One value is gray, three are red, green and blue, and four add alpha, all 0 to 255.
+pub fn map_expression(image: &mut Rasterizer, region: (i64, i64, i64, i64), expression: &Expression, custom: &[f64]) {
+	let (x0, y0, x1, y1) = match clip_region(image, region) {
+		Some(clipped) => clipped,
+		None => return,
+	};
+
+	let (w, h) = ((x1 - x0) as f64, (y1 - y0) as f64);
+	let width = image.width;
+	let outputs = expression.outputs;
+	image.color.par_chunks_mut(width * 4).enumerate().skip(y0).take(y1 - y0).for_each(|(y, row)| {
+		let mut variables: Vec<f64> = vec![0.0; PIXEL_VARIABLES.len()];
+		variables.extend_from_slice(custom);
+		let mut stack = Vec::new();
+		let mut out = [0.0; 4];
+
+		for x in x0..x1 {
+			let pixel = &mut row[x * 4..x * 4 + 4];
+			variables[0] = x as f64;
+			variables[1] = y as f64;
+			variables[2] = (x - x0) as f64 / f64::max(w - 1.0, 1.0);
+			variables[3] = (y - y0) as f64 / f64::max(h - 1.0, 1.0);
+			variables[4] = w;
+			variables[5] = h;
+			for c in 0..4 { variables[6 + c] = pixel[c] as f64; }
+
+			expression.evaluate(&variables, &mut stack, &mut out);
+			let channel = |v: f64| if v.is_nan() { 0 } else { f64::clamp(v + 0.5, 0.0, 255.0) as u8 };
+			match outputs {
+				1 => pixel.copy_from_slice(&[channel(out[0]), channel(out[0]), channel(out[0]), 255]),
+				3 => pixel.copy_from_slice(&[channel(out[0]), channel(out[1]), channel(out[2]), 255]),
+				_ => pixel.copy_from_slice(&[channel(out[0]), channel(out[1]), channel(out[2]), channel(out[3])]),
+			}
+		}
+	});
+}
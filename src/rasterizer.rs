@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
 use rayon::prelude::*;
 
 use crate::color::*;
@@ -5,15 +8,23 @@ use crate::partitioned_rasterizer::PartitionedRasterizer;
 use crate::vector2::*;
 use crate::matrix3::*;
 use crate::font::*;
+use crate::text::*;
 use crate::math::*;
+use crate::shapes::*;
+use crate::curves::*;
+use crate::span::*;
+use crate::palette::*;
 
 use mlua::prelude::*;
 
+// Lines further than this from the screen are cut off before being walked
+const LINE_GUARD_BAND: f64 = 8192.0;
+
 // Draw Mode Definition
 pub type PSetOp = fn(&mut Rasterizer, usize, Color);
 
 /// Controls how a Rasterizer should draw incoming pixels.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DrawMode {
     NoOp,
     NoAlpha,
@@ -38,9 +49,76 @@ pub enum DrawMode {
     PatternInvertedOpaque,
     PatternInvertedBgAlpha,
     PatternInvertedBgOpaque,
+    /// Palette mode, colors are palette indices made by index_color. They go through the PaletteRemap and aren't blended.
+    Palette,
     // Collect,
 }
 
+/// How drawing interacts with the stencil buffer.
+/// Write and Erase only change the stencil, where the drawn pixel is at least half opaque, and leave the colors alone.
+/// Inside and Outside only draw where the stencil is set or not set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StencilMode {
+    Off,
+    Write,
+    Erase,
+    Inside,
+    Outside,
+}
+
+/// Which coordinates the drawing functions take.
+/// World space goes through the camera, screen space draws straight to the framebuffer for things like UI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawSpace {
+    World,
+    Screen,
+}
+
+/// How transformed images pick their colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageSampling {
+    /// Takes the closest pixel, keeping pixel art sharp.
+    Nearest,
+    /// Blends the four closest pixels, smooth but blurry.
+    Bilinear,
+    /// Pixel art rotation without the jagged edges nearest leaves, by sampling an 8x Scale2x upscale of the image.
+    /// Slower, so it's best kept for sprites. Anything bigger than 256x256 falls back to nearest.
+    RotSprite,
+}
+
+/// How textures follow a triangle. Perspective uses each vertex's w so textures on tilted pseudo-3D surfaces don't warp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureMapping {
+    Affine,
+    Perspective,
+}
+
+impl TextureMapping {
+    pub fn from_str(name: &str) -> TextureMapping {
+        match name {
+            "perspective" => TextureMapping::Perspective,
+            _ => TextureMapping::Affine,
+        }
+    }
+}
+
+/// A corner of a triangle drawn by ptriangle_textured_raw or pmesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: Vector2,
+    /// Texture coordinates, 0.0 to 1.0 covers the image once and anything past that repeats it.
+    pub uv: Vector2,
+    pub color: Color,
+    /// Depth for perspective mapping, 1.0 for anything flat.
+    pub w: f64,
+}
+
+impl Vertex {
+    pub fn new(x: f64, y: f64, u: f64, v: f64, color: Color) -> Vertex {
+        Vertex { position: Vector2::new(x, y), uv: Vector2::new(u, v), color, w: 1.0 }
+    }
+}
+
 fn pset_noop(rasterizer: &mut Rasterizer, idx: usize, color: Color) {
     rasterizer.color[idx + 0] = color.r;  // R
     rasterizer.color[idx + 1] = color.g;  // G
@@ -69,6 +147,21 @@ fn pset_opaque(rasterizer: &mut Rasterizer, idx: usize, color: Color) {
     rasterizer.drawn_pixels_since_clear += 1;
 }
 
+/// Draw palette indices, swapped through pal() and skipped if palt() made them transparent.
+/// Tint and opacity don't mean anything for an index, so they're ignored.
+fn pset_palette(rasterizer: &mut Rasterizer, idx: usize, color: Color) {
+    if color.a < 128 { return; }
+    let index = match rasterizer.palette_remap.apply(color.r) {
+        Some(index) => index,
+        None => return,
+    };
+    rasterizer.color[idx + 0] = index;  // R
+    rasterizer.color[idx + 1] = index;  // G
+    rasterizer.color[idx + 2] = index;  // B
+    rasterizer.color[idx + 3] = 255;    // A
+    rasterizer.drawn_pixels_since_clear += 1;
+}
+
 /// Draw pixels if they are fully opaque, otherwise ignore them. Forces them to be the tint color.
 /// Useful for flashes or making masks
 fn pset_force_tint(rasterizer: &mut Rasterizer, idx: usize, color: Color) {
@@ -241,11 +334,142 @@ pub struct Rasterizer {
     pub camera_scale: Vector2,
     pub camera_matrix: Matrix3,
 
+    /// Scissor rects in screen coordinates as (x0, y0, x1, y1), each one already inside the one below it.
+    pub clip_stack: Vec<(i64, i64, i64, i64)>,
+    // The top clip rect moved into this buffer and limited to its size. pset checks against this instead of the buffer size.
+    clip: (i64, i64, i64, i64),
+
+    /// One bit per pixel, only allocated once something is written to it.
+    stencil: Vec<u64>,
+    pub stencil_mode: StencilMode,
+
+    /// Transform stack for drawing hierarchies, like a turret on a tank. The current transform is always on top.
+    pub transform_stack: Vec<Matrix3>,
+
+    /// The transform stack, camera (in world space) and partition offset combined. Every drawing function places its points through this.
+    pub draw_space: DrawSpace,
+    pub transform: Matrix3,
+    transform_is_offset: bool,
+
     pub draw_mode: DrawMode,
     pub tint: Color,
     pub opacity: u8,
+    pub image_sampling: ImageSampling,
+    /// pal() and palt() for DrawMode::Palette.
+    pub palette_remap: PaletteRemap,
 
     pub drawn_pixels_since_clear: u64,
+
+    rotsprite_cache: RotSpriteCache,
+}
+
+// Gets a pixel from a section of an image, anything outside the section is clear
+fn sample_section(image: &Rasterizer, rx: i64, ry: i64, rw: i64, rh: i64, ix: i64, iy: i64) -> Color {
+    if ix < 0 || iy < 0 || ix >= rw || iy >= rh { return Color::clear(); }
+    image.pget(rx + ix, ry + iy)
+}
+
+// Blends the four pixels around a position in a section of an image.
+// Colors are weighted by their alpha so clear pixels around a sprite don't darken its edges.
+fn sample_bilinear(image: &Rasterizer, rx: i64, ry: i64, rw: i64, rh: i64, u: f64, v: f64) -> Color {
+    let (fx, fy) = (u - 0.5, v - 0.5);
+    let (x0, y0) = (f64::floor(fx), f64::floor(fy));
+    let (tx, ty) = (fx - x0, fy - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let taps = [
+        (x0, y0, (1.0 - tx) * (1.0 - ty)),
+        (x0 + 1, y0, tx * (1.0 - ty)),
+        (x0, y0 + 1, (1.0 - tx) * ty),
+        (x0 + 1, y0 + 1, tx * ty),
+    ];
+
+    let (mut r, mut g, mut b, mut a) = (0.0, 0.0, 0.0, 0.0);
+    for (ix, iy, weight) in taps {
+        let color = sample_section(image, rx, ry, rw, rh, ix, iy);
+        let weight = weight * color.a as f64;
+        r += color.r as f64 * weight;
+        g += color.g as f64 * weight;
+        b += color.b as f64 * weight;
+        a += weight;
+    }
+
+    if a < 0.5 { return Color::clear(); }
+    Color::new((r / a + 0.5) as u8, (g / a + 0.5) as u8, (b / a + 0.5) as u8, (a + 0.5) as u8)
+}
+
+// Doubles the size of pixel art with Scale2x, which rounds off diagonal steps instead of making blocks
+fn scale2x(pixels: &[Color], width: usize, height: usize) -> Vec<Color> {
+    let mut out: Vec<Color> = vec![Color::clear(); width * height * 4];
+    let at = |x: i64, y: i64| pixels[(i64::clamp(y, 0, height as i64 - 1) as usize) * width + i64::clamp(x, 0, width as i64 - 1) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as i64, y as i64);
+            let p = at(xi, yi);
+            let (up, right, left, down) = (at(xi, yi - 1), at(xi + 1, yi), at(xi - 1, yi), at(xi, yi + 1));
+
+            let top_left = if left == up && left != down && up != right { up } else { p };
+            let top_right = if up == right && up != left && right != down { right } else { p };
+            let bottom_left = if down == left && down != right && left != up { left } else { p };
+            let bottom_right = if right == down && right != up && down != left { down } else { p };
+
+            let idx = y * 2 * width * 2 + x * 2;
+            out[idx] = top_left;
+            out[idx + 1] = top_right;
+            out[idx + width * 2] = bottom_left;
+            out[idx + width * 2 + 1] = bottom_right;
+        }
+    }
+    out
+}
+
+// Sections kept upscaled for RotSprite before the cache starts over
+const ROTSPRITE_CACHE_SIZE: usize = 64;
+
+// Upscaled copies of an image's sections for RotSprite, so they're made once instead of on every draw and for every band.
+// Each keeps the pixels it was made from, so a section that has been drawn over since is upscaled again.
+// A copy of the image, like the one made when a shared image is changed, starts with an empty cache.
+#[derive(Default)]
+struct RotSpriteCache(Mutex<HashMap<(i64, i64, i64, i64), UpscaledSection>>);
+
+// The section's pixels and the section upscaled
+type UpscaledSection = (Vec<Color>, Arc<Vec<Color>>);
+
+impl Clone for RotSpriteCache {
+    fn clone(&self) -> Self {
+        RotSpriteCache::default()
+    }
+}
+
+impl RotSpriteCache {
+    // The section scaled up 8 times with three passes of Scale2x
+    fn upscaled(&self, image: &Rasterizer, rx: i64, ry: i64, rw: i64, rh: i64) -> Arc<Vec<Color>> {
+        let mut section: Vec<Color> = Vec::with_capacity((rw * rh) as usize);
+        for iy in 0..rh {
+            for ix in 0..rw {
+                section.push(image.pget(rx + ix, ry + iy));
+            }
+        }
+
+        let mut cache = self.0.lock().unwrap();
+        if let Some((source, upscaled)) = cache.get(&(rx, ry, rw, rh)) {
+            if *source == section { return upscaled.clone(); }
+        }
+
+        let mut pixels = section.clone();
+        let (mut width, mut height) = (rw as usize, rh as usize);
+        for _ in 0..3 {
+            pixels = scale2x(&pixels, width, height);
+            width *= 2;
+            height *= 2;
+        }
+        let upscaled = Arc::new(pixels);
+
+        if cache.len() >= ROTSPRITE_CACHE_SIZE { cache.clear(); }
+        cache.insert((rx, ry, rw, rh), (section, upscaled.clone()));
+        upscaled
+    }
 }
 
 impl Rasterizer {
@@ -275,11 +499,27 @@ impl Rasterizer {
             camera_scale: Vector2::ONE,
             camera_matrix: Matrix3::identity(),
 
+            clip_stack: Vec::new(),
+            clip: (0, 0, width as i64, height as i64),
+
+            stencil: Vec::new(),
+            stencil_mode: StencilMode::Off,
+
+            transform_stack: vec![Matrix3::identity()],
+
+            draw_space: DrawSpace::World,
+            transform: Matrix3::identity(),
+            transform_is_offset: true,
+
             draw_mode: DrawMode::Opaque,
             tint: Color::white(),
             opacity: 255,
+            image_sampling: ImageSampling::Nearest,
+            palette_remap: PaletteRemap::identity(),
 
             drawn_pixels_since_clear: 0,
+
+            rotsprite_cache: RotSpriteCache::default(),
         }
     }
 
@@ -307,14 +547,30 @@ impl Rasterizer {
                     camera_scale: Vector2::ONE,
                     camera_matrix: Matrix3::identity(),
 
+                    clip_stack: Vec::new(),
+                    clip: (0, 0, image.width as i64, image.height as i64),
+
+                    stencil: Vec::new(),
+                    stencil_mode: StencilMode::Off,
+
+                    transform_stack: vec![Matrix3::identity()],
+
+                    draw_space: DrawSpace::World,
+                    transform: Matrix3::identity(),
+                    transform_is_offset: true,
+
                     draw_mode: DrawMode::Opaque,
                     tint: Color::white(),
                     opacity: 255,
+                    image_sampling: ImageSampling::Nearest,
+                    palette_remap: PaletteRemap::identity(),
 
                     offset_x: 0,
                     offset_y: 0,
 
                     drawn_pixels_since_clear: 0,
+
+                    rotsprite_cache: RotSpriteCache::default(),
                 })
 			},
 			Err(reason) => {
@@ -329,12 +585,15 @@ impl Rasterizer {
         self.width = width;
         self.height = height;
         self.color = vec![0; width * height * 4];
+        self.stencil = Vec::new();
+        self.update_clip();
     }
 
     /// Sets the Rasterizers drawing mode for incoming pixels. Should be defined before every drawing operation.
     /// # Arguments
     /// * 'mode' - Which drawing function should the Rasterizer use.
     pub fn set_draw_mode(&mut self, mode: DrawMode) {
+        self.draw_mode = mode;
         match mode {
             DrawMode::NoOp                  => {self.pset_op = pset_noop;}
             DrawMode::NoAlpha               => {self.pset_op = pset_noalpha;}
@@ -347,6 +606,7 @@ impl Rasterizer {
             DrawMode::InvertedOpaque        => {self.pset_op = pset_inverted_opaque;}
             DrawMode::InvertedBgOpaque      => {self.pset_op = pset_inverted_bg_opaque;}
             DrawMode::InvertedBgAlpha       => {self.pset_op = pset_inverted_bg_alpha;}
+            DrawMode::Palette               => {self.pset_op = pset_palette;}
             _ => {},
         }
     }
@@ -427,6 +687,7 @@ impl Rasterizer {
     /// Clears the frame memory directly, leaving a black screen.
     pub fn clear(&mut self) {
         self.color = vec![0; self.width * self.height * 4];
+        self.clear_stencil();
         self.drawn_pixels_since_clear = 0;
         self.render_next_frame_as_animation = false;
     }
@@ -452,10 +713,98 @@ impl Rasterizer {
             });
         }
         
+        self.clear_stencil();
         self.drawn_pixels_since_clear = 0;
         self.render_next_frame_as_animation = false;
     }
 
+    /// Limits drawing to a rectangle on the screen, replacing every other clip rect.
+    pub fn set_clip(&mut self, x: i64, y: i64, w: i64, h: i64) {
+        self.clip_stack.clear();
+        self.push_clip(x, y, w, h);
+    }
+
+    /// Limits drawing to the part of a rectangle on the screen that is inside the current clip rect.
+    /// Clip rects are always in screen coordinates, the camera and transforms don't affect them.
+    pub fn push_clip(&mut self, x: i64, y: i64, w: i64, h: i64) {
+        let (mut x0, mut y0, mut x1, mut y1) = (x, y, x + i64::max(w, 0), y + i64::max(h, 0));
+        if self.clip_stack.len() > 0 {
+            let (px0, py0, px1, py1) = self.clip_stack[self.clip_stack.len() - 1];
+            x0 = i64::max(x0, px0); y0 = i64::max(y0, py0);
+            x1 = i64::max(i64::min(x1, px1), x0); y1 = i64::max(i64::min(y1, py1), y0);
+        }
+        self.clip_stack.push((x0, y0, x1, y1));
+        self.update_clip();
+    }
+
+    /// Goes back to the clip rect before the last push_clip.
+    pub fn pop_clip(&mut self) {
+        if self.clip_stack.pop().is_none() {
+            println!("ERROR - DRAWING: pop_clip was called more times than push_clip!");
+        }
+        self.update_clip();
+    }
+
+    /// Removes every clip rect so the whole screen can be drawn to.
+    pub fn clear_clip(&mut self) {
+        self.clip_stack.clear();
+        self.update_clip();
+    }
+
+    /// Rebuilds the clip bounds pset uses from the clip stack and partition offset.
+    pub fn update_clip(&mut self) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        self.clip = match self.clip_stack.last() {
+            None => (0, 0, width, height),
+            Some((x0, y0, x1, y1)) => {
+                let (ox, oy) = (self.offset_x as i64, self.offset_y as i64);
+                let cx0 = i64::clamp(x0 - ox, 0, width);
+                let cy0 = i64::clamp(y0 - oy, 0, height);
+                (cx0, cy0, i64::clamp(x1 - ox, cx0, width), i64::clamp(y1 - oy, cy0, height))
+            }
+        };
+    }
+
+    /// Sets how drawing uses the stencil buffer.
+    pub fn set_stencil_mode(&mut self, mode: StencilMode) {
+        self.stencil_mode = mode;
+    }
+
+    /// Unsets every pixel of the stencil buffer. Clearing the screen also clears the stencil.
+    pub fn clear_stencil(&mut self) {
+        self.stencil.iter_mut().for_each(|bits| *bits = 0);
+    }
+
+    /// Returns true if the stencil is set at a pixel in this buffer.
+    pub fn stencil_get(&self, x: i64, y: i64) -> bool {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 { return false; }
+        let bit = y as usize * self.width + x as usize;
+        self.stencil.get(bit >> 6).map_or(false, |bits| bits & (1 << (bit & 63)) != 0)
+    }
+
+    // Writes to or tests against the stencil, returns true if the pixel should still be drawn
+    fn stencil_pass(&mut self, x: i64, y: i64, color: Color) -> bool {
+        match self.stencil_mode {
+            StencilMode::Off => true,
+            StencilMode::Inside => self.stencil_get(x, y),
+            StencilMode::Outside => !self.stencil_get(x, y),
+            StencilMode::Write | StencilMode::Erase => {
+                if color.a >= 128 {
+                    if self.stencil.is_empty() {
+                        self.stencil = vec![0; (self.width * self.height + 63) / 64];
+                    }
+                    let bit = y as usize * self.width + x as usize;
+                    if self.stencil_mode == StencilMode::Write {
+                        self.stencil[bit >> 6] |= 1 << (bit & 63);
+                    } else {
+                        self.stencil[bit >> 6] &= !(1 << (bit & 63));
+                    }
+                }
+                false
+            }
+        }
+    }
+
     pub fn update_camera(&mut self) {
         // Camera is usually in the top left corner so we need to change the zoom scaling so it fits in the middle of the screen
         let camera_offset: Vector2 = Vector2::new(
@@ -470,27 +819,156 @@ impl Rasterizer {
 
         // Combine matricies using matrix multiplication
         self.camera_matrix = camera_mtx_p * camera_mtx_r * camera_mtx_s * camera_mtx_o;
+        self.update_transform();
     }
 
-    /// Draws a pixel to the color buffer, using the Rasterizers set DrawMode. DrawMode defaults to Opaque.
+    /// Sets whether drawing functions take world or screen coordinates.
+    pub fn set_draw_space(&mut self, space: DrawSpace) {
+        self.draw_space = space;
+        self.update_transform();
+    }
+
+    /// Rebuilds the drawing transform from the transform stack, camera matrix, draw space and partition offset.
+    pub fn update_transform(&mut self) {
+        let offset = Matrix3::translated(Vector2::new(-(self.offset_x as f64), -(self.offset_y as f64)));
+        let local = self.transform_stack.last().copied().unwrap_or(Matrix3::identity());
+        self.transform = match self.draw_space {
+            DrawSpace::World => offset * self.camera_matrix * local,
+            DrawSpace::Screen => offset * local,
+        };
+        self.transform_is_offset = self.transform.is_translation();
+    }
+
+    /// Saves the current transform so it can be restored with pop_transform.
+    pub fn push_transform(&mut self) {
+        let current = self.transform_stack.last().copied().unwrap_or(Matrix3::identity());
+        self.transform_stack.push(current);
+    }
+
+    /// Restores the transform saved by the last push_transform.
+    pub fn pop_transform(&mut self) {
+        if self.transform_stack.len() <= 1 {
+            println!("ERROR - DRAWING: pop_transform was called more times than push_transform!");
+            return;
+        }
+        self.transform_stack.pop();
+        self.update_transform();
+    }
+
+    /// Empties the transform stack and goes back to no transform at all.
+    pub fn reset_transform(&mut self) {
+        self.transform_stack = vec![Matrix3::identity()];
+        self.update_transform();
+    }
+
+    /// Applies a matrix to the current transform, so it happens before everything already on it.
+    pub fn apply_transform(&mut self, mtx: Matrix3) {
+        let last = self.transform_stack.len() - 1;
+        self.transform_stack[last] = self.transform_stack[last] * mtx;
+        self.update_transform();
+    }
+
+    pub fn translate(&mut self, x: f64, y: f64) {
+        self.apply_transform(Matrix3::translated(Vector2::new(x, y)));
+    }
+
+    pub fn rotate(&mut self, radians: f64) {
+        self.apply_transform(Matrix3::rotated(radians));
+    }
+
+    pub fn scale(&mut self, x: f64, y: f64) {
+        self.apply_transform(Matrix3::scaled(Vector2::new(x, y)));
+    }
+
+    pub fn shear(&mut self, x: f64, y: f64) {
+        self.apply_transform(Matrix3::sheared(Vector2::new(x, y)));
+    }
+
+    /// Converts a position on the screen to where it is in the world, useful for mouse picking.
+    pub fn screen_to_world(&self, x: f64, y: f64) -> Vector2 {
+        self.camera_matrix.inv().forward(Vector2::new(x, y))
+    }
+
+    /// Converts a position in the world to where the camera puts it on the screen.
+    pub fn world_to_screen(&self, x: f64, y: f64) -> Vector2 {
+        self.camera_matrix.forward(Vector2::new(x, y))
+    }
+
+    // Places a point through the drawing transform and rounds it to the nearest pixel
+    fn to_raw(&self, x: f64, y: f64) -> (i64, i64) {
+        let p = self.transform.forward(Vector2::new(x, y));
+        (f64::floor(p.x + 0.5) as i64, f64::floor(p.y + 0.5) as i64)
+    }
+
+    /// Draws a pixel through the camera. The pixel is moved by the camera but stays a single pixel when zoomed in.
     pub fn pset(&mut self, x: i64, y: i64, color: Color) {
+        let (x, y) = self.to_raw(x as f64, y as f64);
+        self.pset_raw(x, y, color);
+    }
+
+    /// Draws a pixel to the color buffer, using the Rasterizers set DrawMode. DrawMode defaults to Opaque.
+    /// Functions ending in _raw take coordinates in the color buffer itself and ignore the camera.
+    pub fn pset_raw(&mut self, x: i64, y: i64, color: Color) {
         self.drawn_pixels_since_clear += 1;
         //let x = x.rem_euclid(self.width as i64);
         //let y = y.rem_euclid(self.height as i64);
-        let idx: usize = ((y * (self.width as i64) + x) * 4) as usize;
 
-        let out_left: bool = x < 0;
-        let out_right: bool = x > (self.width) as i64 - 1;
-        let out_top: bool = y < 0;
-        let out_bottom: bool = y > (self.height) as i64 - 1;
-        let out_of_range: bool = idx > (self.width * self.height * 4) - 1;
+        // The clip bounds are never bigger than the buffer, so this is also the bounds check
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        if x < clip_x0 || x >= clip_x1 || y < clip_y0 || y >= clip_y1 { return; }
 
-        if out_of_range || out_left || out_right || out_top || out_bottom  { return; }
+        if self.stencil_mode != StencilMode::Off && !self.stencil_pass(x, y, color) { return; }
+
+        let idx: usize = ((y * (self.width as i64) + x) * 4) as usize;
 
         // We have to put paraenthesis around the fn() variables or else the compiler will think it's a method.
         (self.pset_op)(self, idx, color);
     }
 
+    /// Draws a row of pixels from x0 up to, but not including, x1 in one color.
+    /// Clips once for the whole row and blends it all at once when the DrawMode allows it, which is much faster than pset.
+    pub fn pspan_raw(&mut self, x0: i64, x1: i64, y: i64, color: Color) {
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        if y < clip_y0 || y >= clip_y1 { return; }
+        let (x0, x1) = (i64::max(x0, clip_x0), i64::min(x1, clip_x1));
+        if x0 >= x1 { return; }
+
+        if self.stencil_mode != StencilMode::Off || !has_span_op(self.draw_mode) {
+            for x in x0..x1 { self.pset_raw(x, y, color); }
+            return;
+        }
+
+        let row = (y * self.width as i64) as usize;
+        let dst = &mut self.color[(row + x0 as usize) * 4..(row + x1 as usize) * 4];
+        self.drawn_pixels_since_clear += draw_span(self.draw_mode, dst, SpanSource::Color(color), self.tint, self.opacity);
+    }
+
+    /// Draws width pixels from a row of an image, starting at image_x, image_y, with the first one at x, y.
+    /// Clear pixels are skipped, same as pimg.
+    pub fn pimgspan_raw(&mut self, image: &Rasterizer, x: i64, y: i64, image_x: i64, image_y: i64, width: i64) {
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        if y < clip_y0 || y >= clip_y1 || image_y < 0 || image_y >= image.height as i64 { return; }
+
+        // Cut the row down to what is inside both the clip rect and the image
+        let start = i64::max(i64::max(0, clip_x0 - x), -image_x);
+        let end = i64::min(i64::min(width, clip_x1 - x), image.width as i64 - image_x);
+        if start >= end { return; }
+
+        if self.stencil_mode != StencilMode::Off || !has_span_op(self.draw_mode) {
+            for i in start..end {
+                let color = image.pget(image_x + i, image_y);
+                if color.a > 0 { self.pset_raw(x + i, y, color); }
+            }
+            return;
+        }
+
+        let row = (y * self.width as i64) as usize;
+        let image_row = (image_y * image.width as i64) as usize;
+        let dst = &mut self.color[(row + (x + start) as usize) * 4..(row + (x + end) as usize) * 4];
+        let src = &image.color[(image_row + (image_x + start) as usize) * 4..(image_row + (image_x + end) as usize) * 4];
+        self.drawn_pixels_since_clear += draw_span(self.draw_mode, dst, SpanSource::Pixels(src), self.tint, self.opacity);
+    }
+
     /// Gets a color from the color buffer.
     pub fn pget(&self, x: i64, y: i64) -> Color {
 
@@ -512,18 +990,42 @@ impl Rasterizer {
         );
     }
     
-    /// Draws a line across two points
+    /// Draws a line across two points.
     pub fn pline(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Color) {
+        let (x0, y0) = self.to_raw(x0 as f64, y0 as f64);
+        let (x1, y1) = self.to_raw(x1 as f64, y1 as f64);
+        self.pline_raw(x0, y0, x1, y1, color);
+    }
+
+    /// Draws a line across two points
+    pub fn pline_raw(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Color) {
         // Cant find original source but it's been modified for Rust from C or C++
 
-        let x0 = i64::clamp(x0, 0, self.width as i64);
-        let x1 = i64::clamp(x1, 0, self.width as i64);
-        let y0 = i64::clamp(y0, 0, self.height as i64);
-        let y1 = i64::clamp(y1, 0, self.height as i64);
+        // Skip lines that miss the clip rect entirely, with a pixel to spare since the stepped pixels wander off the exact line
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        if clip_x1 <= clip_x0 || clip_y1 <= clip_y0 { return; }
+        let visible = clip_line(
+            x0 as f64, y0 as f64, x1 as f64, y1 as f64,
+            (clip_x0 - 1) as f64, (clip_y0 - 1) as f64, clip_x1 as f64, clip_y1 as f64
+        );
+        if visible.is_none() { return; }
+
+        // Lines are walked from their real ends and pset drops the pixels outside the clip rect.
+        // Cutting them at the clip rect would round their ends differently in every partition and leave seams,
+        // so only very long lines are cut, at a band that is in the same place on the screen for every partition.
+        let (ox, oy) = (self.offset_x as f64, self.offset_y as f64);
+        let guarded = clip_line(
+            x0 as f64, y0 as f64, x1 as f64, y1 as f64,
+            -ox - LINE_GUARD_BAND, -oy - LINE_GUARD_BAND, -ox + LINE_GUARD_BAND, -oy + LINE_GUARD_BAND
+        );
+        if guarded.is_none() { return; }
+        let (cx0, cy0, cx1, cy1) = guarded.unwrap();
 
         // Create local variables for moving start point
-        let mut x0 = x0;
-        let mut y0 = y0;
+        let mut x0 = f64::round(cx0) as i64;
+        let mut y0 = f64::round(cy0) as i64;
+        let x1 = f64::round(cx1) as i64;
+        let y1 = f64::round(cy1) as i64;
     
         // Get absolute x/y offset
         let dx = if x0 > x1 { x0 - x1 } else { x1 - x0 };
@@ -539,7 +1041,7 @@ impl Rasterizer {
     
         loop {
             // Set pixel
-            self.pset(x0, y0, color);
+            self.pset_raw(x0, y0, color);
     
             // Check end condition
             if x0 == x1 && y0 == y1 { break };
@@ -552,9 +1054,30 @@ impl Rasterizer {
             if err2 < dy { err += dx; y0 += sy; }
         }
     }
-    
-        /// Draws a rectangle onto the screen. Can either be filled or outlined.
+
+    /// Draws a rectangle onto the screen. Can either be filled or outlined.
+    /// A rotated or sheared camera turns the rectangle into a polygon.
     pub fn prectangle(&mut self, filled: bool, x: i64, y: i64, w: i64, h: i64, color: Color) {
+        if self.transform.is_axis_aligned() {
+            let (x0, y0) = self.to_raw(x as f64, y as f64);
+            let (x1, y1) = self.to_raw((x + w) as f64, (y + h) as f64);
+            self.prectangle_raw(filled, i64::min(x0, x1), i64::min(y0, y1), (x1 - x0).abs(), (y1 - y0).abs(), color);
+            return;
+        }
+
+        let corners: Vec<Vector2> = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)].iter()
+            .map(|(cx, cy)| self.transform.forward(Vector2::new(*cx as f64, *cy as f64)))
+            .collect();
+
+        if filled {
+            self.fill_polygon_raw(&corners, FillRule::NonZero, color);
+        } else {
+            self.polyline_raw(&corners, true, color);
+        }
+    }
+
+    /// Draws a rectangle onto the screen. Can either be filled or outlined.
+    pub fn prectangle_raw(&mut self, filled: bool, x: i64, y: i64, w: i64, h: i64, color: Color) {
         let x0 = i64::clamp(x, 0, self.width as i64);
         let x1 = i64::clamp(x + w, 0, self.width as i64);
         let y0 = i64::clamp(y, 0, self.height as i64);
@@ -562,25 +1085,48 @@ impl Rasterizer {
     
         if filled {
             for py in y0..y1 {
-                for px in x0..x1 {
-                    self.pset(px, py, color);
-                }
+                self.pspan_raw(x0, x1, py, color);
             }
         } else {
-            for tops in x0..x1+1 {
-                self.pset(tops, y0, color);
-                self.pset(tops, y1, color);
+            // The edges keep their place when they're off the buffer, pset leaves them out
+            let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+            let (x0, y0, x1, y1) = (x, y, x + w, y + h);
+            for tops in i64::max(x0, clip_x0)..i64::min(x1 + 1, clip_x1) {
+                self.pset_raw(tops, y0, color);
+                self.pset_raw(tops, y1, color);
             }
 
-            for sides in y0..y1 {
-                self.pset(x0, sides, color);
-                self.pset(x1, sides, color);
+            for sides in i64::max(y0, clip_y0)..i64::min(y1, clip_y1) {
+                self.pset_raw(x0, sides, color);
+                self.pset_raw(x1, sides, color);
             }
         }
     }
-    
+
     /// Draws a circle onto the screen. Can either be filled or outlined.
-    pub fn pcircle(&mut self, filled: bool, xc: i64, yc: i64, r: i64, color: Color) { 
+    /// A camera with uneven scaling or shearing turns the circle into an ellipse.
+    pub fn pcircle(&mut self, filled: bool, xc: i64, yc: i64, r: i64, color: Color) {
+        let scale = self.transform.uniform_scale();
+        if scale.is_some() {
+            let (rxc, ryc) = self.to_raw(xc as f64, yc as f64);
+            self.pcircle_raw(filled, rxc, ryc, f64::round(r as f64 * scale.unwrap()) as i64, color);
+            return;
+        }
+
+        let segments = circle_segments(r as f64 * self.transform.max_scale());
+        let points: Vec<Vector2> = circle_points(Vector2::new(xc as f64, yc as f64), r as f64, segments).iter()
+            .map(|p| self.transform.forward(*p))
+            .collect();
+
+        if filled {
+            self.fill_polygon_raw(&points, FillRule::NonZero, color);
+        } else {
+            self.polyline_raw(&points, true, color);
+        }
+    }
+
+    /// Draws a circle onto the screen. Can either be filled or outlined.
+    pub fn pcircle_raw(&mut self, filled: bool, xc: i64, yc: i64, r: i64, color: Color) { 
 
         let minx = i64::clamp(xc - r, 0, self.width  as i64);
         let maxx = i64::clamp((xc + r) + 1, 0, self.width  as i64);
@@ -589,25 +1135,28 @@ impl Rasterizer {
 
         if filled {
             for py in miny..maxy {
-                for px in minx..maxx {
-                    if ((px - xc) * (px - xc)) + ((py - yc) * (py - yc)) <= r * r {
-                        self.pset(px, py, color);
-                    }
-                }
+                // Widest dx where dx * dx + dy * dy <= r * r
+                let dy = py - yc;
+                let remaining = r * r - dy * dy;
+                if remaining < 0 { continue; }
+                let mut dx = f64::sqrt(remaining as f64) as i64;
+                while dx * dx > remaining { dx -= 1; }
+                while (dx + 1) * (dx + 1) <= remaining { dx += 1; }
+                self.pspan_raw(i64::max(xc - dx, minx), i64::min(xc + dx + 1, maxx), py, color);
             }
         } else {
             let mut x: i64 = 0;
             let mut y: i64 = r; 
             let mut d: i64 = 3 - 2 * r;
             
-            self.pset(xc+x, yc+y, color); 
-            self.pset(xc-x, yc+y, color);
-            self.pset(xc+x, yc-y, color); 
-            self.pset(xc-x, yc-y, color); 
-            self.pset(xc+y, yc+x, color);
-            self.pset(xc-y, yc+x, color);
-            self.pset(xc+y, yc-x, color); 
-            self.pset(xc-y, yc-x, color);
+            self.pset_raw(xc+x, yc+y, color); 
+            self.pset_raw(xc-x, yc+y, color);
+            self.pset_raw(xc+x, yc-y, color); 
+            self.pset_raw(xc-x, yc-y, color); 
+            self.pset_raw(xc+y, yc+x, color);
+            self.pset_raw(xc-y, yc+x, color);
+            self.pset_raw(xc+y, yc-x, color); 
+            self.pset_raw(xc-y, yc-x, color);
     
             while y >= x
             { 
@@ -619,269 +1168,547 @@ impl Rasterizer {
                 } else {
                     d = d + 4 * x + 6;
                 } 
-                self.pset(xc+x, yc+y, color); 
-                self.pset(xc-x, yc+y, color);
-                self.pset(xc+x, yc-y, color); 
-                self.pset(xc-x, yc-y, color); 
-                self.pset(xc+y, yc+x, color);
-                self.pset(xc-y, yc+x, color);
-                self.pset(xc+y, yc-x, color); 
-                self.pset(xc-y, yc-x, color);
+                self.pset_raw(xc+x, yc+y, color); 
+                self.pset_raw(xc-x, yc+y, color);
+                self.pset_raw(xc+x, yc-y, color); 
+                self.pset_raw(xc-x, yc-y, color); 
+                self.pset_raw(xc+y, yc+x, color);
+                self.pset_raw(xc-y, yc+x, color);
+                self.pset_raw(xc+y, yc-x, color); 
+                self.pset_raw(xc-y, yc-x, color);
             }   
         }
     }
 
-    /// Draws an image directly to the screen.
+    /// Draws an image to the screen. The image is only resampled if the camera rotates or scales it.
     pub fn pimg(&mut self, image: &Rasterizer, x: i64, y: i64) {
+        if self.transform_is_offset {
+            let (x, y) = self.to_raw(x as f64, y as f64);
+            self.pimg_raw(image, x, y);
+        } else {
+            let mtx = self.transform * Matrix3::translated(Vector2::new(x as f64, y as f64));
+            self.pimg_transformed_raw(image, mtx, 0, 0, image.width as i64, image.height as i64);
+        }
+    }
+
+    /// Draws an image directly to the screen.
+    pub fn pimg_raw(&mut self, image: &Rasterizer, x: i64, y: i64) {
         for ly in 0..image.height as i64 {
-            for lx in 0..image.width as i64 {
-                let pc = image.pget(lx, ly);
-                let px = x + lx;
-                let py = y + ly;
-                
-                // Pixel out of bounds
-                if pc.a <= 0 || (px < 0 || px > self.width as i64) || (py < 0 || py > self.height as i64) { continue; }
-
-                self.pset(x + lx, y + ly, pc);
-            }
+            self.pimgspan_raw(image, x, y + ly, 0, ly, image.width as i64);
         }
     }
 
-    /// Draws a section of an image directly to the screen.
+    /// Draws a section of an image to the screen. The section is only resampled if the camera rotates or scales it.
     pub fn pimgrect(&mut self, image: &Rasterizer, x: i64, y: i64, rx: i64, ry: i64, rw: i64, rh: i64) {
-        let range_x = i64::clamp(rx + rw, 0, self.width as i64);
-        let range_y = i64::clamp(ry + rh, 0, self.height as i64);
-        for ly in ry..range_y {
-            for lx in rx..range_x {
-                let mlx = lx.rem_euclid(image.width as i64);
-                let mly = ly.rem_euclid(image.height as i64);
-
-                let px: i64 = (x + mlx as i64) - rx as i64;
-                let py: i64 = (y + mly as i64) - ry as i64;
+        if self.transform_is_offset {
+            let (x, y) = self.to_raw(x as f64, y as f64);
+            self.pimgrect_raw(image, x, y, rx, ry, rw, rh);
+        } else {
+            let mtx = self.transform * Matrix3::translated(Vector2::new(x as f64, y as f64));
+            self.pimg_transformed_raw(image, mtx, rx, ry, rw, rh);
+        }
+    }
 
-                self.pset(px, py, image.pget(mlx as i64, mly as i64));
-            }
+    /// Draws a section of an image directly to the screen.
+    pub fn pimgrect_raw(&mut self, image: &Rasterizer, x: i64, y: i64, rx: i64, ry: i64, rw: i64, rh: i64) {
+        for ly in 0..rh {
+            self.pimgspan_raw(image, x, y + ly, rx, ry + ly, rw);
         }
     }
 
     /// Draws a rotated and scaled image to the screen using matrix multiplication.
     pub fn pimgmtx(&mut self, image: &Rasterizer, position_x: f64, position_y: f64, rotation: f64, scale_x: f64, scale_y: f64, offset_x: f64, offset_y: f64) {
+        self.pimgrectmtx(image, position_x, position_y, rotation, scale_x, scale_y, offset_x, offset_y, 0, 0, image.width as i64, image.height as i64, false, false);
+    }
+
+    /// Draws a section of an image moved, rotated and scaled, like a frame from a sprite sheet.
+    /// The offset is the pivot as a fraction of the section, so 0.5, 0.5 rotates around its center.
+    /// Flipping mirrors the section in place, the pivot stays at the same spot.
+    pub fn pimgrectmtx(&mut self, image: &Rasterizer, position_x: f64, position_y: f64, rotation: f64, scale_x: f64, scale_y: f64, offset_x: f64, offset_y: f64, rx: i64, ry: i64, rw: i64, rh: i64, flip_x: bool, flip_y: bool) {
+        if rw <= 0 || rh <= 0 { return; }
 
         // Early out if the image is going to be too small to draw
-        let area_x = image.width as f64 * scale_x;
-        let area_y = image.height as f64 * scale_y;
+        let area_x = rw as f64 * scale_x;
+        let area_y = rh as f64 * scale_y;
 
-        if area_x * area_y < 1.0 {
+        if (area_x * area_y).abs() < 1.0 {
             return;
         }
 
-        let offset_x = -lerpf(0.0, image.width as f64, offset_x);
-        let offset_y = -lerpf(0.0, image.height as f64, offset_y);
+        let offset_x = -lerpf(0.0, rw as f64, offset_x);
+        let offset_y = -lerpf(0.0, rh as f64, offset_y);
 
         let position: Vector2 = Vector2::new(position_x, position_y);
         let offset: Vector2 = Vector2::new(offset_x, offset_y);
         let scale: Vector2 = Vector2::new(scale_x, scale_y);
-        
+        let flip: Vector2 = Vector2::new(if flip_x { -1.0 } else { 1.0 }, if flip_y { -1.0 } else { 1.0 });
 
         // Get sprite matrix setup
+        let mtx_f = Matrix3::translated(Vector2::new(if flip_x { rw as f64 } else { 0.0 }, if flip_y { rh as f64 } else { 0.0 })) * Matrix3::scaled(flip);
         let mtx_o = Matrix3::translated(offset);
         let mtx_r = Matrix3::rotated(rotation);
         let mtx_p = Matrix3::translated(position);
         let mtx_s = Matrix3::scaled(scale);
 
-        let smtx = mtx_p * mtx_r * mtx_s * mtx_o;
-
-        // Combine camera matrix with sprite matrix
-        let cmtx = self.camera_matrix * smtx;
-
-        // We have to get the rotated bounding box of the rotated sprite in order to draw it correctly without blank pixels
-        let start_center: Vector2 = cmtx.forward(Vector2::ZERO);
-        let (mut sx, mut sy, mut ex, mut ey) = (start_center.x, start_center.y, start_center.x, start_center.y);
-
-        // Top-Left Corner
-        let p1: Vector2 = cmtx.forward(Vector2::ZERO);
-        sx = f64::min(sx, p1.x); sy = f64::min(sy, p1.y);
-        ex = f64::max(ex, p1.x); ey = f64::max(ey, p1.y);
-
-        // Bottom-Right Corner
-        let p2: Vector2 = cmtx.forward(Vector2::new(image.width as f64, image.height as f64));
-        sx = f64::min(sx, p2.x); sy = f64::min(sy, p2.y);
-        ex = f64::max(ex, p2.x); ey = f64::max(ey, p2.y);
-
-        // Bottom-Left Corner
-        let p3: Vector2 = cmtx.forward(Vector2::new(0.0, image.height as f64));
-        sx = f64::min(sx, p3.x); sy = f64::min(sy, p3.y);
-        ex = f64::max(ex, p3.x); ey = f64::max(ey, p3.y);
-
-        // Top-Right Corner
-        let p4: Vector2 = cmtx.forward(Vector2::new(image.width as f64, 0.0));
-        sx = f64::min(sx, p4.x); sy = f64::min(sy, p4.y);
-        ex = f64::max(ex, p4.x); ey = f64::max(ey, p4.y);
+        let smtx = mtx_p * mtx_r * mtx_s * mtx_o * mtx_f;
 
-        // Extend the bounding box by a few pixels to catch clipping errors
-        let mut rsx = sx as i64;
-        let mut rsy = sy as i64;
-        let mut rex = ex as i64+1;
-        let mut rey = ey as i64+1;
-
-        // Sprite isn't even in frame, don't draw anything
-        if (rex < 0 || rsx > self.width as i64) && (rey < 0 || rsy > self.height as i64) { return; }
-
-        // Okay but clamp the ranges in frame so we're not wasting time on stuff offscreen
+        // Combine the camera and partition offset with the sprite matrix
+        let cmtx = self.transform * smtx;
+        self.pimg_transformed_raw(image, cmtx, rx, ry, rw, rh);
+    }
 
-        rsx = i64::clamp(rsx, 0, self.width as i64);
-        rsy = i64::clamp(rsy, 0, self.height as i64);
-        rex = i64::clamp(rex, 0, self.width as i64);
-        rey = i64::clamp(rey, 0, self.height as i64);
+    /// Sets how images drawn through a rotating or scaling transform pick their colors.
+    pub fn set_image_sampling(&mut self, sampling: ImageSampling) {
+        self.image_sampling = sampling;
+    }
 
-        let cmtx_inv = cmtx.clone().inv();
+    /// Draws a section of an image through a matrix that places it in the color buffer.
+    /// The matrix maps positions inside the section (0, 0 to rw, rh) to the color buffer.
+    pub fn pimg_transformed_raw(&mut self, image: &Rasterizer, cmtx: Matrix3, rx: i64, ry: i64, rw: i64, rh: i64) {
+        if rw <= 0 || rh <= 0 { return; }
+
+        // We have to get the bounding box of the transformed section in order to draw it without blank pixels
+        let corners = [
+            cmtx.forward(Vector2::ZERO),
+            cmtx.forward(Vector2::new(rw as f64, 0.0)),
+            cmtx.forward(Vector2::new(0.0, rh as f64)),
+            cmtx.forward(Vector2::new(rw as f64, rh as f64)),
+        ];
+        let (sx, sy) = corners.iter().fold((f64::MAX, f64::MAX), |(x, y), p| (f64::min(x, p.x), f64::min(y, p.y)));
+        let (ex, ey) = corners.iter().fold((f64::MIN, f64::MIN), |(x, y), p| (f64::max(x, p.x), f64::max(y, p.y)));
+
+        // Bilinear blends in pixels just past the edges, so it needs one more pixel of room
+        let margin = if self.image_sampling == ImageSampling::Bilinear { 1 } else { 0 };
+
+        // Only look at pixels inside the clip rect so we're not wasting time on stuff offscreen
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        let rsx = i64::max(f64::floor(sx) as i64 - margin, clip_x0);
+        let rsy = i64::max(f64::floor(sy) as i64 - margin, clip_y0);
+        let rex = i64::min(f64::ceil(ex) as i64 + margin, clip_x1);
+        let rey = i64::min(f64::ceil(ey) as i64 + margin, clip_y1);
+        if rsx >= rex || rsy >= rey { return; }
+
+        let cmtx_inv = cmtx.inv();
+
+        // RotSprite samples an upscaled copy of the section, the upscaled copy is 64 times bigger so huge images are left out
+        let mut sampling = self.image_sampling;
+        let upscaled: Arc<Vec<Color>> = if sampling == ImageSampling::RotSprite && rw * rh <= 65536 {
+            image.rotsprite_cache.upscaled(image, rx, ry, rw, rh)
+        } else {
+            if sampling == ImageSampling::RotSprite { sampling = ImageSampling::Nearest; }
+            Arc::new(Vec::new())
+        };
 
 		// We can finally draw!
         for ly in rsy..rey {
             for lx in rsx..rex {
-                // We have to use the inverted compound matrix (cmtx_inv) in order to get the correct pixel data from the image.
-                let ip: Vector2 = cmtx_inv.forward(Vector2::new(lx as f64, ly as f64));
-
-                // Ceil the transformed pixel positions to fix the colot pullingg
-                let color: Color = image.pget(f64::ceil(ip.x) as i64, f64::ceil(ip.y) as i64);
+                // The inverted compound matrix takes the center of the screen pixel back into the image
+                let ip: Vector2 = cmtx_inv.forward(Vector2::new(lx as f64 + 0.5, ly as f64 + 0.5));
+
+                let color: Color = match sampling {
+                    ImageSampling::Nearest => sample_section(image, rx, ry, rw, rh, f64::floor(ip.x) as i64, f64::floor(ip.y) as i64),
+                    ImageSampling::Bilinear => sample_bilinear(image, rx, ry, rw, rh, ip.x, ip.y),
+                    ImageSampling::RotSprite => {
+                        let (ux, uy) = (f64::floor(ip.x * 8.0) as i64, f64::floor(ip.y * 8.0) as i64);
+                        if ux < 0 || uy < 0 || ux >= rw * 8 || uy >= rh * 8 { continue; }
+                        upscaled[(uy * rw * 8 + ux) as usize]
+                    },
+                };
 
                 // We skip drawing entirely if the alpha is zero.
                 // Otherwise leaves weird grey box
-                if color.a <= 0 { continue; }
-                self.pset(lx as i64, ly as i64, color);
+                if color.a == 0 { continue; }
+                self.pset_raw(lx, ly, color);
             }
         }
     }
 
-    /// Draws text directly to the screen using a provided font.
+    /// Draws text directly to the screen using a provided font. Lines wrap at word boundaries when a wrap width is given.
+    /// Proportional fonts (like ones loaded from TTF files) use each glyphs own offset, advance and kerning.
     pub fn pprint(&mut self, font: &Font, text: String, x: i64, y: i64, newline_space: i64, wrap_width: Option<u32>) {
-        let mut jumpx: i64 = 0;
-        let mut jumpy: i64 = 0;
-        let chars: Vec<char> = text.chars().collect();
+        let layout = layout_text(font, &text, wrap_width.map(|w| w as i64), TextAlign::Left, newline_space, false);
+        self.ptext(font, &layout, x, y, 0.0);
+    }
 
-        for i in 0..chars.len() {
-            
-            if chars[i] == '\n' { jumpy += font.glyph_height as i64 + newline_space; jumpx = 0; continue; }
-            if chars[i] == ' ' { jumpx += font.glyph_width as i64; continue; }
-            for j in 0..font.glyphidx.len() {
-                if font.glyphidx[j] == chars[i] {
-                    let rectx: i64 = (j as i64 * font.glyph_width as i64) % (font.fontimg.width as i64);
-                    let recty: i64 = ((j as i64 * font.glyph_width as i64) / font.fontimg.width as i64) * font.glyph_height as i64;
-                    let rectw: i64 = font.glyph_width as i64;
-                    let recth: i64 = font.glyph_height as i64;
-                    
-                    self.pimgrect(&font.fontimg, x + jumpx as i64, y + jumpy as i64, rectx, recty, rectw, recth);
-                    
-
-                    jumpx += font.glyph_width as i64 + font.glyph_spacing as i64;
-                }
-            }
-            if wrap_width.is_some() && (jumpx as u32) > wrap_width.unwrap() { jumpy += font.glyph_height as i64 + newline_space; jumpx = 0; }
+    /// Draws text that has already been laid out. Time drives the wave and shake markup effects.
+    pub fn ptext(&mut self, font: &Font, layout: &TextLayout, x: i64, y: i64, time: f64) {
+        let tint = self.tint;
+
+        for glyph in &layout.glyphs {
+            let (gx, gy) = glyph.position(font, x, y, time);
+            self.tint = match glyph.style.color {
+                Some(color) => color * tint,
+                None => tint,
+            };
+            self.pglyph(font.chain(glyph.font), glyph.glyph, gx, gy);
         }
+
+        self.tint = tint;
     }
 
-    /// Draws a triangle directly to the screen.
-    /// Algorithm written by nusan for the PICO-8 3D Renderer 
+    /// Draws a single glyph from a font, where x and y are the cursor position at the top of the line.
+    pub fn pglyph(&mut self, font: &Font, glyph: usize, x: i64, y: i64) {
+        let rect = font.glyph_rect(glyph);
+        if rect.is_some() {
+            let rect = rect.unwrap();
+            self.pimgrect(&font.pages[rect.page], x + rect.offset_x, y + rect.offset_y, rect.x, rect.y, rect.w, rect.h);
+        }
+    }
+
+    /// Draws a triangle onto the screen. Can either be filled or outlined.
     pub fn ptriangle(&mut self, filled: bool, x1: i64, y1: i64, x2: i64, y2: i64, x3: i64, y3: i64, color: Color) {
+        let (x1, y1) = self.to_raw(x1 as f64, y1 as f64);
+        let (x2, y2) = self.to_raw(x2 as f64, y2 as f64);
+        let (x3, y3) = self.to_raw(x3 as f64, y3 as f64);
+        self.ptriangle_raw(filled, x1, y1, x2, y2, x3, y3, color);
+    }
+
+    /// Draws a triangle directly to the screen.
+    pub fn ptriangle_raw(&mut self, filled: bool, x1: i64, y1: i64, x2: i64, y2: i64, x3: i64, y3: i64, color: Color) {
         if filled {
-            // Collect pixels from lines without drawing to the screen
-            let vl12 = self.cline(x1, y1, x2, y2);
-            let vl23 = self.cline(x2, y2, x3, y3);
-            let vl31 = self.cline(x3, y3, x1, y1);
-
-            let mut edge_pixels: Vec<(i64, i64)> = Vec::new();
-            edge_pixels.extend(vl12);
-            edge_pixels.extend(vl23);
-            edge_pixels.extend(vl31);
-  
-
-            let mut scanline_rows: Vec<Vec<(i64, i64)>> = vec![Vec::new(); self.height];
-
-            for p in edge_pixels {
-                if p.1 >= 0 && p.1 < self.height as i64{
-                    scanline_rows[p.1 as usize].push(p);
+            let vertices = [
+                Vertex::new(x1 as f64, y1 as f64, 0.0, 0.0, color),
+                Vertex::new(x2 as f64, y2 as f64, 0.0, 0.0, color),
+                Vertex::new(x3 as f64, y3 as f64, 0.0, 0.0, color),
+            ];
+            self.ptriangle_textured_raw(vertices, None, TextureMapping::Affine);
+        } else {
+            self.pline_raw(x1, y1, x2, y2, color);
+            self.pline_raw(x1, y1, x3, y3, color);
+            self.pline_raw(x2, y2, x3, y3, color);
+        }
+    }
+
+    /// Fills a triangle that is already in the color buffer, blending the vertex colors across it and multiplying them with the texture if there is one.
+    /// Pixels are filled when their centers are inside. Pixels exactly on an edge belong to the triangle on its top or left,
+    /// so triangles sharing an edge never leave gaps or draw a pixel twice.
+    pub fn ptriangle_textured_raw(&mut self, vertices: [Vertex; 3], texture: Option<&Rasterizer>, mapping: TextureMapping) {
+        let [v0, mut v1, mut v2] = vertices;
+        let mut area = edge2(v0.position.x, v0.position.y, v1.position.x, v1.position.y, v2.position.x, v2.position.y);
+        if area == 0.0 || !area.is_finite() { return; }
+        if area < 0.0 {
+            std::mem::swap(&mut v1, &mut v2);
+            area = -area;
+        }
+        let (p0, p1, p2) = (v0.position, v1.position, v2.position);
+
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        let min_x = i64::max(f64::floor(p0.x.min(p1.x).min(p2.x)) as i64, clip_x0);
+        let max_x = i64::min(f64::ceil(p0.x.max(p1.x).max(p2.x)) as i64, clip_x1);
+        let min_y = i64::max(f64::floor(p0.y.min(p1.y).min(p2.y)) as i64, clip_y0);
+        let max_y = i64::min(f64::ceil(p0.y.max(p1.y).max(p2.y)) as i64, clip_y1);
+        if min_x >= max_x || min_y >= max_y { return; }
+
+        let top_left = |a: Vector2, b: Vector2| (a.y == b.y && b.x > a.x) || b.y < a.y;
+        let owns = [top_left(p1, p2), top_left(p2, p0), top_left(p0, p1)];
+        let inside = |weight: f64, owns_edge: bool| weight > 0.0 || (weight == 0.0 && owns_edge);
+
+        // One flat color doesn't need anything interpolated
+        let flat = texture.is_none() && v0.color == v1.color && v1.color == v2.color;
+        let inv_w = [1.0 / v0.w, 1.0 / v1.w, 1.0 / v2.w];
+        let (texture_width, texture_height) = texture.map_or((0, 0), |t| (t.width as i64, t.height as i64));
+        if texture.is_some() && (texture_width == 0 || texture_height == 0) { return; }
+
+        for py in min_y..max_y {
+            let sample_y = py as f64 + 0.5;
+            let sample_x = min_x as f64 + 0.5;
+
+            // Edge functions are linear, so each row only needs to step them along x
+            let mut e0 = edge2(p1.x, p1.y, p2.x, p2.y, sample_x, sample_y);
+            let mut e1 = edge2(p2.x, p2.y, p0.x, p0.y, sample_x, sample_y);
+            let mut e2 = edge2(p0.x, p0.y, p1.x, p1.y, sample_x, sample_y);
+            let (step0, step1, step2) = (p1.y - p2.y, p2.y - p0.y, p0.y - p1.y);
+
+            // Triangles are convex, so the covered pixels of a flat row are drawn as one span afterwards
+            let (mut span_x0, mut span_x1) = (max_x, min_x);
+            for px in min_x..max_x {
+                if inside(e0, owns[0]) && inside(e1, owns[1]) && inside(e2, owns[2]) {
+                    if flat {
+                        span_x0 = i64::min(span_x0, px);
+                        span_x1 = px + 1;
+                    } else {
+                        let (mut b0, mut b1, mut b2) = (e0 / area, e1 / area, e2 / area);
+                        if mapping == TextureMapping::Perspective {
+                            let (q0, q1, q2) = (b0 * inv_w[0], b1 * inv_w[1], b2 * inv_w[2]);
+                            let sum = q0 + q1 + q2;
+                            b0 = q0 / sum; b1 = q1 / sum; b2 = q2 / sum;
+                        }
+
+                        let channel = |c0: u8, c1: u8, c2: u8| f64::clamp(c0 as f64 * b0 + c1 as f64 * b1 + c2 as f64 * b2 + 0.5, 0.0, 255.0) as u8;
+                        let mut color = Color::new(
+                            channel(v0.color.r, v1.color.r, v2.color.r),
+                            channel(v0.color.g, v1.color.g, v2.color.g),
+                            channel(v0.color.b, v1.color.b, v2.color.b),
+                            channel(v0.color.a, v1.color.a, v2.color.a),
+                        );
+
+                        if texture.is_some() {
+                            let u = v0.uv.x * b0 + v1.uv.x * b1 + v2.uv.x * b2;
+                            let v = v0.uv.y * b0 + v1.uv.y * b1 + v2.uv.y * b2;
+                            let tx = (f64::floor(u * texture_width as f64) as i64).rem_euclid(texture_width);
+                            let ty = (f64::floor(v * texture_height as f64) as i64).rem_euclid(texture_height);
+                            let idx = ((ty * texture_width + tx) * 4) as usize;
+                            let texel = &texture.unwrap().color[idx..idx + 4];
+
+                            let mul = |a: u8, b: u8| ((a as u32 * b as u32 + 255) >> 8) as u8;
+                            color = Color::new(mul(texel[0], color.r), mul(texel[1], color.g), mul(texel[2], color.b), mul(texel[3], color.a));
+                        }
+
+                        self.pset_raw(px, py, color);
+                    }
                 }
+                e0 += step0;
+                e1 += step1;
+                e2 += step2;
             }
+            if span_x0 < span_x1 { self.pspan_raw(span_x0, span_x1, py, v0.color); }
+        }
+    }
 
-            for row in scanline_rows {
-                if row.len() == 0 { continue; }
-                let height = row[0].1;
-                self.pline(row[0].0, height, row[row.len()-1].0, height, color);
+    /// Draws triangles through the camera, every three indices pick the corners of one triangle.
+    /// Without a texture the vertex colors are blended across each triangle.
+    pub fn pmesh(&mut self, texture: Option<&Rasterizer>, vertices: &[Vertex], indices: &[usize], mapping: TextureMapping) {
+        if indices.iter().any(|i| *i >= vertices.len()) {
+            println!("ERROR - DRAWING: Mesh has indices past its {} vertices!", vertices.len());
+            return;
+        }
+
+        for triangle in indices.chunks_exact(3) {
+            let mut corners = [vertices[triangle[0]], vertices[triangle[1]], vertices[triangle[2]]];
+            for corner in corners.iter_mut() {
+                corner.position = self.transform.forward(corner.position);
             }
-        } else {
-            self.pline(x1, y1, x2, y2, color);
-            self.pline(x1, y1, x3, y3, color);
-            self.pline(x2, y2, x3, y3, color);
+            self.ptriangle_textured_raw(corners, texture, mapping);
         }
     }
 
     /// Draws a quadratic beizer curve onto the screen.
     pub fn pbeizer(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, mx: i64, my: i64, color: Color) {
-        let mut step: f64 = 0.0;
+        let curve = Curve::quadratic(Vector2::new(x0 as f64, y0 as f64), Vector2::new(mx as f64, my as f64), Vector2::new(x1 as f64, y1 as f64));
+        self.pcurve(&curve, 1.0, color);
+    }
 
-        // Get the maximal number of pixels we will need to use and get its inverse as a step size.
-        // Otherwise we don't know how many pixels we will need to draw
-        let stride_c1 = self.cline(x0, y0, mx, my).len() as f64;
-        let stride_c2 = self.cline(mx, my, x1, y1).len() as f64;
+    /// Draws a quadratic beizer curve directly to the screen.
+    pub fn pbeizer_raw(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, mx: i64, my: i64, color: Color) {
+        let curve = Curve::quadratic(Vector2::new(x0 as f64, y0 as f64), Vector2::new(mx as f64, my as f64), Vector2::new(x1 as f64, y1 as f64));
+        self.polyline_raw(&curve.flatten(0.25), false, color);
+    }
 
-        let stride: f64 = (1.0 / (stride_c1 + stride_c2)) * 0.5;
+    /// Draws a cubic beizer curve onto the screen.
+    pub fn pbeizer_cubic(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, mx0: i64, my0: i64, mx1: i64, my1: i64, color: Color) {
+        let curve = Curve::cubic(
+            Vector2::new(x0 as f64, y0 as f64), Vector2::new(mx0 as f64, my0 as f64),
+            Vector2::new(mx1 as f64, my1 as f64), Vector2::new(x1 as f64, y1 as f64)
+        );
+        self.pcurve(&curve, 1.0, color);
+    }
+
+    /// Draws a cubic beizer curve directly to the screen.
+    pub fn pbeizer_cubic_raw(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, mx0: i64, my0: i64, mx1: i64, my1: i64, color: Color) {
+        let curve = Curve::cubic(
+            Vector2::new(x0 as f64, y0 as f64), Vector2::new(mx0 as f64, my0 as f64),
+            Vector2::new(mx1 as f64, my1 as f64), Vector2::new(x1 as f64, y1 as f64)
+        );
+        self.polyline_raw(&curve.flatten(0.25), false, color);
+    }
 
-        let x0 = x0 as f64;
-        let y0 = x0 as f64;
-        let x1 = x1 as f64;
-        let y1 = y1 as f64;
-        let mx = mx as f64;
-        let my = my as f64;
+    /// How closely curves are flattened before drawing, fine enough that zooming in still keeps them within a quarter pixel.
+    pub fn curve_tolerance(&self) -> f64 {
+        0.25 / f64::max(self.transform.max_scale(), 1e-9)
+    }
 
-        loop {
-            if step > 1.0 { break; }
+    /// Draws any curve through the camera, with a width like ppolyline.
+    pub fn pcurve(&mut self, curve: &Curve, width: f64, color: Color) {
+        let points = curve.flatten(self.curve_tolerance());
+        self.ppolyline(&points, width, curve.closed, LineJoin::Miter, LineCap::Butt, color);
+    }
 
-            let px0 = f64::clamp(lerpf(x0, mx, step), 0.0, self.width as f64);
-            let py0 = f64::clamp(lerpf(y0, my, step), 0.0, self.height as f64);
+    /// Draws part of a circle from the start angle to the end angle in radians.
+    pub fn parc(&mut self, xc: f64, yc: f64, r: f64, start_angle: f64, end_angle: f64, width: f64, color: Color) {
+        self.pcurve(&Curve::arc(Vector2::new(xc, yc), r, start_angle, end_angle), width, color);
+    }
 
-            let px1 = f64::clamp(lerpf(px0, x1, step), 0.0, self.width as f64);
-            let py1 = f64::clamp(lerpf(py0, y1, step), 0.0, self.height as f64);
+    /// Draws an ellipse rotated in radians. Can either be filled or outlined.
+    pub fn pellipse(&mut self, filled: bool, xc: f64, yc: f64, rx: f64, ry: f64, rotation: f64, width: f64, color: Color) {
+        let curve = Curve::ellipse(Vector2::new(xc, yc), rx, ry, rotation);
+        if filled {
+            let points = curve.flatten(self.curve_tolerance());
+            self.ppolygon(true, &points, FillRule::NonZero, color);
+        } else {
+            self.pcurve(&curve, width, color);
+        }
+    }
 
-            self.pset(px1 as i64, py1 as i64, color);
-            step += stride;
+    /// Draws lines between points that are already in the color buffer, used for shapes the camera has turned into polygons.
+    pub fn polyline_raw(&mut self, points: &[Vector2], closed: bool, color: Color) {
+        if points.len() < 2 { return; }
+
+        let round = |p: Vector2| (f64::floor(p.x + 0.5) as i64, f64::floor(p.y + 0.5) as i64);
+        let segments = if closed { points.len() } else { points.len() - 1 };
+
+        for i in 0..segments {
+            let (x0, y0) = round(points[i]);
+            let (x1, y1) = round(points[(i + 1) % points.len()]);
+            self.pline_raw(x0, y0, x1, y1, color);
         }
     }
 
-    /// Draws a cubic beizer curve onto the screen.
-    pub fn pbeizer_cubic(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, mx0: i64, my0: i64, mx1: i64, my1: i64, color: Color) {
-        let mut step: f64 = 0.0;
+    /// Fills a polygon that is already in the color buffer with scanlines, sampling at pixel centers.
+    pub fn fill_polygon_raw(&mut self, points: &[Vector2], rule: FillRule, color: Color) {
+        self.fill_contours_raw(&[points], rule, color);
+    }
+
+    /// Fills several outlines as one shape, so holes and overlaps follow the fill rule instead of drawing twice.
+    pub fn fill_contours_raw(&mut self, contours: &[&[Vector2]], rule: FillRule, color: Color) {
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        let points = contours.iter().flat_map(|c| c.iter());
+        let (min_y, max_y) = points.fold((f64::MAX, f64::MIN), |(min, max), p| (f64::min(min, p.y), f64::max(max, p.y)));
+        if min_y > max_y { return; }
+
+        let start_y = i64::max(f64::ceil(min_y - 0.5) as i64, clip_y0);
+        let end_y = i64::min(f64::ceil(max_y - 0.5) as i64, clip_y1);
+
+        // Each crossing remembers if its edge goes down or up, so the winding number can be counted
+        let mut crossings: Vec<(f64, i32)> = Vec::new();
+        for py in start_y..end_y {
+            let sample_y = py as f64 + 0.5;
+
+            crossings.clear();
+            for contour in contours {
+                for i in 0..contour.len() {
+                    let a = contour[i];
+                    let b = contour[(i + 1) % contour.len()];
+                    if a.y <= sample_y && b.y > sample_y {
+                        crossings.push((a.x + (sample_y - a.y) / (b.y - a.y) * (b.x - a.x), 1));
+                    } else if b.y <= sample_y && a.y > sample_y {
+                        crossings.push((a.x + (sample_y - a.y) / (b.y - a.y) * (b.x - a.x), -1));
+                    }
+                }
+            }
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+            let mut winding = 0;
+            for i in 0..crossings.len() {
+                winding += crossings[i].1;
+                let inside = match rule {
+                    FillRule::EvenOdd => winding % 2 != 0,
+                    FillRule::NonZero => winding != 0,
+                };
+                if !inside || i + 1 >= crossings.len() { continue; }
+
+                let start_x = i64::max(f64::ceil(crossings[i].0 - 0.5) as i64, clip_x0);
+                let end_x = i64::min(f64::ceil(crossings[i + 1].0 - 0.5) as i64, clip_x1);
+                self.pspan_raw(start_x, end_x, py, color);
+            }
+        }
+    }
 
-        // Get the maximal number of pixels we will need to use and get its inverse as a step size.
-        // Otherwise we don't know how many pixels we will need to draw
-        let stride_c1: f64 = self.cline(x0, y0, mx0, my0).len() as f64;
-        let stride_c2: f64 = self.cline(mx0, my0, mx1, my1).len() as f64;
-        let stride_c3: f64 = self.cline(mx1, my1, x1, y1).len() as f64;
+    /// Draws an anti-aliased line through the camera, see pline_aa_raw.
+    pub fn pline_aa(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: Color) {
+        let p0 = self.transform.forward(Vector2::new(x0, y0));
+        let p1 = self.transform.forward(Vector2::new(x1, y1));
+        self.pline_aa_raw(p0.x, p0.y, p1.x, p1.y, color);
+    }
 
-        let stride = (1.0 / (stride_c1 + stride_c2 + stride_c3)) * 0.5;
+    /// Draws an anti-aliased line with Xiaolin Wu's algorithm, whole numbers are the centers of pixels.
+    /// The edges fade out through the colors alpha, so it needs an alpha blending DrawMode to look smooth.
+    pub fn pline_aa_raw(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: Color) {
+        // Wu's line touches one pixel either side of itself and draws its end points fainter,
+        // so clip with enough room that an end that was cut off never lands inside the clip rect
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = self.clip;
+        let clipped = clip_line(x0, y0, x1, y1, clip_x0 as f64 - 2.0, clip_y0 as f64 - 2.0, clip_x1 as f64 + 1.0, clip_y1 as f64 + 1.0);
+        if clipped.is_none() { return; }
+        let (mut x0, mut y0, mut x1, mut y1) = clipped.unwrap();
+
+        // Always step along the longer axis, from left to right
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
 
-        let x0 = x0 as f64;
-        let y0 = x0 as f64;
-        let x1 = x1 as f64;
-        let y1 = y1 as f64;
-        let mx0 = mx0 as f64;
-        let my0 = my0 as f64;
-        let mx1 = mx1 as f64;
-        let my1 = my1 as f64;
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+        let fpart = |v: f64| v - v.floor();
+
+        // First end point
+        let x_start = f64::floor(x0 + 0.5);
+        let y_start = y0 + gradient * (x_start - x0);
+        let x_gap = 1.0 - fpart(x0 + 0.5);
+        self.plot_aa(steep, x_start as i64, y_start.floor() as i64, (1.0 - fpart(y_start)) * x_gap, color);
+        self.plot_aa(steep, x_start as i64, y_start.floor() as i64 + 1, fpart(y_start) * x_gap, color);
+
+        // Last end point
+        let x_end = f64::floor(x1 + 0.5);
+        let y_end = y1 + gradient * (x_end - x1);
+        let x_gap = fpart(x1 + 0.5);
+        self.plot_aa(steep, x_end as i64, y_end.floor() as i64, (1.0 - fpart(y_end)) * x_gap, color);
+        self.plot_aa(steep, x_end as i64, y_end.floor() as i64 + 1, fpart(y_end) * x_gap, color);
+
+        let mut y = y_start + gradient;
+        for x in (x_start as i64 + 1)..(x_end as i64) {
+            self.plot_aa(steep, x, y.floor() as i64, 1.0 - fpart(y), color);
+            self.plot_aa(steep, x, y.floor() as i64 + 1, fpart(y), color);
+            y += gradient;
+        }
+    }
 
-        loop {
-            if step > 1.0 { break; }
+    // Draws one pixel of an anti-aliased line, with the coverage scaling the alpha
+    fn plot_aa(&mut self, steep: bool, x: i64, y: i64, coverage: f64, color: Color) {
+        let alpha = f64::round(color.a as f64 * f64::clamp(coverage, 0.0, 1.0)) as u8;
+        if alpha == 0 { return; }
+        let (x, y) = if steep { (y, x) } else { (x, y) };
+        self.pset_raw(x, y, Color::new(color.r, color.g, color.b, alpha));
+    }
 
-            let px0 = f64::clamp(lerpf(x0, mx0, step), 0.0, self.width as f64);
-            let py0 = f64::clamp(lerpf(y0, my0, step), 0.0, self.height as f64);
+    /// Draws a line with a width through the camera. Whole numbers are the centers of pixels, like pline.
+    pub fn pline_thick(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, width: f64, cap: LineCap, color: Color) {
+        self.ppolyline(&[Vector2::new(x0, y0), Vector2::new(x1, y1)], width, false, LineJoin::Miter, cap, color);
+    }
 
-            let px1 = f64::clamp(lerpf(px0, mx1, step), 0.0, self.width as f64);
-            let py1 = f64::clamp(lerpf(py0, my1, step), 0.0, self.height as f64);
+    /// Draws lines between points through the camera. Lines 1 pixel wide or thinner use pline,
+    /// wider lines are turned into polygons with caps and joins and filled as one shape.
+    pub fn ppolyline(&mut self, points: &[Vector2], width: f64, closed: bool, join: LineJoin, cap: LineCap, color: Color) {
+        if width <= 1.0 {
+            let raw: Vec<Vector2> = points.iter().map(|p| self.transform.forward(*p)).collect();
+            self.polyline_raw(&raw, closed, color);
+            return;
+        }
+
+        let segments = circle_segments(width / 2.0 * self.transform.max_scale());
+        let contours = stroke_polyline(points, width, closed, join, cap, segments);
+
+        // Offset by half a pixel so the line is centered on the pixels its points land on
+        let raw: Vec<Vec<Vector2>> = contours.iter().map(|contour| {
+            contour.iter().map(|p| self.transform.forward(*p) + Vector2::new(0.5, 0.5)).collect()
+        }).collect();
+        let raw_refs: Vec<&[Vector2]> = raw.iter().map(|c| c.as_slice()).collect();
+        self.fill_contours_raw(&raw_refs, FillRule::NonZero, color);
+    }
 
-            let px2 = f64::clamp(lerpf(px1, x1, step), 0.0, self.width as f64);
-            let py2 = f64::clamp(lerpf(py1, y1, step), 0.0, self.height as f64);
+    /// Draws anti-aliased lines between points through the camera, see pline_aa_raw.
+    pub fn ppolyline_aa(&mut self, points: &[Vector2], closed: bool, color: Color) {
+        if points.len() < 2 { return; }
+        let raw: Vec<Vector2> = points.iter().map(|p| self.transform.forward(*p)).collect();
+        let segments = if closed { raw.len() } else { raw.len() - 1 };
+        for i in 0..segments {
+            let (a, b) = (raw[i], raw[(i + 1) % raw.len()]);
+            self.pline_aa_raw(a.x, a.y, b.x, b.y, color);
+        }
+    }
 
-            self.pset(px2 as i64, py2 as i64, color);
-            step += stride;
+    /// Draws a polygon through the camera, it can be concave or cross itself. Can either be filled or outlined.
+    /// Filled polygons cover pixels like prectangle does, so a square from 0 to 4 fills 4x4 pixels.
+    pub fn ppolygon(&mut self, filled: bool, points: &[Vector2], rule: FillRule, color: Color) {
+        let raw: Vec<Vector2> = points.iter().map(|p| self.transform.forward(*p)).collect();
+        if filled {
+            self.fill_polygon_raw(&raw, rule, color);
+        } else {
+            self.polyline_raw(&raw, true, color);
         }
     }
 
@@ -0,0 +1,43 @@
+use std::rc::Rc;
+
+use mlua::prelude::*;
+
+use crate::transition::Transition;
+
+/// A scene is a Lua table kept in the registry. Shared so a scene can still be drawn by a transition after it leaves the stack.
+pub type SceneKey = Rc<LuaRegistryKey>;
+
+/// A transition between the scenes that were on screen and the ones that are now.
+pub struct SceneTransition {
+	pub transition: Transition,
+	/// The scenes that were on screen before the change, drawn into the transition's old frame every frame.
+	pub from: Vec<SceneKey>,
+	/// Scenes that left the stack. They get exit once the transition is done, so they can keep drawing until then.
+	pub exiting: Vec<SceneKey>,
+}
+
+/// The scene stack. Only the top scene is updated, scenes under an overlay are drawn under it but paused.
+pub struct SceneManager {
+	pub stack: Vec<SceneKey>,
+	pub transition: Option<SceneTransition>,
+}
+
+impl SceneManager {
+	pub fn new() -> SceneManager {
+		SceneManager { stack: Vec::new(), transition: None }
+	}
+
+	pub fn top(&self) -> Option<SceneKey> {
+		self.stack.last().cloned()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.stack.is_empty()
+	}
+}
+
+impl Default for SceneManager {
+	fn default() -> Self {
+		SceneManager::new()
+	}
+}
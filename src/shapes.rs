@@ -0,0 +1,212 @@
+use crate::vector2::*;
+
+/// How the ends of thick lines are drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineCap {
+	/// Stops exactly at the end point.
+	Butt,
+	/// Sticks out past the end point by half the line width.
+	Square,
+	Round,
+}
+
+impl LineCap {
+	pub fn from_str(name: &str) -> LineCap {
+		match name {
+			"square" 	=> LineCap::Square,
+			"round" 	=> LineCap::Round,
+			_ 			=> LineCap::Butt,
+		}
+	}
+}
+
+/// How the corners of thick polylines are drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineJoin {
+	/// A sharp corner, turns into a bevel when the corner is too sharp.
+	Miter,
+	Bevel,
+	Round,
+}
+
+impl LineJoin {
+	pub fn from_str(name: &str) -> LineJoin {
+		match name {
+			"bevel" 	=> LineJoin::Bevel,
+			"round" 	=> LineJoin::Round,
+			_ 			=> LineJoin::Miter,
+		}
+	}
+}
+
+/// Decides which parts of a polygon that crosses itself are filled.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FillRule {
+	/// Areas inside an odd number of edges are filled, so overlaps leave holes.
+	EvenOdd,
+	/// Every area the outline goes around is filled.
+	NonZero,
+}
+
+impl FillRule {
+	pub fn from_str(name: &str) -> FillRule {
+		match name {
+			"evenodd" 	=> FillRule::EvenOdd,
+			_ 			=> FillRule::NonZero,
+		}
+	}
+}
+
+// Miters longer than this many half widths become bevels, same as the SVG default
+const MITER_LIMIT: f64 = 4.0;
+
+const OUT_LEFT: u8 = 1;
+const OUT_RIGHT: u8 = 2;
+const OUT_TOP: u8 = 4;
+const OUT_BOTTOM: u8 = 8;
+
+fn outcode(x: f64, y: f64, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> u8 {
+	let mut code = 0;
+	if x < min_x { code |= OUT_LEFT; } else if x > max_x { code |= OUT_RIGHT; }
+	if y < min_y { code |= OUT_TOP; } else if y > max_y { code |= OUT_BOTTOM; }
+	code
+}
+
+/// Cuts a line down to the part inside a rectangle with Cohen-Sutherland, keeping its slope.
+/// Returns None if none of the line is inside.
+pub fn clip_line(x0: f64, y0: f64, x1: f64, y1: f64, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Option<(f64, f64, f64, f64)> {
+	let (mut x0, mut y0, mut x1, mut y1) = (x0, y0, x1, y1);
+	let mut code0 = outcode(x0, y0, min_x, min_y, max_x, max_y);
+	let mut code1 = outcode(x1, y1, min_x, min_y, max_x, max_y);
+
+	loop {
+		if code0 | code1 == 0 { return Some((x0, y0, x1, y1)); }
+		if code0 & code1 != 0 { return None; }
+
+		// Move whichever end is outside onto the edge it is outside of
+		let code = if code0 != 0 { code0 } else { code1 };
+		let (x, y) = if code & OUT_TOP != 0 {
+			(x0 + (x1 - x0) * (min_y - y0) / (y1 - y0), min_y)
+		} else if code & OUT_BOTTOM != 0 {
+			(x0 + (x1 - x0) * (max_y - y0) / (y1 - y0), max_y)
+		} else if code & OUT_RIGHT != 0 {
+			(max_x, y0 + (y1 - y0) * (max_x - x0) / (x1 - x0))
+		} else {
+			(min_x, y0 + (y1 - y0) * (min_x - x0) / (x1 - x0))
+		};
+
+		if code == code0 {
+			x0 = x; y0 = y;
+			code0 = outcode(x0, y0, min_x, min_y, max_x, max_y);
+		} else {
+			x1 = x; y1 = y;
+			code1 = outcode(x1, y1, min_x, min_y, max_x, max_y);
+		}
+	}
+}
+
+/// Returns how many segments a circle needs so each one is only a few pixels long.
+pub fn circle_segments(radius: f64) -> usize {
+	usize::clamp((radius * std::f64::consts::TAU / 4.0) as usize, 12, 256)
+}
+
+/// Returns the furthest the polygons from stroke_polyline can reach past its points, which is the longest miter allowed.
+pub fn stroke_reach(width: f64) -> f64 {
+	width / 2.0 * MITER_LIMIT
+}
+
+/// Returns the points around a circle, going the same way as the polygons from stroke_polyline.
+pub fn circle_points(center: Vector2, radius: f64, segments: usize) -> Vec<Vector2> {
+	(0..segments).map(|i| {
+		let angle = i as f64 / segments as f64 * std::f64::consts::TAU;
+		Vector2::new(center.x + angle.cos() * radius, center.y + angle.sin() * radius)
+	}).collect()
+}
+
+/// Returns twice the signed area of a polygon, the sign tells which way it winds.
+pub fn polygon_area2(points: &[Vector2]) -> f64 {
+	(0..points.len()).fold(0.0, |area, i| area + Vector2::cross(points[i], points[(i + 1) % points.len()]))
+}
+
+/// Turns a polyline into polygons covering it at a width, with caps on the ends and joins at the corners.
+/// The polygons overlap but all wind the same way, so they should be filled together with FillRule::NonZero.
+/// Round caps and joins use round_segments points per circle.
+pub fn stroke_polyline(points: &[Vector2], width: f64, closed: bool, join: LineJoin, cap: LineCap, round_segments: usize) -> Vec<Vec<Vector2>> {
+	let mut contours: Vec<Vec<Vector2>> = Vec::new();
+	let half = width / 2.0;
+	if half <= 0.0 || points.is_empty() { return contours; }
+
+	// Repeated points have no direction to offset from
+	let mut points = points.to_vec();
+	points.dedup_by(|a, b| Vector2::distance(*a, *b) < 1e-9);
+	if points.len() > 2 && Vector2::distance(points[0], points[points.len() - 1]) < 1e-9 { points.pop(); }
+	let count = points.len();
+	let closed = closed && count > 2;
+
+	// A single point is still drawn as a dot, unless its caps are flat
+	if count == 1 {
+		let p = points[0];
+		match cap {
+			LineCap::Butt => {},
+			LineCap::Square => contours.push(vec![
+				Vector2::new(p.x - half, p.y - half), Vector2::new(p.x + half, p.y - half),
+				Vector2::new(p.x + half, p.y + half), Vector2::new(p.x - half, p.y + half),
+			]),
+			LineCap::Round => contours.push(circle_points(p, half, round_segments)),
+		}
+		return contours;
+	}
+
+	let segment_count = if closed { count } else { count - 1 };
+	for i in 0..segment_count {
+		let (mut a, mut b) = (points[i], points[(i + 1) % count]);
+		let dir = (b - a).normalized();
+		let normal = Vector2::new(-dir.y, dir.x) * half;
+
+		if !closed && cap == LineCap::Square {
+			if i == 0 { a = a - dir * half; }
+			if i == segment_count - 1 { b = b + dir * half; }
+		}
+		contours.push(vec![a + normal, b + normal, b - normal, a - normal]);
+	}
+
+	// The inside of every corner is already covered by the segments, so joins only fill the outside
+	let joints = if closed { 0..count } else { 1..count - 1 };
+	for i in joints {
+		let v = points[i];
+		let d0 = (v - points[(i + count - 1) % count]).normalized();
+		let d1 = (points[(i + 1) % count] - v).normalized();
+		if Vector2::cross(d0, d1).abs() < 1e-9 && Vector2::dot(d0, d1) > 0.0 { continue; }
+
+		let n0 = Vector2::new(-d0.y, d0.x);
+		let n1 = Vector2::new(-d1.y, d1.x);
+		let side = if Vector2::dot(d1, n0) > 0.0 { -1.0 } else { 1.0 };
+		let outer0 = v + n0 * (half * side);
+		let outer1 = v + n1 * (half * side);
+
+		match join {
+			LineJoin::Round => contours.push(circle_points(v, half, round_segments)),
+			LineJoin::Bevel => contours.push(vec![v, outer0, outer1]),
+			LineJoin::Miter => {
+				let bisector = n0 + n1;
+				let cos_half = if bisector.magnitude() > 1e-9 { Vector2::dot(bisector.normalized(), n0) } else { 0.0 };
+				if cos_half > 1.0 / MITER_LIMIT {
+					let tip = v + bisector.normalized() * (half * side / cos_half);
+					contours.push(vec![v, outer0, tip, outer1]);
+				} else {
+					contours.push(vec![v, outer0, outer1]);
+				}
+			}
+		}
+	}
+
+	if !closed && cap == LineCap::Round {
+		contours.push(circle_points(points[0], half, round_segments));
+		contours.push(circle_points(points[count - 1], half, round_segments));
+	}
+
+	for contour in contours.iter_mut() {
+		if polygon_area2(contour) < 0.0 { contour.reverse(); }
+	}
+	contours
+}
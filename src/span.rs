@@ -0,0 +1,129 @@
+use wide::{u8x16, u16x16, CmpEq};
+
+use crate::color::*;
+use crate::rasterizer::DrawMode;
+
+// Four RGBA pixels fill the 16 lanes
+const SPAN_BYTES: usize = 16;
+
+/// Where the pixels of a span come from.
+#[derive(Debug, Clone, Copy)]
+pub enum SpanSource<'a> {
+	/// Every pixel is the same color, like filled shapes.
+	Color(Color),
+	/// RGBA bytes with one pixel for every pixel in the span, like a row of an image. Clear pixels are skipped.
+	Pixels(&'a [u8]),
+}
+
+/// Returns true if the DrawMode can draw whole rows at once, the other modes still draw pixel by pixel.
+pub fn has_span_op(mode: DrawMode) -> bool {
+	matches!(mode, DrawMode::NoOp | DrawMode::NoAlpha | DrawMode::Opaque | DrawMode::ForceTint | DrawMode::Alpha)
+}
+
+/// Draws a row of pixels into dst, four pixels at a time, the same way the DrawMode's pset would.
+/// dst has to already be clipped to the buffer. Returns how many pixels were drawn.
+pub fn draw_span(mode: DrawMode, dst: &mut [u8], src: SpanSource, tint: Color, opacity: u8) -> u64 {
+	let src_bytes: [u8; SPAN_BYTES];
+	let (src_chunk, src_stride): (&[u8], usize) = match src {
+		SpanSource::Color(color) => {
+			// Filling with one color is just copying, no blending needed
+			let fill = match mode {
+				DrawMode::NoOp => Some(color),
+				DrawMode::NoAlpha => Some(color * tint),
+				DrawMode::Opaque => if color.a < 255 { return 0; } else { Some(color * tint) },
+				DrawMode::ForceTint => if color.a < 255 { return 0; } else { Some(tint) },
+				_ => None,
+			};
+			if let Some(fill) = fill {
+				dst.chunks_exact_mut(4).for_each(|c| c.copy_from_slice(&[fill.r, fill.g, fill.b, fill.a]));
+				return (dst.len() / 4) as u64;
+			}
+
+			src_bytes = [
+				color.r, color.g, color.b, color.a, color.r, color.g, color.b, color.a,
+				color.r, color.g, color.b, color.a, color.r, color.g, color.b, color.a,
+			];
+			(&src_bytes, 0)
+		},
+		SpanSource::Pixels(pixels) => {
+			if pixels.len() < dst.len() { return 0; }
+			(pixels, SPAN_BYTES)
+		},
+	};
+	let skip_clear = matches!(src, SpanSource::Pixels(_));
+
+	let tint_lanes = u16x16::new([
+		tint.r as u16, tint.g as u16, tint.b as u16, 255, tint.r as u16, tint.g as u16, tint.b as u16, 255,
+		tint.r as u16, tint.g as u16, tint.b as u16, 255, tint.r as u16, tint.g as u16, tint.b as u16, 255,
+	]);
+	let tint_bytes = [tint.r, tint.g, tint.b, tint.a];
+	let transparency = 255 - opacity;
+
+	let mut drawn: u64 = 0;
+	let whole = dst.len() / SPAN_BYTES * SPAN_BYTES;
+	let (dst_simd, dst_rest) = dst.split_at_mut(whole);
+
+	for (i, d) in dst_simd.chunks_exact_mut(SPAN_BYTES).enumerate() {
+		let s = &src_chunk[i * src_stride..i * src_stride + SPAN_BYTES];
+
+		// Every pixel's alpha copied into all four of its lanes
+		let mut alpha_array = [0u16; SPAN_BYTES];
+		for p in 0..4 {
+			let a = s[p * 4 + 3] as u16;
+			alpha_array[p * 4..p * 4 + 4].copy_from_slice(&[a, a, a, a]);
+			if !skip_clear || a > 0 { drawn += 1; }
+		}
+		let alpha = u16x16::new(alpha_array);
+
+		let s16 = u16x16::from(u8x16::new(s.try_into().unwrap()));
+		let d16 = u16x16::from(u8x16::new((&*d).try_into().unwrap()));
+
+		// Same rounding as multiplying Colors
+		let tinted = (s16 * tint_lanes + u16x16::splat(255)) >> 8;
+
+		let out = match mode {
+			DrawMode::NoOp => s16,
+			DrawMode::NoAlpha => tinted,
+			DrawMode::Opaque => alpha.cmp_eq(u16x16::splat(255)).blend(tinted, d16),
+			DrawMode::ForceTint => alpha.cmp_eq(u16x16::splat(255)).blend(u16x16::from(u8x16::new([
+				tint_bytes[0], tint_bytes[1], tint_bytes[2], tint_bytes[3], tint_bytes[0], tint_bytes[1], tint_bytes[2], tint_bytes[3],
+				tint_bytes[0], tint_bytes[1], tint_bytes[2], tint_bytes[3], tint_bytes[0], tint_bytes[1], tint_bytes[2], tint_bytes[3],
+			])), d16),
+			_ => {
+				// Alpha, the same as Color::blend_fast with the alpha lanes forced to opaque
+				let blend_alpha = alpha.saturating_sub(u16x16::splat(transparency as u16));
+				let blended = tinted * blend_alpha + d16 * (u16x16::splat(255) - blend_alpha);
+				// Exact rounded division by 255 for anything up to 255 * 255
+				let blended = ((blended + u16x16::splat(128)) + ((blended + u16x16::splat(128)) >> 8)) >> 8;
+				blended | u16x16::new([0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255])
+			},
+		};
+
+		// Clear source pixels leave the buffer alone
+		let out = if skip_clear { alpha.cmp_eq(u16x16::splat(0)).blend(d16, out) } else { out };
+		let out = out.to_array();
+		for (byte, value) in d.iter_mut().zip(out.iter()) {
+			*byte = *value as u8;
+		}
+	}
+
+	// The last few pixels that don't fill all the lanes
+	for (i, d) in dst_rest.chunks_exact_mut(4).enumerate() {
+		let offset = (whole / 4 + i) * 4;
+		let s = if src_stride == 0 { &src_chunk[0..4] } else { &src_chunk[offset..offset + 4] };
+		let color = Color::new(s[0], s[1], s[2], s[3]);
+		if skip_clear && color.a == 0 { continue; }
+		drawn += 1;
+
+		let out = match mode {
+			DrawMode::NoOp => color,
+			DrawMode::NoAlpha => color * tint,
+			DrawMode::Opaque => if color.a < 255 { continue; } else { color * tint },
+			DrawMode::ForceTint => if color.a < 255 { continue; } else { tint },
+			_ => Color::blend_fast(color * tint, Color::new(d[0], d[1], d[2], 255), opacity),
+		};
+		d.copy_from_slice(&[out.r, out.g, out.b, out.a]);
+	}
+
+	drawn
+}
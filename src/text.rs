@@ -0,0 +1,290 @@
+use crate::color::*;
+use crate::font::*;
+
+/// How lines of text are placed inside their box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextAlign {
+	Left,
+	Center,
+	Right,
+	/// Stretches the spaces between words so every line fills the box, except the last line of a paragraph.
+	Justify,
+}
+
+impl TextAlign {
+	pub fn from_str(name: &str) -> TextAlign {
+		match name {
+			"center" 	=> TextAlign::Center,
+			"right" 	=> TextAlign::Right,
+			"justify" 	=> TextAlign::Justify,
+			_ 			=> TextAlign::Left,
+		}
+	}
+}
+
+/// Inline style from text markup. Color is multiplied with the tint for that glyph, wave and shake move it around over time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+	pub color: Option<Color>,
+	pub wave: bool,
+	pub shake: bool,
+}
+
+impl TextStyle {
+	pub fn plain() -> TextStyle {
+		TextStyle { color: None, wave: false, shake: false }
+	}
+}
+
+/// A single glyph placed by the layout, relative to the top-left of the text box.
+#[derive(Debug, Copy, Clone)]
+pub struct LayoutGlyph {
+	/// Which font in the fallback chain the glyph comes from, 0 is the font the text was laid out with.
+	pub font: usize,
+	pub glyph: usize,
+	pub x: i64,
+	pub y: i64,
+	pub style: TextStyle,
+	/// Position of the character in the text, used to offset effects so glyphs don't all move together.
+	pub index: usize,
+}
+
+impl LayoutGlyph {
+	/// Where the glyph is drawn for text at x, y, after the wave and shake effects at this time.
+	/// Glyphs from fallback fonts are moved so their baseline lines up with the main font.
+	pub fn position(&self, font: &Font, x: i64, y: i64, time: f64) -> (i64, i64) {
+		let (mut gx, mut gy) = (x + self.x, y + self.y);
+
+		if self.style.wave {
+			let amplitude = f64::max(font.glyph_height as f64 * 0.15, 1.0);
+			gy += (f64::sin(time * 8.0 + self.index as f64 * 0.6) * amplitude).round() as i64;
+		}
+
+		if self.style.shake {
+			// Cheap hash so every glyph jitters differently, changing 30 times a second
+			let seed = (self.index as u64).wrapping_mul(2654435761) ^ ((time * 30.0) as u64).wrapping_mul(40503);
+			gx += (seed % 3) as i64 - 1;
+			gy += ((seed / 3) % 3) as i64 - 1;
+		}
+
+		gy += font.baseline - font.chain(self.font).baseline;
+		(gx, gy)
+	}
+}
+
+/// Text that has been wrapped, aligned and measured, ready to be drawn with the Rasterizers ptext function.
+pub struct TextLayout {
+	pub glyphs: Vec<LayoutGlyph>,
+	pub width: i64,
+	pub height: i64,
+}
+
+/// Splits text into characters and their styles.
+/// Supported tags are [color=RRGGBB] or [color=RRGGBBAA] ... [/color], [wave] ... [/wave] and [shake] ... [/shake].
+/// Use [[ to print a '['. Unknown tags are printed as-is.
+pub fn parse_markup(text: &str) -> Vec<(char, TextStyle)> {
+	let chars: Vec<char> = text.chars().collect();
+	let mut styled: Vec<(char, TextStyle)> = Vec::with_capacity(chars.len());
+
+	let mut colors: Vec<Color> = Vec::new();
+	let mut wave_depth: u32 = 0;
+	let mut shake_depth: u32 = 0;
+
+	let mut i: usize = 0;
+	while i < chars.len() {
+		let style = TextStyle { color: colors.last().copied(), wave: wave_depth > 0, shake: shake_depth > 0 };
+
+		if chars[i] == '[' {
+			if i + 1 < chars.len() && chars[i + 1] == '[' {
+				styled.push(('[', style));
+				i += 2;
+				continue;
+			}
+
+			let close = chars[i..].iter().position(|c| *c == ']');
+			if close.is_some() {
+				let tag: String = chars[i + 1..i + close.unwrap()].iter().collect();
+				let mut known = true;
+
+				match tag.as_str() {
+					"/color" 	=> { colors.pop(); },
+					"wave" 		=> { wave_depth += 1; },
+					"/wave" 	=> { wave_depth = wave_depth.saturating_sub(1); },
+					"shake" 	=> { shake_depth += 1; },
+					"/shake" 	=> { shake_depth = shake_depth.saturating_sub(1); },
+					_ => {
+						match tag.strip_prefix("color=").and_then(|hex| parse_hex_color(hex)) {
+							Some(color) => { colors.push(color); },
+							None => { known = false; }
+						}
+					}
+				}
+
+				if known {
+					i += close.unwrap() + 1;
+					continue;
+				}
+			}
+		}
+
+		styled.push((chars[i], style));
+		i += 1;
+	}
+
+	styled
+}
+
+fn parse_hex_color(hex: &str) -> Option<Color> {
+	let hex = hex.trim_start_matches('#');
+	if hex.len() != 6 && hex.len() != 8 { return None; }
+	// Checked first so the slices below can't land inside a multi-byte character
+	if !hex.bytes().all(|b| b.is_ascii_hexdigit()) { return None; }
+
+	let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+	let a = if hex.len() == 8 { channel(6)? } else { 255 };
+	Some(Color::new(channel(0)?, channel(2)?, channel(4)?, a))
+}
+
+// A word and the spaces written before it, glyphs are (index into styled, x offset in the word)
+struct LayoutWord {
+	spaces: i64,
+	glyphs: Vec<(usize, i64)>,
+	width: i64,
+}
+
+struct LayoutLine {
+	words: Vec<LayoutWord>,
+	/// Spaces after the last word, kept at the end of a paragraph but dropped where a line wraps.
+	trailing_spaces: i64,
+	paragraph_end: bool,
+}
+
+/// Wraps text into lines at word boundaries and places every glyph.
+/// Runs of spaces are kept as written, except where a line wraps. Words longer than the wrap width are broken between characters.
+/// Without a wrap width lines only break at '\n'. If markup is false the text is printed exactly as written.
+pub fn layout_text(font: &Font, text: &str, wrap_width: Option<i64>, align: TextAlign, line_spacing: i64, markup: bool) -> TextLayout {
+	let styled: Vec<(char, TextStyle)> = if markup {
+		parse_markup(text)
+	} else {
+		text.chars().map(|c| (c, TextStyle::plain())).collect()
+	};
+
+	let space_width = font.advance(' ');
+
+	let mut lines: Vec<LayoutLine> = Vec::new();
+	let mut line: Vec<LayoutWord> = Vec::new();
+	let mut line_width: i64 = 0;
+
+	let mut word = LayoutWord { spaces: 0, glyphs: Vec::new(), width: 0 };
+	let mut spaces: i64 = 0;
+
+	let flush_word = |line: &mut Vec<LayoutWord>, line_width: &mut i64, lines: &mut Vec<LayoutLine>, word: &mut LayoutWord, spaces: &mut i64| {
+		if word.glyphs.is_empty() { return; }
+
+		let spaced_width = *line_width + *spaces * space_width + word.width;
+		if wrap_width.is_some() && spaced_width > wrap_width.unwrap() && !line.is_empty() {
+			// The spaces at the wrap point are dropped
+			lines.push(LayoutLine { words: std::mem::take(line), trailing_spaces: 0, paragraph_end: false });
+			word.spaces = 0;
+			*line_width = word.width;
+		} else {
+			word.spaces = *spaces;
+			*line_width = spaced_width;
+		}
+
+		line.push(std::mem::replace(word, LayoutWord { spaces: 0, glyphs: Vec::new(), width: 0 }));
+		*spaces = 0;
+	};
+
+	for i in 0..styled.len() {
+		let c = styled[i].0;
+
+		if c == '\n' {
+			flush_word(&mut line, &mut line_width, &mut lines, &mut word, &mut spaces);
+			lines.push(LayoutLine { words: std::mem::take(&mut line), trailing_spaces: spaces, paragraph_end: true });
+			line_width = 0;
+			spaces = 0;
+			continue;
+		}
+
+		if c == ' ' {
+			flush_word(&mut line, &mut line_width, &mut lines, &mut word, &mut spaces);
+			spaces += 1;
+			continue;
+		}
+
+		let kern = match word.glyphs.last() {
+			Some((idx, _)) => font.kern(styled[*idx].0, c),
+			None => 0,
+		};
+		let advance = font.advance(c);
+
+		// Break words that can never fit on a line by themselves
+		if wrap_width.is_some() && !word.glyphs.is_empty() && word.width + kern + advance > wrap_width.unwrap() {
+			flush_word(&mut line, &mut line_width, &mut lines, &mut word, &mut spaces);
+			lines.push(LayoutLine { words: std::mem::take(&mut line), trailing_spaces: 0, paragraph_end: false });
+			line_width = 0;
+			word.glyphs.push((i, 0));
+			word.width = advance;
+			continue;
+		}
+
+		word.glyphs.push((i, word.width + kern));
+		word.width += kern + advance;
+	}
+	flush_word(&mut line, &mut line_width, &mut lines, &mut word, &mut spaces);
+	lines.push(LayoutLine { words: line, trailing_spaces: spaces, paragraph_end: true });
+
+	// Measure every line so alignment knows the size of the box
+	let line_widths: Vec<i64> = lines.iter().map(|line| {
+		let words_width: i64 = line.words.iter().map(|w| w.spaces * space_width + w.width).sum();
+		words_width + line.trailing_spaces * space_width
+	}).collect();
+
+	let widest = line_widths.iter().copied().max().unwrap_or(0);
+	let box_width = match wrap_width { Some(w) => w, None => widest };
+
+	let mut glyphs: Vec<LayoutGlyph> = Vec::with_capacity(styled.len());
+	let line_height = font.glyph_height as i64 + line_spacing;
+
+	for (line_idx, line) in lines.iter().enumerate() {
+		let slack = box_width - line_widths[line_idx];
+
+		// Justified lines spread the slack over the gaps between words, on top of the spaces already there
+		let (start_x, extra) = match align {
+			TextAlign::Left 	=> (0, 0.0),
+			TextAlign::Center 	=> (slack / 2, 0.0),
+			TextAlign::Right 	=> (slack, 0.0),
+			TextAlign::Justify 	=> {
+				if line.paragraph_end || line.words.len() < 2 {
+					(0, 0.0)
+				} else {
+					(0, slack as f64 / (line.words.len() - 1) as f64)
+				}
+			},
+		};
+
+		let mut pen_x = start_x;
+		for (word_idx, word) in line.words.iter().enumerate() {
+			// Justified gaps are fractional, round from the line start so the error doesn't build up
+			let justified = (extra * word_idx as f64).round() as i64;
+			pen_x += word.spaces * space_width;
+
+			for (idx, x) in &word.glyphs {
+				let (c, style) = styled[*idx];
+				let resolved = font.resolve(c);
+				if resolved.is_some() {
+					let (chain_idx, glyph) = resolved.unwrap();
+					glyphs.push(LayoutGlyph { font: chain_idx, glyph, x: pen_x + justified + x, y: line_idx as i64 * line_height, style, index: *idx });
+				}
+			}
+			pen_x += word.width;
+		}
+	}
+
+	TextLayout {
+		glyphs,
+		width: if align == TextAlign::Left { widest } else { i64::max(widest, box_width) },
+		height: (lines.len() as i64 * line_height - line_spacing).max(0),
+	}
+}
@@ -0,0 +1,140 @@
+use rayon::prelude::*;
+
+use crate::color::Color;
+use crate::easing::Easing;
+
+/// The way a wipe moves across the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WipeDirection {
+	Left,
+	Right,
+	Up,
+	Down,
+}
+
+impl WipeDirection {
+	pub fn from_str(name: &str) -> Option<WipeDirection> {
+		match name {
+			"left" => Some(WipeDirection::Left),
+			"right" => Some(WipeDirection::Right),
+			"up" => Some(WipeDirection::Up),
+			"down" => Some(WipeDirection::Down),
+			_ => None,
+		}
+	}
+}
+
+/// How the old frame turns into the new one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+	/// Blends straight from the old frame to the new one.
+	Fade,
+	/// Fades the old frame out to a color over the first half, and the new one in from it over the second.
+	FadeColor(Color),
+	/// The new frame is uncovered from one edge, with the edge moving in the direction given.
+	Wipe(WipeDirection),
+	/// The new frame shows up a few scattered pixels at a time.
+	Dissolve,
+}
+
+/// A transition between two frames. The old frame is kept in from, the new one is drawn as usual and blended with it by apply.
+/// Fades blend colors, so in palette mode only wipes and dissolves make sense.
+pub struct Transition {
+	pub kind: TransitionKind,
+	pub duration: f64,
+	pub easing: Easing,
+	pub time: f64,
+	/// The old frame, RGBA the same size as the screen.
+	pub from: Vec<u8>,
+}
+
+impl Transition {
+	pub fn new(kind: TransitionKind, duration: f64, easing: Easing) -> Transition {
+		Transition { kind, duration, easing, time: 0.0, from: Vec::new() }
+	}
+
+	pub fn update(&mut self, dt: f64) {
+		self.time += dt;
+	}
+
+	pub fn is_done(&self) -> bool {
+		self.time >= self.duration
+	}
+
+	/// 0.0 is all the old frame and 1.0 is all the new one.
+	pub fn progress(&self) -> f64 {
+		if self.duration <= 0.0 { return 1.0; }
+		self.easing.apply(self.time / self.duration)
+	}
+
+	/// Blends the old frame over the new frame in to. Nothing happens if the old frame is a different size, like after a resolution change.
+	pub fn apply(&self, to: &mut [u8], width: usize, height: usize) {
+		if width == 0 || height == 0 || self.from.len() != to.len() || to.len() != width * height * 4 { return; }
+		let t = f64::clamp(self.progress(), 0.0, 1.0);
+		let from = &self.from;
+
+		match self.kind {
+			TransitionKind::Fade => {
+				let amount = (t * 256.0) as u32;
+				to.par_chunks_mut(width * 4).zip(from.par_chunks(width * 4)).for_each(|(row, from_row)| {
+					for (d, s) in row.iter_mut().zip(from_row.iter()) {
+						*d = ((*s as u32 * (256 - amount) + *d as u32 * amount) >> 8) as u8;
+					}
+				});
+			},
+			TransitionKind::FadeColor(color) => {
+				// Both halves are a blend with the color, just from the other side
+				let (amount, first_half) = if t < 0.5 { ((t * 2.0 * 256.0) as u32, true) } else { (((1.0 - t) * 2.0 * 256.0) as u32, false) };
+				let color = [color.r as u32, color.g as u32, color.b as u32, color.a as u32];
+				to.par_chunks_mut(width * 4).zip(from.par_chunks(width * 4)).for_each(|(row, from_row)| {
+					for (d, s) in row.chunks_exact_mut(4).zip(from_row.chunks_exact(4)) {
+						let source = if first_half { s } else { &*d };
+						let mut pixel = [0u8; 4];
+						for c in 0..4 {
+							pixel[c] = ((source[c] as u32 * (256 - amount) + color[c] * amount) >> 8) as u8;
+						}
+						d.copy_from_slice(&pixel);
+					}
+				});
+			},
+			TransitionKind::Wipe(direction) => {
+				// Rows or columns before the edge show the new frame
+				let (length, horizontal) = match direction {
+					WipeDirection::Left | WipeDirection::Right => (width, true),
+					WipeDirection::Up | WipeDirection::Down => (height, false),
+				};
+				let edge = f64::round(t * length as f64) as usize;
+				let is_new = move |x: usize, y: usize| {
+					let position = if horizontal { x } else { y };
+					match direction {
+						WipeDirection::Right | WipeDirection::Down => position < edge,
+						WipeDirection::Left | WipeDirection::Up => position >= length - edge,
+					}
+				};
+				to.par_chunks_mut(width * 4).zip(from.par_chunks(width * 4)).enumerate().for_each(|(y, (row, from_row))| {
+					for (x, (d, s)) in row.chunks_exact_mut(4).zip(from_row.chunks_exact(4)).enumerate() {
+						if !is_new(x, y) { d.copy_from_slice(s); }
+					}
+				});
+			},
+			TransitionKind::Dissolve => {
+				let threshold = (t * 65536.0) as u32;
+				to.par_chunks_mut(width * 4).zip(from.par_chunks(width * 4)).enumerate().for_each(|(y, (row, from_row))| {
+					for (x, (d, s)) in row.chunks_exact_mut(4).zip(from_row.chunks_exact(4)).enumerate() {
+						if dissolve_order(x as u32, y as u32) >= threshold { d.copy_from_slice(s); }
+					}
+				});
+			},
+		}
+	}
+}
+
+// When a pixel turns over in a dissolve, 0 to 65535. A hash so there's no visible pattern.
+#[inline]
+fn dissolve_order(x: u32, y: u32) -> u32 {
+	let mut h = x.wrapping_mul(0x8da6b343) ^ y.wrapping_mul(0xd8163841);
+	h ^= h >> 15;
+	h = h.wrapping_mul(0x2c1b3c6d);
+	h ^= h >> 12;
+	h & 0xffff
+}
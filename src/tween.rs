@@ -0,0 +1,356 @@
+use mlua::prelude::*;
+
+use crate::color::Color;
+use crate::easing::Easing;
+use crate::partitioned_rasterizer::PartitionedRasterizer;
+use crate::vector2::Vector2;
+
+/// A value a tween can move between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TweenValue {
+	Number(f64),
+	Vector(Vector2),
+	Color(Color),
+}
+
+impl TweenValue {
+	pub fn from_lua_value(value: &LuaValue) -> Option<TweenValue> {
+		match value {
+			LuaValue::Integer(n) => Some(TweenValue::Number(*n as f64)),
+			LuaValue::Number(n) => Some(TweenValue::Number(*n)),
+			LuaValue::UserData(ud) => {
+				if let Ok(v) = ud.borrow::<Vector2>() { return Some(TweenValue::Vector(*v)); }
+				if let Ok(c) = ud.borrow::<Color>() { return Some(TweenValue::Color(*c)); }
+				None
+			},
+			_ => None,
+		}
+	}
+
+	pub fn into_lua_value<'lua>(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+		match self {
+			TweenValue::Number(n) => n.to_lua(lua),
+			TweenValue::Vector(v) => v.to_lua(lua),
+			TweenValue::Color(c) => c.to_lua(lua),
+		}
+	}
+
+	/// Blends from one value to another, t can go past 0.0 and 1.0 for easings that overshoot.
+	/// A number counts as a vector with both parts the same, so a scale can go from a vec2 to 2.
+	pub fn lerp(from: TweenValue, to: TweenValue, t: f64) -> Option<TweenValue> {
+		let number = |a: f64, b: f64| a + (b - a) * t;
+		match (from, to) {
+			(TweenValue::Number(a), TweenValue::Number(b)) => Some(TweenValue::Number(number(a, b))),
+			(TweenValue::Vector(a), TweenValue::Vector(b)) => Some(TweenValue::Vector(Vector2::new(number(a.x, b.x), number(a.y, b.y)))),
+			(TweenValue::Vector(a), TweenValue::Number(b)) => Some(TweenValue::Vector(Vector2::new(number(a.x, b), number(a.y, b)))),
+			(TweenValue::Number(a), TweenValue::Vector(b)) => Some(TweenValue::Vector(Vector2::new(number(a, b.x), number(a, b.y)))),
+			(TweenValue::Color(a), TweenValue::Color(b)) => {
+				let channel = |a: u8, b: u8| f64::clamp(number(a as f64, b as f64) + 0.5, 0.0, 255.0) as u8;
+				Some(TweenValue::Color(Color::new(channel(a.r, b.r), channel(a.g, b.g), channel(a.b, b.b), channel(a.a, b.a))))
+			},
+			_ => None,
+		}
+	}
+}
+
+/// What a tween changes.
+pub enum TweenTarget {
+	/// Fields of a Lua table.
+	Table(LuaRegistryKey),
+	/// x, y, position, rotation and scale.
+	Camera,
+	/// tint and opacity.
+	Draw,
+}
+
+impl TweenTarget {
+	/// Checks that an engine property exists and can be set to the value.
+	pub fn check(&self, name: &str, value: TweenValue) -> Result<(), String> {
+		let fits = match (self, name, value) {
+			(TweenTarget::Table(_), _, _) => true,
+			(TweenTarget::Camera, "x" | "y" | "rotation", TweenValue::Number(_)) => true,
+			(TweenTarget::Camera, "position", TweenValue::Vector(_)) => true,
+			(TweenTarget::Camera, "scale", TweenValue::Number(_) | TweenValue::Vector(_)) => true,
+			(TweenTarget::Draw, "tint", TweenValue::Color(_)) => true,
+			(TweenTarget::Draw, "opacity", TweenValue::Number(_)) => true,
+			(TweenTarget::Camera, _, _) => return Err(format!("the camera can tween x, y, position, rotation and scale, not \"{}\"", name)),
+			(TweenTarget::Draw, _, _) => return Err(format!("drawing can tween tint and opacity, not \"{}\"", name)),
+		};
+		if fits { Ok(()) } else { Err(format!("\"{}\" can't be tweened to that kind of value", name)) }
+	}
+
+	fn read(&self, name: &str, lua: &Lua, rst: &PartitionedRasterizer) -> LuaResult<Option<TweenValue>> {
+		let camera = (rst.rasterizer.camera_position, rst.rasterizer.camera_scale);
+		Ok(match self {
+			TweenTarget::Table(key) => {
+				// Raw, so a metamethod can't call back into the API while the tweens are borrowed
+				let table: LuaTable = lua.registry_value(key)?;
+				TweenValue::from_lua_value(&table.raw_get::<_, LuaValue>(name)?)
+			},
+			TweenTarget::Camera => match name {
+				"x" => Some(TweenValue::Number(camera.0.x)),
+				"y" => Some(TweenValue::Number(camera.0.y)),
+				"position" => Some(TweenValue::Vector(camera.0)),
+				"rotation" => Some(TweenValue::Number(rst.rasterizer.camera_rotation)),
+				"scale" => Some(TweenValue::Vector(camera.1)),
+				_ => None,
+			},
+			TweenTarget::Draw => match name {
+				"tint" => Some(TweenValue::Color(rst.rasterizer.tint)),
+				"opacity" => Some(TweenValue::Number(rst.rasterizer.opacity as f64)),
+				_ => None,
+			},
+		})
+	}
+
+	fn write(&self, name: &str, value: TweenValue, lua: &Lua, rst: &mut PartitionedRasterizer) -> LuaResult<()> {
+		let position = rst.rasterizer.camera_position;
+		match (self, name, value) {
+			(TweenTarget::Table(key), _, _) => {
+				let table: LuaTable = lua.registry_value(key)?;
+				table.raw_set(name, value.into_lua_value(lua)?)?;
+			},
+			(TweenTarget::Camera, "x", TweenValue::Number(x)) => rst.set_camera_position(x, position.y),
+			(TweenTarget::Camera, "y", TweenValue::Number(y)) => rst.set_camera_position(position.x, y),
+			(TweenTarget::Camera, "position", TweenValue::Vector(v)) => rst.set_camera_position(v.x, v.y),
+			(TweenTarget::Camera, "rotation", TweenValue::Number(r)) => rst.set_camera_rotation(r),
+			(TweenTarget::Camera, "scale", TweenValue::Vector(v)) => rst.set_camera_scale(v.x, v.y),
+			(TweenTarget::Camera, "scale", TweenValue::Number(s)) => rst.set_camera_scale(s, s),
+			(TweenTarget::Draw, "tint", TweenValue::Color(c)) => rst.set_tint(c),
+			(TweenTarget::Draw, "opacity", TweenValue::Number(o)) => rst.set_opacity(f64::clamp(o + 0.5, 0.0, 255.0) as u8),
+			_ => {},
+		}
+		Ok(())
+	}
+}
+
+/// One value a tween moves, the start is read when the tween starts.
+pub struct TweenProperty {
+	pub name: String,
+	pub from: Option<TweenValue>,
+	pub to: TweenValue,
+}
+
+pub enum TweenKind {
+	Tween { target: TweenTarget, properties: Vec<TweenProperty>, duration: f64, easing: Easing },
+	/// Plays its tweens one after another.
+	Sequence(Vec<TweenNode>),
+	/// Plays its tweens all at once, until the longest one is done.
+	Parallel(Vec<TweenNode>),
+}
+
+// Everything a tween needs while it's being applied
+pub struct TweenContext<'a, 'lua> {
+	pub lua: &'lua Lua,
+	pub rasterizer: &'a mut PartitionedRasterizer,
+	/// Completion callbacks, called once the tweens are done being updated.
+	pub callbacks: Vec<LuaFunction<'lua>>,
+}
+
+/// A tween, or a sequence or group of them. Tweens don't keep their own time, they're told where to be,
+/// so loops and yoyos are just the same time played again or backwards.
+pub struct TweenNode {
+	pub kind: TweenKind,
+	/// Seconds to wait before starting, only once and not every loop.
+	pub delay: f64,
+	/// How many times it plays, 0 or less plays forever.
+	pub loops: i64,
+	/// Plays every other loop backwards.
+	pub yoyo: bool,
+	/// Called the first time it finishes.
+	pub on_complete: Option<LuaRegistryKey>,
+	pub completed: bool,
+
+	// The last time inside a play that was applied, so nothing is set twice
+	last: Option<f64>,
+}
+
+impl TweenNode {
+	pub fn new(kind: TweenKind) -> TweenNode {
+		TweenNode { kind, delay: 0.0, loops: 1, yoyo: false, on_complete: None, completed: false, last: None }
+	}
+
+	// How long it takes to play once
+	fn length(&self) -> f64 {
+		match &self.kind {
+			TweenKind::Tween { duration, .. } => f64::max(*duration, 0.0),
+			TweenKind::Sequence(children) => children.iter().map(|child| child.total()).sum(),
+			TweenKind::Parallel(children) => children.iter().map(|child| child.total()).fold(0.0, f64::max),
+		}
+	}
+
+	/// How long until it's done, including the delay and every loop.
+	pub fn total(&self) -> f64 {
+		let length = self.length();
+		if length <= 0.0 { return self.delay; }
+		if self.loops <= 0 { return f64::INFINITY; }
+		self.delay + length * self.loops as f64
+	}
+
+	/// Sets everything to where it should be at time seconds after the start.
+	pub fn apply_at(&mut self, time: f64, ctx: &mut TweenContext) -> LuaResult<()> {
+		let local = time - self.delay;
+		if local < 0.0 {
+			// Only rewound if it already played, like going backwards through a yoyo sequence
+			if matches!(self.last, Some(last) if last != 0.0) { self.apply_play(0.0, ctx)?; }
+			return Ok(());
+		}
+
+		let length = self.length();
+		let plays = if self.loops <= 0 { f64::INFINITY } else { self.loops as f64 };
+		let finished = length <= 0.0 || local >= length * plays;
+		let (play, within) = if finished {
+			(plays - 1.0, length)
+		} else {
+			let play = f64::floor(local / length);
+			(play, local - play * length)
+		};
+		let within = if self.yoyo && play % 2.0 == 1.0 { length - within } else { within };
+		self.apply_play(within, ctx)?;
+
+		if finished && !self.completed {
+			self.completed = true;
+			if let Some(key) = &self.on_complete {
+				ctx.callbacks.push(ctx.lua.registry_value(key)?);
+			}
+		}
+		Ok(())
+	}
+
+	// Sets everything to where it should be at a time inside one play
+	fn apply_play(&mut self, within: f64, ctx: &mut TweenContext) -> LuaResult<()> {
+		if self.last == Some(within) { return Ok(()); }
+		self.last = Some(within);
+
+		match &mut self.kind {
+			TweenKind::Tween { target, properties, duration, easing } => {
+				let t = if *duration > 0.0 { easing.apply(within / *duration) } else { 1.0 };
+				for property in properties.iter_mut() {
+					if property.from.is_none() {
+						property.from = target.read(&property.name, ctx.lua, ctx.rasterizer)?;
+						if property.from.is_none() {
+							println!("ERROR - TWEEN: \"{}\" isn't a number, vec2 or color, so it can't be tweened!", property.name);
+						}
+					}
+					if let Some(value) = property.from.and_then(|from| TweenValue::lerp(from, property.to, t)) {
+						target.write(&property.name, value, ctx.lua, ctx.rasterizer)?;
+					}
+				}
+				if let TweenTarget::Camera = target { ctx.rasterizer.update_camera(); }
+			},
+			TweenKind::Sequence(children) => {
+				let mut starts: Vec<f64> = Vec::with_capacity(children.len());
+				let mut start = 0.0;
+				for child in children.iter() {
+					starts.push(start);
+					start += child.total();
+				}
+
+				// Children that haven't started are rewound last to first, then the rest play first to last,
+				// so when two children tween the same thing the later one wins
+				for (child, start) in children.iter_mut().zip(starts.iter()).rev().filter(|(_, start)| **start > within) {
+					child.apply_at(within - start, ctx)?;
+				}
+				for (child, start) in children.iter_mut().zip(starts.iter()).filter(|(_, start)| **start <= within) {
+					child.apply_at(within - start, ctx)?;
+				}
+			},
+			TweenKind::Parallel(children) => {
+				for child in children.iter_mut() {
+					child.apply_at(within, ctx)?;
+				}
+			},
+		}
+		Ok(())
+	}
+}
+
+pub struct RunningTween {
+	pub id: u64,
+	pub node: TweenNode,
+	pub time: f64,
+}
+
+pub struct Timer {
+	pub id: u64,
+	pub interval: f64,
+	pub remaining: f64,
+	pub repeating: bool,
+	pub callback: LuaRegistryKey,
+}
+
+/// Every running tween and timer. Tweens and timers share ids, so an id is never reused for either.
+pub struct TweenManager {
+	pub tweens: Vec<RunningTween>,
+	pub timers: Vec<Timer>,
+	next_id: u64,
+}
+
+impl TweenManager {
+	pub fn new() -> TweenManager {
+		TweenManager { tweens: Vec::new(), timers: Vec::new(), next_id: 1 }
+	}
+
+	fn take_id(&mut self) -> u64 {
+		self.next_id += 1;
+		self.next_id - 1
+	}
+
+	pub fn add_tween(&mut self, node: TweenNode) -> u64 {
+		let id = self.take_id();
+		self.tweens.push(RunningTween { id, node, time: 0.0 });
+		id
+	}
+
+	/// Takes a tween out so it can be put in a sequence or group.
+	pub fn take_tween(&mut self, id: u64) -> Option<TweenNode> {
+		let idx = self.tweens.iter().position(|tween| tween.id == id)?;
+		Some(self.tweens.remove(idx).node)
+	}
+
+	pub fn add_timer(&mut self, interval: f64, repeating: bool, callback: LuaRegistryKey) -> u64 {
+		let id = self.take_id();
+		self.timers.push(Timer { id, interval, remaining: interval, repeating, callback });
+		id
+	}
+
+	/// Stops a tween or timer where it is, without calling anything.
+	pub fn cancel(&mut self, id: u64) {
+		self.tweens.retain(|tween| tween.id != id);
+		self.timers.retain(|timer| timer.id != id);
+	}
+
+	pub fn clear(&mut self) {
+		self.tweens.clear();
+		self.timers.clear();
+	}
+
+	/// Moves every tween and timer along by dt. Returns the callbacks to call, with the id of the timer
+	/// for the ones from every so they can be stopped.
+	pub fn update<'lua>(&mut self, lua: &'lua Lua, rasterizer: &mut PartitionedRasterizer, dt: f64) -> LuaResult<Vec<(Option<u64>, LuaFunction<'lua>)>> {
+		let mut ctx = TweenContext { lua, rasterizer, callbacks: Vec::new() };
+		for tween in self.tweens.iter_mut() {
+			tween.time += dt;
+			tween.node.apply_at(tween.time, &mut ctx)?;
+		}
+		self.tweens.retain(|tween| !tween.node.completed);
+
+		let mut callbacks: Vec<(Option<u64>, LuaFunction)> = ctx.callbacks.into_iter().map(|callback| (None, callback)).collect();
+		for timer in self.timers.iter_mut() {
+			timer.remaining -= dt;
+			if timer.remaining > 0.0 { continue; }
+
+			callbacks.push((if timer.repeating { Some(timer.id) } else { None }, lua.registry_value(&timer.callback)?));
+			// At most once an update, a long frame doesn't call it over and over to catch up
+			timer.remaining = if timer.repeating { f64::max(timer.remaining + timer.interval, 0.0) } else { f64::NAN };
+		}
+		self.timers.retain(|timer| timer.repeating || !timer.remaining.is_nan());
+		Ok(callbacks)
+	}
+}
+
+impl Default for TweenManager {
+	fn default() -> Self {
+		TweenManager::new()
+	}
+}
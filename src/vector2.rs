@@ -1,5 +1,7 @@
 use crate::math::*;
 
+use mlua::prelude::*;
+
 /// Two-dimensional floating-point Vector to be used as either a position or direction.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Vector2 {
@@ -193,6 +195,74 @@ impl Vector2 {
 	}
 }
 
+// vec2 in Lua, numbers in its math count as a vec2 with both x and y set to them so v * 2 and 2 * v both work
+impl LuaUserData for Vector2 {
+	fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+		fields.add_field_method_get("x", |_, v| Ok(v.x));
+		fields.add_field_method_get("y", |_, v| Ok(v.y));
+		fields.add_field_method_set("x", |_, v, x: f64| { v.x = x; Ok(()) });
+		fields.add_field_method_set("y", |_, v, y: f64| { v.y = y; Ok(()) });
+	}
+
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		fn operand(value: LuaValue) -> LuaResult<Vector2> {
+			match value {
+				LuaValue::Integer(n) => Ok(Vector2::new(n as f64, n as f64)),
+				LuaValue::Number(n) => Ok(Vector2::new(n, n)),
+				LuaValue::UserData(ud) => Ok(*ud.borrow::<Vector2>()?),
+				_ => Err(LuaError::RuntimeError(format!("vec2 math needs another vec2 or a number, not a {}", value.type_name()))),
+			}
+		}
+
+		methods.add_method("unpack", |_, v, ()| Ok((v.x, v.y)));
+		methods.add_method("copy", |_, v, ()| Ok(*v));
+		methods.add_method("ratio", |_, v, ()| Ok(v.ratio()));
+		methods.add_method("magnitude", |_, v, ()| Ok(v.magnitude()));
+		methods.add_method("magnitude_sqr", |_, v, ()| Ok(v.magnitude_sqr()));
+		methods.add_method("normalized", |_, v, ()| Ok(v.normalized()));
+		methods.add_method("inverse", |_, v, ()| Ok(v.inverse()));
+		methods.add_method("rotated", |_, v, radians: f64| Ok(v.rotated(radians)));
+		methods.add_method("rotated_pivot", |_, v, (radians, pivot): (f64, Vector2)| Ok(v.rotated_pivot(radians, pivot)));
+
+		methods.add_method("angle", |_, v, other: Vector2| Ok(Vector2::angle(*v, other)));
+		methods.add_method("angle_between", |_, v, (other, fov): (Vector2, f64)| Ok(Vector2::angle_between(*v, other, fov)));
+		methods.add_method("dot", |_, v, other: Vector2| Ok(Vector2::dot(*v, other)));
+		methods.add_method("cross", |_, v, other: Vector2| Ok(Vector2::cross(*v, other)));
+		methods.add_method("distance", |_, v, other: Vector2| Ok(Vector2::distance(*v, other)));
+		methods.add_method("direction", |_, v, other: Vector2| Ok(Vector2::direction(*v, other)));
+		methods.add_method("slide", |_, v, normal: Vector2| Ok(Vector2::slide(*v, normal)));
+		methods.add_method("reflect", |_, v, normal: Vector2| Ok(Vector2::reflect(*v, normal)));
+		methods.add_method("lerp", |_, v, (other, t): (Vector2, f64)| Ok(Vector2::lerp(*v, other, t)));
+		methods.add_method("point_in_aabb", |_, v, (aabb_point, aabb_extents): (Vector2, Vector2)| Ok(v.point_in_aabb(aabb_point, aabb_extents)));
+
+		// The vec2 is the start of the first line, returns hit, point
+		methods.add_method("intersection_infinite", |_, v, (end, other_start, other_end): (Vector2, Vector2, Vector2)| {
+			Ok(Vector2::intersection_infinite(*v, end, other_start, other_end))
+		});
+		methods.add_method("intersection_segment", |_, v, (ray_end, line_start, line_end): (Vector2, Vector2, Vector2)| {
+			Ok(Vector2::intersection_segment(*v, ray_end, line_start, line_end))
+		});
+
+		// These change the vec2 itself instead of making a new one, for hot loops that would otherwise make lots of garbage
+		methods.add_method_mut("set", |_, v, (x, y): (f64, Option<f64>)| { *v = Vector2::new(x, y.unwrap_or(x)); Ok(()) });
+		methods.add_method_mut("add", |_, v, other: LuaValue| { *v += operand(other)?; Ok(()) });
+		methods.add_method_mut("sub", |_, v, other: LuaValue| { *v -= operand(other)?; Ok(()) });
+		methods.add_method_mut("mul", |_, v, other: LuaValue| { *v *= operand(other)?; Ok(()) });
+		methods.add_method_mut("div", |_, v, other: LuaValue| { *v = *v / operand(other)?; Ok(()) });
+		methods.add_method_mut("normalize", |_, v, ()| { v.normalize(); Ok(()) });
+		methods.add_method_mut("rotate", |_, v, radians: f64| { *v = v.rotated(radians); Ok(()) });
+
+		methods.add_meta_function(LuaMetaMethod::Add, |_, (a, b): (LuaValue, LuaValue)| Ok(operand(a)? + operand(b)?));
+		methods.add_meta_function(LuaMetaMethod::Sub, |_, (a, b): (LuaValue, LuaValue)| Ok(operand(a)? - operand(b)?));
+		methods.add_meta_function(LuaMetaMethod::Mul, |_, (a, b): (LuaValue, LuaValue)| Ok(operand(a)? * operand(b)?));
+		methods.add_meta_function(LuaMetaMethod::Div, |_, (a, b): (LuaValue, LuaValue)| Ok(operand(a)? / operand(b)?));
+		methods.add_meta_function(LuaMetaMethod::Mod, |_, (a, b): (LuaValue, LuaValue)| Ok(operand(a)? % operand(b)?));
+		methods.add_meta_method(LuaMetaMethod::Unm, |_, v, ()| Ok(-*v));
+		methods.add_meta_function(LuaMetaMethod::Eq, |_, (a, b): (Vector2, Vector2)| Ok(a == b));
+		methods.add_meta_method(LuaMetaMethod::ToString, |_, v, ()| Ok(format!("vec2({}, {})", v.x, v.y)));
+	}
+}
+
 impl std::ops::Add for Vector2 {
 	type Output = Self;
 